@@ -7,7 +7,8 @@ use self::dummy_engine::*;
 use std::marker::PhantomData;
 
 use super::{
-    create_proof, create_proof_batch, generate_parameters, prepare_verifying_key, verify_proof,
+    create_proof, create_proof_batch, create_proof_batch_priority_chunked, generate_parameters,
+    prepare_verifying_key, verify_proof,
 };
 use crate::{Circuit, ConstraintSystem, SynthesisError};
 
@@ -438,3 +439,65 @@ fn test_create_batch_single() {
         assert!(verify_proof(&pvk, &proof, &[Fr::one()]).unwrap());
     }
 }
+
+#[test]
+fn test_create_batch_chunked() {
+    // test consistency between chunked and unchunked batch creation
+    let g1 = Fr::one();
+    let g2 = Fr::one();
+    let alpha = Fr::from_str("48577").unwrap();
+    let beta = Fr::from_str("22580").unwrap();
+    let gamma = Fr::from_str("53332").unwrap();
+    let delta = Fr::from_str("5481").unwrap();
+    let tau = Fr::from_str("3673").unwrap();
+
+    let params = {
+        let c = XORDemo::<DummyEngine> {
+            a: None,
+            b: None,
+            _marker: PhantomData,
+        };
+
+        generate_parameters(c, g1, g2, alpha, beta, gamma, delta, tau).unwrap()
+    };
+
+    let pvk = prepare_verifying_key(&params.vk);
+
+    let circuits = [(true, false), (false, true), (true, true), (false, false)]
+        .iter()
+        .map(|&(a, b)| XORDemo {
+            a: Some(a),
+            b: Some(b),
+            _marker: PhantomData,
+        })
+        .collect::<Vec<_>>();
+    let inputs = [Fr::one(), Fr::one(), Fr::zero(), Fr::zero()];
+    let r_s = (1..=4)
+        .map(|i| Fr::from_str(&(27130 + i).to_string()).unwrap())
+        .collect::<Vec<_>>();
+    let s_s = (1..=4)
+        .map(|i| Fr::from_str(&(17140 + i).to_string()).unwrap())
+        .collect::<Vec<_>>();
+
+    let unchunked =
+        create_proof_batch(circuits.clone(), &params, r_s.clone(), s_s.clone()).unwrap();
+    assert_eq!(unchunked.len(), 4);
+
+    for chunk_size in &[1, 3, 4, 5] {
+        let chunked = create_proof_batch_priority_chunked(
+            circuits.clone(),
+            &params,
+            r_s.clone(),
+            s_s.clone(),
+            false,
+            Some(*chunk_size),
+        )
+        .unwrap();
+
+        assert_eq!(chunked, unchunked, "chunk size {}", chunk_size);
+    }
+
+    for (proof, input) in unchunked.iter().zip(inputs.iter()) {
+        assert!(verify_proof(&pvk, proof, &[*input]).unwrap());
+    }
+}
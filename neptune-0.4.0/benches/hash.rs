@@ -108,6 +108,15 @@ where
     group.finish();
 }
 
+fn bench_hash_chain(c: &mut Criterion) {
+    let constants = PoseidonConstants::<Bls12, typenum::U2>::new();
+    let start = scalar_from_u64::<Bls12>(42);
+
+    c.bench_function("Poseidon hash chain 10000", |b| {
+        b.iter(|| Poseidon::hash_chain(start, 10000, &constants))
+    });
+}
+
 criterion_group! {
     name = hash;
 
@@ -115,4 +124,5 @@ criterion_group! {
 
     targets = bench_hash::<typenum::U2>, bench_hash::<typenum::U4>, bench_hash::<typenum::U8>, bench_hash::<typenum::U11>
 }
-criterion_main!(hash);
+criterion_group!(hash_chain, bench_hash_chain);
+criterion_main!(hash, hash_chain);
@@ -0,0 +1,69 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use generic_array::{typenum, ArrayLength};
+use neptune::poseidon::{HashMode, PoseidonConstants};
+use neptune::*;
+use paired::bls12_381::{Bls12, Fr};
+
+/// Hashes one fixed preimage in every `HashMode`. The constants are built once, outside of the
+/// measured loop, so only hashing is timed.
+fn bench_hash_modes<Arity>(c: &mut Criterion)
+where
+    Arity: typenum::Unsigned
+        + std::ops::Add<typenum::bit::B1>
+        + std::ops::Add<typenum::uint::UInt<typenum::uint::UTerm, typenum::bit::B1>>,
+    typenum::Add1<Arity>: ArrayLength<Fr>,
+{
+    let preimage: Vec<Scalar> = (0..Arity::to_usize())
+        .map(|i| scalar_from_u64::<Bls12>(i as u64))
+        .collect();
+    let constants = PoseidonConstants::<Bls12, Arity>::new();
+
+    let mut group = c.benchmark_group(format!("poseidon-arity-{}", Arity::to_usize()));
+
+    for (name, mode) in &[
+        ("Correct", HashMode::Correct),
+        ("OptimizedDynamic", HashMode::OptimizedDynamic),
+        ("OptimizedStatic", HashMode::OptimizedStatic),
+    ] {
+        group.bench_with_input(BenchmarkId::new("hash_in_mode", name), mode, |b, &mode| {
+            let mut h = Poseidon::<Bls12, Arity>::new(&constants);
+            b.iter(|| {
+                h.set_preimage(&preimage);
+                black_box(h.hash_in_mode(mode))
+            })
+        });
+    }
+
+    group.finish();
+}
+
+/// Builds the `PoseidonConstants` of each arity. This is slow, so fewer samples are taken.
+fn bench_constants(c: &mut Criterion) {
+    let mut group = c.benchmark_group("poseidon-constants");
+    group.sample_size(10);
+
+    group.bench_function(BenchmarkId::new("new", 2), |b| {
+        b.iter(PoseidonConstants::<Bls12, typenum::U2>::new)
+    });
+    group.bench_function(BenchmarkId::new("new", 4), |b| {
+        b.iter(PoseidonConstants::<Bls12, typenum::U4>::new)
+    });
+    group.bench_function(BenchmarkId::new("new", 8), |b| {
+        b.iter(PoseidonConstants::<Bls12, typenum::U8>::new)
+    });
+    group.bench_function(BenchmarkId::new("new", 11), |b| {
+        b.iter(PoseidonConstants::<Bls12, typenum::U11>::new)
+    });
+
+    group.finish();
+}
+
+criterion_group! {
+    name = hash_modes;
+
+    config = Criterion::default();
+
+    targets = bench_hash_modes::<typenum::U2>, bench_hash_modes::<typenum::U4>, bench_hash_modes::<typenum::U8>, bench_hash_modes::<typenum::U11>
+}
+criterion_group!(constants, bench_constants);
+criterion_main!(hash_modes, constants);
@@ -0,0 +1,152 @@
+use bellperson::gadgets::num::AllocatedNum;
+use bellperson::{ConstraintSystem, Index, LinearCombination, SynthesisError, Variable};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use generic_array::typenum::U2;
+use neptune::circuit::{poseidon_hash, PoseidonCircuitShared};
+use neptune::poseidon::PoseidonConstants;
+use neptune::scalar_from_u64;
+use paired::bls12_381::{Bls12, Fr};
+
+/// Evaluates assignments and counts constraints, without keeping any of them.
+#[derive(Default)]
+struct CountingCS {
+    aux: usize,
+    inputs: usize,
+    constraints: usize,
+}
+
+impl ConstraintSystem<Bls12> for CountingCS {
+    type Root = Self;
+
+    fn alloc<F, A, AR>(&mut self, _annotation: A, f: F) -> Result<Variable, SynthesisError>
+    where
+        F: FnOnce() -> Result<Fr, SynthesisError>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        black_box(f()?);
+        self.aux += 1;
+        Ok(Variable::new_unchecked(Index::Aux(self.aux - 1)))
+    }
+
+    fn alloc_input<F, A, AR>(&mut self, _annotation: A, f: F) -> Result<Variable, SynthesisError>
+    where
+        F: FnOnce() -> Result<Fr, SynthesisError>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        black_box(f()?);
+        self.inputs += 1;
+        Ok(Variable::new_unchecked(Index::Input(self.inputs)))
+    }
+
+    fn enforce<A, AR, LA, LB, LC>(&mut self, _annotation: A, a: LA, b: LB, c: LC)
+    where
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+        LA: FnOnce(LinearCombination<Bls12>) -> LinearCombination<Bls12>,
+        LB: FnOnce(LinearCombination<Bls12>) -> LinearCombination<Bls12>,
+        LC: FnOnce(LinearCombination<Bls12>) -> LinearCombination<Bls12>,
+    {
+        black_box(a(LinearCombination::zero()));
+        black_box(b(LinearCombination::zero()));
+        black_box(c(LinearCombination::zero()));
+        self.constraints += 1;
+    }
+
+    fn push_namespace<NR, N>(&mut self, _name_fn: N)
+    where
+        NR: Into<String>,
+        N: FnOnce() -> NR,
+    {
+    }
+
+    fn pop_namespace(&mut self) {}
+
+    fn get_root(&mut self) -> &mut Self::Root {
+        self
+    }
+}
+
+fn alloc_preimage(cs: &mut CountingCS, i: usize) -> Vec<AllocatedNum<Bls12>> {
+    (0..2)
+        .map(|j| {
+            AllocatedNum::alloc(cs.namespace(|| format!("preimage {} {}", i, j)), || {
+                Ok(scalar_from_u64::<Bls12>((2 * i + j) as u64))
+            })
+            .unwrap()
+        })
+        .collect()
+}
+
+fn synthesize_hashes(constants: &PoseidonConstants<Bls12, U2>, hashes: usize) -> usize {
+    let mut cs = CountingCS::default();
+
+    for i in 0..hashes {
+        let preimage = alloc_preimage(&mut cs, i);
+        poseidon_hash(cs.namespace(|| format!("hash {}", i)), preimage, constants).unwrap();
+    }
+
+    cs.constraints
+}
+
+fn synthesize_shared_hashes(shared: &PoseidonCircuitShared<Bls12, U2>, hashes: usize) -> usize {
+    let mut cs = CountingCS::default();
+
+    for i in 0..hashes {
+        let preimage = alloc_preimage(&mut cs, i);
+        shared
+            .hash(cs.namespace(|| format!("hash {}", i)), preimage)
+            .unwrap();
+    }
+
+    cs.constraints
+}
+
+fn bench_synthesis(c: &mut Criterion) {
+    let precomputed = PoseidonConstants::<Bls12, U2>::new();
+    let mut copied = precomputed.clone();
+    copied.circuit_precomputed_mds_columns = false;
+
+    assert_eq!(
+        synthesize_hashes(&precomputed, 1),
+        synthesize_hashes(&copied, 1)
+    );
+
+    let mut group = c.benchmark_group("synthesize-512-hashes");
+    group.sample_size(10);
+
+    group.bench_function("precomputed MDS columns", |b| {
+        b.iter(|| synthesize_hashes(&precomputed, 512))
+    });
+    group.bench_function("copied MDS columns", |b| {
+        b.iter(|| synthesize_hashes(&copied, 512))
+    });
+
+    group.finish();
+}
+
+fn bench_shared_synthesis(c: &mut Criterion) {
+    let constants = PoseidonConstants::<Bls12, U2>::new();
+
+    assert_eq!(
+        synthesize_hashes(&constants, 1),
+        synthesize_shared_hashes(&PoseidonCircuitShared::new(&constants), 1)
+    );
+
+    let mut group = c.benchmark_group("synthesize-1000-hashes");
+    group.sample_size(10);
+
+    group.bench_function("per-hash constants", |b| {
+        b.iter(|| synthesize_hashes(&constants, 1000))
+    });
+    // Building the shared data is part of synthesizing the circuit.
+    group.bench_function("shared constants", |b| {
+        b.iter(|| synthesize_shared_hashes(&PoseidonCircuitShared::new(&constants), 1000))
+    });
+
+    group.finish();
+}
+
+criterion_group!(synthesis, bench_synthesis, bench_shared_synthesis);
+criterion_main!(synthesis);
@@ -1,11 +1,13 @@
 use crate::poseidon::PoseidonConstants;
+use crate::round_numbers;
 
 use bellperson::gadgets::num::AllocatedNum;
-use bellperson::{ConstraintSystem, SynthesisError};
+use bellperson::{ConstraintSystem, LinearCombination, SynthesisError, Variable};
 use ff::Field;
 use ff::ScalarEngine as Engine;
 use generic_array::typenum;
 use generic_array::ArrayLength;
+use std::borrow::Cow;
 use std::marker::PhantomData;
 
 #[derive(Clone)]
@@ -23,6 +25,8 @@ where
     elements: Vec<AllocatedNum<E>>,
     pos: usize,
     constants: &'a PoseidonConstants<E, Arity>,
+    shared: Option<&'a PoseidonCircuitShared<'a, E, Arity>>,
+    partial_rounds_done: usize,
     _w: PhantomData<Arity>,
 }
 
@@ -45,10 +49,24 @@ where
             elements,
             pos: width,
             constants,
+            shared: None,
+            partial_rounds_done: 0,
             _w: PhantomData::<Arity>,
         }
     }
 
+    /// Create a new Poseidon hasher for `preimage`, which reads its MDS columns and partial
+    /// round constant terms from `shared` instead of recomputing them.
+    fn new_with_shared(
+        elements: Vec<AllocatedNum<E>>,
+        shared: &'a PoseidonCircuitShared<'a, E, Arity>,
+    ) -> Self {
+        PoseidonCircuit {
+            shared: Some(shared),
+            ..Self::new(elements, shared.constants)
+        }
+    }
+
     fn hash<CS: ConstraintSystem<E>>(
         &mut self,
         mut cs: CS,
@@ -78,9 +96,10 @@ where
             let round_key = self.constants.round_constants[constants_offset];
             constants_offset += 1;
 
-            self.elements[i] = quintic_s_box(
+            self.elements[i] = s_box(
                 cs.namespace(|| format!("quintic s-box {}", i)),
                 &self.elements[i],
+                self.constants.s_box_exponent(),
                 Some(round_key),
             )?
         }
@@ -95,14 +114,16 @@ where
         let round_key = self.constants.round_constants[self.constants_offset];
         self.constants_offset += 1;
         // Apply the quintic S-Box to the first element.
-        self.elements[0] = quintic_s_box(
+        self.elements[0] = s_box(
             cs.namespace(|| "solitary quintic s-box"),
             &self.elements[0],
+            self.constants.s_box_exponent(),
             Some(round_key),
         )?;
 
         // Multiply the elements by the constant MDS matrix
         self.product_mds(cs.namespace(|| "mds matrix product"), true)?;
+        self.partial_rounds_done += 1;
 
         Ok(())
     }
@@ -115,18 +136,14 @@ where
         let mut result: Vec<AllocatedNum<E>> = Vec::with_capacity(self.constants.width());
 
         for j in 0..self.constants.width() {
-            let column = self.constants.mds_matrices.m[j].to_vec();
-            // TODO: This could be cached per round to save synthesis time.
+            let column = self.mds_column(j);
             let constant_term = if add_round_keys {
-                let mut acc = E::Fr::zero();
-                // Dot product of column and this round's keys.
-                for k in 1..self.constants.width() {
-                    let mut tmp = column[k];
-                    let rk = self.constants.round_constants[self.constants_offset + k - 1];
-                    tmp.mul_assign(&rk);
-                    acc.add_assign(&tmp);
-                }
-                Some(acc)
+                Some(self.partial_round_term(
+                    j,
+                    &column,
+                    self.partial_rounds_done,
+                    self.constants_offset,
+                ))
             } else {
                 None
             };
@@ -147,6 +164,116 @@ where
         Ok(())
     }
 
+    /// Column `j` of the MDS matrix.
+    fn mds_column(&self, j: usize) -> Cow<'_, [E::Fr]> {
+        // The MDS matrix is symmetric, so row j of its transpose is the same as row j of
+        // the matrix itself.
+        if let Some(shared) = self.shared {
+            Cow::Borrowed(&shared.mds_columns[j])
+        } else if self.constants.circuit_precomputed_mds_columns {
+            Cow::Borrowed(&self.constants.mds_matrix_transposed[j])
+        } else {
+            Cow::Owned(self.constants.mds_matrices.m[j].to_vec())
+        }
+    }
+
+    /// The round keys folded into MDS column `j` by partial round `partial_round`, whose
+    /// round keys start at `constants_offset`.
+    fn partial_round_term(
+        &self,
+        j: usize,
+        column: &[E::Fr],
+        partial_round: usize,
+        constants_offset: usize,
+    ) -> E::Fr {
+        if let Some(shared) = self.shared {
+            shared.partial_round_terms[partial_round][j]
+        } else {
+            partial_round_constant_term::<E>(
+                column,
+                &self.constants.round_constants[constants_offset..],
+            )
+        }
+    }
+
+    /// Evaluates the hash of `preimage` on concrete field values, round by round as `hash`
+    /// computes its witnesses, but without a constraint system. The result is the value of
+    /// the hash `poseidon_hash` synthesizes for `preimage`, and equals the out-of-circuit
+    /// `Poseidon` hash.
+    pub fn compute_witness(&self, preimage: &[E::Fr]) -> E::Fr {
+        assert_eq!(
+            preimage.len(),
+            self.width - 1,
+            "preimage must hold exactly arity elements"
+        );
+        let mut elements = Vec::with_capacity(self.width);
+        elements.push(self.constants.arity_tag);
+        elements.extend_from_slice(preimage);
+
+        let mut constants_offset = 0;
+        let full_round = |elements: &mut Vec<E::Fr>, constants_offset: &mut usize| {
+            for element in elements.iter_mut() {
+                let round_key = self.constants.round_constants[*constants_offset];
+                *constants_offset += 1;
+                crate::s_box::<E>(
+                    element,
+                    self.constants.s_box_exponent(),
+                    Some(&round_key),
+                    None,
+                );
+            }
+            *elements = self.mds_product_value(elements, None, *constants_offset);
+        };
+
+        for _ in 0..self.constants.full_rounds / 2 {
+            full_round(&mut elements, &mut constants_offset);
+        }
+
+        for partial_round in 0..self.constants.partial_rounds {
+            let round_key = self.constants.round_constants[constants_offset];
+            constants_offset += 1;
+            crate::s_box::<E>(
+                &mut elements[0],
+                self.constants.s_box_exponent(),
+                Some(&round_key),
+                None,
+            );
+            elements = self.mds_product_value(&elements, Some(partial_round), constants_offset);
+            constants_offset += self.width - 1;
+        }
+
+        for _ in 0..self.constants.full_rounds / 2 {
+            full_round(&mut elements, &mut constants_offset);
+        }
+
+        elements[1]
+    }
+
+    /// The values `product_mds` computes for `elements`, adding the round key terms of
+    /// `partial_round` if given.
+    fn mds_product_value(
+        &self,
+        elements: &[E::Fr],
+        partial_round: Option<usize>,
+        constants_offset: usize,
+    ) -> Vec<E::Fr> {
+        (0..self.width)
+            .map(|j| {
+                let column = self.mds_column(j);
+                let mut acc = match partial_round {
+                    Some(round) => self.partial_round_term(j, &column, round, constants_offset),
+                    None => E::Fr::zero(),
+                };
+                for (element, scalar) in elements.iter().zip(column.iter()) {
+                    let mut tmp = *element;
+                    tmp.mul_assign(scalar);
+                    acc.add_assign(&tmp);
+                }
+                acc
+            })
+            .collect()
+    }
+
     fn debug(&self) {
         let element_frs: Vec<_> = self.elements.iter().map(|n| n.get_value()).collect();
         dbg!(element_frs, self.constants_offset);
@@ -160,9 +287,10 @@ where
         let round_key = self.constants.round_constants[self.constants_offset];
         self.constants_offset += 1;
         // Apply the quintic S-Box to the first element.
-        self.elements[0] = quintic_s_box(
+        self.elements[0] = s_box(
             cs.namespace(|| "solitary quintic s-box"),
             &self.elements[0],
+            self.constants.s_box_exponent(),
             Some(round_key),
         )?;
 
@@ -199,12 +327,90 @@ where
     }
 }
 
-/// Create circuit for Poseidon hash.
-pub fn poseidon_hash<CS, E, Arity>(
-    mut cs: CS,
+/// The round keys a partial round folds into the MDS product, for one `column` of the MDS
+/// matrix: the dot product of all but the first entry of `column` with `round_keys`.
+fn partial_round_constant_term<E: Engine>(column: &[E::Fr], round_keys: &[E::Fr]) -> E::Fr {
+    let mut acc = E::Fr::zero();
+    for (c, rk) in column[1..].iter().zip(round_keys) {
+        let mut tmp = *c;
+        tmp.mul_assign(rk);
+        acc.add_assign(&tmp);
+    }
+    acc
+}
+
+/// Synthesis data which is the same for every Poseidon hash using the same constants: the MDS
+/// columns and the round key terms of each partial round. Build it once and hash with it
+/// whenever a circuit contains many Poseidon hashes, to avoid recomputing it for every hash.
+/// The resulting constraints are identical to those of `poseidon_hash`.
+pub struct PoseidonCircuitShared<'a, E, Arity>
+where
+    E: Engine,
+    Arity: typenum::Unsigned
+        + std::ops::Add<typenum::bit::B1>
+        + std::ops::Add<typenum::uint::UInt<typenum::uint::UTerm, typenum::bit::B1>>,
+    typenum::Add1<Arity>: ArrayLength<E::Fr>,
+{
+    constants: &'a PoseidonConstants<E, Arity>,
+    mds_columns: Vec<Vec<E::Fr>>,
+    /// The constant term of each MDS column, for every partial round.
+    partial_round_terms: Vec<Vec<E::Fr>>,
+}
+
+impl<'a, E, Arity> PoseidonCircuitShared<'a, E, Arity>
+where
+    E: Engine,
+    Arity: typenum::Unsigned
+        + std::ops::Add<typenum::bit::B1>
+        + std::ops::Add<typenum::uint::UInt<typenum::uint::UTerm, typenum::bit::B1>>,
+    typenum::Add1<Arity>: ArrayLength<E::Fr>,
+{
+    pub fn new(constants: &'a PoseidonConstants<E, Arity>) -> Self {
+        let width = constants.width();
+        let mds_columns = constants.mds_matrix_transposed.clone();
+
+        // Each round consumes one round key per s-box, and a partial round additionally
+        // consumes the round keys folded into its MDS product.
+        let mut offset = (constants.full_rounds / 2) * width;
+        let partial_round_terms: Vec<Vec<E::Fr>> = (0..constants.partial_rounds)
+            .map(|_| {
+                offset += 1;
+                let round_keys = &constants.round_constants[offset..offset + width - 1];
+                offset += width - 1;
+
+                mds_columns
+                    .iter()
+                    .map(|column| partial_round_constant_term::<E>(column, round_keys))
+                    .collect()
+            })
+            .collect();
+
+        PoseidonCircuitShared {
+            constants,
+            mds_columns,
+            partial_round_terms,
+        }
+    }
+
+    /// Create circuit for Poseidon hash, like `poseidon_hash`.
+    pub fn hash<CS: ConstraintSystem<E>>(
+        &self,
+        mut cs: CS,
+        preimage: Vec<AllocatedNum<E>>,
+    ) -> Result<AllocatedNum<E>, SynthesisError> {
+        let preimage = tagged_preimage(&mut cs, preimage, self.constants)?;
+        let mut p = PoseidonCircuit::new_with_shared(preimage, self);
+
+        p.hash(cs)
+    }
+}
+
+/// Add the arity tag to the front of the preimage.
+fn tagged_preimage<CS, E, Arity>(
+    cs: &mut CS,
     mut preimage: Vec<AllocatedNum<E>>,
     constants: &PoseidonConstants<E, Arity>,
-) -> Result<AllocatedNum<E>, SynthesisError>
+) -> Result<Vec<AllocatedNum<E>>, SynthesisError>
 where
     CS: ConstraintSystem<E>,
     E: Engine,
@@ -213,11 +419,29 @@ where
         + std::ops::Add<typenum::uint::UInt<typenum::uint::UTerm, typenum::bit::B1>>,
     typenum::Add1<Arity>: ArrayLength<E::Fr>,
 {
-    // Add the arity tag to the front of the preimage.
     let tag = constants.arity_tag; // This could be shared across hash invocations within a circuit. TODO: add a mechanism for any such shared allocations.
     let tag_num = AllocatedNum::alloc(cs.namespace(|| "arity tag"), || Ok(tag))?;
     preimage.push(tag_num);
     preimage.rotate_right(1);
+
+    Ok(preimage)
+}
+
+/// Create circuit for Poseidon hash.
+pub fn poseidon_hash<CS, E, Arity>(
+    mut cs: CS,
+    preimage: Vec<AllocatedNum<E>>,
+    constants: &PoseidonConstants<E, Arity>,
+) -> Result<AllocatedNum<E>, SynthesisError>
+where
+    CS: ConstraintSystem<E>,
+    E: Engine,
+    Arity: typenum::Unsigned
+        + std::ops::Add<typenum::bit::B1>
+        + std::ops::Add<typenum::uint::UInt<typenum::uint::UTerm, typenum::bit::B1>>,
+    typenum::Add1<Arity>: ArrayLength<E::Fr>,
+{
+    let preimage = tagged_preimage(&mut cs, preimage, constants)?;
     let mut p = PoseidonCircuit::new(preimage, constants);
 
     p.hash(cs)
@@ -249,6 +473,140 @@ where
     poseidon_hash(cs, preimage, &create_poseidon_parameters::<E, Arity>())
 }
 
+/// Create circuit for Poseidon hash, returning the number of constraints it added along with
+/// the digest.
+///
+/// The constraints are counted as they are enforced on `cs`, so the count is exact for any
+/// constraint system, not only those which keep track of their constraints.
+pub fn poseidon_hash_counted<CS, E, Arity>(
+    cs: CS,
+    preimage: Vec<AllocatedNum<E>>,
+    constants: &PoseidonConstants<E, Arity>,
+) -> Result<(AllocatedNum<E>, usize), SynthesisError>
+where
+    CS: ConstraintSystem<E>,
+    E: Engine,
+    Arity: typenum::Unsigned
+        + std::ops::Add<typenum::bit::B1>
+        + std::ops::Add<typenum::uint::UInt<typenum::uint::UTerm, typenum::bit::B1>>,
+    typenum::Add1<Arity>: ArrayLength<E::Fr>,
+{
+    let mut cs = ConstraintCounter::new(cs);
+    let digest = poseidon_hash(&mut cs, preimage, constants)?;
+
+    Ok((digest, cs.constraints))
+}
+
+/// The number of constraints `poseidon_hash` adds for `Arity`, with the default quintic S-Box.
+pub fn poseidon_constraint_count<Arity: typenum::Unsigned>() -> usize {
+    let width = Arity::to_usize() + 1;
+    let (full_rounds, partial_rounds) = round_numbers(Arity::to_usize());
+
+    // Each quintic S-Box takes three constraints, and each element of each MDS product one.
+    let s_boxes = width * full_rounds + partial_rounds;
+    let mds_products = width * (full_rounds + partial_rounds);
+
+    3 * s_boxes + mds_products
+}
+
+/// Forwards everything to `cs`, counting the constraints enforced through it.
+struct ConstraintCounter<E: Engine, CS: ConstraintSystem<E>> {
+    cs: CS,
+    constraints: usize,
+    _e: PhantomData<E>,
+}
+
+impl<E: Engine, CS: ConstraintSystem<E>> ConstraintCounter<E, CS> {
+    fn new(cs: CS) -> Self {
+        ConstraintCounter {
+            cs,
+            constraints: 0,
+            _e: PhantomData,
+        }
+    }
+}
+
+impl<E: Engine, CS: ConstraintSystem<E>> ConstraintSystem<E> for ConstraintCounter<E, CS> {
+    type Root = Self;
+
+    fn one() -> Variable {
+        CS::one()
+    }
+
+    fn alloc<F, A, AR>(&mut self, annotation: A, f: F) -> Result<Variable, SynthesisError>
+    where
+        F: FnOnce() -> Result<E::Fr, SynthesisError>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        self.cs.alloc(annotation, f)
+    }
+
+    fn alloc_input<F, A, AR>(&mut self, annotation: A, f: F) -> Result<Variable, SynthesisError>
+    where
+        F: FnOnce() -> Result<E::Fr, SynthesisError>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        self.cs.alloc_input(annotation, f)
+    }
+
+    fn enforce<A, AR, LA, LB, LC>(&mut self, annotation: A, a: LA, b: LB, c: LC)
+    where
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+        LA: FnOnce(LinearCombination<E>) -> LinearCombination<E>,
+        LB: FnOnce(LinearCombination<E>) -> LinearCombination<E>,
+        LC: FnOnce(LinearCombination<E>) -> LinearCombination<E>,
+    {
+        self.constraints += 1;
+        self.cs.enforce(annotation, a, b, c)
+    }
+
+    fn push_namespace<NR, N>(&mut self, name_fn: N)
+    where
+        NR: Into<String>,
+        N: FnOnce() -> NR,
+    {
+        self.cs.get_root().push_namespace(name_fn)
+    }
+
+    fn pop_namespace(&mut self) {
+        self.cs.get_root().pop_namespace()
+    }
+
+    fn get_root(&mut self) -> &mut Self::Root {
+        self
+    }
+}
+
+/// Compute l^exponent and enforce constraint, for an exponent of 3 or 5. If round_key is
+/// supplied, add it to l first.
+fn s_box<CS: ConstraintSystem<E>, E: Engine>(
+    mut cs: CS,
+    l: &AllocatedNum<E>,
+    exponent: u64,
+    round_key: Option<E::Fr>,
+) -> Result<AllocatedNum<E>, SynthesisError> {
+    match exponent {
+        5 => quintic_s_box(cs, l, round_key),
+        3 => {
+            // If round_key was supplied, add it to l before squaring.
+            let l2 = if let Some(rk) = round_key {
+                square_sum(cs.namespace(|| "(l+rk)^2"), rk, l)?
+            } else {
+                l.square(cs.namespace(|| "l^2"))?
+            };
+            if let Some(rk) = round_key {
+                mul_sum(cs.namespace(|| "l2 * (l + rk)"), &l2, &l, rk)
+            } else {
+                l2.mul(cs.namespace(|| "l^3"), &l)
+            }
+        }
+        _ => Err(SynthesisError::Unsatisfiable),
+    }
+}
+
 /// Compute l^5 and enforce constraint. If round_key is supplied, add it to l first.
 fn quintic_s_box<CS: ConstraintSystem<E>, E: Engine>(
     mut cs: CS,
@@ -9,6 +9,8 @@ pub enum Error {
     IndexOutOfBounds,
     /// The provided leaf was not found in the tree
     Other(String),
+    /// Serialized constants were read into `PoseidonConstants` of another arity.
+    ArityMismatch { expected: usize, found: usize },
 }
 
 impl error::Error for Error {}
@@ -22,6 +24,11 @@ impl fmt::Display for Error {
             ),
             Error::IndexOutOfBounds => write!(f, "The referenced index is outs of bounds."),
             Error::Other(s) => write!(f, "{}", s),
+            Error::ArityMismatch { expected, found } => write!(
+                f,
+                "Expected constants of arity {}, found arity {}.",
+                expected, found
+            ),
         }
     }
 }
@@ -1,5 +1,6 @@
 #![allow(dead_code)]
 
+pub use crate::mds::MDSMatrices;
 pub use crate::poseidon::Poseidon;
 use crate::round_constants::generate_constants;
 pub use error::Error;
@@ -12,6 +13,8 @@ pub mod circuit;
 mod error;
 mod matrix;
 mod mds;
+/// Poseidon Merkle trees
+pub mod merkle;
 /// Poseidon hash
 pub mod poseidon;
 mod preprocessing;
@@ -37,6 +40,78 @@ pub fn round_numbers(arity: usize) -> (usize, usize) {
     (full_rounds, partial_rounds)
 }
 
+/// The round numbers for `arity` and the S-Box `x^s_box_exponent`, 3 or 5.
+///
+/// Those of the quintic S-Box are `round_numbers`. Those of the cubic S-Box are computed by
+/// `calc_round_numbers`, which also yields `round_numbers` for the quintic one.
+pub fn round_numbers_with_s_box_exponent(arity: usize, s_box_exponent: u64) -> (usize, usize) {
+    match s_box_exponent {
+        5 => round_numbers(arity),
+        3 => calc_round_numbers(arity + 1, s_box_exponent),
+        _ => panic!("unsupported S-Box exponent"),
+    }
+}
+
+/// The security level, in bits, the round numbers are chosen for.
+const SECURITY_LEVEL: f64 = 128.0;
+
+/// Computes the round numbers of `width` and the S-Box `x^s_box_exponent` the way the
+/// `calc_round_numbers.py` script accompanying the Poseidon paper (Grassi et al., ePrint
+/// 2019/458) does: the secure round numbers with the fewest S-Boxes, after adding the security
+/// margin of two full rounds and 7.5% more partial rounds.
+pub fn calc_round_numbers(width: usize, s_box_exponent: u64) -> (usize, usize) {
+    let mut best: Option<(usize, usize, usize)> = None;
+
+    for full_rounds in (2..=1000).step_by(2) {
+        for partial_rounds in 4..200 {
+            if !rounds_are_secure(width, s_box_exponent, full_rounds, partial_rounds) {
+                continue;
+            }
+
+            let full_rounds = full_rounds + 2;
+            let partial_rounds = (1.075 * partial_rounds as f64).ceil() as usize;
+            let cost = width * full_rounds + partial_rounds;
+            let better = best.map_or(true, |(best_cost, best_full_rounds, _)| {
+                cost < best_cost || (cost == best_cost && full_rounds < best_full_rounds)
+            });
+            if better {
+                best = Some((cost, full_rounds, partial_rounds));
+            }
+        }
+    }
+
+    let (_, full_rounds, partial_rounds) = best.expect("no secure round numbers");
+    (full_rounds, partial_rounds)
+}
+
+/// Whether the round numbers resist the statistical, interpolation and Gröbner basis attacks
+/// on Poseidon over a `FIELD_SIZE` bit field, per the bounds of the Poseidon paper.
+fn rounds_are_secure(
+    width: usize,
+    s_box_exponent: u64,
+    full_rounds: usize,
+    partial_rounds: usize,
+) -> bool {
+    let n = FIELD_SIZE as f64;
+    let m = SECURITY_LEVEL;
+    let t = width as f64;
+    let r_p = partial_rounds as f64;
+    let inv_log_alpha = 1.0 / (s_box_exponent as f64).log2();
+
+    let statistical = if m <= (n - 3.0) * (t + 1.0) {
+        6.0
+    } else {
+        10.0
+    };
+    let interpolation = inv_log_alpha * m + t.log2() - r_p;
+    let groebner_1 = inv_log_alpha / 2.0 * n - r_p;
+    let groebner_2 = (inv_log_alpha / 3.0 * n - 1.0 - r_p) / (t - 1.0);
+
+    [statistical, interpolation, groebner_1, groebner_2]
+        .iter()
+        .all(|bound| full_rounds as f64 >= bound.ceil())
+}
+
 /// convert
 pub fn scalar_from_u64<E: ScalarEngine>(i: u64) -> E::Fr {
     <E::Fr as PrimeField>::from_repr(<<E::Fr as PrimeField>::Repr as From<u64>>::from(i)).unwrap()
@@ -47,36 +122,103 @@ pub fn scalar_from_u64s(parts: [u64; 4]) -> Scalar {
     Scalar::from_repr(FrRepr(parts)).unwrap()
 }
 
-const SBOX: u8 = 1; // x^5
 const FIELD: u8 = 1; // Gf(p)
 const FIELD_SIZE: usize = 255; // n  Maybe Get this from Scalar.
 
-fn round_constants<E: ScalarEngine>(arity: usize) -> Vec<E::Fr> {
+fn round_constants<E: ScalarEngine>(arity: usize, s_box_exponent: u64) -> Vec<E::Fr> {
     let t = arity + 1;
     let n = t * FIELD_SIZE;
 
-    let (full_rounds, partial_rounds) = round_numbers(arity);
+    let (full_rounds, partial_rounds) = round_numbers_with_s_box_exponent(arity, s_box_exponent);
 
     let r_f = full_rounds as u16;
     let r_p = partial_rounds as u16;
-    generate_constants::<E>(FIELD, SBOX, n as u16, t as u16, r_f, r_p)
+    let s_box = s_box_id(s_box_exponent);
+    generate_constants::<E>(FIELD, s_box, n as u16, t as u16, r_f, r_p)
 }
 
-/// Apply the quintic S-Box (s^5) to a given item
-fn quintic_s_box<E: ScalarEngine>(
+/// The S-Box id the round constants are generated for. The quintic S-Box keeps the id its
+/// constants have always been generated with; the cubic one has the id of `x^alpha` S-Boxes
+/// in the reference Grain LFSR parameter generation.
+fn s_box_id(s_box_exponent: u64) -> u8 {
+    match s_box_exponent {
+        5 => 1,
+        3 => 0,
+        _ => panic!("unsupported S-Box exponent"),
+    }
+}
+
+/// The S-Box exponent used unless `PoseidonConstants` ask for another one.
+pub const DEFAULT_S_BOX_EXPONENT: u64 = 5;
+
+/// Returns whether `x^exponent` is a permutation of `E::Fr`, i.e. whether `exponent` is coprime
+/// to `p - 1`. Only prime exponents are supported, for which it suffices that `p != 1 (mod e)`.
+pub(crate) fn s_box_is_permutation<E: ScalarEngine>(exponent: u64) -> bool {
+    let exponent = u128::from(exponent);
+    let modulus = <E::Fr as PrimeField>::char();
+    // The limbs are little-endian, so reduce from the most significant one down.
+    let remainder = modulus.as_ref().iter().rev().fold(0u128, |acc, &limb| {
+        ((acc << 64) + u128::from(limb)) % exponent
+    });
+
+    remainder != 1
+}
+
+/// Apply the S-Box (s^`exponent`) to all elements, after adding the corresponding round key.
+/// If `round_keys` is empty, no round keys are added. Equivalent to calling `s_box` on each
+/// element, but keeps the whole layer in a single call.
+fn s_box_batch<E: ScalarEngine>(elements: &mut [E::Fr], exponent: u64, round_keys: &[E::Fr]) {
+    if round_keys.is_empty() {
+        elements
+            .iter_mut()
+            .for_each(|l| s_box::<E>(l, exponent, None, None));
+    } else {
+        assert!(
+            round_keys.len() >= elements.len(),
+            "Not enough round keys for the S-box layer"
+        );
+        elements
+            .iter_mut()
+            .zip(round_keys)
+            .for_each(|(l, key)| s_box::<E>(l, exponent, Some(key), None));
+    }
+}
+
+/// Apply the S-Box (s^`exponent`) to a given item. Exponents 3 and 5 use fixed addition chains.
+fn s_box<E: ScalarEngine>(
     l: &mut E::Fr,
+    exponent: u64,
     pre_add: Option<&E::Fr>,
     post_add: Option<&E::Fr>,
 ) {
     if let Some(x) = pre_add {
         l.add_assign(x);
     }
-    let c = *l;
-    let mut tmp = l.clone();
-    tmp.mul_assign(&c);
-    tmp.mul_assign(&tmp.clone());
-    l.mul_assign(&tmp);
+    match exponent {
+        5 => {
+            let c = *l;
+            let mut tmp = l.clone();
+            tmp.mul_assign(&c);
+            tmp.mul_assign(&tmp.clone());
+            l.mul_assign(&tmp);
+        }
+        3 => {
+            let c = *l;
+            l.square();
+            l.mul_assign(&c);
+        }
+        _ => *l = l.pow([exponent]),
+    }
     if let Some(x) = post_add {
         l.add_assign(x);
     }
 }
+
+/// Apply the quintic S-Box (s^5) to a given item
+fn quintic_s_box<E: ScalarEngine>(
+    l: &mut E::Fr,
+    pre_add: Option<&E::Fr>,
+    post_add: Option<&E::Fr>,
+) {
+    s_box::<E>(l, DEFAULT_S_BOX_EXPONENT, pre_add, post_add)
+}
@@ -1,7 +1,9 @@
 use ff::{Field, ScalarEngine};
 
-use crate::matrix::{apply_matrix, invert, is_invertible, mat_mul, minor, Matrix, Scalar};
-use crate::scalar_from_u64;
+use crate::matrix::{
+    apply_matrix, invert, is_identity, is_invertible, mat_mul, minor, Matrix, Scalar,
+};
+use crate::{scalar_from_u64, Error};
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct MDSMatrices<E: ScalarEngine> {
@@ -13,6 +15,47 @@ pub struct MDSMatrices<E: ScalarEngine> {
     pub m_double_prime: Matrix<Scalar<E>>,
 }
 
+impl<E: ScalarEngine> MDSMatrices<E> {
+    /// The rows of the MDS matrix `m`, e.g. to compare it with the matrix of another Poseidon
+    /// implementation.
+    pub fn to_rows(&self) -> Vec<Vec<E::Fr>> {
+        self.m.clone()
+    }
+
+    /// Derives the matrices from the rows of an MDS matrix `m`, as exported by `to_rows`.
+    /// Fails unless `rows` form a square matrix of at least 2x2 which, like its minor `m_hat`,
+    /// is invertible.
+    pub fn from_rows(rows: Vec<Vec<E::Fr>>) -> Result<Self, Error> {
+        let size = rows.len();
+        if size < 2 || rows.iter().any(|row| row.len() != size) {
+            return Err(Error::Other("MDS matrix must be square and >= 2x2".into()));
+        }
+        if !is_invertible::<E>(&rows) {
+            return Err(Error::Other("MDS matrix must be invertible".into()));
+        }
+        if !is_invertible::<E>(&minor::<E>(&rows, 0, 0)) {
+            return Err(Error::Other("MDS matrix minor must be invertible".into()));
+        }
+
+        Ok(derive_mds_matrices(rows))
+    }
+
+    /// Checks that the matrices derived from `m` are consistent with it: `m_inv` and
+    /// `m_hat_inv` are the inverses of `m` and of its minor `m_hat`, and `m_prime` and
+    /// `m_double_prime` factor `m`. This does not check that every square submatrix of `m`
+    /// is invertible.
+    pub fn verify_mds(&self) -> bool {
+        let is_inverse = |a: &Matrix<Scalar<E>>, b: &Matrix<Scalar<E>>| {
+            mat_mul::<E>(a, b).map_or(false, |product| is_identity::<E>(&product))
+        };
+
+        is_inverse(&self.m_inv, &self.m)
+            && self.m_hat == minor::<E>(&self.m, 0, 0)
+            && is_inverse(&self.m_hat_inv, &self.m_hat)
+            && mat_mul::<E>(&self.m_prime, &self.m_double_prime).as_ref() == Some(&self.m)
+    }
+}
+
 pub fn create_mds_matrices<'a, E: ScalarEngine>(t: usize) -> MDSMatrices<E> {
     let m = generate_mds::<E>(t);
     derive_mds_matrices(m)
@@ -288,4 +331,28 @@ mod tests {
         assert_eq!(expected, actual);
     }
     */
+
+    use super::*;
+    use paired::bls12_381::Bls12;
+
+    #[test]
+    fn mds_rows_round_trip() {
+        for width in 2..=12 {
+            let mds = create_mds_matrices::<Bls12>(width);
+            assert!(mds.verify_mds());
+
+            let rows = mds.to_rows();
+            let from_rows = MDSMatrices::<Bls12>::from_rows(rows).unwrap();
+            assert_eq!(from_rows, mds);
+            assert!(from_rows.verify_mds());
+        }
+
+        let mut not_square = create_mds_matrices::<Bls12>(3).to_rows();
+        not_square[1].pop();
+        assert!(MDSMatrices::<Bls12>::from_rows(not_square).is_err());
+
+        let mut singular = create_mds_matrices::<Bls12>(3).to_rows();
+        singular[1] = singular[0].clone();
+        assert!(MDSMatrices::<Bls12>::from_rows(singular).is_err());
+    }
 }
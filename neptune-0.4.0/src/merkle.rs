@@ -0,0 +1,211 @@
+use crate::poseidon::{Poseidon, PoseidonConstants};
+use crate::Error;
+use ff::{Field, ScalarEngine};
+use generic_array::{typenum, ArrayLength};
+use std::ops::Add;
+use typenum::bit::B1;
+use typenum::marker_traits::Unsigned;
+use typenum::uint::{UInt, UTerm};
+use typenum::Add1;
+
+/// Root of the Merkle tree over `leaves` whose nodes are the Poseidon hashes of their `Arity`
+/// children. The number of leaves must be a power of the arity, a single leaf being its own
+/// root.
+pub fn poseidon_merkle_root<E, Arity>(
+    leaves: &[E::Fr],
+    constants: &PoseidonConstants<E, Arity>,
+) -> Result<E::Fr, Error>
+where
+    E: ScalarEngine,
+    Arity: Unsigned + Add<B1> + Add<UInt<UTerm, B1>>,
+    Add1<Arity>: ArrayLength<E::Fr>,
+{
+    let arity = Arity::to_usize();
+    let mut len = leaves.len();
+    while len > 1 && len % arity == 0 {
+        len /= arity;
+    }
+    if len != 1 {
+        return Err(Error::Other(format!(
+            "{} leaves are not a power of the arity {}",
+            leaves.len(),
+            arity
+        )));
+    }
+
+    let mut level = leaves.to_vec();
+    while level.len() > 1 {
+        level = level
+            .chunks(arity)
+            .map(|children| Poseidon::new_with_preimage(children, constants).hash())
+            .collect();
+    }
+
+    Ok(level[0])
+}
+
+/// A Poseidon Merkle tree of a fixed height, which is built by appending leaves one at a time.
+///
+/// Only the rightmost path is kept: the complete subtrees left of it are represented by their
+/// roots, so an append hashes at most one node per level, and the root is updated with one
+/// hash per level. Leaves not yet appended are zero, so once all `Arity^height` leaves are
+/// appended, the root is the `poseidon_merkle_root` of the leaves.
+#[derive(Debug, Clone)]
+pub struct IncrementalPoseidonTree<'a, E, Arity>
+where
+    E: ScalarEngine,
+    Arity: Unsigned + Add<B1> + Add<UInt<UTerm, B1>>,
+    Add1<Arity>: ArrayLength<E::Fr>,
+{
+    height: usize,
+    num_leaves: usize,
+    /// For each level, the roots of the complete subtrees left of the rightmost path that are
+    /// siblings of its node at that level. The last level holds the root of a full tree.
+    frontier: Vec<Vec<E::Fr>>,
+    /// The root of an empty subtree, for each level.
+    empty_roots: Vec<E::Fr>,
+    root: E::Fr,
+    constants: &'a PoseidonConstants<E, Arity>,
+}
+
+impl<'a, E, Arity> IncrementalPoseidonTree<'a, E, Arity>
+where
+    E: ScalarEngine,
+    Arity: Unsigned + Add<B1> + Add<UInt<UTerm, B1>>,
+    Add1<Arity>: ArrayLength<E::Fr>,
+{
+    /// An empty tree with room for `Arity^height` leaves.
+    pub fn new(height: usize, constants: &'a PoseidonConstants<E, Arity>) -> Self {
+        let mut empty_roots = vec![E::Fr::zero()];
+        for level in 0..height {
+            let children = vec![empty_roots[level]; Arity::to_usize()];
+            empty_roots.push(Poseidon::new_with_preimage(&children, constants).hash());
+        }
+
+        IncrementalPoseidonTree {
+            height,
+            num_leaves: 0,
+            frontier: vec![Vec::new(); height + 1],
+            root: empty_roots[height],
+            empty_roots,
+            constants,
+        }
+    }
+
+    /// Append `leaf` after the leaves appended so far, and update the root.
+    pub fn append(&mut self, leaf: E::Fr) -> Result<(), Error> {
+        let arity = Arity::to_usize();
+        if self.num_leaves == arity.pow(self.height as u32) {
+            return Err(Error::FullBuffer);
+        }
+
+        // Carry the completed subtrees up, until a level has room for one more.
+        let mut node = leaf;
+        let mut level = 0;
+        while level < self.height && self.frontier[level].len() == arity - 1 {
+            let mut children = std::mem::replace(&mut self.frontier[level], Vec::new());
+            children.push(node);
+            node = self.hash(&children);
+            level += 1;
+        }
+        self.frontier[level].push(node);
+        self.num_leaves += 1;
+
+        self.root = self.compute_root();
+        Ok(())
+    }
+
+    pub fn root(&self) -> E::Fr {
+        self.root
+    }
+
+    pub fn num_leaves(&self) -> usize {
+        self.num_leaves
+    }
+
+    fn compute_root(&self) -> E::Fr {
+        if let Some(root) = self.frontier[self.height].first() {
+            return *root;
+        }
+
+        // The node of the rightmost path at each level, `None` while its subtree is empty.
+        let mut node = None;
+        for level in 0..self.height {
+            if self.frontier[level].is_empty() && node.is_none() {
+                continue;
+            }
+
+            let mut children = self.frontier[level].clone();
+            children.extend(node);
+            children.resize(Arity::to_usize(), self.empty_roots[level]);
+            node = Some(self.hash(&children));
+        }
+
+        node.unwrap_or(self.empty_roots[self.height])
+    }
+
+    fn hash(&self, children: &[E::Fr]) -> E::Fr {
+        Poseidon::new_with_preimage(children, self.constants).hash()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Scalar;
+    use generic_array::typenum::{U2, U4};
+    use paired::bls12_381::Bls12;
+    use rand::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+
+    #[test]
+    fn incremental_root_matches_merkle_root() {
+        let mut rng = XorShiftRng::from_seed(crate::TEST_SEED);
+        let constants = PoseidonConstants::<Bls12, U2>::new();
+        let leaves: Vec<Scalar> = (0..8).map(|_| Scalar::random(&mut rng)).collect();
+
+        let mut tree = IncrementalPoseidonTree::new(3, &constants);
+        assert_eq!(tree.num_leaves(), 0);
+        assert_eq!(
+            tree.root(),
+            poseidon_merkle_root(&[Scalar::zero(); 8], &constants).unwrap()
+        );
+
+        for (i, leaf) in leaves.iter().enumerate() {
+            tree.append(*leaf).unwrap();
+            assert_eq!(tree.num_leaves(), i + 1);
+
+            // Leaves not yet appended are zero.
+            let mut padded = leaves[..=i].to_vec();
+            padded.resize(8, Scalar::zero());
+            assert_eq!(
+                tree.root(),
+                poseidon_merkle_root(&padded, &constants).unwrap()
+            );
+        }
+
+        assert_eq!(
+            tree.root(),
+            poseidon_merkle_root(&leaves, &constants).unwrap()
+        );
+        assert!(tree.append(Scalar::zero()).is_err());
+    }
+
+    #[test]
+    fn incremental_root_higher_arity() {
+        let mut rng = XorShiftRng::from_seed(crate::TEST_SEED);
+        let constants = PoseidonConstants::<Bls12, U4>::new();
+        let leaves: Vec<Scalar> = (0..16).map(|_| Scalar::random(&mut rng)).collect();
+
+        let mut tree = IncrementalPoseidonTree::new(2, &constants);
+        for leaf in &leaves {
+            tree.append(*leaf).unwrap();
+        }
+
+        assert_eq!(
+            tree.root(),
+            poseidon_merkle_root(&leaves, &constants).unwrap()
+        );
+        assert!(poseidon_merkle_root(&leaves[..8], &constants).is_err());
+    }
+}
@@ -1,16 +1,18 @@
 use crate::matrix::Matrix;
 use crate::mds::{create_mds_matrices, factor_to_sparse_matrices, MDSMatrices};
 use crate::preprocessing::compress_round_constants;
-use crate::{matrix, quintic_s_box};
-use crate::{round_constants, round_numbers, scalar_from_u64, Error};
-use ff::{Field, ScalarEngine};
+use crate::{matrix, s_box, s_box_batch, s_box_is_permutation, DEFAULT_S_BOX_EXPONENT};
+use crate::{round_constants, round_numbers_with_s_box_exponent, scalar_from_u64, Error};
+use ff::{Field, PrimeField, PrimeFieldRepr, ScalarEngine};
 use generic_array::{sequence::GenericSequence, typenum, ArrayLength, GenericArray};
+use std::io::{Read, Write};
 use std::marker::PhantomData;
 use std::ops::Add;
+use std::thread;
 use typenum::bit::B1;
 use typenum::marker_traits::Unsigned;
 use typenum::uint::{UInt, UTerm};
-use typenum::{Add1, U2};
+use typenum::{Add1, U11, U2, U4, U8};
 
 /// The arity tag is the first element of a Poseidon permutation.
 /// This extra element is necessary for 128-bit security.
@@ -18,6 +20,20 @@ pub fn arity_tag<E: ScalarEngine, Arity: Unsigned>() -> E::Fr {
     scalar_from_u64::<E>((1 << Arity::to_usize()) - 1)
 }
 
+/// Derive a domain tag from a human-readable label, such as `"filecoin-porep-v1"`.
+///
+/// The tag can replace `PoseidonConstants::arity_tag`, so that protocols hashing with the same
+/// arity use distinct domains. It is the first 31 bytes of the label's Blake2s digest, read as
+/// a little-endian field element: the mapping is collision-resistant (as Blake2s truncated to
+/// 248 bits), so distinct labels yield distinct tags except with negligible probability, and
+/// a tag equals one of the small default arity tags only with negligible probability.
+pub fn domain_tag_from_label<E: ScalarEngine>(label: &str) -> E::Fr {
+    let digest = blake2s_simd::blake2s(label.as_bytes());
+
+    bytes_to_scalar::<E>(&digest.as_bytes()[..BYTES_PER_ELEMENT])
+        .expect("31 bytes always fit in a field element")
+}
+
 /// The `Poseidon` structure will accept a number of inputs equal to the arity.
 #[derive(Debug, Clone, PartialEq)]
 pub struct Poseidon<'a, E, Arity = U2>
@@ -31,10 +47,31 @@ where
     /// the elements to permute
     pub elements: GenericArray<E::Fr, Add1<Arity>>,
     pos: usize,
+    overflow_policy: OverflowPolicy,
+    /// The digest memoized by `hash_cached`, cleared whenever the preimage changes.
+    cached_digest: Option<E::Fr>,
     constants: &'a PoseidonConstants<E, Arity>,
     _e: PhantomData<E>,
 }
 
+/// What `Poseidon::input` does with an element once all arity positions are filled.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OverflowPolicy {
+    /// Reject the element with `Error::FullBuffer`.
+    Error,
+    /// Hash the full block, then start a new block whose first element is the digest, and
+    /// absorb the element into it. This allows streaming an arbitrary number of elements.
+    Permute,
+    /// Silently drop the element.
+    Ignore,
+}
+
+impl Default for OverflowPolicy {
+    fn default() -> Self {
+        OverflowPolicy::Error
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct PoseidonConstants<E, Arity>
 where
@@ -43,6 +80,12 @@ where
     Add1<Arity>: ArrayLength<E::Fr>,
 {
     pub mds_matrices: MDSMatrices<E>,
+    /// Transpose of `mds_matrices.m`, so that MDS products can walk rows contiguously.
+    pub mds_matrix_transposed: Matrix<E::Fr>,
+    /// Whether circuit synthesis reads MDS columns from `mds_matrix_transposed`, rather than
+    /// copying each column out of `mds_matrices.m` every round. Enabled by default; this
+    /// only affects synthesis time, never the constraints.
+    pub circuit_precomputed_mds_columns: bool,
     pub round_constants: Vec<E::Fr>,
     pub compressed_round_constants: Vec<E::Fr>,
     pub sparse_matrices: Vec<Matrix<E::Fr>>,
@@ -50,10 +93,13 @@ where
     pub full_rounds: usize,
     pub half_full_rounds: usize,
     pub partial_rounds: usize,
+    /// The exponent `e` of the S-Box `x^e`. `x^e` must be a permutation of the field, which
+    /// `new_with_s_box_exponent` checks.
+    s_box_exponent: u64,
     _a: PhantomData<Arity>,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum HashMode {
     // The initial and correct version of the algorithm. We should preserve the ability to hash this way for reference
     // and to preserve confidence in our tests along thew way.
@@ -75,14 +121,21 @@ where
     Add1<Arity>: ArrayLength<E::Fr>,
 {
     pub fn new() -> Self {
+        Self::with_s_box_exponent(DEFAULT_S_BOX_EXPONENT)
+    }
+
+    /// Like `new_with_s_box_exponent`, but does not check that `x^s_box_exponent` is a
+    /// permutation of the field.
+    fn with_s_box_exponent(s_box_exponent: u64) -> Self {
         let arity = Arity::to_usize();
         let width = arity + 1;
 
         let mds_matrices = create_mds_matrices::<E>(width);
 
-        let (full_rounds, partial_rounds) = round_numbers(arity);
+        let (full_rounds, partial_rounds) =
+            round_numbers_with_s_box_exponent(arity, s_box_exponent);
         let half_full_rounds = full_rounds / 2;
-        let round_constants = round_constants::<E>(arity);
+        let round_constants = round_constants::<E>(arity, s_box_exponent);
         let compressed_round_constants = compress_round_constants::<E>(
             width,
             full_rounds,
@@ -94,20 +147,12 @@ where
 
         let sparse_matrices =
             factor_to_sparse_matrices::<E>(mds_matrices.m.clone(), partial_rounds);
+        let mds_matrix_transposed = matrix::transpose::<E>(&mds_matrices.m);
 
-        // Ensure we have enough constants for the sbox rounds
-        assert!(
-            width * (full_rounds + partial_rounds) <= round_constants.len(),
-            "Not enough round constants"
-        );
-
-        assert_eq!(
-            full_rounds * width + partial_rounds,
-            compressed_round_constants.len()
-        );
-
-        Self {
+        let constants = Self {
             mds_matrices,
+            mds_matrix_transposed,
+            circuit_precomputed_mds_columns: true,
             round_constants,
             compressed_round_constants,
             sparse_matrices,
@@ -115,8 +160,29 @@ where
             full_rounds,
             half_full_rounds,
             partial_rounds,
+            s_box_exponent,
             _a: PhantomData::<Arity>,
-        }
+        };
+        // Ensure we have enough constants for the sbox rounds, and that they fit the arity
+        constants.assert_invariants();
+
+        constants
+    }
+
+    /// Like `new`, but uses `x^s_box_exponent` as the S-Box. Exponents 3 and 5 are supported,
+    /// and only on fields where they are a permutation: BLS12-381 needs 5, since 3 divides
+    /// `p - 1`. The round numbers are those of `round_numbers_with_s_box_exponent`, and the
+    /// round constants are generated for them.
+    pub fn new_with_s_box_exponent(s_box_exponent: u64) -> Result<Self, Error> {
+        check_s_box_exponent::<E>(s_box_exponent)?;
+
+        Ok(Self::with_s_box_exponent(s_box_exponent))
+    }
+
+    /// Returns the exponent `e` of the S-Box `x^e`.
+    #[inline]
+    pub fn s_box_exponent(&self) -> u64 {
+        self.s_box_exponent
     }
 
     /// Returns the width.
@@ -130,6 +196,218 @@ where
     pub fn width(&self) -> usize {
         Add1::<Arity>::to_usize()
     }
+
+    /// Serializes the constants to `w`, so they can be loaded with `read_from` instead of
+    /// being generated again.
+    ///
+    /// The constants start with a header of the arity and the width, so that `read_from` can
+    /// reject constants of another arity. Integers are written as little-endian `u64`s and
+    /// field elements in their little-endian representation; vectors and matrices are
+    /// prefixed with their dimensions.
+    pub fn write_to<W: Write>(&self, w: &mut W) -> Result<(), Error> {
+        write_usize(w, self.arity())?;
+        write_usize(w, self.width())?;
+
+        write_usize(w, self.full_rounds)?;
+        write_usize(w, self.partial_rounds)?;
+        write_u64(w, self.s_box_exponent)?;
+        write_u64(w, self.circuit_precomputed_mds_columns as u64)?;
+        write_scalar::<E, _>(w, &self.arity_tag)?;
+
+        write_vector::<E, _>(w, &self.round_constants)?;
+        write_vector::<E, _>(w, &self.compressed_round_constants)?;
+
+        let mds = &self.mds_matrices;
+        for m in &[
+            &mds.m,
+            &mds.m_inv,
+            &mds.m_hat,
+            &mds.m_hat_inv,
+            &mds.m_prime,
+            &mds.m_double_prime,
+            &self.mds_matrix_transposed,
+        ] {
+            write_matrix::<E, _>(w, m)?;
+        }
+
+        write_usize(w, self.sparse_matrices.len())?;
+        for m in &self.sparse_matrices {
+            write_matrix::<E, _>(w, m)?;
+        }
+
+        Ok(())
+    }
+
+    /// Deserializes constants written by `write_to`. Returns `Error::ArityMismatch` if they
+    /// were written for another arity than `Arity`.
+    pub fn read_from<R: Read>(r: &mut R) -> Result<Self, Error> {
+        let expected = Arity::to_usize();
+        let found = read_usize(r)?;
+        if found != expected {
+            return Err(Error::ArityMismatch { expected, found });
+        }
+        let width = read_usize(r)?;
+        if width != Add1::<Arity>::to_usize() {
+            return Err(Error::Other(format!(
+                "width {} does not match arity {}",
+                width, found
+            )));
+        }
+
+        let full_rounds = read_usize(r)?;
+        let partial_rounds = read_usize(r)?;
+        let s_box_exponent = read_u64(r)?;
+        check_s_box_exponent::<E>(s_box_exponent)?;
+        let circuit_precomputed_mds_columns = read_u64(r)? != 0;
+        let arity_tag = read_scalar::<E, _>(r)?;
+
+        let round_constants = read_vector::<E, _>(r)?;
+        let compressed_round_constants = read_vector::<E, _>(r)?;
+
+        let mds_matrices = MDSMatrices {
+            m: read_matrix::<E, _>(r)?,
+            m_inv: read_matrix::<E, _>(r)?,
+            m_hat: read_matrix::<E, _>(r)?,
+            m_hat_inv: read_matrix::<E, _>(r)?,
+            m_prime: read_matrix::<E, _>(r)?,
+            m_double_prime: read_matrix::<E, _>(r)?,
+        };
+        let mds_matrix_transposed = read_matrix::<E, _>(r)?;
+
+        let sparse_matrices = (0..read_usize(r)?)
+            .map(|_| read_matrix::<E, _>(r))
+            .collect::<Result<_, _>>()?;
+
+        let constants = Self {
+            mds_matrices,
+            mds_matrix_transposed,
+            circuit_precomputed_mds_columns,
+            round_constants,
+            compressed_round_constants,
+            sparse_matrices,
+            arity_tag,
+            full_rounds,
+            half_full_rounds: full_rounds / 2,
+            partial_rounds,
+            s_box_exponent,
+            _a: PhantomData::<Arity>,
+        };
+        constants.check_invariants()?;
+
+        Ok(constants)
+    }
+
+    /// Panics unless the constants are consistent with `Arity` and with each other.
+    pub(crate) fn assert_invariants(&self) {
+        if let Err(e) = self.check_invariants() {
+            panic!("inconsistent Poseidon constants: {}", e);
+        }
+    }
+
+    /// Checks that the runtime width and round numbers agree with `Arity`, that there are as
+    /// many round constants and sparse matrices as the rounds use, and that every matrix has
+    /// the dimensions of the width.
+    fn check_invariants(&self) -> Result<(), Error> {
+        let width = self.width();
+        let check = |ok: bool, what: &str| {
+            if ok {
+                Ok(())
+            } else {
+                Err(Error::Other(format!("{} (arity {})", what, self.arity())))
+            }
+        };
+
+        // Whether `m` is `n` x `n`, row by row.
+        let is_square =
+            |m: &Matrix<E::Fr>, n: usize| m.len() == n && m.iter().all(|row| row.len() == n);
+        let mds = &self.mds_matrices;
+
+        check(width == self.arity() + 1, "width is not arity + 1")?;
+        check(
+            [
+                &mds.m,
+                &mds.m_inv,
+                &mds.m_prime,
+                &mds.m_double_prime,
+                &self.mds_matrix_transposed,
+            ]
+            .iter()
+            .all(|m| is_square(*m, width)),
+            "MDS matrices do not match the width",
+        )?;
+        check(
+            is_square(&mds.m_hat, width - 1) && is_square(&mds.m_hat_inv, width - 1),
+            "MDS minors do not match the width",
+        )?;
+        check(self.full_rounds % 2 == 0, "odd number of full rounds")?;
+        check(
+            self.half_full_rounds * 2 == self.full_rounds,
+            "half_full_rounds is not half of full_rounds",
+        )?;
+        check(
+            self.round_constants.len() >= width * (self.full_rounds + self.partial_rounds),
+            "not enough round constants",
+        )?;
+        check(
+            self.compressed_round_constants.len() == self.full_rounds * width + self.partial_rounds,
+            "wrong number of compressed round constants",
+        )?;
+        check(
+            self.sparse_matrices.len() == self.partial_rounds + 1,
+            "wrong number of sparse matrices",
+        )?;
+        check(
+            self.sparse_matrices.iter().all(|m| is_square(m, width)),
+            "sparse matrices do not match the width",
+        )
+    }
+}
+
+/// Checks that `x^s_box_exponent` can be used as the S-Box over `E::Fr`: exponents 3 and 5
+/// are supported, and only where they are a permutation of the field.
+fn check_s_box_exponent<E: ScalarEngine>(s_box_exponent: u64) -> Result<(), Error> {
+    if s_box_exponent != 3 && s_box_exponent != 5 {
+        return Err(Error::Other(format!(
+            "unsupported S-Box exponent: {}",
+            s_box_exponent
+        )));
+    }
+    if !s_box_is_permutation::<E>(s_box_exponent) {
+        return Err(Error::Other(format!(
+            "x^{} is not a permutation of the field",
+            s_box_exponent
+        )));
+    }
+
+    Ok(())
+}
+
+/// `PoseidonConstants` for each of the commonly used arities, built by `new_parallel`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommonPoseidonConstants<E: ScalarEngine> {
+    pub arity2: PoseidonConstants<E, U2>,
+    pub arity4: PoseidonConstants<E, U4>,
+    pub arity8: PoseidonConstants<E, U8>,
+    pub arity11: PoseidonConstants<E, U11>,
+}
+
+impl<E: ScalarEngine> CommonPoseidonConstants<E> {
+    /// Builds the constants of every arity concurrently, each on its own thread. Building
+    /// `PoseidonConstants` is expensive, and the constants of different arities are
+    /// independent, so this takes about as long as building the largest arity alone.
+    pub fn new_parallel() -> Self {
+        let arity4 = thread::spawn(PoseidonConstants::<E, U4>::new);
+        let arity8 = thread::spawn(PoseidonConstants::<E, U8>::new);
+        let arity11 = thread::spawn(PoseidonConstants::<E, U11>::new);
+        let arity2 = PoseidonConstants::<E, U2>::new();
+
+        CommonPoseidonConstants {
+            arity2,
+            arity4: arity4.join().expect("building U4 constants panicked"),
+            arity8: arity8.join().expect("building U8 constants panicked"),
+            arity11: arity11.join().expect("building U11 constants panicked"),
+        }
+    }
 }
 
 impl<'a, E, Arity> Poseidon<'a, E, Arity>
@@ -151,6 +429,8 @@ where
             current_round: 0,
             elements,
             pos: 1,
+            overflow_policy: OverflowPolicy::default(),
+            cached_digest: None,
             constants,
             _e: PhantomData::<E>,
         }
@@ -176,6 +456,39 @@ where
             current_round: 0,
             elements,
             pos: width,
+            overflow_policy: OverflowPolicy::default(),
+            cached_digest: None,
+            constants,
+            _e: PhantomData::<E>,
+        }
+    }
+
+    /// Like `new_with_preimage`, but the preimage length is checked at compile time: `preimage`
+    /// must convert into exactly `Arity` elements, as `[E::Fr; N]` does only when `N` is the
+    /// arity.
+    pub fn new_with_array<P>(preimage: P, constants: &'a PoseidonConstants<E, Arity>) -> Self
+    where
+        Arity: ArrayLength<E::Fr>,
+        P: Into<GenericArray<E::Fr, Arity>>,
+    {
+        let preimage = preimage.into();
+        let elements = GenericArray::generate(|i| {
+            if i == 0 {
+                constants.arity_tag
+            } else {
+                preimage[i - 1]
+            }
+        });
+
+        let width = elements.len();
+
+        Poseidon {
+            constants_offset: 0,
+            current_round: 0,
+            elements,
+            pos: width,
+            overflow_policy: OverflowPolicy::default(),
+            cached_digest: None,
             constants,
             _e: PhantomData::<E>,
         }
@@ -200,18 +513,71 @@ where
             .for_each(|l| *l = scalar_from_u64::<E>(0u64));
         self.elements[0] = self.constants.arity_tag;
         self.pos = 1;
+        self.cached_digest = None;
+    }
+
+    /// Iterate `h_{i+1} = Poseidon(h_i, 0, ...)` starting from `h_0 = start`, returning
+    /// `h_iterations`.
+    ///
+    /// Each step hashes the previous digest in the first preimage position, with every other
+    /// position set to zero, so with arity 2 a step is `poseidon(&[h_i, 0])`. A single hasher is
+    /// reused across all iterations.
+    pub fn hash_chain(
+        start: E::Fr,
+        iterations: usize,
+        constants: &'a PoseidonConstants<E, Arity>,
+    ) -> E::Fr {
+        let mut hasher = Self::new(constants);
+        let mut digest = start;
+
+        for _ in 0..iterations {
+            hasher.reset();
+            hasher
+                .input(digest)
+                .expect("a reset hasher always has room for one element");
+            digest = hasher.hash();
+        }
+
+        digest
+    }
+
+    /// Set how `input` handles elements beyond the arity. The policy survives `reset`.
+    pub fn set_overflow_policy(&mut self, overflow_policy: OverflowPolicy) {
+        self.overflow_policy = overflow_policy;
     }
 
-    /// The returned `usize` represents the element position (within arity) for the input operation
+    /// The returned `usize` represents the element position (within arity) for the input operation.
+    ///
+    /// When all positions are filled, the element is handled according to the overflow policy.
+    /// If it is dropped under `OverflowPolicy::Ignore`, the returned position is the width, one
+    /// past the last valid position.
     pub fn input(&mut self, element: E::Fr) -> Result<usize, Error> {
-        // Cannot input more elements than the defined arity
         if self.pos >= self.constants.width() {
-            return Err(Error::FullBuffer);
+            match self.overflow_policy {
+                // Cannot input more elements than the defined arity
+                OverflowPolicy::Error => return Err(Error::FullBuffer),
+                OverflowPolicy::Ignore => return Ok(self.pos),
+                OverflowPolicy::Permute => {
+                    // The digest takes the first position of the new block, so a single
+                    // position would leave no room for the element.
+                    if self.constants.arity() < 2 {
+                        return Err(Error::Other(
+                            "OverflowPolicy::Permute requires an arity of at least 2".to_string(),
+                        ));
+                    }
+
+                    let digest = self.hash();
+                    self.reset();
+                    self.elements[1] = digest;
+                    self.pos = 2;
+                }
+            }
         }
 
         // Set current element, and increase the pointer
         self.elements[self.pos] = element;
         self.pos += 1;
+        self.cached_digest = None;
 
         Ok(self.pos - 1)
     }
@@ -228,6 +594,56 @@ where
         self.hash_in_mode(DEFAULT_HASH_MODE)
     }
 
+    /// Like `hash`, but the digest is memoized: later calls return it without permuting
+    /// again, until the preimage is changed with `input`, `set_preimage` or `reset`. Unlike a
+    /// second `hash`, which permutes the already permuted state, repeated calls always return
+    /// the digest of the preimage.
+    ///
+    /// Writing to `elements` directly does not clear the memoized digest.
+    pub fn hash_cached(&mut self) -> E::Fr {
+        if let Some(digest) = self.cached_digest {
+            return digest;
+        }
+
+        let digest = self.hash();
+        self.cached_digest = Some(digest);
+        digest
+    }
+
+    /// Consumes the hasher and returns the digest of its preimage, so it cannot accidentally
+    /// be hashed again. A digest memoized by `hash_cached` is returned as is.
+    pub fn finalize(mut self) -> E::Fr {
+        self.hash_cached()
+    }
+
+    /// Squeeze `n` elements out of the hasher, treating it as a sponge.
+    ///
+    /// The state is split into a capacity of one element, the first one (initially the arity
+    /// tag), and a rate of `arity` elements, the remaining ones. The absorbed preimage is
+    /// permuted once, then the rate elements are output in order, the state being permuted
+    /// again whenever they are exhausted. The capacity element is never output. The first
+    /// element squeezed is the digest `hash` would return.
+    ///
+    /// Like `hash`, this consumes the preimage: call `reset` before hashing again.
+    pub fn squeeze(&mut self, n: usize) -> Vec<E::Fr> {
+        let mut output = Vec::with_capacity(n);
+
+        while output.len() < n {
+            self.permute();
+            let remaining = n - output.len();
+            output.extend(self.elements[1..].iter().take(remaining));
+        }
+
+        output
+    }
+
+    /// Permute the current state in place, without resetting it.
+    fn permute(&mut self) {
+        self.constants_offset = 0;
+        self.current_round = 0;
+        self.hash();
+    }
+
     /// The number of rounds is divided into two equal parts for the full rounds, plus the partial rounds.
     ///
     /// The returned element is the second poseidon element, the first is the arity tag.
@@ -314,21 +730,14 @@ where
         // Round keys are added in the S-box to match circuits (where the addition is free)
         // and in preparation for the shift to adding round keys after (rather than before) applying the S-box.
 
-        let pre_round_keys = self
-            .constants
-            .round_constants
-            .iter()
-            .skip(self.constants_offset)
-            .map(|x| Some(x));
+        let width = self.elements.len();
+        let constants = self.constants;
+        let pre_round_keys =
+            &constants.round_constants[self.constants_offset..self.constants_offset + width];
 
-        self.elements
-            .iter_mut()
-            .zip(pre_round_keys)
-            .for_each(|(l, pre)| {
-                quintic_s_box::<E>(l, pre, None);
-            });
+        s_box_batch::<E>(&mut self.elements, constants.s_box_exponent, pre_round_keys);
 
-        self.constants_offset += self.elements.len();
+        self.constants_offset += width;
 
         // M(B)
         // Multiply the elements by the constant MDS matrix
@@ -345,18 +754,13 @@ where
 
         // Round keys are added in the S-box to match circuits (where the addition is free).
         // If requested, add round keys synthesized from following round after (rather than before) applying the S-box.
-        let pre_round_keys = self
-            .constants
-            .round_constants
-            .iter()
-            .skip(self.constants_offset)
-            .map(|x| {
-                if add_current_round_keys {
-                    Some(x)
-                } else {
-                    None
-                }
-            });
+        let width = self.elements.len();
+        let constants = self.constants;
+        let pre_round_keys: &[E::Fr] = if add_current_round_keys {
+            &constants.round_constants[self.constants_offset..self.constants_offset + width]
+        } else {
+            &[]
+        };
 
         if absorb_next_round_keys {
             // Using the notation from `test_inverse` in matrix.rs:
@@ -390,23 +794,16 @@ where
             // S = M(M^-1(S))
             assert_eq!(&post_vec, &original, "Oh no, the inversion trick failed.");
 
-            let post_round_keys = inverted_vec.iter();
-
             // S-Box Output = B.
+            s_box_batch::<E>(&mut self.elements, constants.s_box_exponent, pre_round_keys);
+
             // With post-add, result is B + M^-1(S).
             self.elements
                 .iter_mut()
-                .zip(pre_round_keys.zip(post_round_keys))
-                .for_each(|(l, (pre, post))| {
-                    quintic_s_box::<E>(l, pre, Some(post));
-                });
+                .zip(inverted_vec.iter())
+                .for_each(|(l, post)| l.add_assign(post));
         } else {
-            self.elements
-                .iter_mut()
-                .zip(pre_round_keys)
-                .for_each(|(l, pre)| {
-                    quintic_s_box::<E>(l, pre, None);
-                });
+            s_box_batch::<E>(&mut self.elements, constants.s_box_exponent, pre_round_keys);
         }
         let mut consumed = 0;
         if add_current_round_keys {
@@ -434,6 +831,7 @@ where
             .skip(self.constants_offset)
             .take(to_take);
 
+        let exponent = self.constants.s_box_exponent;
         if !last_round {
             let needed = self.constants_offset + to_take;
             assert!(
@@ -453,13 +851,13 @@ where
                 } else {
                     Some(post)
                 };
-                quintic_s_box::<E>(l, None, post_key);
+                s_box::<E>(l, exponent, None, post_key);
             });
         // We need this because post_round_keys will have been empty, so it didn't happen in the for_each. :(
         if last_round {
             self.elements
                 .iter_mut()
-                .for_each(|l| quintic_s_box::<E>(l, None, None));
+                .for_each(|l| s_box::<E>(l, exponent, None, None));
         } else {
             self.constants_offset += self.elements.len();
         }
@@ -472,7 +870,12 @@ where
         self.add_round_constants();
 
         // Apply the quintic S-Box to the first element
-        quintic_s_box::<E>(&mut self.elements[0], None, None);
+        s_box::<E>(
+            &mut self.elements[0],
+            self.constants.s_box_exponent,
+            None,
+            None,
+        );
 
         // Multiply the elements by the constant MDS matrix
         self.product_mds();
@@ -480,7 +883,12 @@ where
 
     pub fn partial_round_dynamic(&mut self) {
         // Apply the quintic S-Box to the first element
-        quintic_s_box::<E>(&mut self.elements[0], None, None);
+        s_box::<E>(
+            &mut self.elements[0],
+            self.constants.s_box_exponent,
+            None,
+            None,
+        );
 
         // Multiply the elements by the constant MDS matrix
         self.product_mds();
@@ -491,7 +899,12 @@ where
         let post_round_key = self.constants.compressed_round_constants[self.constants_offset];
 
         // Apply the quintic S-Box to the first element
-        quintic_s_box::<E>(&mut self.elements[0], None, Some(&post_round_key));
+        s_box::<E>(
+            &mut self.elements[0],
+            self.constants.s_box_exponent,
+            None,
+            Some(&post_round_key),
+        );
         self.constants_offset += 1;
 
         self.product_mds_static();
@@ -528,7 +941,7 @@ where
     /// Set the provided elements with the result of the product between the elements and the constant
     /// MDS matrix.
     fn product_mds(&mut self) {
-        self.product_mds_with_matrix(&self.constants.mds_matrices.m);
+        self.product_mds_with_matrix_transposed(&self.constants.mds_matrix_transposed);
     }
 
     /// Set the provided elements with the result of the product between the elements and the appropriate
@@ -570,6 +983,22 @@ where
         std::mem::replace(&mut self.elements, result);
     }
 
+    /// Like `product_mds_with_matrix`, but takes the transpose of the matrix so that each output
+    /// element is a dot product over one contiguous row.
+    fn product_mds_with_matrix_transposed(&mut self, transposed: &Matrix<E::Fr>) {
+        let mut result = GenericArray::<E::Fr, Add1<Arity>>::generate(|_| E::Fr::zero());
+
+        for (val, row) in result.iter_mut().zip(transposed.iter()) {
+            for (m, element) in row.iter().zip(self.elements.iter()) {
+                let mut tmp = *m;
+                tmp.mul_assign(element);
+                val.add_assign(&tmp);
+            }
+        }
+
+        std::mem::replace(&mut self.elements, result);
+    }
+
     // Sparse matrix in this context means one of the form, M''.
     fn product_mds_with_sparse_matrix(&mut self, matrix: &Matrix<E::Fr>) {
         let mut result = GenericArray::<E::Fr, Add1<Arity>>::generate(|_| E::Fr::zero());
@@ -611,80 +1040,641 @@ where
     Poseidon::<E, Arity>::new_with_preimage(preimage, &constants).hash()
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::*;
-    use ff::Field;
-    use generic_array::typenum::{U11, U2, U4, U8};
-    use paired::bls12_381::Bls12;
-    use std::time::{*};
+/// Like `poseidon`, but hashes in `mode`. Every mode produces the same digest, but
+/// `OptimizedStatic` is the fastest.
+///
+/// NOTE: this is just as expensive as `poseidon`, since building the constants dominates the
+/// hashing itself. To hash more than a few preimages, build the constants once, e.g. with
+/// `CommonPoseidonConstants::new_parallel`, and call `Poseidon::hash_in_mode` instead.
+pub fn poseidon_in_mode<E, Arity>(preimage: &[E::Fr], mode: HashMode) -> E::Fr
+where
+    E: ScalarEngine,
+    Arity: Unsigned + Add<B1> + Add<UInt<UTerm, B1>>,
+    Add1<Arity>: ArrayLength<E::Fr>,
+{
+    let constants = PoseidonConstants::<E, Arity>::new();
+    Poseidon::<E, Arity>::new_with_preimage(preimage, &constants).hash_in_mode(mode)
+}
 
+/// Poseidon hash of a fixed-size preimage, e.g. `poseidon_array([a, b], &constants)` with
+/// `PoseidonConstants<E, U2>`.
+///
+/// Const generics are not available, so the array length is paired with the typenum `Arity`
+/// through `generic_array`'s `From<[T; N]>` implementations: an array whose length is not the
+/// arity does not convert, and fails to compile instead of panicking like `poseidon`.
+pub fn poseidon_array<E, Arity, P>(preimage: P, constants: &PoseidonConstants<E, Arity>) -> E::Fr
+where
+    E: ScalarEngine,
+    Arity: Unsigned + Add<B1> + Add<UInt<UTerm, B1>> + ArrayLength<E::Fr>,
+    Add1<Arity>: ArrayLength<E::Fr>,
+    P: Into<GenericArray<E::Fr, Arity>>,
+{
+    Poseidon::new_with_array(preimage, constants).hash()
+}
 
-    #[test]
-    #[ignore]
-    fn reset() {
-        let test_arity = 2;
-        let preimage = vec![Scalar::one(); test_arity];
-        let constants = PoseidonConstants::new();
-        let mut h = Poseidon::<Bls12, U2>::new_with_preimage(&preimage, &constants);
-        h.hash();
-        h.reset();
+/// Poseidon hash of the empty preimage, i.e. of `Arity` zero elements behind the arity tag,
+/// which is what `Poseidon::new(constants).hash()` yields.
+///
+/// The digest only depends on the arity, so it is the canonical digest of empty or padding
+/// leaves, distinct for trees of different arities.
+pub fn poseidon_empty<E, Arity>(constants: &PoseidonConstants<E, Arity>) -> E::Fr
+where
+    E: ScalarEngine,
+    Arity: Unsigned + Add<B1> + Add<UInt<UTerm, B1>>,
+    Add1<Arity>: ArrayLength<E::Fr>,
+{
+    Poseidon::new(constants).hash()
+}
 
-        let default = Poseidon::<Bls12, U2>::new(&constants);
-        assert_eq!(default.pos, h.pos);
-        assert_eq!(default.elements, h.elements);
-        assert_eq!(default.constants_offset, h.constants_offset);
+/// Hiding commitment to `value` with the random `blinding` factor: the Poseidon hash of the
+/// preimage holding `blinding` first, then the elements of `value`, then zeros up to the
+/// arity, with the domain tag of `value.len()` in place of the arity tag.
+///
+/// The commitment hides `value` as long as `blinding` is uniformly random and kept secret
+/// until it is opened with `poseidon_commit_verify`. The tag binds the length of `value`, so
+/// values only differing in trailing zeros, which pad to the same preimage, have distinct
+/// commitments.
+///
+/// Returns an error if the arity is smaller than `value.len() + 1`, i.e. too small for the
+/// value and the blinding factor.
+pub fn poseidon_commit<E, Arity>(
+    value: &[E::Fr],
+    blinding: E::Fr,
+    constants: &PoseidonConstants<E, Arity>,
+) -> Result<E::Fr, Error>
+where
+    E: ScalarEngine,
+    Arity: Unsigned + Add<B1> + Add<UInt<UTerm, B1>>,
+    Add1<Arity>: ArrayLength<E::Fr>,
+{
+    let arity = constants.arity();
+    if value.len() >= arity {
+        return Err(Error::Other(format!(
+            "cannot commit to {} elements with arity {}",
+            value.len(),
+            arity
+        )));
     }
 
-    #[test]
-    fn hash_det() {
-        let test_arity = 7;
-        let mut preimage = vec![Scalar::zero(); test_arity];
-        let constants = PoseidonConstants::new();
-        preimage[0] = Scalar::one();
+    let mut preimage = vec![E::Fr::zero(); arity];
+    preimage[0] = blinding;
+    preimage[1..=value.len()].copy_from_slice(value);
 
+    let mut hasher = Poseidon::new_with_preimage(&preimage, constants);
+    hasher.elements[0] = commitment_tag::<E>(value.len());
+    Ok(hasher.hash())
+}
 
-        let start = SystemTime::now();
+/// The domain tag of `poseidon_commit` commitments to values of `len` elements.
+fn commitment_tag<E: ScalarEngine>(len: usize) -> E::Fr {
+    domain_tag_from_label::<E>(&format!("neptune commitment to {} elements", len))
+}
 
-        let mut h = Poseidon::<Bls12, typenum::consts::U7>::new_with_preimage(&preimage, &constants);
-        println!("preimage= {:?}",preimage);
-      //  println!("constants= :{:?}",constants);
-        println!("Poseidon new_with_preimage duration {:?}",SystemTime::now().duration_since(start));
+/// Whether `value` and `blinding` open `commitment`, as made by `poseidon_commit`.
+///
+/// A value too long to be committed to with the arity opens no commitment.
+pub fn poseidon_commit_verify<E, Arity>(
+    commitment: E::Fr,
+    value: &[E::Fr],
+    blinding: E::Fr,
+    constants: &PoseidonConstants<E, Arity>,
+) -> bool
+where
+    E: ScalarEngine,
+    Arity: Unsigned + Add<B1> + Add<UInt<UTerm, B1>>,
+    Add1<Arity>: ArrayLength<E::Fr>,
+{
+    poseidon_commit(value, blinding, constants)
+        .map(|expected| expected == commitment)
+        .unwrap_or(false)
+}
 
-        let start2 = SystemTime::now();
-        let mut h2 = h.clone();
-        let result: <Bls12 as ScalarEngine>::Fr = h.hash();
-        println!("Poseidon  duration:{:?}",SystemTime::now().duration_since(start2));
-        println!("result= :{:?}",result);
+/// Number of bytes packed into each field element by `poseidon_hash_bytes`.
+/// 31 bytes (248 bits) always fit below the BLS12-381 scalar field modulus.
+pub const BYTES_PER_ELEMENT: usize = 31;
+
+/// Hash an arbitrary byte slice with Poseidon.
+///
+/// This is `poseidon_hash_bytes_with` packing 31-byte little-endian elements, after a length
+/// prefix.
+///
+/// Requires an arity of at least 2.
+pub fn poseidon_hash_bytes<E, Arity>(
+    data: &[u8],
+    constants: &PoseidonConstants<E, Arity>,
+) -> Result<E::Fr, Error>
+where
+    E: ScalarEngine,
+    Arity: Unsigned + Add<B1> + Add<UInt<UTerm, B1>>,
+    Add1<Arity>: ArrayLength<E::Fr>,
+{
+    let packing = BytePacking::LittleEndian {
+        bytes_per_element: BYTES_PER_ELEMENT,
+        length_prefix: true,
+    };
+    poseidon_hash_bytes_with(data, packing, constants)
+}
 
-        
-        assert_eq!(result, h2.hash());
+/// How `poseidon_hash_bytes_with` packs bytes into field elements, to reproduce the digests
+/// of an external byte hashing convention.
+///
+/// `data` is split into chunks of `bytes_per_element` bytes, the last one being zero-padded
+/// at its end, and each chunk is read as an integer in the given byte order:
+///
+/// - `LittleEndian`: the first byte of a chunk is its least significant byte. 31 bytes per
+///   element, with a length prefix, is the packing of `poseidon_hash_bytes`.
+/// - `BigEndian`: the first byte of a chunk is its most significant byte, as in EVM
+///   `uint256` words.
+///
+/// `bytes_per_element` is between 1 and 32. Chunks of up to 31 bytes always fit in the field;
+/// with 32 bytes, a chunk that is not below the modulus is rejected rather than reduced.
+///
+/// With `length_prefix`, the byte length of `data` is absorbed before the chunks, so inputs
+/// differing only in trailing zeros do not collide. Without it, they do: only use it where
+/// the length is fixed or committed to elsewhere.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BytePacking {
+    LittleEndian {
+        bytes_per_element: usize,
+        length_prefix: bool,
+    },
+    BigEndian {
+        bytes_per_element: usize,
+        length_prefix: bool,
+    },
+}
+
+impl BytePacking {
+    fn bytes_per_element(self) -> usize {
+        match self {
+            BytePacking::LittleEndian {
+                bytes_per_element, ..
+            }
+            | BytePacking::BigEndian {
+                bytes_per_element, ..
+            } => bytes_per_element,
+        }
     }
 
-    #[test]
-    #[ignore]
-    fn hash_arity_3() {
-        let mut preimage: [Scalar; 3] = [Scalar::zero(); 3];
-        let constants = PoseidonConstants::new();
-        preimage[0] = Scalar::one();
+    fn length_prefix(self) -> bool {
+        match self {
+            BytePacking::LittleEndian { length_prefix, .. }
+            | BytePacking::BigEndian { length_prefix, .. } => length_prefix,
+        }
+    }
 
-        let mut h = Poseidon::<Bls12, typenum::U3>::new_with_preimage(&preimage, &constants);
+    fn pack<E: ScalarEngine>(self, chunk: &[u8]) -> Result<E::Fr, Error> {
+        let mut buf = [0u8; 32];
+        match self {
+            BytePacking::LittleEndian { .. } => buf[..chunk.len()].copy_from_slice(chunk),
+            BytePacking::BigEndian {
+                bytes_per_element, ..
+            } => {
+                for (i, byte) in chunk.iter().enumerate() {
+                    buf[bytes_per_element - 1 - i] = *byte;
+                }
+            }
+        }
 
-        let mut h2 = h.clone();
-        let result: <Bls12 as ScalarEngine>::Fr = h.hash();
+        let mut repr = <E::Fr as PrimeField>::Repr::default();
+        repr.read_le(&buf[..])
+            .map_err(|e| Error::Other(e.to_string()))?;
 
-        assert_eq!(result, h2.hash());
+        E::Fr::from_repr(repr).map_err(|e| Error::Other(e.to_string()))
     }
+}
 
-    #[test]
-    #[ignore]
-    fn hash_values() {
-        hash_values_aux::<U2>();
-        hash_values_aux::<U4>();
-        hash_values_aux::<U8>();
-        hash_values_aux::<U11>();
-    }
+/// Hash an arbitrary byte slice with Poseidon, packing the bytes into field elements as
+/// `packing` describes.
+///
+/// The elements (after the length, with `packing.length_prefix`) are absorbed in blocks of
+/// `arity - 1`, each block hashed together with the running digest (starting at zero) in the
+/// first position, the last block being zero-padded. Nothing is absorbed after the data, and
+/// data packing into no element is hashed as a single block of zeros.
+///
+/// Requires an arity of at least 2.
+pub fn poseidon_hash_bytes_with<E, Arity>(
+    data: &[u8],
+    packing: BytePacking,
+    constants: &PoseidonConstants<E, Arity>,
+) -> Result<E::Fr, Error>
+where
+    E: ScalarEngine,
+    Arity: Unsigned + Add<B1> + Add<UInt<UTerm, B1>>,
+    Add1<Arity>: ArrayLength<E::Fr>,
+{
+    let arity = constants.arity();
+    if arity < 2 {
+        return Err(Error::Other(format!(
+            "byte hashing requires an arity of at least 2, got {}",
+            arity
+        )));
+    }
+    let bytes_per_element = packing.bytes_per_element();
+    if bytes_per_element == 0 || bytes_per_element > 32 {
+        return Err(Error::Other(format!(
+            "cannot pack {} bytes per element",
+            bytes_per_element
+        )));
+    }
+
+    let mut elements = Vec::with_capacity(data.len() / bytes_per_element + 2);
+    if packing.length_prefix() {
+        elements.push(scalar_from_u64::<E>(data.len() as u64));
+    }
+    for chunk in data.chunks(bytes_per_element) {
+        elements.push(packing.pack::<E>(chunk)?);
+    }
+    if elements.is_empty() {
+        elements.push(E::Fr::zero());
+    }
+
+    let mut hasher = Poseidon::<E, Arity>::new(constants);
+    let mut preimage = vec![E::Fr::zero(); arity];
+    let mut digest = E::Fr::zero();
+
+    for block in elements.chunks(arity - 1) {
+        preimage[0] = digest;
+        for (i, slot) in preimage.iter_mut().enumerate().skip(1) {
+            *slot = block.get(i - 1).copied().unwrap_or_else(E::Fr::zero);
+        }
+        hasher.set_preimage(&preimage);
+        digest = hasher.hash();
+    }
+
+    Ok(digest)
+}
+
+/// Generate `n_preimages` reference vectors: fixed preimages with their `HashMode::Correct`
+/// digests, as a cross-language conformance fixture.
+///
+/// Element `j` of preimage `i` is `domain_tag_from_label("neptune reference vector i.j")`,
+/// so other implementations can regenerate the preimages without sharing an RNG.
+pub fn export_reference_vectors<E, Arity>(n_preimages: usize) -> Vec<(Vec<E::Fr>, E::Fr)>
+where
+    E: ScalarEngine,
+    Arity: Unsigned + Add<B1> + Add<UInt<UTerm, B1>>,
+    Add1<Arity>: ArrayLength<E::Fr>,
+{
+    let constants = PoseidonConstants::<E, Arity>::new();
+    let mut hasher = Poseidon::<E, Arity>::new(&constants);
+
+    (0..n_preimages)
+        .map(|i| {
+            let preimage: Vec<E::Fr> = (0..constants.arity())
+                .map(|j| {
+                    domain_tag_from_label::<E>(&format!("neptune reference vector {}.{}", i, j))
+                })
+                .collect();
+            hasher.set_preimage(&preimage);
+            let digest = hasher.hash_in_mode(Correct);
+
+            (preimage, digest)
+        })
+        .collect()
+}
+
+/// Serialize reference vectors to JSON, as a list of `{"preimage": [..], "digest": ..}`
+/// objects whose field elements are big-endian `0x`-prefixed hex strings.
+pub fn reference_vectors_to_json<E: ScalarEngine>(vectors: &[(Vec<E::Fr>, E::Fr)]) -> String {
+    let hex = |x: &E::Fr| format!("\"{}\"", x.into_repr());
+
+    let entries: Vec<String> = vectors
+        .iter()
+        .map(|(preimage, digest)| {
+            let preimage: Vec<String> = preimage.iter().map(hex).collect();
+            format!(
+                "{{\"preimage\":[{}],\"digest\":{}}}",
+                preimage.join(","),
+                hex(digest)
+            )
+        })
+        .collect();
+
+    format!("[{}]", entries.join(","))
+}
+
+/// A deterministic stream of field elements derived from a key, e.g. to pick challenge
+/// positions reproducibly.
+///
+/// Output `i` is the Poseidon hash of the preimage `[key, i, 0, ...]`, so the stream is a
+/// Poseidon-based counter mode. This is NOT a standardized or formally analyzed PRF: its
+/// security rests entirely on Poseidon, and it must not be used as a general-purpose
+/// cryptographic PRF or keystream.
+#[derive(Debug, Clone)]
+pub struct PoseidonPrf<'a, E, Arity = U2>
+where
+    E: ScalarEngine,
+    Arity: Unsigned + Add<B1> + Add<UInt<UTerm, B1>>,
+    Add1<Arity>: ArrayLength<E::Fr>,
+{
+    key: E::Fr,
+    counter: u64,
+    hasher: Poseidon<'a, E, Arity>,
+}
+
+impl<'a, E, Arity> PoseidonPrf<'a, E, Arity>
+where
+    E: ScalarEngine,
+    Arity: Unsigned + Add<B1> + Add<UInt<UTerm, B1>>,
+    Add1<Arity>: ArrayLength<E::Fr>,
+{
+    /// Requires an arity of at least 2, to absorb both the key and the counter.
+    pub fn new(key: E::Fr, constants: &'a PoseidonConstants<E, Arity>) -> Result<Self, Error> {
+        let arity = constants.arity();
+        if arity < 2 {
+            return Err(Error::Other(format!(
+                "PoseidonPrf requires an arity of at least 2, got {}",
+                arity
+            )));
+        }
+
+        Ok(PoseidonPrf {
+            key,
+            counter: 0,
+            hasher: Poseidon::new(constants),
+        })
+    }
+}
+
+/// The stream does not end; the counter wraps after `2^64` elements.
+impl<'a, E, Arity> Iterator for PoseidonPrf<'a, E, Arity>
+where
+    E: ScalarEngine,
+    Arity: Unsigned + Add<B1> + Add<UInt<UTerm, B1>>,
+    Add1<Arity>: ArrayLength<E::Fr>,
+{
+    type Item = E::Fr;
+
+    fn next(&mut self) -> Option<E::Fr> {
+        self.hasher.reset();
+        self.hasher
+            .input(self.key)
+            .expect("a reset hasher has room for the key");
+        self.hasher
+            .input(scalar_from_u64::<E>(self.counter))
+            .expect("an arity of at least 2 leaves room for the counter");
+        self.counter = self.counter.wrapping_add(1);
+
+        Some(self.hasher.hash())
+    }
+}
+
+/// A Fiat-Shamir transcript: protocol messages are absorbed into a running state, from which
+/// challenges are derived.
+///
+/// The state is a single field element, initially the domain tag of the protocol label given
+/// to `new`. Every operation replaces it with the Poseidon digest of
+/// `[state, operation, label tag, ...]`, streamed through the hasher with
+/// `OverflowPolicy::Permute`:
+///
+/// * `absorb` hashes `[state, 0, tag(label), n, e_1, ..., e_n]`,
+/// * `challenge` hashes `[state, 1, tag(label)]` and returns the new state,
+///
+/// where `tag` is `domain_tag_from_label`. The operation code keeps absorbs and challenges
+/// apart, the label tag keeps messages of different meaning apart, and the element count keeps
+/// the zero padding of the last block from being confused with absorbed zeros. A challenge
+/// thus depends on the protocol label and every earlier operation, in order.
+#[derive(Debug, Clone)]
+pub struct Transcript<'a, E, Arity = U2>
+where
+    E: ScalarEngine,
+    Arity: Unsigned + Add<B1> + Add<UInt<UTerm, B1>>,
+    Add1<Arity>: ArrayLength<E::Fr>,
+{
+    state: E::Fr,
+    hasher: Poseidon<'a, E, Arity>,
+}
+
+impl<'a, E, Arity> Transcript<'a, E, Arity>
+where
+    E: ScalarEngine,
+    Arity: Unsigned + Add<B1> + Add<UInt<UTerm, B1>>,
+    Add1<Arity>: ArrayLength<E::Fr>,
+{
+    const ABSORB: u64 = 0;
+    const CHALLENGE: u64 = 1;
+
+    /// Requires an arity of at least 2, to stream the operations through the hasher.
+    pub fn new(
+        protocol_label: &str,
+        constants: &'a PoseidonConstants<E, Arity>,
+    ) -> Result<Self, Error> {
+        let arity = constants.arity();
+        if arity < 2 {
+            return Err(Error::Other(format!(
+                "Transcript requires an arity of at least 2, got {}",
+                arity
+            )));
+        }
+
+        let mut hasher = Poseidon::new(constants);
+        hasher.set_overflow_policy(OverflowPolicy::Permute);
+
+        Ok(Transcript {
+            state: domain_tag_from_label::<E>(protocol_label),
+            hasher,
+        })
+    }
+
+    /// Absorb the protocol message `elements`, labelled with `label`.
+    pub fn absorb(&mut self, label: &str, elements: &[E::Fr]) {
+        self.begin(Self::ABSORB, label);
+        self.input(scalar_from_u64::<E>(elements.len() as u64));
+        for element in elements {
+            self.input(*element);
+        }
+        self.state = self.hasher.hash();
+    }
+
+    /// Derive the challenge labelled with `label` from everything absorbed so far.
+    pub fn challenge(&mut self, label: &str) -> E::Fr {
+        self.begin(Self::CHALLENGE, label);
+        self.state = self.hasher.hash();
+
+        self.state
+    }
+
+    fn begin(&mut self, operation: u64, label: &str) {
+        self.hasher.reset();
+        self.input(self.state);
+        self.input(scalar_from_u64::<E>(operation));
+        self.input(domain_tag_from_label::<E>(label));
+    }
+
+    fn input(&mut self, element: E::Fr) {
+        self.hasher
+            .input(element)
+            .expect("OverflowPolicy::Permute with an arity of at least 2 accepts every element");
+    }
+}
+
+fn bytes_to_scalar<E: ScalarEngine>(bytes: &[u8]) -> Result<E::Fr, Error> {
+    debug_assert!(bytes.len() <= BYTES_PER_ELEMENT);
+
+    let mut buf = [0u8; 32];
+    buf[..bytes.len()].copy_from_slice(bytes);
+
+    let mut repr = <E::Fr as PrimeField>::Repr::default();
+    repr.read_le(&buf[..])
+        .map_err(|e| Error::Other(e.to_string()))?;
+
+    E::Fr::from_repr(repr).map_err(|e| Error::Other(e.to_string()))
+}
+
+fn write_u64<W: Write>(w: &mut W, x: u64) -> Result<(), Error> {
+    w.write_all(&x.to_le_bytes())
+        .map_err(|e| Error::Other(e.to_string()))
+}
+
+fn read_u64<R: Read>(r: &mut R) -> Result<u64, Error> {
+    let mut buf = [0u8; 8];
+    r.read_exact(&mut buf)
+        .map_err(|e| Error::Other(e.to_string()))?;
+
+    Ok(u64::from_le_bytes(buf))
+}
+
+fn write_usize<W: Write>(w: &mut W, x: usize) -> Result<(), Error> {
+    write_u64(w, x as u64)
+}
+
+fn read_usize<R: Read>(r: &mut R) -> Result<usize, Error> {
+    let x = read_u64(r)?;
+    if x > usize::max_value() as u64 {
+        return Err(Error::Other(format!("{} does not fit in a usize", x)));
+    }
+
+    Ok(x as usize)
+}
+
+fn write_scalar<E: ScalarEngine, W: Write>(w: &mut W, x: &E::Fr) -> Result<(), Error> {
+    x.into_repr()
+        .write_le(w)
+        .map_err(|e| Error::Other(e.to_string()))
+}
+
+fn read_scalar<E: ScalarEngine, R: Read>(r: &mut R) -> Result<E::Fr, Error> {
+    let mut repr = <E::Fr as PrimeField>::Repr::default();
+    repr.read_le(r).map_err(|e| Error::Other(e.to_string()))?;
+
+    E::Fr::from_repr(repr).map_err(|e| Error::Other(e.to_string()))
+}
+
+fn write_vector<E: ScalarEngine, W: Write>(w: &mut W, v: &[E::Fr]) -> Result<(), Error> {
+    write_usize(w, v.len())?;
+    for x in v {
+        write_scalar::<E, _>(w, x)?;
+    }
+
+    Ok(())
+}
+
+fn read_vector<E: ScalarEngine, R: Read>(r: &mut R) -> Result<Vec<E::Fr>, Error> {
+    // Not preallocated from the length, which is unchecked until the elements are read.
+    (0..read_usize(r)?)
+        .map(|_| read_scalar::<E, _>(r))
+        .collect()
+}
+
+fn write_matrix<E: ScalarEngine, W: Write>(w: &mut W, m: &Matrix<E::Fr>) -> Result<(), Error> {
+    write_usize(w, m.len())?;
+    for row in m {
+        write_vector::<E, _>(w, row)?;
+    }
+
+    Ok(())
+}
+
+fn read_matrix<E: ScalarEngine, R: Read>(r: &mut R) -> Result<Matrix<E::Fr>, Error> {
+    (0..read_usize(r)?)
+        .map(|_| read_vector::<E, _>(r))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit::{
+        poseidon_constraint_count, poseidon_hash, poseidon_hash_counted, PoseidonCircuit,
+        PoseidonCircuitShared,
+    };
+    use crate::test::TestConstraintSystem;
+    use crate::*;
+    use bellperson::gadgets::num::AllocatedNum;
+    use bellperson::ConstraintSystem;
+    use ff::Field;
+    use generic_array::typenum::{U11, U2, U4, U8};
+    use paired::bls12_381::Bls12;
+    use rand::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+    use std::time::{*};
+
+
+    #[test]
+    #[ignore]
+    fn reset() {
+        let test_arity = 2;
+        let preimage = vec![Scalar::one(); test_arity];
+        let constants = PoseidonConstants::new();
+        let mut h = Poseidon::<Bls12, U2>::new_with_preimage(&preimage, &constants);
+        h.hash();
+        h.reset();
+
+        let default = Poseidon::<Bls12, U2>::new(&constants);
+        assert_eq!(default.pos, h.pos);
+        assert_eq!(default.elements, h.elements);
+        assert_eq!(default.constants_offset, h.constants_offset);
+    }
+
+    #[test]
+    fn hash_det() {
+        let test_arity = 7;
+        let mut preimage = vec![Scalar::zero(); test_arity];
+        let constants = PoseidonConstants::new();
+        preimage[0] = Scalar::one();
+
+
+        let start = SystemTime::now();
+
+        let mut h = Poseidon::<Bls12, typenum::consts::U7>::new_with_preimage(&preimage, &constants);
+        println!("preimage= {:?}",preimage);
+      //  println!("constants= :{:?}",constants);
+        println!("Poseidon new_with_preimage duration {:?}",SystemTime::now().duration_since(start));
+
+        let start2 = SystemTime::now();
+        let mut h2 = h.clone();
+        let result: <Bls12 as ScalarEngine>::Fr = h.hash();
+        println!("Poseidon  duration:{:?}",SystemTime::now().duration_since(start2));
+        println!("result= :{:?}",result);
+
+        
+        assert_eq!(result, h2.hash());
+    }
+
+    #[test]
+    #[ignore]
+    fn hash_arity_3() {
+        let mut preimage: [Scalar; 3] = [Scalar::zero(); 3];
+        let constants = PoseidonConstants::new();
+        preimage[0] = Scalar::one();
+
+        let mut h = Poseidon::<Bls12, typenum::U3>::new_with_preimage(&preimage, &constants);
+
+        let mut h2 = h.clone();
+        let result: <Bls12 as ScalarEngine>::Fr = h.hash();
+
+        assert_eq!(result, h2.hash());
+    }
+
+    #[test]
+    #[ignore]
+    fn hash_values() {
+        hash_values_aux::<U2>();
+        hash_values_aux::<U4>();
+        hash_values_aux::<U8>();
+        hash_values_aux::<U11>();
+    }
 
     /// Simple test vectors to ensure results don't change unintentionally in development.
     fn hash_values_aux<Arity>()
@@ -761,6 +1751,619 @@ mod tests {
         );
     }
 
+    #[test]
+    fn full_round_batch_s_box() {
+        let constants = PoseidonConstants::<Bls12, U4>::new();
+        let preimage: Vec<Scalar> = (1..=4).map(scalar_from_u64::<Bls12>).collect();
+        let mut batched = Poseidon::<Bls12, U4>::new_with_preimage(&preimage, &constants);
+        let mut single = batched.clone();
+
+        batched.full_round();
+
+        single
+            .elements
+            .iter_mut()
+            .zip(constants.round_constants.iter())
+            .for_each(|(l, pre)| crate::quintic_s_box::<Bls12>(l, Some(pre), None));
+        single.constants_offset += single.elements.len();
+        single.product_mds();
+
+        assert_eq!(batched, single);
+
+        let mut batched_dynamic = Poseidon::<Bls12, U4>::new_with_preimage(&preimage, &constants);
+        let mut correct = batched_dynamic.clone();
+        assert_eq!(
+            correct.hash_in_mode(Correct),
+            batched_dynamic.hash_in_mode(OptimizedDynamic)
+        );
+    }
+
+    #[test]
+    fn s_box_exponent() {
+        let mut rng = XorShiftRng::from_seed(crate::TEST_SEED);
+        for _ in 0..10 {
+            let x = Scalar::random(&mut rng);
+            for &exponent in &[3, 5] {
+                let mut l = x;
+                crate::s_box::<Bls12>(&mut l, exponent, None, None);
+                assert_eq!(l, x.pow([exponent]));
+            }
+        }
+
+        // 3 divides r - 1 for BLS12-381, so x^3 is not a permutation there.
+        assert!(PoseidonConstants::<Bls12, U4>::new_with_s_box_exponent(3).is_err());
+        assert!(PoseidonConstants::<Bls12, U4>::new_with_s_box_exponent(7).is_err());
+        assert_eq!(
+            PoseidonConstants::<Bls12, U4>::new_with_s_box_exponent(5).unwrap(),
+            PoseidonConstants::<Bls12, U4>::new()
+        );
+
+        // Bypass the permutation check to exercise the cubic S-Box in every mode and in-circuit.
+        let quintic = PoseidonConstants::<Bls12, U4>::new();
+        let cubic = PoseidonConstants::<Bls12, U4>::with_s_box_exponent(3);
+        assert_eq!(cubic.s_box_exponent(), 3);
+        // The lower degree S-Box needs more partial rounds.
+        assert_eq!((cubic.full_rounds, cubic.partial_rounds), (8, 84));
+        assert_eq!((quintic.full_rounds, quintic.partial_rounds), (8, 56));
+
+        let preimage: Vec<Scalar> = (1..=4).map(scalar_from_u64::<Bls12>).collect();
+        let mut h = Poseidon::<Bls12, U4>::new_with_preimage(&preimage, &cubic);
+        let expected = h.hash_in_mode(Correct);
+        for &mode in &[OptimizedDynamic, OptimizedStatic] {
+            h.set_preimage(&preimage);
+            assert_eq!(expected, h.hash_in_mode(mode));
+        }
+        assert_ne!(
+            expected,
+            Poseidon::<Bls12, U4>::new_with_preimage(&preimage, &quintic).hash()
+        );
+
+        let mut cs = TestConstraintSystem::<Bls12>::new();
+        let data: Vec<AllocatedNum<Bls12>> = preimage
+            .iter()
+            .enumerate()
+            .map(|(i, x)| {
+                AllocatedNum::alloc(cs.namespace(|| format!("data {}", i)), || Ok(*x)).unwrap()
+            })
+            .collect();
+        let out = poseidon_hash(&mut cs, data, &cubic).expect("poseidon hashing failed");
+
+        assert!(cs.is_satisfied());
+        assert_eq!(Some(expected), out.get_value());
+    }
+
+    #[test]
+    fn calculated_round_numbers() {
+        // The bounds reproduce the published quintic table.
+        for width in 2..=12 {
+            assert_eq!(calc_round_numbers(width, 5), round_numbers(width - 1));
+        }
+
+        let cubic: Vec<usize> = (2..=12).map(|w| calc_round_numbers(w, 3).1).collect();
+        assert_eq!(cubic, vec![82, 83, 83, 84, 84, 84, 84, 84, 85, 85, 85]);
+        assert!((2..=12).all(|w| calc_round_numbers(w, 3).0 == 8));
+    }
+
+    #[test]
+    fn shared_circuit_hash() {
+        let constants = PoseidonConstants::<Bls12, U4>::new();
+        let shared = PoseidonCircuitShared::new(&constants);
+
+        let mut plain_cs = TestConstraintSystem::<Bls12>::new();
+        let mut shared_cs = TestConstraintSystem::<Bls12>::new();
+
+        for i in 0..3 {
+            let preimage: Vec<Scalar> = (0..4)
+                .map(|j| scalar_from_u64::<Bls12>(4 * i + j))
+                .collect();
+            let alloc = |cs: &mut TestConstraintSystem<Bls12>| -> Vec<AllocatedNum<Bls12>> {
+                preimage
+                    .iter()
+                    .enumerate()
+                    .map(|(j, x)| {
+                        AllocatedNum::alloc(cs.namespace(|| format!("data {} {}", i, j)), || Ok(*x))
+                            .unwrap()
+                    })
+                    .collect()
+            };
+
+            let plain_data = alloc(&mut plain_cs);
+            let plain = poseidon_hash(
+                plain_cs.namespace(|| format!("hash {}", i)),
+                plain_data,
+                &constants,
+            )
+            .unwrap();
+            let shared_data = alloc(&mut shared_cs);
+            let out = shared
+                .hash(shared_cs.namespace(|| format!("hash {}", i)), shared_data)
+                .unwrap();
+
+            assert_eq!(Some(poseidon::<Bls12, U4>(&preimage)), out.get_value());
+            assert_eq!(plain.get_value(), out.get_value());
+        }
+
+        assert!(shared_cs.is_satisfied());
+        assert_eq!(plain_cs.num_constraints(), shared_cs.num_constraints());
+    }
+
+    #[test]
+    fn circuit_compute_witness() {
+        let mut rng = XorShiftRng::from_seed(crate::TEST_SEED);
+        let quintic = PoseidonConstants::<Bls12, U4>::new();
+        // x^3 is not a permutation of the BLS12-381 scalar field, so bypass that check like
+        // the `s_box_exponent` test: the witness only has to match the circuit.
+        let cubic = PoseidonConstants::<Bls12, U4>::with_s_box_exponent(3);
+
+        for constants in &[quintic, cubic] {
+            let preimage: Vec<Scalar> = (0..4).map(|_| Scalar::random(&mut rng)).collect();
+
+            let mut cs = TestConstraintSystem::<Bls12>::new();
+            let data: Vec<AllocatedNum<Bls12>> = preimage
+                .iter()
+                .enumerate()
+                .map(|(i, x)| {
+                    AllocatedNum::alloc(cs.namespace(|| format!("data {}", i)), || Ok(*x)).unwrap()
+                })
+                .collect();
+            let out = poseidon_hash(&mut cs, data, constants).expect("poseidon hashing failed");
+
+            let witness = PoseidonCircuit::new(Vec::new(), constants).compute_witness(&preimage);
+
+            assert!(cs.is_satisfied());
+            assert_eq!(out.get_value(), Some(witness));
+            assert_eq!(
+                Poseidon::new_with_preimage(&preimage, constants).hash(),
+                witness
+            );
+        }
+    }
+
+    #[test]
+    fn circuit_hash_counted() {
+        let mut rng = XorShiftRng::from_seed(crate::TEST_SEED);
+        let constants = PoseidonConstants::<Bls12, U2>::new();
+
+        let mut cs = TestConstraintSystem::<Bls12>::new();
+        let data: Vec<AllocatedNum<Bls12>> = (0..2)
+            .map(|i| {
+                let x = Scalar::random(&mut rng);
+                AllocatedNum::alloc(cs.namespace(|| format!("data {}", i)), || Ok(x)).unwrap()
+            })
+            .collect();
+        let before = cs.num_constraints();
+        let (_, added) = poseidon_hash_counted(cs.namespace(|| "poseidon"), data, &constants)
+            .expect("poseidon hashing failed");
+
+        assert!(cs.is_satisfied());
+        assert_eq!(added, poseidon_constraint_count::<U2>());
+        assert_eq!(added, cs.num_constraints() - before);
+    }
+
+    #[test]
+    fn constants_serialization() {
+        let constants = PoseidonConstants::<Bls12, U4>::new();
+        let mut bytes = Vec::new();
+        constants.write_to(&mut bytes).unwrap();
+
+        let read = PoseidonConstants::<Bls12, U4>::read_from(&mut &bytes[..]).unwrap();
+        assert_eq!(read, constants);
+
+        match PoseidonConstants::<Bls12, U8>::read_from(&mut &bytes[..]) {
+            Err(Error::ArityMismatch { expected, found }) => {
+                assert_eq!(expected, 8);
+                assert_eq!(found, 4);
+            }
+            other => panic!("expected an arity mismatch, got {:?}", other),
+        }
+
+        assert!(PoseidonConstants::<Bls12, U4>::read_from(&mut &bytes[..bytes.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn constants_invariants() {
+        PoseidonConstants::<Bls12, U2>::new().assert_invariants();
+        PoseidonConstants::<Bls12, U4>::new().assert_invariants();
+        PoseidonConstants::<Bls12, U8>::new().assert_invariants();
+        PoseidonConstants::<Bls12, U11>::new().assert_invariants();
+
+        let rejected = |constants: PoseidonConstants<Bls12, U4>| {
+            let mut bytes = Vec::new();
+            constants.write_to(&mut bytes).unwrap();
+            PoseidonConstants::<Bls12, U4>::read_from(&mut &bytes[..]).is_err()
+        };
+
+        let mut constants = PoseidonConstants::<Bls12, U4>::new();
+        constants.compressed_round_constants.pop();
+        assert!(rejected(constants));
+
+        let mut constants = PoseidonConstants::<Bls12, U4>::new();
+        constants.mds_matrices.m[2].pop();
+        assert!(rejected(constants));
+
+        let mut constants = PoseidonConstants::<Bls12, U4>::new();
+        constants.mds_matrices.m_hat.push(vec![Scalar::zero(); 4]);
+        assert!(rejected(constants));
+
+        let mut constants = PoseidonConstants::<Bls12, U4>::new();
+        constants.sparse_matrices[1].pop();
+        assert!(rejected(constants));
+
+        // x^3 is not a permutation of the BLS12-381 scalar field.
+        assert!(rejected(
+            PoseidonConstants::<Bls12, U4>::with_s_box_exponent(3)
+        ));
+        let mut constants = PoseidonConstants::<Bls12, U4>::new();
+        constants.s_box_exponent = 7;
+        assert!(rejected(constants));
+    }
+
+    #[test]
+    fn convenience_in_mode() {
+        let mut rng = XorShiftRng::from_seed(crate::TEST_SEED);
+        let preimage: Vec<Scalar> = (0..4).map(|_| Scalar::random(&mut rng)).collect();
+
+        let expected = poseidon::<Bls12, U4>(&preimage);
+        for mode in &[Correct, OptimizedDynamic, OptimizedStatic] {
+            assert_eq!(poseidon_in_mode::<Bls12, U4>(&preimage, *mode), expected);
+        }
+    }
+
+    #[test]
+    fn hash_cached_is_stable() {
+        let mut rng = XorShiftRng::from_seed(crate::TEST_SEED);
+        let constants = PoseidonConstants::<Bls12, U2>::new();
+        let preimage = [Scalar::random(&mut rng), Scalar::random(&mut rng)];
+        let expected = poseidon::<Bls12, U2>(&preimage);
+
+        let mut h = Poseidon::new_with_preimage(&preimage, &constants);
+        assert_eq!(h.hash_cached(), expected);
+        assert_eq!(h.hash_cached(), expected);
+        assert_eq!(h.clone().finalize(), expected);
+
+        // A bare second `hash` permutes the already permuted state.
+        let mut bare = Poseidon::new_with_preimage(&preimage, &constants);
+        assert_eq!(bare.hash(), expected);
+        assert_ne!(bare.hash(), expected);
+
+        // Changing the preimage clears the memoized digest.
+        let swapped = [preimage[1], preimage[0]];
+        h.set_preimage(&swapped);
+        assert_eq!(h.hash_cached(), poseidon::<Bls12, U2>(&swapped));
+    }
+
+    #[test]
+    fn empty_preimage() {
+        let constants = PoseidonConstants::<Bls12, U2>::new();
+        let empty = poseidon_empty(&constants);
+        let zeros = [Scalar::zero(); 2];
+
+        assert_eq!(empty, Poseidon::new(&constants).hash());
+        assert_eq!(empty, poseidon::<Bls12, U2>(&zeros));
+
+        // Stable across freshly generated constants, but distinct per arity.
+        let regenerated = PoseidonConstants::<Bls12, U2>::new();
+        assert_eq!(empty, poseidon_empty(&regenerated));
+        let arity4 = PoseidonConstants::<Bls12, U4>::new();
+        assert_ne!(empty, poseidon_empty(&arity4));
+    }
+
+    #[test]
+    fn common_constants_parallel() {
+        let common = CommonPoseidonConstants::<Bls12>::new_parallel();
+
+        assert_eq!(common.arity2, PoseidonConstants::<Bls12, U2>::new());
+        assert_eq!(common.arity11, PoseidonConstants::<Bls12, U11>::new());
+    }
+
+    #[test]
+    fn input_overflow_policies() {
+        let constants = PoseidonConstants::<Bls12, U2>::new();
+        let elements: Vec<Scalar> = (1..=3).map(scalar_from_u64::<Bls12>).collect();
+
+        let mut error = Poseidon::<Bls12, U2>::new(&constants);
+        assert_eq!(OverflowPolicy::Error, error.overflow_policy);
+        error.input(elements[0]).unwrap();
+        error.input(elements[1]).unwrap();
+        match error.input(elements[2]) {
+            Err(Error::FullBuffer) => (),
+            other => panic!("expected FullBuffer, got {:?}", other),
+        }
+
+        let mut ignore = Poseidon::<Bls12, U2>::new(&constants);
+        ignore.set_overflow_policy(OverflowPolicy::Ignore);
+        for element in &elements {
+            ignore.input(*element).unwrap();
+        }
+        assert_eq!(poseidon::<Bls12, U2>(&elements[..2]), ignore.hash());
+
+        let mut permute = Poseidon::<Bls12, U2>::new(&constants);
+        permute.set_overflow_policy(OverflowPolicy::Permute);
+        for element in &elements {
+            permute.input(*element).unwrap();
+        }
+        let first_block = poseidon::<Bls12, U2>(&elements[..2]);
+        assert_eq!(
+            poseidon::<Bls12, U2>(&[first_block, elements[2]]),
+            permute.hash()
+        );
+    }
+
+    #[test]
+    fn domain_tag_from_labels() {
+        let porep = domain_tag_from_label::<Bls12>("filecoin-porep-v1");
+        let post = domain_tag_from_label::<Bls12>("filecoin-post-v1");
+
+        assert_ne!(porep, post);
+        assert_eq!(porep, domain_tag_from_label::<Bls12>("filecoin-porep-v1"));
+        assert_ne!(porep, arity_tag::<Bls12, U2>());
+    }
+
+    #[test]
+    fn reference_vectors() {
+        let vectors = export_reference_vectors::<Bls12, U2>(4);
+        assert_eq!(vectors.len(), 4);
+        // The preimages are fixed, so the vectors must not change between runs.
+        assert_eq!(vectors, export_reference_vectors::<Bls12, U2>(4));
+
+        let constants = PoseidonConstants::<Bls12, U2>::new();
+        let mut h = Poseidon::<Bls12, U2>::new(&constants);
+        for (i, (preimage, digest)) in vectors.iter().enumerate() {
+            assert_eq!(preimage.len(), 2);
+            assert_eq!(
+                preimage[1],
+                domain_tag_from_label::<Bls12>(&format!("neptune reference vector {}.1", i))
+            );
+
+            h.set_preimage(preimage);
+            assert_eq!(*digest, h.hash_optimized_static());
+        }
+
+        // Known answers (big-endian hex), so the fixture stays stable across releases.
+        let expected = [
+            "0x51cbde27cbc5f3e3db100b0181aa6af2aee61dc0487c4250906e65a3228514f8",
+            "0x5c2947c38d4cc3ba64d2b029208ff23c9008ed3ec8fa70362f586148aaf67908",
+            "0x5983f6eb0f7f7151cbd0a92b513b8d1936c134c1fb733d147963c71d8a021c8e",
+            "0x224ac2b3de99b16ab84fc4bd31fbc2cb533f93c2d158cf70074e26987a64cf5b",
+        ];
+        for ((_, digest), expected) in vectors.iter().zip(expected.iter()) {
+            assert_eq!(format!("{}", digest.into_repr()), *expected);
+        }
+
+        let json = reference_vectors_to_json::<Bls12>(&vectors);
+        assert!(json.starts_with("[{\"preimage\":[\"0x"));
+        assert_eq!(json.matches("\"digest\"").count(), 4);
+    }
+
+    #[test]
+    fn hash_chain() {
+        let constants = PoseidonConstants::<Bls12, U2>::new();
+        let start = scalar_from_u64::<Bls12>(42);
+
+        let mut expected = start;
+        for _ in 0..3 {
+            expected = poseidon::<Bls12, U2>(&[expected, Scalar::zero()]);
+        }
+
+        assert_eq!(expected, Poseidon::hash_chain(start, 3, &constants));
+        assert_eq!(start, Poseidon::hash_chain(start, 0, &constants));
+    }
+
+    #[test]
+    fn hash_bytes() {
+        let constants = PoseidonConstants::<Bls12, U2>::new();
+
+        let empty = poseidon_hash_bytes(&[], &constants).unwrap();
+        let expected_empty = poseidon::<Bls12, U2>(&[Scalar::zero(), Scalar::zero()]);
+        assert_eq!(expected_empty, empty);
+
+        let short = [1u8, 2, 3];
+        let short_digest = poseidon_hash_bytes(&short, &constants).unwrap();
+        let short_element = bytes_to_scalar::<Bls12>(&short).unwrap();
+        let length = poseidon::<Bls12, U2>(&[Scalar::zero(), scalar_from_u64::<Bls12>(3)]);
+        let expected_short = poseidon::<Bls12, U2>(&[length, short_element]);
+        assert_eq!(expected_short, short_digest);
+
+        let long: Vec<u8> = (0..100u8).collect();
+        let long_digest = poseidon_hash_bytes(&long, &constants).unwrap();
+        let mut expected_long =
+            poseidon::<Bls12, U2>(&[Scalar::zero(), scalar_from_u64::<Bls12>(100)]);
+        for chunk in long.chunks(BYTES_PER_ELEMENT) {
+            let element = bytes_to_scalar::<Bls12>(chunk).unwrap();
+            expected_long = poseidon::<Bls12, U2>(&[expected_long, element]);
+        }
+        assert_eq!(expected_long, long_digest);
+
+        // Known answers (big-endian hex), so the digests stay stable across runs and releases.
+        let hex = |x: Scalar| format!("{}", x.into_repr());
+        assert_eq!(
+            hex(empty),
+            "0x341b182fe5320e25f9089620ef780d4b1f7d74764434a66aff409492f04c3afd"
+        );
+        assert_eq!(
+            hex(short_digest),
+            "0x06b78d8bd0e56f91a587fc56b0952289b5096c556f3e0024a3f092552325d8ec"
+        );
+        assert_eq!(
+            hex(long_digest),
+            "0x4a58255003f3e7cf0e55d4682d343365fec0f2ea2d532db977aee5c5099b4c79"
+        );
+
+        // Stable across runs, and sensitive to trailing zeros.
+        assert_eq!(long_digest, poseidon_hash_bytes(&long, &constants).unwrap());
+        assert_ne!(
+            short_digest,
+            poseidon_hash_bytes(&[1u8, 2, 3, 0], &constants).unwrap()
+        );
+        assert_ne!(empty, short_digest);
+        assert_ne!(short_digest, long_digest);
+
+        let unary = PoseidonConstants::<Bls12, typenum::U1>::new();
+        assert!(poseidon_hash_bytes(&short, &unary).is_err());
+    }
+
+    #[test]
+    fn hash_bytes_with_packing() {
+        use BytePacking::{BigEndian, LittleEndian};
+
+        let constants = PoseidonConstants::<Bls12, U4>::new();
+        let data: Vec<u8> = (1..=70u8).collect();
+
+        let mut packings = Vec::new();
+        for &bytes_per_element in &[31, 32] {
+            for &length_prefix in &[false, true] {
+                packings.push(LittleEndian {
+                    bytes_per_element,
+                    length_prefix,
+                });
+                packings.push(BigEndian {
+                    bytes_per_element,
+                    length_prefix,
+                });
+            }
+        }
+
+        let digests: Vec<Scalar> = packings
+            .iter()
+            .map(|packing| poseidon_hash_bytes_with(&data, *packing, &constants).unwrap())
+            .collect();
+
+        // Known answers (big-endian hex), distinct for every packing.
+        let expected = [
+            "0x075e73a311527b56fb2dd4908ccdad7f07f2e05702b97376de2d1c9435c4197c",
+            "0x2a746ce0fa92e752208c85c1e656d48fd370b995e0a7af0b3f22b64053466b3b",
+            "0x58aded51dc596f000da1c9cf2c24d078945691589d08e8014f00c199e5a5053e",
+            "0x5152998a98f4594d3700f01d4322d05bc99af5b3fb6b90c90c83427de5a0e40d",
+            "0x516cf37062d3769c34108a3d51a4d66e1872ccd0ee909d08fc04f56a0857c2e0",
+            "0x6a50c8bd2b999f037108edf16807841f041ab0454e43251368059626e914d5af",
+            "0x313e07260e6e2bc50850af7a425ab85799799a4185096e7d9cac27a2c257951f",
+            "0x10f71abab355ee43a5b4c9da6188e5e7eb4d37f9c25c57e9d98d16310fe4e553",
+        ];
+        for ((packing, digest), expected) in packings.iter().zip(&digests).zip(&expected) {
+            assert_eq!(
+                format!("{}", digest.into_repr()),
+                *expected,
+                "{:?}",
+                packing
+            );
+        }
+
+        // `poseidon_hash_bytes` is the length-prefixed little-endian 31 byte packing.
+        assert_eq!(digests[2], poseidon_hash_bytes(&data, &constants).unwrap());
+
+        // Without the prefix, the three little-endian 31 byte elements make a single block.
+        let elements: Vec<Scalar> = data
+            .chunks(BYTES_PER_ELEMENT)
+            .map(|chunk| bytes_to_scalar::<Bls12>(chunk).unwrap())
+            .collect();
+        let mut preimage = vec![Scalar::zero()];
+        preimage.extend(elements);
+        assert_eq!(digests[0], poseidon::<Bls12, U4>(&preimage));
+
+        // A big-endian chunk is the reversed, zero-padded little-endian one.
+        let mut reversed = [0u8; 31];
+        reversed[28..].copy_from_slice(&[3, 2, 1]);
+        let big_endian = BigEndian {
+            bytes_per_element: 31,
+            length_prefix: false,
+        };
+        assert_eq!(
+            poseidon_hash_bytes_with(&[1, 2, 3], big_endian, &constants).unwrap(),
+            poseidon::<Bls12, U4>(&[
+                Scalar::zero(),
+                bytes_to_scalar::<Bls12>(&reversed).unwrap(),
+                Scalar::zero(),
+                Scalar::zero(),
+            ])
+        );
+
+        // Only the length prefix tells trailing zeros apart.
+        let hash = |data: &[u8], length_prefix| {
+            let packing = LittleEndian {
+                bytes_per_element: 31,
+                length_prefix,
+            };
+            poseidon_hash_bytes_with(data, packing, &constants).unwrap()
+        };
+        assert_eq!(hash(&[1, 2, 3], false), hash(&[1, 2, 3, 0], false));
+        assert_ne!(hash(&[1, 2, 3], true), hash(&[1, 2, 3, 0], true));
+
+        // 32 byte elements must be below the modulus.
+        let packing = BigEndian {
+            bytes_per_element: 32,
+            length_prefix: false,
+        };
+        assert!(poseidon_hash_bytes_with(&[0xff; 32], packing, &constants).is_err());
+        let packing = LittleEndian {
+            bytes_per_element: 33,
+            length_prefix: false,
+        };
+        assert!(poseidon_hash_bytes_with(&data, packing, &constants).is_err());
+    }
+
+    #[test]
+    fn commit() {
+        let mut rng = XorShiftRng::from_seed(crate::TEST_SEED);
+        let constants = PoseidonConstants::<Bls12, U4>::new();
+        let value: Vec<Scalar> = (0..3).map(|_| Scalar::random(&mut rng)).collect();
+        let blinding = Scalar::random(&mut rng);
+        let other_blinding = Scalar::random(&mut rng);
+
+        let commitment = poseidon_commit(&value, blinding, &constants).unwrap();
+        let mut h = Poseidon::<Bls12, U4>::new_with_preimage(
+            &[blinding, value[0], value[1], value[2]],
+            &constants,
+        );
+        h.elements[0] = domain_tag_from_label::<Bls12>("neptune commitment to 3 elements");
+        assert_eq!(commitment, h.hash());
+        assert_ne!(
+            commitment,
+            poseidon_commit(&value, other_blinding, &constants).unwrap()
+        );
+
+        let verify = |value: &[Scalar], blinding| {
+            poseidon_commit_verify(commitment, value, blinding, &constants)
+        };
+        assert!(verify(&value, blinding));
+        assert!(!verify(&value, other_blinding));
+        let mut other_value = value.clone();
+        other_value[1] = Scalar::random(&mut rng);
+        assert!(!verify(&other_value, blinding));
+        assert!(!verify(&value[..2], blinding));
+
+        // Values padding to the same preimage are told apart by their length.
+        let a = [value[0]];
+        let a_zero = [value[0], Scalar::zero()];
+        assert_ne!(
+            poseidon_commit(&a, blinding, &constants).unwrap(),
+            poseidon_commit(&a_zero, blinding, &constants).unwrap()
+        );
+
+        // The arity holds at most 3 value elements besides the blinding factor.
+        let too_long = [Scalar::one(); 4];
+        assert!(poseidon_commit(&too_long, blinding, &constants).is_err());
+        assert!(!verify(&too_long, blinding));
+    }
+
+    #[test]
+    fn product_mds_transposed() {
+        let mut rng = XorShiftRng::from_seed(crate::TEST_SEED);
+        let constants = PoseidonConstants::<Bls12, U8>::new();
+
+        for _ in 0..10 {
+            let preimage: Vec<Scalar> = (0..constants.arity())
+                .map(|_| Scalar::random(&mut rng))
+                .collect();
+            let mut transposed = Poseidon::<Bls12, U8>::new_with_preimage(&preimage, &constants);
+            let mut plain = transposed.clone();
+
+            transposed.product_mds_with_matrix_transposed(&constants.mds_matrix_transposed);
+            plain.product_mds_with_matrix(&constants.mds_matrices.m);
+
+            assert_eq!(plain.elements, transposed.elements);
+        }
+    }
+
     #[test]
     #[ignore]
     fn hash_compare_optimized() {
@@ -786,4 +2389,114 @@ mod tests {
         assert_eq!(digest_correct, digest_optimized_dynamic);
         assert_eq!(digest_correct, digest_optimized_static);
     }
+
+    #[test]
+    fn poseidon_prf() {
+        let constants = PoseidonConstants::<Bls12, U2>::new();
+        let key = scalar_from_u64::<Bls12>(7);
+
+        let stream = |key| {
+            let prf = PoseidonPrf::<Bls12, U2>::new(key, &constants).unwrap();
+            prf.take(10).collect::<Vec<_>>()
+        };
+
+        let outputs = stream(key);
+        assert_eq!(outputs, stream(key));
+        assert_eq!(outputs[0], poseidon::<Bls12, U2>(&[key, Scalar::zero()]));
+        assert_eq!(outputs[1], poseidon::<Bls12, U2>(&[key, Scalar::one()]));
+        for (i, x) in outputs.iter().enumerate() {
+            assert!(!outputs[i + 1..].contains(x));
+        }
+
+        let other = stream(scalar_from_u64::<Bls12>(8));
+        assert!(outputs.iter().zip(other.iter()).all(|(a, b)| a != b));
+
+        let constants = PoseidonConstants::<Bls12, typenum::U1>::new();
+        assert!(PoseidonPrf::<Bls12, typenum::U1>::new(key, &constants).is_err());
+    }
+
+    #[test]
+    fn hash_array() {
+        let a = scalar_from_u64::<Bls12>(1);
+        let b = scalar_from_u64::<Bls12>(2);
+
+        let constants = PoseidonConstants::<Bls12, U2>::new();
+        assert_eq!(
+            poseidon::<Bls12, U2>(&[a, b]),
+            poseidon_array([a, b], &constants)
+        );
+
+        let constants = PoseidonConstants::<Bls12, U4>::new();
+        assert_eq!(
+            poseidon::<Bls12, U4>(&[a, b, a, b]),
+            poseidon_array([a, b, a, b], &constants)
+        );
+    }
+
+    #[test]
+    fn transcript() {
+        let constants = PoseidonConstants::<Bls12, U2>::new();
+        let a: Vec<Scalar> = (1..=3).map(scalar_from_u64::<Bls12>).collect();
+        let b = vec![scalar_from_u64::<Bls12>(4)];
+
+        let run = |protocol: &str, messages: &[(&str, &[Scalar])]| {
+            let mut transcript = Transcript::<Bls12, U2>::new(protocol, &constants).unwrap();
+            for (label, elements) in messages {
+                transcript.absorb(label, elements);
+            }
+            (transcript.challenge("c1"), transcript.challenge("c2"))
+        };
+
+        let (c1, c2) = run("proto", &[("a", &a[..]), ("b", &b[..])]);
+        assert_eq!((c1, c2), run("proto", &[("a", &a[..]), ("b", &b[..])]));
+        assert_ne!(c1, c2);
+
+        // Reordering the absorbs, or changing any label, changes the challenges.
+        assert_ne!(c1, run("proto", &[("b", &b[..]), ("a", &a[..])]).0);
+        assert_ne!(c1, run("proto", &[("a", &b[..]), ("b", &a[..])]).0);
+        assert_ne!(c1, run("proto", &[("a", &a[..]), ("x", &b[..])]).0);
+        assert_ne!(c1, run("other", &[("a", &a[..]), ("b", &b[..])]).0);
+
+        // Absorbed zeros are not confused with padding.
+        let empty: [Scalar; 0] = [];
+        let zero = [Scalar::zero()];
+        assert_ne!(
+            run("proto", &[("a", &empty[..])]).0,
+            run("proto", &[("a", &zero[..])]).0
+        );
+
+        let constants = PoseidonConstants::<Bls12, typenum::U1>::new();
+        assert!(Transcript::<Bls12, typenum::U1>::new("proto", &constants).is_err());
+    }
+
+    #[test]
+    fn squeeze() {
+        let constants = PoseidonConstants::<Bls12, U2>::new();
+        let preimage = [scalar_from_u64::<Bls12>(1), scalar_from_u64::<Bls12>(2)];
+
+        let mut h = Poseidon::<Bls12, U2>::new_with_preimage(&preimage, &constants);
+        let digest = h.hash();
+
+        let mut h = Poseidon::<Bls12, U2>::new_with_preimage(&preimage, &constants);
+        assert_eq!(h.squeeze(1), vec![digest]);
+
+        let width = constants.width();
+        let mut h = Poseidon::<Bls12, U2>::new_with_preimage(&preimage, &constants);
+        let squeezed = h.squeeze(width);
+        assert_eq!(squeezed.len(), width);
+        assert_eq!(squeezed[0], digest);
+        for (i, x) in squeezed.iter().enumerate() {
+            assert!(!squeezed[i + 1..].contains(x));
+        }
+
+        // The first `arity` elements come from the first permutation, the rest from permuting
+        // the resulting state again.
+        let mut h = Poseidon::<Bls12, U2>::new_with_preimage(&preimage, &constants);
+        h.hash();
+        assert_eq!(squeezed[..2], h.elements[1..]);
+        h.constants_offset = 0;
+        h.current_round = 0;
+        h.hash();
+        assert_eq!(squeezed[2], h.elements[1]);
+    }
 }
@@ -104,6 +104,7 @@ pub fn run(sector_size: usize) -> anyhow::Result<()> {
                 .get(&(sector_size as u64))
                 .unwrap(),
         ),
+        graph_seed: None,
     };
     let cache_dir = tempfile::tempdir().unwrap();
     let sector_id = SectorId::from(SECTOR_ID);
@@ -159,6 +160,7 @@ pub fn run(sector_size: usize) -> anyhow::Result<()> {
         challenge_count: POST_CHALLENGE_COUNT,
         challenged_nodes: POST_CHALLENGED_NODES,
         priority: true,
+        read_chunk_bytes: None,
     };
 
     let gen_candidates_measurement = measure(|| {
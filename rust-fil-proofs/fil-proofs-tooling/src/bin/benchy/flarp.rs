@@ -1,5 +1,9 @@
 use std::sync::atomic::Ordering::Relaxed;
+use std::time::Duration;
 
+#[cfg(any(test, feature = "measurements"))]
+use anyhow::anyhow;
+use anyhow::Result;
 use bellperson::Circuit;
 use fil_proofs_tooling::{measure, Metadata};
 use filecoin_proofs::constants::{DefaultTreeHasher, POREP_PARTITIONS};
@@ -9,24 +13,28 @@ use filecoin_proofs::types::*;
 use filecoin_proofs::types::{PoStConfig, SectorSize};
 use filecoin_proofs::{
     generate_candidates, generate_post, seal_commit_phase1, seal_commit_phase2, verify_post,
-    PoRepConfig,
+    PoRepConfig, SealPreCommitOutput,
 };
 use log::info;
 use paired::bls12_381::Bls12;
 use serde::{Deserialize, Serialize};
-use storage_proofs::circuit::bench::BenchCS;
+use storage_proofs::circuit::bench::constraint_count;
 use storage_proofs::circuit::election_post::{ElectionPoStCircuit, ElectionPoStCompound};
 use storage_proofs::compound_proof::CompoundProof;
 use storage_proofs::election_post::ElectionPoSt;
 use storage_proofs::hasher::Sha256Hasher;
 #[cfg(feature = "measurements")]
-use storage_proofs::measurements::Operation;
-#[cfg(feature = "measurements")]
 use storage_proofs::measurements::OP_MEASUREMENTS;
-use storage_proofs::parameter_cache::CacheableParameters;
+#[cfg(any(test, feature = "measurements"))]
+use storage_proofs::measurements::{OpMeasurement, Operation};
+use storage_proofs::parameter_cache::{CacheableParameters, ParameterSetMetadata};
 use storage_proofs::proof::ProofScheme;
+use storage_proofs::sector::SectorId;
 
-use crate::shared::{create_replicas, CHALLENGE_COUNT, PROVER_ID, RANDOMNESS, TICKET_BYTES};
+use crate::shared::{
+    create_replicas, create_replicas_streaming, porep_config, replica_seeds,
+    PreCommitReplicaOutput, CHALLENGE_COUNT, PROVER_ID, RANDOMNESS, TICKET_BYTES,
+};
 
 type FlarpHasher = DefaultTreeHasher;
 
@@ -61,6 +69,11 @@ impl FlarpInputs {
 pub struct FlarpOutputs {
     comm_d_cpu_time_ms: u64,
     comm_d_wall_time_ms: u64,
+    encode_layer_total_cpu_time_ms: u64,
+    encode_layer_total_wall_time_ms: u64,
+    /// The encoding time of each layer, indexed from layer 1.
+    encode_layer_cpu_time_ms: Vec<u64>,
+    encode_layer_wall_time_ms: Vec<u64>,
     encode_window_time_all_cpu_time_ms: u64,
     encode_window_time_all_wall_time_ms: u64,
     encoding_cpu_time_ms: u64,
@@ -94,10 +107,12 @@ pub struct FlarpOutputs {
 }
 
 #[cfg(not(feature = "measurements"))]
-fn augment_with_op_measurements(mut _output: &mut FlarpOutputs) {}
+fn augment_with_op_measurements(mut _output: &mut FlarpOutputs) -> Result<()> {
+    Ok(())
+}
 
 #[cfg(feature = "measurements")]
-fn augment_with_op_measurements(mut output: &mut FlarpOutputs) {
+fn augment_with_op_measurements(output: &mut FlarpOutputs) -> Result<()> {
     // drop the tx side of the channel, causing the iterator to yield None
     // see also: https://doc.rust-lang.org/src/std/sync/mpsc/mod.rs.html#368
     OP_MEASUREMENTS
@@ -112,53 +127,76 @@ fn augment_with_op_measurements(mut output: &mut FlarpOutputs) {
         .expect("failed to acquire lock on rx side of perf channel");
 
     for m in measurements.iter() {
-        use Operation::*;
-        let cpu_time = m.cpu_time.as_millis() as u64;
-        let wall_time = m.wall_time.as_millis() as u64;
-
-        match m.op {
-            GenerateTreeC => {
-                output.generate_tree_c_cpu_time_ms = cpu_time;
-                output.generate_tree_c_wall_time_ms = wall_time;
-            }
-            GenerateTreeRLast => {
-                output.tree_r_last_cpu_time_ms = cpu_time;
-                output.tree_r_last_wall_time_ms = wall_time;
-            }
-            CommD => {
-                output.comm_d_cpu_time_ms = cpu_time;
-                output.comm_d_wall_time_ms = wall_time;
-            }
-            EncodeWindowTimeAll => {
-                output.encode_window_time_all_cpu_time_ms = cpu_time;
-                output.encode_window_time_all_wall_time_ms = wall_time;
-            }
-            WindowCommLeavesTime => {
-                output.window_comm_leaves_time_cpu_time_ms = cpu_time;
-                output.window_comm_leaves_time_wall_time_ms = wall_time;
-            }
-            PorepCommitTime => {
-                output.porep_commit_time_cpu_time_ms = cpu_time;
-                output.porep_commit_time_wall_time_ms = wall_time;
-            }
-            PostInclusionProofs => {
-                output.epost_inclusions_cpu_time_ms = cpu_time;
-                output.epost_inclusions_wall_time_ms = wall_time;
-            }
-            PostFinalizeTicket => {
-                output.post_finalize_ticket_cpu_time_ms = cpu_time;
-                output.post_finalize_ticket_time_ms = wall_time;
-            }
-            PostReadChallengedRange => {
-                output.post_read_challenged_range_cpu_time_ms = cpu_time;
-                output.post_read_challenged_range_time_ms = wall_time;
-            }
-            PostPartialTicketHash => {
-                output.post_partial_ticket_hash_cpu_time_ms = cpu_time;
-                output.post_partial_ticket_hash_time_ms = wall_time;
+        record_op_measurement(output, &m)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(any(test, feature = "measurements"))]
+fn record_op_measurement(output: &mut FlarpOutputs, m: &OpMeasurement) -> Result<()> {
+    use Operation::*;
+    let cpu_time = m.cpu_time.as_millis() as u64;
+    let wall_time = m.wall_time.as_millis() as u64;
+
+    match m.op {
+        GenerateTreeC => {
+            output.generate_tree_c_cpu_time_ms = cpu_time;
+            output.generate_tree_c_wall_time_ms = wall_time;
+        }
+        GenerateTreeRLast => {
+            output.tree_r_last_cpu_time_ms = cpu_time;
+            output.tree_r_last_wall_time_ms = wall_time;
+        }
+        CommD => {
+            output.comm_d_cpu_time_ms = cpu_time;
+            output.comm_d_wall_time_ms = wall_time;
+        }
+        EncodeLayerTotal => {
+            let index = m
+                .layer
+                .and_then(|layer| layer.checked_sub(1))
+                .ok_or_else(|| anyhow!("invalid layer {:?}, layers are indexed from 1", m.layer))?;
+            if output.encode_layer_cpu_time_ms.len() <= index {
+                output.encode_layer_cpu_time_ms.resize(index + 1, 0);
+                output.encode_layer_wall_time_ms.resize(index + 1, 0);
             }
+            output.encode_layer_cpu_time_ms[index] = cpu_time;
+            output.encode_layer_wall_time_ms[index] = wall_time;
+            output.encode_layer_total_cpu_time_ms = output.encode_layer_cpu_time_ms.iter().sum();
+            output.encode_layer_total_wall_time_ms = output.encode_layer_wall_time_ms.iter().sum();
+        }
+        EncodeWindowTimeAll => {
+            output.encode_window_time_all_cpu_time_ms = cpu_time;
+            output.encode_window_time_all_wall_time_ms = wall_time;
+        }
+        WindowCommLeavesTime => {
+            output.window_comm_leaves_time_cpu_time_ms = cpu_time;
+            output.window_comm_leaves_time_wall_time_ms = wall_time;
+        }
+        PorepCommitTime => {
+            output.porep_commit_time_cpu_time_ms = cpu_time;
+            output.porep_commit_time_wall_time_ms = wall_time;
+        }
+        PostInclusionProofs => {
+            output.epost_inclusions_cpu_time_ms = cpu_time;
+            output.epost_inclusions_wall_time_ms = wall_time;
+        }
+        PostFinalizeTicket => {
+            output.post_finalize_ticket_cpu_time_ms = cpu_time;
+            output.post_finalize_ticket_time_ms = wall_time;
+        }
+        PostReadChallengedRange => {
+            output.post_read_challenged_range_cpu_time_ms = cpu_time;
+            output.post_read_challenged_range_time_ms = wall_time;
+        }
+        PostPartialTicketHash => {
+            output.post_partial_ticket_hash_cpu_time_ms = cpu_time;
+            output.post_partial_ticket_hash_time_ms = wall_time;
         }
     }
+
+    Ok(())
 }
 
 fn configure_global_config(inputs: &FlarpInputs) {
@@ -183,6 +221,7 @@ pub fn run(
     skip_seal_proof: bool,
     skip_post_proof: bool,
     only_replicate: bool,
+    stream_replicas: bool,
 ) -> Metadata<FlarpReport> {
     configure_global_config(&inputs);
 
@@ -191,124 +230,179 @@ pub fn run(
     let sector_size = SectorSize(inputs.sector_size_bytes());
 
     assert!(inputs.num_sectors > 0, "Missing num_sectors");
+    assert!(
+        !(stream_replicas && only_replicate),
+        "streaming replicas proves each one, so it cannot be combined with only replicating"
+    );
 
-    let (cfg, created, replica_measurement) =
-        create_replicas(sector_size, inputs.num_sectors as usize);
+    if stream_replicas {
+        generate_params(&inputs);
+
+        // Prove each replica as soon as it is sealed, before it is deleted and the next one
+        // is created, so that a single replica is on disk at any time.
+        let cfg = porep_config(sector_size);
+        let seeds = replica_seeds(inputs.num_sectors as usize, &mut rand::thread_rng());
+        let mut encoding_cpu_time = Duration::default();
+        let mut encoding_wall_time = Duration::default();
+        let mut first = true;
+        create_replicas_streaming(sector_size, &seeds, |sector_id, replica_info, measured| {
+            encoding_cpu_time += measured.cpu_time;
+            encoding_wall_time += measured.wall_time;
+
+            if !skip_seal_proof {
+                prove_replica(
+                    cfg,
+                    sector_id,
+                    &replica_info,
+                    &measured.return_value,
+                    &mut outputs,
+                );
+            }
+            if !skip_post_proof && first {
+                measure_post(&inputs, sector_id, &replica_info, &mut outputs);
+            }
+            first = false;
+        });
 
-    if only_replicate {
-        augment_with_op_measurements(&mut outputs);
-        return Metadata::wrap(FlarpReport { inputs, outputs })
-            .expect("failed to retrieve metadata");
-    }
+        if !skip_post_proof {
+            outputs.encoding_wall_time_ms = encoding_wall_time.as_millis() as u64;
+            outputs.encoding_cpu_time_ms = encoding_cpu_time.as_millis() as u64;
+        }
+    } else {
+        let (cfg, created, replica_measurement) =
+            create_replicas(sector_size, inputs.num_sectors as usize);
+
+        if only_replicate {
+            augment_with_op_measurements(&mut outputs).expect("invalid op measurement");
+            return Metadata::wrap(FlarpReport { inputs, outputs })
+                .expect("failed to retrieve metadata");
+        }
 
-    generate_params(&inputs);
+        generate_params(&inputs);
 
-    if !skip_seal_proof {
-        for (value, (sector_id, replica_info)) in
-            replica_measurement.return_value.iter().zip(created.iter())
-        {
-            let measured = measure(|| {
-                let phase1_output = seal_commit_phase1(
-                    cfg,
-                    &replica_info.private_replica_info.cache_dir_path(),
-                    PROVER_ID,
-                    *sector_id,
-                    TICKET_BYTES,
-                    RANDOMNESS,
-                    value.clone(),
-                    &replica_info.piece_info,
-                )?;
-                seal_commit_phase2(cfg, phase1_output, PROVER_ID, *sector_id)
-            })
-            .expect("failed to prove sector");
+        if !skip_seal_proof {
+            for (value, (sector_id, replica_info)) in
+                replica_measurement.return_value.iter().zip(created.iter())
+            {
+                prove_replica(cfg, *sector_id, replica_info, value, &mut outputs);
+            }
+        }
 
-            outputs.porep_proof_gen_cpu_time_ms += measured.cpu_time.as_millis() as u64;
-            outputs.porep_proof_gen_wall_time_ms += measured.wall_time.as_millis() as u64;
+        if !skip_post_proof {
+            let (sector_id, replica_info) = &created[0];
+            measure_post(&inputs, *sector_id, replica_info, &mut outputs);
+
+            outputs.encoding_wall_time_ms = replica_measurement.wall_time.as_millis() as u64;
+            outputs.encoding_cpu_time_ms = replica_measurement.cpu_time.as_millis() as u64;
         }
     }
 
-    if !skip_post_proof {
-        let (sector_id, replica_info) = &created[0];
-
-        // replica_info is moved into the PoSt scope
-        let encoding_wall_time_ms = replica_measurement.wall_time.as_millis() as u64;
-        let encoding_cpu_time_ms = replica_measurement.cpu_time.as_millis() as u64;
+    augment_with_op_measurements(&mut outputs).expect("invalid op measurement");
+    outputs.circuits = run_measure_circuits(&inputs);
 
-        // Measure PoSt generation and verification.
-        let post_config = PoStConfig {
-            sector_size,
-            challenge_count: inputs.post_challenges as usize,
-            challenged_nodes: inputs.post_challenged_nodes as usize,
-            priority: true,
-        };
+    Metadata::wrap(FlarpReport { inputs, outputs }).expect("failed to retrieve metadata")
+}
 
-        let gen_candidates_measurement = measure(|| {
-            generate_candidates(
-                post_config,
-                &RANDOMNESS,
-                CHALLENGE_COUNT,
-                &vec![(*sector_id, replica_info.private_replica_info.clone())]
-                    .into_iter()
-                    .collect(),
-                PROVER_ID,
-            )
-        })
-        .expect("failed to generate post candidates");
-
-        outputs.epost_cpu_time_ms = gen_candidates_measurement.cpu_time.as_millis() as u64;
-        outputs.epost_wall_time_ms = gen_candidates_measurement.wall_time.as_millis() as u64;
-
-        let candidates = &gen_candidates_measurement.return_value;
-
-        let gen_post_measurement = measure(|| {
-            generate_post(
-                post_config,
-                &RANDOMNESS,
-                &vec![(*sector_id, replica_info.private_replica_info.clone())]
-                    .into_iter()
-                    .collect(),
-                candidates.clone(),
-                PROVER_ID,
-            )
-        })
-        .expect("failed to generate PoSt");
-
-        outputs.post_proof_gen_cpu_time_ms = gen_post_measurement.cpu_time.as_millis() as u64;
-        outputs.post_proof_gen_wall_time_ms = gen_post_measurement.wall_time.as_millis() as u64;
-
-        let post_proof = &gen_post_measurement.return_value;
-
-        let verify_post_measurement = measure(|| {
-            verify_post(
-                post_config,
-                &RANDOMNESS,
-                CHALLENGE_COUNT,
-                post_proof,
-                &vec![(*sector_id, replica_info.public_replica_info.clone())]
-                    .into_iter()
-                    .collect(),
-                &candidates.clone(),
-                PROVER_ID,
-            )
-        })
-        .expect("verify_post function returned an error");
-
-        assert!(
-            verify_post_measurement.return_value,
-            "generated PoSt was invalid"
-        );
+/// Generate the seal proof of a replica, adding the time it took to `outputs`.
+fn prove_replica(
+    cfg: PoRepConfig,
+    sector_id: SectorId,
+    replica_info: &PreCommitReplicaOutput,
+    pre_commit_output: &SealPreCommitOutput,
+    outputs: &mut FlarpOutputs,
+) {
+    let measured = measure(|| {
+        let phase1_output = seal_commit_phase1(
+            cfg,
+            &replica_info.private_replica_info.cache_dir_path(),
+            PROVER_ID,
+            sector_id,
+            TICKET_BYTES,
+            RANDOMNESS,
+            pre_commit_output.clone(),
+            &replica_info.piece_info,
+        )?;
+        seal_commit_phase2(cfg, phase1_output, PROVER_ID, sector_id)
+    })
+    .expect("failed to prove sector");
+
+    outputs.porep_proof_gen_cpu_time_ms += measured.cpu_time.as_millis() as u64;
+    outputs.porep_proof_gen_wall_time_ms += measured.wall_time.as_millis() as u64;
+}
 
-        outputs.post_verify_cpu_time_ms = verify_post_measurement.cpu_time.as_millis() as u64;
-        outputs.post_verify_wall_time_ms = verify_post_measurement.wall_time.as_millis() as u64;
+/// Measure PoSt generation and verification over a single replica.
+fn measure_post(
+    inputs: &FlarpInputs,
+    sector_id: SectorId,
+    replica_info: &PreCommitReplicaOutput,
+    outputs: &mut FlarpOutputs,
+) {
+    let post_config = PoStConfig {
+        sector_size: SectorSize(inputs.sector_size_bytes()),
+        challenge_count: inputs.post_challenges as usize,
+        challenged_nodes: inputs.post_challenged_nodes as usize,
+        priority: true,
+        read_chunk_bytes: None,
+    };
 
-        outputs.encoding_wall_time_ms = encoding_wall_time_ms;
-        outputs.encoding_cpu_time_ms = encoding_cpu_time_ms;
-    }
+    let gen_candidates_measurement = measure(|| {
+        generate_candidates(
+            post_config,
+            &RANDOMNESS,
+            CHALLENGE_COUNT,
+            &vec![(sector_id, replica_info.private_replica_info.clone())]
+                .into_iter()
+                .collect(),
+            PROVER_ID,
+        )
+    })
+    .expect("failed to generate post candidates");
+
+    outputs.epost_cpu_time_ms = gen_candidates_measurement.cpu_time.as_millis() as u64;
+    outputs.epost_wall_time_ms = gen_candidates_measurement.wall_time.as_millis() as u64;
+
+    let candidates = &gen_candidates_measurement.return_value;
+
+    let gen_post_measurement = measure(|| {
+        generate_post(
+            post_config,
+            &RANDOMNESS,
+            &vec![(sector_id, replica_info.private_replica_info.clone())]
+                .into_iter()
+                .collect(),
+            candidates.clone(),
+            PROVER_ID,
+        )
+    })
+    .expect("failed to generate PoSt");
+
+    outputs.post_proof_gen_cpu_time_ms = gen_post_measurement.cpu_time.as_millis() as u64;
+    outputs.post_proof_gen_wall_time_ms = gen_post_measurement.wall_time.as_millis() as u64;
+
+    let post_proof = &gen_post_measurement.return_value;
+
+    let verify_post_measurement = measure(|| {
+        verify_post(
+            post_config,
+            &RANDOMNESS,
+            CHALLENGE_COUNT,
+            post_proof,
+            &vec![(sector_id, replica_info.public_replica_info.clone())]
+                .into_iter()
+                .collect(),
+            &candidates.clone(),
+            PROVER_ID,
+        )
+    })
+    .expect("verify_post function returned an error");
 
-    augment_with_op_measurements(&mut outputs);
-    outputs.circuits = run_measure_circuits(&inputs);
+    assert!(
+        verify_post_measurement.return_value,
+        "generated PoSt was invalid"
+    );
 
-    Metadata::wrap(FlarpReport { inputs, outputs }).expect("failed to retrieve metadata")
+    outputs.post_verify_cpu_time_ms = verify_post_measurement.cpu_time.as_millis() as u64;
+    outputs.post_verify_wall_time_ms = verify_post_measurement.wall_time.as_millis() as u64;
 }
 
 #[derive(Default, Debug, Serialize)]
@@ -364,14 +458,13 @@ fn measure_porep_circuit(i: &FlarpInputs) -> usize {
 
     let pp = StackedDrg::<FlarpHasher, Sha256Hasher>::setup(&sp).unwrap();
 
-    let mut cs = BenchCS::<Bls12>::new();
-    <StackedCompound<_, _> as CompoundProof<_, StackedDrg<FlarpHasher, Sha256Hasher>, _>>::blank_circuit(
-        &pp,
-    )
-    .synthesize(&mut cs)
-    .unwrap();
-
-    cs.num_constraints()
+    constraint_count::<Bls12, _>(&pp.identifier(), |cs| {
+        <StackedCompound<_, _> as CompoundProof<_, StackedDrg<FlarpHasher, Sha256Hasher>, _>>::blank_circuit(
+            &pp,
+        )
+        .synthesize(cs)
+    })
+    .unwrap()
 }
 
 fn measure_post_circuit(i: &FlarpInputs) -> usize {
@@ -383,17 +476,16 @@ fn measure_post_circuit(i: &FlarpInputs) -> usize {
         challenge_count: i.post_challenges as usize,
         challenged_nodes: i.post_challenged_nodes as usize,
         priority: true,
+        read_chunk_bytes: None,
     };
 
     let vanilla_params = post_setup_params(post_config);
     let pp = election_post::ElectionPoSt::<FlarpHasher>::setup(&vanilla_params).unwrap();
 
-    let mut cs = BenchCS::<Bls12>::new();
-    ElectionPoStCompound::<FlarpHasher>::blank_circuit(&pp)
-        .synthesize(&mut cs)
-        .unwrap();
-
-    cs.num_constraints()
+    constraint_count::<Bls12, _>(&pp.identifier(), |cs| {
+        ElectionPoStCompound::<FlarpHasher>::blank_circuit(&pp).synthesize(cs)
+    })
+    .unwrap()
 }
 
 fn measure_kdf_circuit(i: &FlarpInputs) -> usize {
@@ -406,44 +498,46 @@ fn measure_kdf_circuit(i: &FlarpInputs) -> usize {
     use storage_proofs::fr32::fr_into_bytes;
     use storage_proofs::util::bytes_into_boolean_vec_be;
 
-    let mut cs = BenchCS::<Bls12>::new();
-    let rng = &mut thread_rng();
-
     let parents = i.drg_parents + i.expander_parents;
 
-    let id: Vec<u8> = fr_into_bytes::<Bls12>(&Fr::random(rng));
-    let parents: Vec<Vec<u8>> = (0..parents)
-        .map(|_| fr_into_bytes::<Bls12>(&Fr::random(rng)))
-        .collect();
+    constraint_count::<Bls12, _>(&format!("create_label{{parents: {}}}", parents), |cs| {
+        let rng = &mut thread_rng();
 
-    let id_bits: Vec<Boolean> = {
-        let mut cs = cs.namespace(|| "id");
-        bytes_into_boolean_vec_be(&mut cs, Some(id.as_slice()), id.len()).unwrap()
-    };
-    let parents_bits: Vec<Vec<Boolean>> = parents
-        .iter()
-        .enumerate()
-        .map(|(i, p)| {
-            let mut cs = cs.namespace(|| format!("parents {}", i));
-            bytes_into_boolean_vec_be(&mut cs, Some(p.as_slice()), p.len()).unwrap()
-        })
-        .collect();
-
-    let window_index_raw = 12u64;
-    let node_raw = 123_456_789u64;
-    let window_index = uint64::UInt64::constant(window_index_raw);
-    let node = uint64::UInt64::constant(node_raw);
-
-    storage_proofs::circuit::create_label::create_label(
-        cs.namespace(|| "create_label"),
-        &id_bits,
-        parents_bits,
-        Some(window_index),
-        Some(node),
-    )
-    .expect("key derivation function failed");
+        let id: Vec<u8> = fr_into_bytes::<Bls12>(&Fr::random(rng));
+        let parents: Vec<Vec<u8>> = (0..parents)
+            .map(|_| fr_into_bytes::<Bls12>(&Fr::random(rng)))
+            .collect();
 
-    cs.num_constraints()
+        let id_bits: Vec<Boolean> = {
+            let mut cs = cs.namespace(|| "id");
+            bytes_into_boolean_vec_be(&mut cs, Some(id.as_slice()), id.len()).unwrap()
+        };
+        let parents_bits: Vec<Vec<Boolean>> = parents
+            .iter()
+            .enumerate()
+            .map(|(i, p)| {
+                let mut cs = cs.namespace(|| format!("parents {}", i));
+                bytes_into_boolean_vec_be(&mut cs, Some(p.as_slice()), p.len()).unwrap()
+            })
+            .collect();
+
+        let window_index_raw = 12u64;
+        let node_raw = 123_456_789u64;
+        let window_index = uint64::UInt64::constant(window_index_raw);
+        let node = uint64::UInt64::constant(node_raw);
+
+        storage_proofs::circuit::create_label::create_label(
+            cs.namespace(|| "create_label"),
+            &id_bits,
+            parents_bits,
+            Some(window_index),
+            Some(node),
+        )
+        .expect("key derivation function failed");
+
+        Ok(())
+    })
+    .unwrap()
 }
 
 fn generate_params(i: &FlarpInputs) {
@@ -463,6 +557,7 @@ fn generate_params(i: &FlarpInputs) {
     cache_porep_params(PoRepConfig {
         sector_size,
         partitions,
+        graph_seed: None,
     });
 
     info!("generating params: post");
@@ -471,6 +566,7 @@ fn generate_params(i: &FlarpInputs) {
         challenge_count: i.post_challenges as usize,
         challenged_nodes: i.post_challenged_nodes as usize,
         priority: true,
+        read_chunk_bytes: None,
     });
 }
 
@@ -554,3 +650,63 @@ fn cache_post_params(post_config: PoStConfig) {
             .expect("failed to get verifying key");
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::Duration;
+
+    fn encode_layer(layer: Option<usize>, cpu_time: u64, wall_time: u64) -> OpMeasurement {
+        OpMeasurement {
+            op: Operation::EncodeLayerTotal,
+            layer,
+            cpu_time: Duration::from_millis(cpu_time),
+            wall_time: Duration::from_millis(wall_time),
+        }
+    }
+
+    #[test]
+    fn test_encode_layer_measurements() {
+        let mut output = FlarpOutputs::default();
+
+        // Layers are not necessarily measured in order.
+        for &layer in &[3, 1, 4, 2] {
+            let m = encode_layer(Some(layer), layer as u64, 20 * layer as u64);
+            record_op_measurement(&mut output, &m).unwrap();
+        }
+        record_op_measurement(
+            &mut output,
+            &OpMeasurement {
+                op: Operation::EncodeWindowTimeAll,
+                layer: None,
+                cpu_time: Duration::from_millis(100),
+                wall_time: Duration::from_millis(200),
+            },
+        )
+        .unwrap();
+
+        let json = serde_json::to_value(&output).unwrap();
+        assert_eq!(
+            json["encode_layer_cpu_time_ms"],
+            serde_json::json!([1, 2, 3, 4])
+        );
+        assert_eq!(
+            json["encode_layer_wall_time_ms"],
+            serde_json::json!([20, 40, 60, 80])
+        );
+        assert_eq!(json["encode_layer_total_cpu_time_ms"], 10);
+        assert_eq!(json["encode_layer_total_wall_time_ms"], 200);
+        assert_eq!(json["encode_window_time_all_cpu_time_ms"], 100);
+        assert_eq!(json["encode_window_time_all_wall_time_ms"], 200);
+    }
+
+    #[test]
+    fn test_encode_layer_measurement_without_layer() {
+        let mut output = FlarpOutputs::default();
+
+        assert!(record_op_measurement(&mut output, &encode_layer(Some(0), 1, 1)).is_err());
+        assert!(record_op_measurement(&mut output, &encode_layer(None, 1, 1)).is_err());
+        assert!(output.encode_layer_cpu_time_ms.is_empty());
+    }
+}
@@ -2,6 +2,7 @@ use std::io::{stdin, stdout};
 
 use anyhow::Result;
 use clap::{value_t, App, Arg, SubCommand};
+use filecoin_proofs::types::SectorSize;
 
 use crate::flarp::FlarpInputs;
 
@@ -11,6 +12,7 @@ mod hash_fns;
 mod merkleproofs;
 mod shared;
 mod stacked;
+mod verify;
 
 fn main() -> Result<()> {
     fil_logger::init();
@@ -134,6 +136,13 @@ fn main() -> Result<()> {
                 .long("only-replicate")
                 .takes_value(false)
                 .help("only run replication"),
+        )
+        .arg(
+            Arg::with_name("stream-replicas")
+                .long("stream-replicas")
+                .takes_value(false)
+                .conflicts_with("only-replicate")
+                .help("replicate and prove one sector at a time, keeping a single replica on disk"),
         );
 
     let merkleproof_cmd = SubCommand::with_name("merkleproofs")
@@ -153,6 +162,30 @@ fn main() -> Result<()> {
                 .takes_value(true),
         );
 
+    let verify_cmd = SubCommand::with_name("verify")
+        .about("Benchmark seal proof verification")
+        .arg(
+            Arg::with_name("config")
+                .long("config")
+                .takes_value(true)
+                .required_unless("size")
+                .help("path to the JSON of a sealed sector's commitments and proof"),
+        )
+        .arg(
+            Arg::with_name("size")
+                .long("size")
+                .takes_value(true)
+                .conflicts_with("config")
+                .help("seal a sector of this size in KiB to verify"),
+        )
+        .arg(
+            Arg::with_name("iterations")
+                .long("iterations")
+                .default_value("10")
+                .help("How many times to verify the proof")
+                .takes_value(true),
+        );
+
     let matches = App::new("benchy")
         .version("0.1")
         .subcommand(stacked_cmd)
@@ -160,6 +193,7 @@ fn main() -> Result<()> {
         .subcommand(hash_cmd)
         .subcommand(flarp_cmd)
         .subcommand(merkleproof_cmd)
+        .subcommand(verify_cmd)
         .get_matches();
 
     match matches.subcommand() {
@@ -215,11 +249,23 @@ fn main() -> Result<()> {
                 m.is_present("skip-seal-proof"),
                 m.is_present("skip-post-proof"),
                 m.is_present("only-replicate"),
+                m.is_present("stream-replicas"),
             );
 
             serde_json::to_writer(stdout(), &outputs)
                 .expect("failed to write FlarpOutput to stdout")
         }
+        ("verify", Some(m)) => {
+            let inputs = if m.is_present("config") {
+                let file = value_t!(m, "config", String)?;
+                serde_json::from_reader(std::fs::File::open(&file)?)?
+            } else {
+                let sector_size_kibs = value_t!(m, "size", u64)?;
+                verify::seal_sector(SectorSize(sector_size_kibs * 1024))?
+            };
+
+            verify::run(inputs, value_t!(m, "iterations", usize)?)?.print();
+        }
         _ => panic!("carnation"),
     }
 
@@ -1,7 +1,8 @@
 use std::io::{BufWriter, Seek, SeekFrom, Write};
 
 use log::info;
-use rand::RngCore;
+use rand::{Rng, RngCore, SeedableRng};
+use rand_xorshift::XorShiftRng;
 use rayon::prelude::*;
 use tempfile::NamedTempFile;
 
@@ -25,7 +26,20 @@ pub struct PreCommitReplicaOutput {
     pub public_replica_info: PublicReplicaInfo,
 }
 
-pub fn create_piece(piece_bytes: UnpaddedBytesAmount) -> (NamedTempFile, PieceInfo) {
+/// The sector id and piece data seed of a replica to create. Replicas created from the same
+/// seed have the same commitments.
+pub type ReplicaSeed = (SectorId, [u8; 16]);
+
+pub fn replica_seeds<R: Rng>(qty_sectors: usize, rng: &mut R) -> Vec<ReplicaSeed> {
+    (0..qty_sectors)
+        .map(|_| (SectorId::from(rng.gen::<u64>()), rng.gen()))
+        .collect()
+}
+
+pub fn create_piece(
+    piece_bytes: UnpaddedBytesAmount,
+    seed: [u8; 16],
+) -> (NamedTempFile, PieceInfo) {
     info!("create_piece");
     let mut file = NamedTempFile::new().expect("failed to create piece file");
     {
@@ -33,7 +47,7 @@ pub fn create_piece(piece_bytes: UnpaddedBytesAmount) -> (NamedTempFile, PieceIn
         let mut len = u64::from(piece_bytes) as usize;
         let chunk_size = 8 * 1024 * 1024;
         let mut buffer = vec![0u8; chunk_size];
-        rand::thread_rng().fill_bytes(&mut buffer);
+        XorShiftRng::from_seed(seed).fill_bytes(&mut buffer);
 
         while len > 0 {
             let to_write = std::cmp::min(len, chunk_size);
@@ -67,6 +81,20 @@ pub fn create_piece(piece_bytes: UnpaddedBytesAmount) -> (NamedTempFile, PieceIn
     (file, info)
 }
 
+pub(super) fn porep_config(sector_size: SectorSize) -> PoRepConfig {
+    PoRepConfig {
+        sector_size,
+        partitions: PoRepProofPartitions(
+            *POREP_PARTITIONS
+                .read()
+                .unwrap()
+                .get(&u64::from(sector_size))
+                .expect("unknown sector size"),
+        ),
+        graph_seed: None,
+    }
+}
+
 pub fn create_replicas(
     sector_size: SectorSize,
     qty_sectors: usize,
@@ -75,20 +103,28 @@ pub fn create_replicas(
     Vec<(SectorId, PreCommitReplicaOutput)>,
     FuncMeasurement<Vec<SealPreCommitOutput>>,
 ) {
+    create_replicas_from_seeds(
+        sector_size,
+        &replica_seeds(qty_sectors, &mut rand::thread_rng()),
+    )
+}
+
+/// Creates all replicas at once: their pieces are generated, then all sectors are sealed,
+/// and every replica's files are kept until the process exits.
+pub fn create_replicas_from_seeds(
+    sector_size: SectorSize,
+    seeds: &[ReplicaSeed],
+) -> (
+    PoRepConfig,
+    Vec<(SectorId, PreCommitReplicaOutput)>,
+    FuncMeasurement<Vec<SealPreCommitOutput>>,
+) {
+    let qty_sectors = seeds.len();
     info!("creating replicas: {:?} - {}", sector_size, qty_sectors);
     let sector_size_unpadded_bytes_ammount =
         UnpaddedBytesAmount::from(PaddedBytesAmount::from(sector_size));
 
-    let porep_config = PoRepConfig {
-        sector_size,
-        partitions: PoRepProofPartitions(
-            *POREP_PARTITIONS
-                .read()
-                .unwrap()
-                .get(&u64::from(sector_size))
-                .expect("unknown sector size"),
-        ),
-    };
+    let porep_config = porep_config(sector_size);
 
     let mut out: Vec<(SectorId, PreCommitReplicaOutput)> = Default::default();
     let mut sector_ids = Vec::new();
@@ -96,10 +132,10 @@ pub fn create_replicas(
     let mut staged_files = Vec::new();
     let mut sealed_files = Vec::new();
 
-    for i in 0..qty_sectors {
+    for (i, (sector_id, _)) in seeds.iter().enumerate() {
         info!("creating sector {}/{}", i, qty_sectors);
 
-        sector_ids.push(SectorId::from(rand::random::<u64>()));
+        sector_ids.push(*sector_id);
         cache_dirs.push(tempfile::tempdir().expect("failed to create cache dir"));
 
         let staged_file =
@@ -112,12 +148,10 @@ pub fn create_replicas(
         staged_files.push(staged_file);
     }
 
-    let (piece_files, piece_infos): (Vec<_>, Vec<_>) = (0..qty_sectors)
-        .into_par_iter()
-        .map(|_i| {
-            let (piece_file, piece_info) = create_piece(UnpaddedBytesAmount::from(
-                PaddedBytesAmount::from(sector_size),
-            ));
+    let (piece_files, piece_infos): (Vec<_>, Vec<_>) = seeds
+        .par_iter()
+        .map(|(_, seed)| {
+            let (piece_file, piece_info) = create_piece(sector_size_unpadded_bytes_ammount, *seed);
             (piece_file, vec![piece_info])
         })
         .unzip();
@@ -211,3 +245,111 @@ pub fn create_replicas(
 
     (porep_config, out, seal_pre_commit_outputs)
 }
+
+/// Like `create_replicas_from_seeds`, but creates one replica at a time and hands it to `f` as
+/// soon as it is sealed, together with the measurement of its sealing. The replica's files
+/// are deleted once `f` returns, so a caller proving and discarding each replica in `f` only
+/// needs the resources of a single replica at any time.
+pub fn create_replicas_streaming<F>(
+    sector_size: SectorSize,
+    seeds: &[ReplicaSeed],
+    mut f: F,
+) -> PoRepConfig
+where
+    F: FnMut(SectorId, PreCommitReplicaOutput, FuncMeasurement<SealPreCommitOutput>),
+{
+    info!(
+        "creating replicas one at a time: {:?} - {}",
+        sector_size,
+        seeds.len()
+    );
+    let sector_size_unpadded_bytes_ammount =
+        UnpaddedBytesAmount::from(PaddedBytesAmount::from(sector_size));
+    let porep_config = porep_config(sector_size);
+
+    for (i, (sector_id, seed)) in seeds.iter().enumerate() {
+        info!("creating sector {}/{}", i, seeds.len());
+
+        let cache_dir = tempfile::tempdir().expect("failed to create cache dir");
+        let mut staged_file =
+            NamedTempFile::new().expect("could not create temp file for staged sector");
+        let sealed_file =
+            NamedTempFile::new().expect("could not create temp file for sealed sector");
+
+        let (mut piece_file, piece_info) = create_piece(sector_size_unpadded_bytes_ammount, *seed);
+        add_piece(
+            &mut piece_file,
+            &mut staged_file,
+            sector_size_unpadded_bytes_ammount,
+            &[],
+        )
+        .unwrap();
+        let piece_info = vec![piece_info];
+
+        let seal_pre_commit_output = measure(|| {
+            let phase1 = seal_pre_commit_phase1(
+                porep_config,
+                &cache_dir,
+                &staged_file,
+                &sealed_file,
+                PROVER_ID,
+                *sector_id,
+                TICKET_BYTES,
+                &piece_info,
+            )?;
+            seal_pre_commit_phase2(porep_config, phase1, &cache_dir, &sealed_file)
+        })
+        .expect("seal_pre_commit produced an error");
+
+        let comm_r = seal_pre_commit_output.return_value.comm_r;
+        let replica = PreCommitReplicaOutput {
+            piece_info,
+            private_replica_info: PrivateReplicaInfo::new(
+                sealed_file.path().to_str().unwrap().to_string(),
+                comm_r,
+                cache_dir.path().to_path_buf(),
+            )
+            .expect("failed to create PrivateReplicaInfo"),
+            public_replica_info: PublicReplicaInfo::new(comm_r)
+                .expect("failed to create PublicReplicaInfo"),
+        };
+
+        f(*sector_id, replica, seal_pre_commit_output);
+    }
+
+    porep_config
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use filecoin_proofs::constants::SECTOR_SIZE_ONE_KIB;
+
+    #[test]
+    fn streaming_replicas_match_batch_replicas() {
+        let sector_size = SectorSize(SECTOR_SIZE_ONE_KIB);
+        let seeds = replica_seeds(3, &mut XorShiftRng::from_seed([7; 16]));
+
+        let (_, batch, batch_outputs) = create_replicas_from_seeds(sector_size, &seeds);
+
+        let mut streamed = Vec::new();
+        create_replicas_streaming(sector_size, &seeds, |sector_id, replica, output| {
+            // The replica's files still exist while it is being handed out.
+            assert!(replica.private_replica_info.cache_dir_path().exists());
+            streamed.push((sector_id, replica.piece_info, output.return_value));
+        });
+
+        assert_eq!(streamed.len(), 3);
+        for (((sector_id, replica), expected), (streamed_id, piece_info, output)) in batch
+            .iter()
+            .zip(batch_outputs.return_value.iter())
+            .zip(streamed.iter())
+        {
+            assert_eq!(sector_id, streamed_id);
+            assert_eq!(&replica.piece_info, piece_info);
+            assert_eq!(expected.comm_r, output.comm_r);
+            assert_eq!(expected.comm_d, output.comm_d);
+        }
+    }
+}
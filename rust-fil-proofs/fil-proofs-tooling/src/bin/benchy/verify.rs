@@ -0,0 +1,166 @@
+use std::io::stdout;
+use std::time::{Duration, Instant};
+
+use anyhow::{ensure, Result};
+use fil_proofs_tooling::Metadata;
+use filecoin_proofs::types::{Commitment, ProverId, SectorSize, Ticket};
+use filecoin_proofs::{seal_commit_phase1, seal_commit_phase2, verify_seal};
+use log::info;
+use serde::{Deserialize, Serialize};
+use storage_proofs::sector::SectorId;
+
+use crate::shared::{create_replicas, porep_config, PROVER_ID, RANDOMNESS, TICKET_BYTES};
+
+/// A sealed sector and its seal proof, i.e. everything `verify_seal` needs.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct VerifyInputs {
+    sector_size: u64,
+    comm_r: Commitment,
+    comm_d: Commitment,
+    prover_id: ProverId,
+    sector_id: u64,
+    ticket: Ticket,
+    seed: Ticket,
+    proof: Vec<u8>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct VerifyOutputs {
+    iterations: usize,
+    mean_verify_wall_time_us: u64,
+    p99_verify_wall_time_us: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct VerifyReport {
+    inputs: VerifyInputs,
+    outputs: VerifyOutputs,
+}
+
+impl VerifyReport {
+    /// Print all results to stdout
+    pub fn print(&self) {
+        let wrapped = Metadata::wrap(&self).expect("failed to retrieve metadata");
+        serde_json::to_writer(stdout(), &wrapped).expect("cannot write report JSON to stdout");
+    }
+}
+
+/// Seals and proves a single sector of `sector_size`, for benchmarking when no pre-sealed
+/// sector is given.
+pub fn seal_sector(sector_size: SectorSize) -> Result<VerifyInputs> {
+    info!("sealing a sector to verify: {:?}", sector_size);
+
+    let (cfg, mut created, replica_measurement) = create_replicas(sector_size, 1);
+    let (sector_id, replica_info) = created.pop().expect("no replica was created");
+    let pre_commit_output = replica_measurement.return_value[0].clone();
+
+    let phase1_output = seal_commit_phase1(
+        cfg,
+        &replica_info.private_replica_info.cache_dir_path(),
+        PROVER_ID,
+        sector_id,
+        TICKET_BYTES,
+        RANDOMNESS,
+        pre_commit_output.clone(),
+        &replica_info.piece_info,
+    )?;
+    let commit_output = seal_commit_phase2(cfg, phase1_output, PROVER_ID, sector_id)?;
+
+    Ok(VerifyInputs {
+        sector_size: u64::from(sector_size),
+        comm_r: pre_commit_output.comm_r,
+        comm_d: pre_commit_output.comm_d,
+        prover_id: PROVER_ID,
+        sector_id: u64::from(sector_id),
+        ticket: TICKET_BYTES,
+        seed: RANDOMNESS,
+        proof: commit_output.proof,
+    })
+}
+
+/// Verifies the seal proof of `inputs` `iterations` times and reports the mean and p99
+/// latency of a single `verify_seal`.
+pub fn run(inputs: VerifyInputs, iterations: usize) -> Result<VerifyReport> {
+    ensure!(iterations > 0, "at least one iteration is required");
+    info!(
+        "Benchy Verify: sector-size={}, iterations={}",
+        inputs.sector_size, iterations
+    );
+
+    let cfg = porep_config(SectorSize(inputs.sector_size));
+    let mut latencies = Vec::with_capacity(iterations);
+
+    for _ in 0..iterations {
+        let start = Instant::now();
+        let valid = verify_seal(
+            cfg,
+            inputs.comm_r,
+            inputs.comm_d,
+            inputs.prover_id,
+            SectorId::from(inputs.sector_id),
+            inputs.ticket,
+            inputs.seed,
+            &inputs.proof,
+        )?;
+        latencies.push(start.elapsed());
+
+        ensure!(valid, "seal proof failed to verify");
+    }
+
+    let (mean, p99) = mean_and_p99(&mut latencies);
+
+    Ok(VerifyReport {
+        inputs,
+        outputs: VerifyOutputs {
+            iterations,
+            mean_verify_wall_time_us: mean.as_micros() as u64,
+            p99_verify_wall_time_us: p99.as_micros() as u64,
+        },
+    })
+}
+
+/// The mean and p99 of a non-empty set of latencies, sorting them in place.
+fn mean_and_p99(latencies: &mut [Duration]) -> (Duration, Duration) {
+    latencies.sort();
+    let n = latencies.len();
+    let mean = latencies.iter().sum::<Duration>() / n as u32;
+    // The smallest latency at least 99% of the iterations did not exceed.
+    let p99 = latencies[(n * 99 + 99) / 100 - 1];
+
+    (mean, p99)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use filecoin_proofs::constants::SECTOR_SIZE_ONE_KIB;
+
+    #[test]
+    fn verify_one_kib_sector() {
+        let inputs = seal_sector(SectorSize(SECTOR_SIZE_ONE_KIB)).unwrap();
+        let report = run(inputs, 3).unwrap();
+
+        assert_eq!(report.outputs.iterations, 3);
+    }
+
+    #[test]
+    fn latency_percentiles() {
+        let ms = Duration::from_millis;
+
+        // 1ms to 200ms, out of order.
+        let mut latencies: Vec<Duration> = (1..=200).rev().map(ms).collect();
+        latencies.swap(0, 150);
+        let (mean, p99) = mean_and_p99(&mut latencies);
+        assert_eq!(mean, Duration::from_micros(100_500));
+        assert_eq!(p99, ms(198));
+        assert!(latencies[0] <= mean && mean <= p99 && p99 <= latencies[199]);
+
+        // With few samples, the p99 is the maximum.
+        let (mean, p99) = mean_and_p99(&mut [ms(3), ms(1), ms(2)]);
+        assert_eq!((mean, p99), (ms(2), ms(3)));
+
+        let (mean, p99) = mean_and_p99(&mut [ms(5)]);
+        assert_eq!((mean, p99), (ms(5), ms(5)));
+    }
+}
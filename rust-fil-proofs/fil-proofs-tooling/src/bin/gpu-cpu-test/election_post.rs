@@ -31,6 +31,7 @@ const POST_CONFIG: PoStConfig = PoStConfig {
     challenge_count: POST_CHALLENGE_COUNT,
     challenged_nodes: POST_CHALLENGED_NODES,
     priority: false,
+    read_chunk_bytes: None,
 };
 
 fn generate_piece_infos(mut staged_file: &NamedTempFile) -> Vec<PieceInfo> {
@@ -85,6 +86,7 @@ pub fn generate_seal_fixture(cache_dir_path: &Path) -> (SealPreCommitOutput, Vec
         partitions: PoRepProofPartitions(
             *POREP_PARTITIONS.read().unwrap().get(&SECTOR_SIZE).unwrap(),
         ),
+        graph_seed: None,
     };
     let phase1_output = seal_pre_commit_phase1(
         porep_config,
@@ -118,6 +120,7 @@ pub fn do_generate_seal(
         partitions: PoRepProofPartitions(
             *POREP_PARTITIONS.read().unwrap().get(&SECTOR_SIZE).unwrap(),
         ),
+        graph_seed: None,
     };
     let phase1_output = seal_commit_phase1(
         porep_config,
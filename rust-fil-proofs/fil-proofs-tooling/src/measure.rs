@@ -9,6 +9,9 @@ pub struct FuncMeasurement<T> {
     pub return_value: T,
 }
 
+/// Runs `f`, measuring the CPU time of the process and the wall time it took. Wall time is
+/// read from the monotonic `Instant` clock, so clock adjustments (e.g. by NTP) during a long
+/// measurement cannot skew it or make it negative.
 pub fn measure<T, F>(f: F) -> Result<FuncMeasurement<T>>
 where
     F: FnOnce() -> Result<T>,
@@ -24,3 +27,24 @@ where
         return_value: x,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::thread;
+
+    #[test]
+    fn test_measure_wall_time() {
+        let sleep = Duration::from_millis(50);
+        let measurement = measure(|| {
+            thread::sleep(sleep);
+            Ok(42)
+        })
+        .unwrap();
+
+        assert_eq!(measurement.return_value, 42);
+        assert!(measurement.wall_time >= sleep);
+        assert!(measurement.wall_time < sleep * 20);
+    }
+}
@@ -109,6 +109,7 @@ pub fn test_seal_lifecycle() -> Result<()> {
         partitions: PoRepProofPartitions(
             *POREP_PARTITIONS.read().unwrap().get(&sector_size).unwrap(),
         ),
+        graph_seed: None,
     };
 
     println!("PoRepConfig = {:?}",config); 
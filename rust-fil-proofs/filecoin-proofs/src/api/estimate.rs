@@ -0,0 +1,159 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use bellperson::multicore::Worker;
+use bellperson::multiexp::{multiexp, FullDensity};
+use bellperson::Circuit;
+use ff::{Field, PrimeField};
+use futures::Future;
+use groupy::CurveAffine;
+use lazy_static::lazy_static;
+use paired::bls12_381::{Bls12, Fr, G1Affine};
+use storage_proofs::circuit::bench::constraint_count;
+use storage_proofs::circuit::stacked::StackedCompound;
+use storage_proofs::compound_proof::CompoundProof;
+use storage_proofs::drgraph::{graph_height, Graph};
+use storage_proofs::hasher::{Domain, HashFunction, Hasher};
+use storage_proofs::parameter_cache::ParameterSetMetadata;
+use storage_proofs::stacked::StackedDrg;
+use storage_proofs::util::NODE_SIZE;
+
+use crate::constants::{DefaultPieceHasher, DefaultTreeHasher};
+use crate::parameters::porep_public_params;
+use crate::types::{PaddedBytesAmount, PoRepConfig, PoRepProofPartitions, SealTimeEstimate};
+
+/// How many hashes of each kind the calibration times.
+const CALIBRATION_HASHES: usize = 1 << 12;
+/// The number of bases of the calibration multiexp.
+const CALIBRATION_MULTIEXP_BASES: usize = 1 << 12;
+/// The size of the input hashed per calibration sha256 hash.
+const CALIBRATION_HASH_BYTES: usize = 1024;
+
+/// A groth proof takes multiexps over the A, B and C queries, of about one base per
+/// constraint each. The B query is computed in G2 as well, which costs about three times as
+/// much as in G1.
+const MULTIEXP_BASES_PER_CONSTRAINT: f64 = 6.0;
+
+lazy_static! {
+    static ref CALIBRATION: Calibration = Calibration::run();
+}
+
+/// The measured cost of the operations sealing is made of, in seconds per operation.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Calibration {
+    /// Hashing a single byte with sha256, as the labels and tree-d are hashed.
+    pub(crate) sha256_byte: f64,
+    /// Hashing two nodes with the tree hasher.
+    pub(crate) tree_hash: f64,
+    /// A single base of a G1 multiexp.
+    pub(crate) multiexp_base: f64,
+}
+
+impl Calibration {
+    fn run() -> Self {
+        let data = vec![7u8; CALIBRATION_HASH_BYTES];
+        let start = Instant::now();
+        for _ in 0..CALIBRATION_HASHES {
+            <DefaultPieceHasher as Hasher>::Function::hash(&data);
+        }
+        let sha256_byte = per_op(start.elapsed(), CALIBRATION_HASHES * CALIBRATION_HASH_BYTES);
+
+        let rng = &mut rand::thread_rng();
+        let mut node = <DefaultTreeHasher as Hasher>::Domain::random(rng);
+        let other = <DefaultTreeHasher as Hasher>::Domain::random(rng);
+        let start = Instant::now();
+        for _ in 0..CALIBRATION_HASHES {
+            node = <DefaultTreeHasher as Hasher>::Function::hash2(&node, &other);
+        }
+        let tree_hash = per_op(start.elapsed(), CALIBRATION_HASHES);
+
+        let bases = Arc::new(vec![G1Affine::one(); CALIBRATION_MULTIEXP_BASES]);
+        let exponents = Arc::new(
+            (0..CALIBRATION_MULTIEXP_BASES)
+                .map(|_| Fr::random(rng).into_repr())
+                .collect::<Vec<_>>(),
+        );
+        let start = Instant::now();
+        multiexp(
+            &Worker::new(),
+            (bases, 0),
+            FullDensity,
+            exponents,
+            &mut None,
+        )
+        .wait()
+        .expect("multiexp over valid bases cannot fail");
+        let multiexp_base = per_op(start.elapsed(), CALIBRATION_MULTIEXP_BASES);
+
+        Calibration {
+            sha256_byte,
+            tree_hash,
+            multiexp_base,
+        }
+    }
+}
+
+fn per_op(elapsed: Duration, ops: usize) -> f64 {
+    elapsed.as_secs_f64() / ops as f64
+}
+
+/// Estimates how long each phase of sealing a sector of `porep_config` takes on this machine.
+///
+/// A small calibration times sha256 and tree hashing and a CPU multiexp, once per process,
+/// and the result is extrapolated to the sector's node and constraint count:
+///
+/// * `pre_commit_phase1` - labeling, one sha256 hash of a node's parents per node and layer.
+/// * `pre_commit_phase2` - building tree-d with sha256, and tree-c and tree-r-last, about one
+///   tree hash per layer and node for the columns plus one per node for the trees.
+/// * `commit_phase1` - the inclusion proofs of every challenge, one tree hash per tree level.
+/// * `commit_phase2` - the groth proving multiexps of every partition.
+///
+/// This is a heuristic: it ignores disk I/O, parallelism and the sublinear cost of large
+/// multiexps, and proving on a GPU is much faster. Use it to compare sector sizes and
+/// machines, not as a deadline. Counting the circuit's constraints synthesizes it once per
+/// config and process, which takes a while for large sectors.
+pub fn estimate_seal_time(porep_config: PoRepConfig) -> Result<SealTimeEstimate> {
+    estimate_seal_time_with(porep_config, *CALIBRATION)
+}
+
+/// Like `estimate_seal_time`, but extrapolates the given per-operation costs rather than those
+/// measured on this machine.
+pub(crate) fn estimate_seal_time_with(
+    porep_config: PoRepConfig,
+    calibration: Calibration,
+) -> Result<SealTimeEstimate> {
+    let public_params = porep_public_params(porep_config)?;
+
+    let nodes = (u64::from(PaddedBytesAmount::from(porep_config)) / NODE_SIZE as u64) as f64;
+    let layers = public_params.layer_challenges.layers() as f64;
+    let partitions = usize::from(PoRepProofPartitions::from(porep_config)) as f64;
+    let challenges = public_params.layer_challenges.challenges_count_all() as f64 * partitions;
+    let height = graph_height(nodes as usize) as f64;
+
+    let label_bytes = ((2 + public_params.graph.degree()) * NODE_SIZE) as f64;
+    let constraints = constraint_count::<Bls12, _>(&public_params.identifier(), |cs| {
+        <StackedCompound<DefaultTreeHasher, DefaultPieceHasher> as CompoundProof<
+            _,
+            StackedDrg<DefaultTreeHasher, DefaultPieceHasher>,
+            _,
+        >>::blank_circuit(&public_params)
+        .synthesize(cs)
+    })? as f64;
+
+    let tree_d = nodes * 2.0 * NODE_SIZE as f64 * calibration.sha256_byte;
+    let trees_c_and_r_last = nodes * (layers + 2.0) * calibration.tree_hash;
+
+    Ok(SealTimeEstimate {
+        pre_commit_phase1: seconds(layers * nodes * label_bytes * calibration.sha256_byte),
+        pre_commit_phase2: seconds(tree_d + trees_c_and_r_last),
+        commit_phase1: seconds(challenges * (layers + 3.0) * height * calibration.tree_hash),
+        commit_phase2: seconds(
+            partitions * constraints * MULTIEXP_BASES_PER_CONSTRAINT * calibration.multiexp_base,
+        ),
+    })
+}
+
+fn seconds(secs: f64) -> Duration {
+    Duration::from_secs_f64(secs)
+}
@@ -0,0 +1,119 @@
+use anyhow::{ensure, Result};
+use paired::bls12_381::{Bls12, Fr};
+use storage_proofs::sector::SectorId;
+use storage_proofs::stacked::{self, build_vec_tree, generate_replica_id, StackedDrg};
+
+use crate::api::seal::verify_vanilla_proofs;
+use crate::api::util::commitment_from_fr;
+use crate::constants::{DefaultPieceHasher, DefaultTreeHasher};
+use crate::parameters::porep_public_params;
+use crate::pieces::verify_pieces;
+use crate::types::{
+    Commitment, PaddedBytesAmount, PieceInfo, PoRepConfig, PoRepProofPartitions, ProverId,
+    SealCommitPhase1Output, Ticket,
+};
+
+/// The output of `seal_in_memory`.
+#[derive(Clone, Debug)]
+pub struct InMemorySealOutput {
+    pub comm_r: Commitment,
+    pub comm_d: Commitment,
+    /// The sealed sector.
+    pub replica: Vec<u8>,
+    /// Passed to `seal_commit_phase2` to generate the proof.
+    pub commit_phase1_output: SealCommitPhase1Output,
+}
+
+/// Seals `staged_sector` up to the output of `seal_commit_phase1`, keeping the labels, trees
+/// and replica in memory instead of in a cache directory and sealed file. The commitments and
+/// vanilla proofs are the same as those of sealing the sector on disk.
+///
+/// This holds every layer of the sector in memory at once, so it is only meant for the tiny
+/// sectors of tests.
+///
+/// # Arguments
+///
+/// * `porep_config` - porep configuration containing the number of bytes in this sector.
+/// * `staged_sector` - the staged sector, zero-padded to the sector size if shorter.
+/// * `prover_id` - the prover-id that is sealing this sector.
+/// * `sector_id` - the sector-id of this sector.
+/// * `ticket` - the ticket that will be used to generate this sector's replica-id.
+/// * `seed` - the seed used to derive the porep challenges.
+/// * `piece_infos` - the piece info (commitment and byte length) for each piece in this sector.
+#[allow(clippy::too_many_arguments)]
+pub fn seal_in_memory(
+    porep_config: PoRepConfig,
+    staged_sector: &[u8],
+    prover_id: ProverId,
+    sector_id: SectorId,
+    ticket: Ticket,
+    seed: Ticket,
+    piece_infos: &[PieceInfo],
+) -> Result<InMemorySealOutput> {
+    let sector_bytes = usize::from(PaddedBytesAmount::from(porep_config));
+    ensure!(
+        staged_sector.len() <= sector_bytes,
+        "staged sector is larger than the sector ({} > {})",
+        staged_sector.len(),
+        sector_bytes
+    );
+
+    let mut replica = staged_sector.to_vec();
+    replica.resize(sector_bytes, 0);
+
+    let data_tree = build_vec_tree::<DefaultPieceHasher>(&replica)?;
+    let comm_d_root: Fr = data_tree.root().into();
+    let comm_d = commitment_from_fr::<Bls12>(comm_d_root);
+    ensure!(
+        verify_pieces(&comm_d, piece_infos, porep_config.into())?,
+        "pieces and comm_d do not match"
+    );
+
+    let replica_id =
+        generate_replica_id::<DefaultTreeHasher, _>(&prover_id, sector_id.into(), &ticket, comm_d);
+
+    let vanilla_params = porep_public_params(porep_config)?;
+    let (tau, p_aux, aux) =
+        StackedDrg::<DefaultTreeHasher, DefaultPieceHasher>::replicate_in_memory(
+            &vanilla_params,
+            &replica_id,
+            &mut replica,
+            data_tree,
+        )?;
+    let comm_r = commitment_from_fr::<Bls12>(tau.comm_r.into());
+
+    let public_inputs = stacked::PublicInputs {
+        replica_id,
+        tau: Some(tau),
+        k: None,
+        seed,
+    };
+    let vanilla_proofs =
+        StackedDrg::<DefaultTreeHasher, DefaultPieceHasher>::prove_all_partitions_in_memory(
+            &vanilla_params,
+            &public_inputs,
+            &p_aux,
+            &aux,
+            usize::from(PoRepProofPartitions::from(porep_config)),
+        )?;
+
+    let commit_phase1_output = SealCommitPhase1Output {
+        vanilla_proofs,
+        comm_r,
+        comm_d,
+        replica_id,
+        seed,
+        ticket,
+    };
+    ensure!(
+        verify_vanilla_proofs(porep_config, &commit_phase1_output)?,
+        "Invalid vanilla proof generated"
+    );
+
+    Ok(InMemorySealOutput {
+        comm_r,
+        comm_d,
+        replica,
+        commit_phase1_output,
+    })
+}
@@ -8,26 +8,32 @@ use storage_proofs::hasher::Hasher;
 use storage_proofs::porep::PoRep;
 use storage_proofs::sector::SectorId;
 use storage_proofs::stacked::{generate_replica_id, CacheKey, StackedDrg};
-use tempfile::tempfile;
+use tempfile::tempfile_in;
 
-use crate::api::util::{as_safe_commitment, get_tree_leafs};
+use crate::api::util::{as_safe_commitment, get_tree_leafs, seal_temp_dir};
 use crate::constants::{
     DefaultPieceHasher, DefaultTreeHasher,
     MINIMUM_RESERVED_BYTES_FOR_PIECE_IN_FULLY_ALIGNED_SECTOR as MINIMUM_PIECE_SIZE,
 };
 use crate::fr32::{write_padded, write_unpadded};
-use crate::parameters::public_params;
+use crate::parameters::porep_public_params;
 use crate::pieces::get_aligned_source;
 use crate::types::{
-    Commitment, PaddedBytesAmount, PieceInfo, PoRepConfig, PoRepProofPartitions, ProverId, Ticket,
-    UnpaddedByteIndex, UnpaddedBytesAmount,
+    Commitment, PaddedBytesAmount, PieceInfo, PoRepConfig, ProverId, Ticket, UnpaddedByteIndex,
+    UnpaddedBytesAmount,
 };
 
+mod estimate;
+#[cfg(any(test, feature = "test-utils"))]
+mod in_memory;
 mod post;
 mod seal;
 pub(crate) mod util;
 pub mod arstests;
 
+pub use self::estimate::*;
+#[cfg(any(test, feature = "test-utils"))]
+pub use self::in_memory::*;
 pub use self::post::*;
 pub use self::seal::*;
 use std::io;
@@ -63,6 +69,81 @@ pub fn get_unsealed_range<T: Into<PathBuf> + AsRef<Path>>(
     offset: UnpaddedByteIndex,
     num_bytes: UnpaddedBytesAmount,
 ) -> Result<UnpaddedBytesAmount> {
+    let unsealed_all = unseal_sector(
+        porep_config,
+        cache_path,
+        &sealed_path,
+        prover_id,
+        sector_id,
+        comm_d,
+        ticket,
+    )?;
+
+    let f_out = File::create(&output_path)
+        .with_context(|| format!("could not create output_path={:?}", output_path.as_ref()))?;
+    let mut buf_writer = BufWriter::new(f_out);
+
+    let written = write_unsealed_range(&unsealed_all, &mut buf_writer, offset, num_bytes)
+        .with_context(|| format!("could not write to output_path={:?}", output_path.as_ref()))?;
+
+    Ok(UnpaddedBytesAmount(written as u64))
+}
+
+/// Unseals the sector at `sealed_path` once and returns the bytes of each of `ranges`, in the
+/// order they were requested. Prefer this over repeated calls to `get_unsealed_range` when
+/// reading several pieces from the same sector, as the sector is only decoded once.
+///
+/// # Arguments
+///
+/// * `porep_config` - porep configuration containing the sector size.
+/// * `cache_path` - path to the directory in which the sector data's Merkle Tree is written.
+/// * `sealed_path` - path to the sealed sector file that we will unseal and read the ranges from.
+/// * `prover_id` - the prover-id that sealed the sector.
+/// * `sector_id` - the sector-id of the sealed sector.
+/// * `comm_d` - the commitment to the sector's data.
+/// * `ticket` - the ticket that was used to generate the sector's replica-id.
+/// * `ranges` - the `(offset, num_bytes)` of each byte range in the unsealed sector to read.
+#[allow(clippy::too_many_arguments)]
+pub fn get_unsealed_ranges<T: Into<PathBuf> + AsRef<Path>>(
+    porep_config: PoRepConfig,
+    cache_path: T,
+    sealed_path: T,
+    prover_id: ProverId,
+    sector_id: SectorId,
+    comm_d: Commitment,
+    ticket: Ticket,
+    ranges: &[(UnpaddedByteIndex, UnpaddedBytesAmount)],
+) -> Result<Vec<Vec<u8>>> {
+    let unsealed_all = unseal_sector(
+        porep_config,
+        cache_path,
+        &sealed_path,
+        prover_id,
+        sector_id,
+        comm_d,
+        ticket,
+    )?;
+
+    ranges
+        .iter()
+        .map(|&(offset, num_bytes)| {
+            let mut range = Vec::with_capacity(usize::from(num_bytes));
+            write_unsealed_range(&unsealed_all, &mut range, offset, num_bytes)?;
+            Ok(range)
+        })
+        .collect()
+}
+
+/// Decodes the whole sector at `sealed_path`, returning its padded unsealed bytes.
+fn unseal_sector<T: Into<PathBuf> + AsRef<Path>>(
+    porep_config: PoRepConfig,
+    cache_path: T,
+    sealed_path: &T,
+    prover_id: ProverId,
+    sector_id: SectorId,
+    comm_d: Commitment,
+    ticket: Ticket,
+) -> Result<Vec<u8>> {
     ensure!(comm_d != [0; 32], "Invalid all zero commitment (comm_d)");
 
     let comm_d =
@@ -71,18 +152,14 @@ pub fn get_unsealed_range<T: Into<PathBuf> + AsRef<Path>>(
     let replica_id =
         generate_replica_id::<DefaultTreeHasher, _>(&prover_id, sector_id.into(), &ticket, comm_d);
 
-    let f_in = File::open(&sealed_path)
+    let f_in = File::open(sealed_path)
         .with_context(|| format!("could not open sealed_path={:?}", sealed_path.as_ref()))?;
     let mut data = Vec::new();
     f_in.take(u64::from(PaddedBytesAmount::from(porep_config)))
         .read_to_end(&mut data)?;
 
-    let f_out = File::create(&output_path)
-        .with_context(|| format!("could not create output_path={:?}", output_path.as_ref()))?;
-    let mut buf_writer = BufWriter::new(f_out);
-
     let tree_leafs =
-        get_tree_leafs::<<DefaultPieceHasher as Hasher>::Domain>(porep_config.sector_size);
+        get_tree_leafs::<<DefaultPieceHasher as Hasher>::Domain>(porep_config.sector_size)?;
     // MT for original data is always named tree-d, and it will be
     // referenced later in the process as such.
     let config = StoreConfig::new(
@@ -90,35 +167,46 @@ pub fn get_unsealed_range<T: Into<PathBuf> + AsRef<Path>>(
         CacheKey::CommDTree.to_string(),
         StoreConfig::default_cached_above_base_layer(tree_leafs),
     );
-    let pp = public_params(
-        PaddedBytesAmount::from(porep_config),
-        usize::from(PoRepProofPartitions::from(porep_config)),
-    )?;
-
-    let offset_padded: PaddedBytesAmount = UnpaddedBytesAmount::from(offset).into();
-    let num_bytes_padded: PaddedBytesAmount = num_bytes.into();
+    let pp = porep_public_params(porep_config)?;
 
-    let unsealed_all = StackedDrg::<DefaultTreeHasher, DefaultPieceHasher>::extract_all(
+    StackedDrg::<DefaultTreeHasher, DefaultPieceHasher>::extract_all(
         &pp,
         &replica_id,
         &data,
         Some(config),
-    )?;
+    )
+}
+
+/// Writes the unpadded bytes of `offset..offset + num_bytes` from an unsealed sector to `target`.
+fn write_unsealed_range<W: Write>(
+    unsealed_all: &[u8],
+    target: &mut W,
+    offset: UnpaddedByteIndex,
+    num_bytes: UnpaddedBytesAmount,
+) -> Result<usize> {
+    let offset_padded: PaddedBytesAmount = UnpaddedBytesAmount::from(offset).into();
+    let num_bytes_padded: PaddedBytesAmount = num_bytes.into();
+
     let start: usize = offset_padded.into();
     let end = start + usize::from(num_bytes_padded);
+    ensure!(
+        end <= unsealed_all.len(),
+        "range {}..{} is out of bounds of the unsealed sector ({} bytes)",
+        start,
+        end,
+        unsealed_all.len()
+    );
     let unsealed = &unsealed_all[start..end];
 
-    // If the call to `extract_range` was successful, the `unsealed` vector must
-    // have a length which equals `num_bytes_padded`. The byte at its 0-index
-    // byte will be the the byte at index `offset_padded` in the sealed sector.
-    let written = write_unpadded(unsealed, &mut buf_writer, 0, num_bytes.into())
-        .with_context(|| format!("could not write to output_path={:?}", output_path.as_ref()))?;
+    // The byte at index 0 of `unsealed` is the byte at index `offset_padded` in the sector.
+    let written = write_unpadded(unsealed, target, 0, num_bytes.into())?;
 
-    Ok(UnpaddedBytesAmount(written as u64))
+    Ok(written)
 }
 
 /// Generates a piece commitment for the provided byte source. Returns an error
-/// if the byte source produced more than `piece_size` bytes.
+/// if the byte source produced more than `piece_size` bytes. The preprocessed
+/// bytes are staged in a temp file under the configured `seal_temp_dir`.
 ///
 /// # Arguments
 ///
@@ -128,10 +216,21 @@ pub fn get_unsealed_range<T: Into<PathBuf> + AsRef<Path>>(
 pub fn generate_piece_commitment<T: std::io::Read>(
     source: T,
     piece_size: UnpaddedBytesAmount,
+) -> Result<PieceInfo> {
+    generate_piece_commitment_in(source, piece_size, &seal_temp_dir())
+}
+
+/// Like `generate_piece_commitment`, but stages the preprocessed bytes in a temp file under
+/// `temp_dir`.
+fn generate_piece_commitment_in<T: std::io::Read>(
+    source: T,
+    piece_size: UnpaddedBytesAmount,
+    temp_dir: &Path,
 ) -> Result<PieceInfo> {
     ensure_piece_size(piece_size)?;
 
-    let mut temp_piece_file = tempfile()?;
+    let mut temp_piece_file = tempfile_in(temp_dir)
+        .with_context(|| format!("could not create temp file in {:?}", temp_dir.display()))?;
 
     // send the source through the preprocessor, writing output to temp file
     let n = UnpaddedBytesAmount(
@@ -323,16 +422,24 @@ mod tests {
     use std::io::{Seek, SeekFrom, Write};
     use std::sync::Once;
 
-    use ff::Field;
+    use bellperson::groth16;
+    use ff::{Field, PrimeField};
     use paired::bls12_381::{Bls12, Fr};
     use rand::{Rng, SeedableRng};
     use rand_xorshift::XorShiftRng;
+    use storage_proofs::circuit::multi_proof::MultiProof;
     use storage_proofs::election_post::Candidate;
     use storage_proofs::fr32::bytes_into_fr;
+    use storage_proofs::stacked::TemporaryAux;
     use tempfile::NamedTempFile;
 
     use crate::constants::{POREP_PARTITIONS, SECTOR_SIZE_ONE_KIB, SECTOR_SIZE_16_MIB,SINGLE_PARTITION_PROOF_LEN};
-    use crate::types::{PoStConfig, SectorSize};
+    use crate::parameters::porep_setup_params;
+    use crate::types::{
+        ChallengeSeed, PoRepProofPartitions, PoStConfig, ProveCommitSectorParams, SealCommitOutput,
+        SealCommitPhase1Output, SealPhase, SealPreCommitOutput, SealPreCommitPhase1Output,
+        SectorMetadata, SectorSize,
+    };
 
     static INIT_LOGGER: Once = Once::new();
     fn init_logger() {
@@ -341,6 +448,119 @@ mod tests {
         });
     }
 
+    #[test]
+    fn test_validate_proof_len_constant() {
+        validate_proof_len_constant().expect("SINGLE_PARTITION_PROOF_LEN is out of date");
+    }
+
+    #[test]
+    fn test_load_params_verified() -> Result<()> {
+        use crate::caches::{
+            check_param_file_digest, ensure_param_file_digest, load_params_verified,
+            param_file_digest,
+        };
+        use crate::param::get_digest_for_file_within_cache;
+
+        // The parameters of the test sector sizes are not in parameters.json, so they are only
+        // checked against a supplied digest.
+        let config = one_kib_porep_config();
+        load_params_verified(config, None)?;
+        let params_path = config.get_cache_params_path()?;
+        let digest = param_file_digest(&params_path)?;
+
+        load_params_verified(config, Some(digest))?;
+        let err = load_params_verified(config, Some([0; 32]))
+            .expect_err("parameters must not load under the wrong digest");
+        match err.downcast_ref::<storage_proofs::error::Error>() {
+            Some(storage_proofs::error::Error::ParamDigestMismatch(path, expected, actual)) => {
+                assert_eq!(path, &params_path);
+                assert_eq!(expected, &hex::encode([0; 32]));
+                assert_eq!(actual, &hex::encode(digest));
+            }
+            other => panic!("expected ParamDigestMismatch, got {:?}", other),
+        }
+
+        // Tamper with a copy in the parameter cache, since other tests share the cached
+        // parameters.
+        let mut bytes = std::fs::read(&params_path)?;
+        let middle = bytes.len() / 2;
+        bytes[middle] ^= 1;
+        let mut tampered = tempfile::Builder::new()
+            .suffix(".params")
+            .tempfile_in(params_path.parent().unwrap())?;
+        tampered.write_all(&bytes)?;
+        tampered.flush()?;
+
+        let err = ensure_param_file_digest(tampered.path(), &digest)
+            .expect_err("a tampered parameter file must be rejected");
+        match err.downcast_ref::<storage_proofs::error::Error>() {
+            Some(storage_proofs::error::Error::ParamDigestMismatch(path, expected, _)) => {
+                assert_eq!(path, tampered.path());
+                assert_eq!(expected, &hex::encode(digest));
+            }
+            other => panic!("expected ParamDigestMismatch, got {:?}", other),
+        }
+
+        // Likewise under the digests of parameters.json.
+        let filename = params_path.file_name().unwrap().to_str().unwrap();
+        let listed_digest = get_digest_for_file_within_cache(filename)?;
+        check_param_file_digest(filename, &listed_digest)?;
+        let tampered_filename = tampered.path().file_name().unwrap().to_str().unwrap();
+        assert!(check_param_file_digest(tampered_filename, &listed_digest).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_graph_parents() -> Result<()> {
+        use crate::constants::{DRG_DEGREE, EXP_DEGREE};
+        use std::sync::atomic::Ordering;
+
+        let config = one_kib_porep_config();
+        let nodes = usize::from(PaddedBytesAmount::from(config)) / 32;
+
+        for &node in &[1, nodes / 2, nodes - 1] {
+            let (base_parents, expanded_parents) = graph_parents(config, node)?;
+
+            assert_eq!(
+                base_parents.len(),
+                DRG_DEGREE.load(Ordering::Relaxed) as usize
+            );
+            assert_eq!(
+                expanded_parents.len(),
+                EXP_DEGREE.load(Ordering::Relaxed) as usize
+            );
+            // DRG parents precede their node in the same layer.
+            assert!(base_parents.iter().all(|&parent| parent < node));
+            assert!(expanded_parents.iter().all(|&parent| parent < nodes));
+        }
+
+        assert!(graph_parents(config, nodes).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_seal_temp_dir() -> Result<()> {
+        let piece_size = UnpaddedBytesAmount(127);
+        let temp_dir = tempfile::tempdir()?;
+
+        let piece_info =
+            generate_piece_commitment_in(&[0u8; 127][..], piece_size, temp_dir.path())?;
+        assert_eq!(
+            piece_info,
+            generate_piece_commitment(&[0u8; 127][..], piece_size)?
+        );
+
+        // The temp file is created in the given directory, so a missing one is an error.
+        let missing = temp_dir.path().join("missing");
+        let err = generate_piece_commitment_in(&[0u8; 127][..], piece_size, &missing)
+            .expect_err("the temp file must not be created outside of the seal temp dir");
+        assert!(err.to_string().contains("could not create temp file"));
+
+        Ok(())
+    }
+
     #[test]
     fn test_verify_seal_fr32_validation() {
         let convertible_to_fr_bytes = [0; 32];
@@ -362,6 +582,7 @@ mod tests {
                             .get(&SECTOR_SIZE_ONE_KIB)
                             .unwrap(),
                     ),
+                    graph_seed: None,
                 },
                 not_convertible_to_fr_bytes,
                 convertible_to_fr_bytes,
@@ -396,6 +617,7 @@ mod tests {
                             .get(&SECTOR_SIZE_ONE_KIB)
                             .unwrap(),
                     ),
+                    graph_seed: None,
                 },
                 convertible_to_fr_bytes,
                 not_convertible_to_fr_bytes,
@@ -446,6 +668,7 @@ mod tests {
                 challenge_count: crate::constants::POST_CHALLENGE_COUNT,
                 challenged_nodes: crate::constants::POST_CHALLENGED_NODES,
                 priority: false,
+                read_chunk_bytes: None,
             },
             &[0; 32],
             1,
@@ -468,6 +691,1992 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_generate_post_deterministic() -> Result<()> {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+        let (staged, pre_commit_output) = pre_commit_for_test(rng)?;
+        let prover_id = staged.prover_id;
+        let sector_id = staged.sector_id;
+
+        let post_config = PoStConfig::new(
+            SectorSize(SECTOR_SIZE_ONE_KIB),
+            crate::constants::POST_CHALLENGE_COUNT,
+            crate::constants::POST_CHALLENGED_NODES,
+        )?;
+        let randomness: ChallengeSeed = rng.gen();
+        let challenge_count = 1;
+
+        let mut private_replicas = BTreeMap::new();
+        private_replicas.insert(
+            sector_id,
+            staged.private_replica_info(pre_commit_output.comm_r)?,
+        );
+        let mut public_replicas = BTreeMap::new();
+        public_replicas.insert(sector_id, PublicReplicaInfo::new(pre_commit_output.comm_r)?);
+
+        let winners = generate_candidates(
+            post_config,
+            &randomness,
+            challenge_count,
+            &private_replicas,
+            prover_id,
+        )?;
+
+        let seed: [u8; 32] = rng.gen();
+        let generate = |seed| {
+            generate_post_deterministic(
+                post_config,
+                &randomness,
+                &private_replicas,
+                winners.clone(),
+                prover_id,
+                seed,
+            )
+        };
+        let a = generate(seed)?;
+        let b = generate(seed)?;
+        assert_eq!(a, b, "same seed must produce identical proofs");
+
+        let mut other_seed = seed;
+        other_seed[0] ^= 1;
+        assert_ne!(a, generate(other_seed)?);
+
+        for proofs in &[a, b] {
+            assert!(verify_post(
+                post_config,
+                &randomness,
+                challenge_count,
+                proofs,
+                &public_replicas,
+                &winners,
+                prover_id,
+            )?);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_post_read_chunk_bytes() -> Result<()> {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+        let (staged, pre_commit_output) = pre_commit_for_test(rng)?;
+        let prover_id = staged.prover_id;
+
+        let mut private_replicas = BTreeMap::new();
+        private_replicas.insert(
+            staged.sector_id,
+            staged.private_replica_info(pre_commit_output.comm_r)?,
+        );
+
+        let randomness: ChallengeSeed = rng.gen();
+        let seed: [u8; 32] = rng.gen();
+        let candidates_and_proofs = |read_chunk_bytes| -> Result<_> {
+            let post_config = PoStConfig {
+                read_chunk_bytes,
+                ..PoStConfig::new(
+                    SectorSize(SECTOR_SIZE_ONE_KIB),
+                    crate::constants::POST_CHALLENGE_COUNT,
+                    crate::constants::POST_CHALLENGED_NODES,
+                )?
+            };
+            post_config.validate()?;
+
+            let candidates =
+                generate_candidates(post_config, &randomness, 2, &private_replicas, prover_id)?;
+            let proofs = generate_post_deterministic(
+                post_config,
+                &randomness,
+                &private_replicas,
+                candidates.clone(),
+                prover_id,
+                seed,
+            )?;
+
+            Ok((candidates, proofs))
+        };
+
+        assert_eq!(
+            candidates_and_proofs(None)?,
+            candidates_and_proofs(Some(storage_proofs::util::NODE_SIZE))?
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_generate_candidates_ordering() -> Result<()> {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+
+        let config = one_kib_porep_config();
+        let prover_id: ProverId = rng.gen();
+        let mut sectors = Vec::new();
+        let mut private_replicas = BTreeMap::new();
+        for id in 1..=2 {
+            let mut staged = stage_sector_for_test_with_sector_id(rng, config, SectorId::from(id))?;
+            staged.prover_id = prover_id;
+            let pre_commit_output = staged.pre_commit()?;
+
+            private_replicas.insert(
+                staged.sector_id,
+                staged.private_replica_info(pre_commit_output.comm_r)?,
+            );
+            sectors.push(staged);
+        }
+
+        let post_config = PoStConfig::new(
+            SectorSize(SECTOR_SIZE_ONE_KIB),
+            crate::constants::POST_CHALLENGE_COUNT,
+            crate::constants::POST_CHALLENGED_NODES,
+        )?;
+        let randomness: ChallengeSeed = rng.gen();
+        let generate =
+            || generate_candidates(post_config, &randomness, 6, &private_replicas, prover_id);
+
+        let a = generate()?;
+        let b = generate()?;
+        assert_eq!(a, b, "same inputs must produce the same candidates");
+
+        let key = |c: &Candidate| (c.sector_id, c.partial_ticket.into_repr());
+        for pair in a.windows(2) {
+            assert!(key(&pair[0]) < key(&pair[1]), "unsorted or duplicate");
+        }
+
+        Ok(())
+    }
+
+    fn create_staged_sector(sector_size: u64) -> Result<(Vec<u8>, Vec<PieceInfo>, NamedTempFile)> {
+        let number_of_bytes_in_piece = UnpaddedBytesAmount::from(PaddedBytesAmount(sector_size));
+        let piece_bytes: Vec<u8> = (0..number_of_bytes_in_piece.0)
+            .map(|x| x as u8 % 200)
+            .collect();
+
+        let mut piece_file = NamedTempFile::new()?;
+        piece_file.write_all(&piece_bytes)?;
+        piece_file.as_file_mut().sync_all()?;
+        piece_file.as_file_mut().seek(SeekFrom::Start(0))?;
+
+        let piece_info =
+            generate_piece_commitment(piece_file.as_file_mut(), number_of_bytes_in_piece)?;
+        piece_file.as_file_mut().seek(SeekFrom::Start(0))?;
+
+        let mut staged_sector_file = NamedTempFile::new()?;
+        add_piece(
+            &mut piece_file,
+            &mut staged_sector_file,
+            number_of_bytes_in_piece,
+            &[],
+        )?;
+
+        Ok((piece_bytes, vec![piece_info], staged_sector_file))
+    }
+
+    fn one_kib_porep_config() -> PoRepConfig {
+        PoRepConfig::from_sector_size(SectorSize(SECTOR_SIZE_ONE_KIB)).unwrap()
+    }
+
+    struct SealedSector {
+        config: PoRepConfig,
+        comm_r: Commitment,
+        comm_d: Commitment,
+        prover_id: ProverId,
+        sector_id: SectorId,
+        ticket: Ticket,
+        seed: Ticket,
+        proof: Vec<u8>,
+    }
+
+    fn seal_sector_for_test(rng: &mut XorShiftRng) -> Result<SealedSector> {
+        let (mut sealed, commit_phase1_output) = commit_phase1_for_test(rng)?;
+        let commit_output = seal_commit_phase2(
+            sealed.config,
+            commit_phase1_output,
+            sealed.prover_id,
+            sealed.sector_id,
+        )?;
+        sealed.proof = commit_output.proof;
+
+        Ok(sealed)
+    }
+
+    /// Runs a sector through `seal_commit_phase1`. The returned sector has no proof yet.
+    fn commit_phase1_for_test(
+        rng: &mut XorShiftRng,
+    ) -> Result<(SealedSector, SealCommitPhase1Output)> {
+        commit_phase1_for_test_with_config(rng, one_kib_porep_config())
+    }
+
+    fn commit_phase1_for_test_with_config(
+        rng: &mut XorShiftRng,
+        config: PoRepConfig,
+    ) -> Result<(SealedSector, SealCommitPhase1Output)> {
+        commit_phase1_for_test_with_sector_id(rng, config, SectorId::from(12))
+    }
+
+    fn commit_phase1_for_test_with_sector_id(
+        rng: &mut XorShiftRng,
+        config: PoRepConfig,
+        sector_id: SectorId,
+    ) -> Result<(SealedSector, SealCommitPhase1Output)> {
+        let staged = stage_sector_for_test_with_sector_id(rng, config, sector_id)?;
+        let pre_commit_output = staged.pre_commit()?;
+        let comm_r = pre_commit_output.comm_r;
+        let comm_d = pre_commit_output.comm_d;
+
+        let commit_phase1_output = staged.commit_phase1(pre_commit_output)?;
+
+        let sealed = SealedSector {
+            config,
+            comm_r,
+            comm_d,
+            prover_id: staged.prover_id,
+            sector_id,
+            ticket: staged.ticket,
+            seed: staged.seed,
+            proof: Vec::new(),
+        };
+
+        Ok((sealed, commit_phase1_output))
+    }
+
+    /// A sector staged for sealing, with the ids and randomness it is sealed with.
+    struct StagedSector {
+        config: PoRepConfig,
+        prover_id: ProverId,
+        sector_id: SectorId,
+        ticket: Ticket,
+        seed: Ticket,
+        files: SectorFiles,
+    }
+
+    /// The files a sector is sealed from and to, deleted once dropped.
+    struct SectorFiles {
+        piece_bytes: Vec<u8>,
+        piece_infos: Vec<PieceInfo>,
+        staged_sector_file: NamedTempFile,
+        cache_dir: tempfile::TempDir,
+        sealed_sector_file: NamedTempFile,
+    }
+
+    impl StagedSector {
+        fn pre_commit_phase1(&self) -> Result<SealPreCommitPhase1Output> {
+            seal_pre_commit_phase1(
+                self.config,
+                self.files.cache_dir.path(),
+                self.files.staged_sector_file.path(),
+                self.files.sealed_sector_file.path(),
+                self.prover_id,
+                self.sector_id,
+                self.ticket,
+                &self.files.piece_infos,
+            )
+        }
+
+        /// Runs both pre-commit phases.
+        fn pre_commit(&self) -> Result<SealPreCommitOutput> {
+            let phase1_output = self.pre_commit_phase1()?;
+            seal_pre_commit_phase2(
+                self.config,
+                phase1_output,
+                self.files.cache_dir.path(),
+                self.files.sealed_sector_file.path(),
+            )
+        }
+
+        fn commit_phase1(
+            &self,
+            pre_commit_output: SealPreCommitOutput,
+        ) -> Result<SealCommitPhase1Output> {
+            seal_commit_phase1(
+                self.config,
+                self.files.cache_dir.path(),
+                self.prover_id,
+                self.sector_id,
+                self.ticket,
+                self.seed,
+                pre_commit_output,
+                &self.files.piece_infos,
+            )
+        }
+
+        /// The replica to generate PoSts for, once pre-committed to `comm_r`.
+        fn private_replica_info(&self, comm_r: Commitment) -> Result<PrivateReplicaInfo> {
+            PrivateReplicaInfo::new(
+                self.files
+                    .sealed_sector_file
+                    .path()
+                    .to_string_lossy()
+                    .into_owned(),
+                comm_r,
+                self.files.cache_dir.path().to_path_buf(),
+            )
+        }
+    }
+
+    fn stage_sector_for_test(rng: &mut XorShiftRng) -> Result<StagedSector> {
+        stage_sector_for_test_with_config(rng, one_kib_porep_config())
+    }
+
+    fn stage_sector_for_test_with_config(
+        rng: &mut XorShiftRng,
+        config: PoRepConfig,
+    ) -> Result<StagedSector> {
+        stage_sector_for_test_with_sector_id(rng, config, SectorId::from(12))
+    }
+
+    fn stage_sector_for_test_with_sector_id(
+        rng: &mut XorShiftRng,
+        config: PoRepConfig,
+        sector_id: SectorId,
+    ) -> Result<StagedSector> {
+        let (piece_bytes, piece_infos, staged_sector_file) =
+            create_staged_sector(u64::from(config.sector_size))?;
+        let files = SectorFiles {
+            piece_bytes,
+            piece_infos,
+            staged_sector_file,
+            cache_dir: tempfile::tempdir()?,
+            sealed_sector_file: NamedTempFile::new()?,
+        };
+
+        Ok(StagedSector {
+            config,
+            prover_id: rng.gen(),
+            sector_id,
+            ticket: rng.gen(),
+            seed: rng.gen(),
+            files,
+        })
+    }
+
+    /// Runs a sector through `seal_pre_commit_phase2`, keeping its cache and replica.
+    fn pre_commit_for_test(rng: &mut XorShiftRng) -> Result<(StagedSector, SealPreCommitOutput)> {
+        let staged = stage_sector_for_test(rng)?;
+        let pre_commit_output = staged.pre_commit()?;
+
+        Ok((staged, pre_commit_output))
+    }
+
+    #[test]
+    fn test_seal_pre_commit_phase1_read_only_cache_path() -> Result<()> {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+        let staged = stage_sector_for_test(rng)?;
+        let cache_dir = staged.files.cache_dir.path();
+
+        let mut permissions = std::fs::metadata(cache_dir)?.permissions();
+        permissions.set_readonly(true);
+        std::fs::set_permissions(cache_dir, permissions.clone())?;
+
+        let result = staged.pre_commit_phase1();
+
+        permissions.set_readonly(false);
+        std::fs::set_permissions(cache_dir, permissions)?;
+
+        let err = result.expect_err("a read-only cache_path must be rejected");
+        assert!(format!("{}", err).contains("is read-only"));
+        // The check runs before the staged sector is copied into place.
+        assert_eq!(
+            std::fs::metadata(staged.files.sealed_sector_file.path())?.len(),
+            0
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_estimate_seal_time() -> Result<()> {
+        use super::estimate::{estimate_seal_time_with, Calibration};
+
+        // Costs large enough for the nanosecond rounding of the estimates to be negligible.
+        let calibration = Calibration {
+            sha256_byte: 1e-3,
+            tree_hash: 1e-2,
+            multiexp_base: 1e-1,
+        };
+        let small_config = one_kib_porep_config();
+        let big_config = PoRepConfig::from_sector_size(SectorSize(SECTOR_SIZE_16_MIB))?;
+        let small = estimate_seal_time_with(small_config, calibration)?;
+        let big = estimate_seal_time_with(big_config, calibration)?;
+        assert_eq!(small, estimate_seal_time_with(small_config, calibration)?);
+
+        assert!(big.pre_commit_phase1 > small.pre_commit_phase1);
+        assert!(big.pre_commit_phase2 > small.pre_commit_phase2);
+        assert!(big.commit_phase2 > small.commit_phase2);
+        assert!(big.total() > small.total());
+
+        // Labeling grows with the number of nodes and layers.
+        let layers = |config: PoRepConfig| -> Result<f64> {
+            Ok(porep_setup_params(config)?.layer_challenges.layers() as f64)
+        };
+        let expected = (SECTOR_SIZE_16_MIB / SECTOR_SIZE_ONE_KIB) as f64 * layers(big_config)?
+            / layers(small_config)?;
+        let ratio = big.pre_commit_phase1.as_secs_f64() / small.pre_commit_phase1.as_secs_f64();
+        assert!((ratio / expected - 1.0).abs() < 1e-9);
+
+        // Each phase scales with the cost of the operations it is made of, and only those.
+        let slower_multiexp = Calibration {
+            multiexp_base: 2.0 * calibration.multiexp_base,
+            ..calibration
+        };
+        let slower = estimate_seal_time_with(small_config, slower_multiexp)?;
+        assert_eq!(slower.pre_commit_phase1, small.pre_commit_phase1);
+        assert_eq!(slower.pre_commit_phase2, small.pre_commit_phase2);
+        assert_eq!(slower.commit_phase1, small.commit_phase1);
+        let ratio = slower.commit_phase2.as_secs_f64() / small.commit_phase2.as_secs_f64();
+        assert!((ratio - 2.0).abs() < 1e-9);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_seal_pre_commit_phase2_missing_out_path() -> Result<()> {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+        let staged = stage_sector_for_test(rng)?;
+        let phase1_output = staged.pre_commit_phase1()?;
+
+        let cache_dir = staged.files.cache_dir.path();
+        let missing = cache_dir.join("missing-replica");
+        let err = seal_pre_commit_phase2(staged.config, phase1_output, cache_dir, &missing)
+            .expect_err("a missing out_path must be rejected");
+        let message = format!("{}", err);
+        assert!(message.contains("does not exist"));
+        assert!(message.contains("detect_seal_phase"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_seal_pre_commit_phase2_with_writers() -> Result<()> {
+        use crate::types::PersistentAux;
+
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+        let staged = stage_sector_for_test(rng)?;
+        let cache_dir = staged.files.cache_dir.path();
+        let p_aux_path = cache_dir.join(CacheKey::PAux.to_string());
+        let t_aux_path = cache_dir.join(CacheKey::TAux.to_string());
+
+        let mut p_aux_buf = Vec::new();
+        let mut t_aux_buf = Vec::new();
+        let from_writers = seal_pre_commit_phase2_with_writers(
+            staged.config,
+            staged.pre_commit_phase1()?,
+            cache_dir,
+            staged.files.sealed_sector_file.path(),
+            &mut p_aux_buf,
+            &mut t_aux_buf,
+        )?;
+        assert!(!p_aux_path.exists());
+        assert!(!t_aux_path.exists());
+
+        // Seal the same sector again, persisting p_aux and t_aux to the cache.
+        let from_files = staged.pre_commit()?;
+        assert_eq!(from_writers, from_files);
+
+        let p_aux: PersistentAux = bincode::deserialize(&p_aux_buf)?;
+        let p_aux_file: PersistentAux = seal::deserialize_aux_file(&p_aux_path)?;
+        assert_eq!(p_aux, p_aux_file);
+
+        let t_aux = bincode::deserialize(&t_aux_buf)?;
+        let t_aux_file = seal::deserialize_aux_file(&t_aux_path)?;
+        assert_t_aux_eq(&t_aux, &t_aux_file)?;
+
+        Ok(())
+    }
+
+    /// Asserts that `a` and `b` are equal. `TemporaryAux` has no `PartialEq`, so this compares
+    /// their canonical encodings.
+    fn assert_t_aux_eq(
+        a: &TemporaryAux<DefaultTreeHasher, DefaultPieceHasher>,
+        b: &TemporaryAux<DefaultTreeHasher, DefaultPieceHasher>,
+    ) -> Result<()> {
+        assert_eq!(bincode::serialize(a)?, bincode::serialize(b)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_unsealed_ranges() -> Result<()> {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+        let (staged, pre_commit_output) = pre_commit_for_test(rng)?;
+        let files = &staged.files;
+
+        let ranges = [
+            (UnpaddedByteIndex(508), UnpaddedBytesAmount(254)),
+            (UnpaddedByteIndex(0), UnpaddedBytesAmount(127)),
+        ];
+        let unsealed = get_unsealed_ranges(
+            staged.config,
+            files.cache_dir.path(),
+            files.sealed_sector_file.path(),
+            staged.prover_id,
+            staged.sector_id,
+            pre_commit_output.comm_d,
+            staged.ticket,
+            &ranges,
+        )?;
+        assert_eq!(unsealed.len(), ranges.len());
+
+        for (&(offset, num_bytes), range) in ranges.iter().zip(unsealed.iter()) {
+            let mut unseal_file = NamedTempFile::new()?;
+            get_unsealed_range(
+                staged.config,
+                files.cache_dir.path(),
+                files.sealed_sector_file.path(),
+                unseal_file.path(),
+                staged.prover_id,
+                staged.sector_id,
+                pre_commit_output.comm_d,
+                staged.ticket,
+                offset,
+                num_bytes,
+            )?;
+            let mut single = Vec::new();
+            unseal_file.read_to_end(&mut single)?;
+
+            assert_eq!(range, &single);
+            let start = usize::from(offset);
+            let end = start + usize::from(num_bytes);
+            assert_eq!(&range[..], &files.piece_bytes[start..end]);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_resume_from_persisted_commit_phase1() -> Result<()> {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+        let (sealed, commit_phase1_output) = commit_phase1_for_test(rng)?;
+
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("commit-phase1");
+        persist_commit_phase1(&commit_phase1_output, &path)?;
+        drop(commit_phase1_output);
+
+        let two_partitions = PoRepConfig {
+            partitions: PoRepProofPartitions(2),
+            ..sealed.config
+        };
+        let err = load_commit_phase1(two_partitions, &path)
+            .expect_err("an output with the wrong partition count must be rejected");
+        assert!(format!("{}", err).contains("partitions"));
+
+        let loaded = load_commit_phase1(sealed.config, &path)?;
+        let commit_output =
+            seal_commit_phase2(sealed.config, loaded, sealed.prover_id, sealed.sector_id)?;
+
+        assert!(verify_seal(
+            sealed.config,
+            sealed.comm_r,
+            sealed.comm_d,
+            sealed.prover_id,
+            sealed.sector_id,
+            sealed.ticket,
+            sealed.seed,
+            &commit_output.proof,
+        )?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_seal_with_graph_seed() -> Result<()> {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+        let config = PoRepConfig {
+            graph_seed: Some([7; 28]),
+            ..one_kib_porep_config()
+        };
+        let (sealed, commit_phase1_output) = commit_phase1_for_test_with_config(rng, config)?;
+        let commit_output = seal_commit_phase2(
+            config,
+            commit_phase1_output,
+            sealed.prover_id,
+            sealed.sector_id,
+        )?;
+
+        let verify = |config| {
+            verify_seal(
+                config,
+                sealed.comm_r,
+                sealed.comm_d,
+                sealed.prover_id,
+                sealed.sector_id,
+                sealed.ticket,
+                sealed.seed,
+                &commit_output.proof,
+            )
+        };
+        assert!(verify(config)?);
+
+        // Verifying against the default seed, or any other, checks a different graph.
+        assert!(!verify(one_kib_porep_config())?);
+
+        let other_seed = PoRepConfig {
+            graph_seed: Some([8; 28]),
+            ..config
+        };
+        assert!(!verify(other_seed)?);
+
+        Ok(())
+    }
+
+    #[test]
+    #[ignore] // Slow test – run only when compiled for release.
+    fn test_verify_seals_grouped() -> Result<()> {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+        let small = seal_sector_for_test(rng)?;
+
+        let big_config = PoRepConfig::from_sector_size(SectorSize(SECTOR_SIZE_16_MIB))?;
+        let (mut big, commit_phase1_output) = commit_phase1_for_test_with_config(rng, big_config)?;
+        big.proof = seal_commit_phase2(
+            big_config,
+            commit_phase1_output,
+            big.prover_id,
+            big.sector_id,
+        )?
+        .proof;
+
+        let item = |sealed: &SealedSector, seed: Ticket| {
+            (
+                sealed.config,
+                sealed.comm_r,
+                sealed.comm_d,
+                sealed.prover_id,
+                sealed.sector_id,
+                sealed.ticket,
+                seed,
+                sealed.proof.clone(),
+            )
+        };
+        let small_wrong_seed = assert_rejects_wrong_seed(&small)?;
+        let big_wrong_seed = assert_rejects_wrong_seed(&big)?;
+        let items = vec![
+            item(&small, small.seed),
+            item(&big, big_wrong_seed),
+            item(&small, small_wrong_seed),
+            item(&big, big.seed),
+            item(&small, small.seed),
+        ];
+
+        let valid = verify_seals_grouped(&items)?;
+        assert_eq!(valid, vec![true, false, false, true, true]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_batch_seal_find_invalid() -> Result<()> {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+        let sealed = seal_sector_for_test(rng)?;
+
+        let mut seeds = vec![sealed.seed; 3];
+        seeds[2] = assert_rejects_wrong_seed(&sealed)?;
+
+        let invalid = verify_batch_seal_find_invalid(
+            sealed.config,
+            &[sealed.comm_r; 3],
+            &[sealed.comm_d; 3],
+            &[sealed.prover_id; 3],
+            &[sealed.sector_id; 3],
+            &[sealed.ticket; 3],
+            &seeds,
+            &[&sealed.proof[..]; 3],
+        )?;
+        assert_eq!(invalid, vec![2]);
+
+        let invalid = verify_batch_seal_find_invalid(
+            sealed.config,
+            &[sealed.comm_r; 2],
+            &[sealed.comm_d; 2],
+            &[sealed.prover_id; 2],
+            &[sealed.sector_id; 2],
+            &[sealed.ticket; 2],
+            &[sealed.seed; 2],
+            &[&sealed.proof[..]; 2],
+        )?;
+        assert!(invalid.is_empty());
+
+        Ok(())
+    }
+
+    /// Returns a seed `sealed` was not sealed with, after asserting that its proof does not
+    /// verify against it.
+    fn assert_rejects_wrong_seed(sealed: &SealedSector) -> Result<Ticket> {
+        let mut seed = sealed.seed;
+        seed[0] ^= 1;
+        assert!(!verify_seal(
+            sealed.config,
+            sealed.comm_r,
+            sealed.comm_d,
+            sealed.prover_id,
+            sealed.sector_id,
+            sealed.ticket,
+            seed,
+            &sealed.proof,
+        )?);
+
+        Ok(seed)
+    }
+
+    #[test]
+    fn test_verify_batch_seal_map() -> Result<()> {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+        let config = one_kib_porep_config();
+
+        let mut sectors = Vec::new();
+        for id in 1..=3 {
+            let (mut sealed, commit_phase1_output) =
+                commit_phase1_for_test_with_sector_id(rng, config, SectorId::from(id))?;
+            sealed.proof = seal_commit_phase2(
+                config,
+                commit_phase1_output,
+                sealed.prover_id,
+                sealed.sector_id,
+            )?
+            .proof;
+            sectors.push(sealed);
+        }
+
+        let mut seeds: Vec<Ticket> = sectors.iter().map(|s| s.seed).collect();
+        seeds[1] = assert_rejects_wrong_seed(&sectors[1])?;
+
+        let results = verify_batch_seal_map(
+            config,
+            &sectors.iter().map(|s| s.comm_r).collect::<Vec<_>>(),
+            &sectors.iter().map(|s| s.comm_d).collect::<Vec<_>>(),
+            &sectors.iter().map(|s| s.prover_id).collect::<Vec<_>>(),
+            &sectors.iter().map(|s| s.sector_id).collect::<Vec<_>>(),
+            &sectors.iter().map(|s| s.ticket).collect::<Vec<_>>(),
+            &seeds,
+            &sectors.iter().map(|s| &s.proof[..]).collect::<Vec<_>>(),
+        )?;
+
+        let expected: BTreeMap<SectorId, bool> = vec![
+            (SectorId::from(1), true),
+            (SectorId::from(2), false),
+            (SectorId::from(3), true),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(results, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_seal_in_memory_matches_disk() -> Result<()> {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+        let (sealed, disk_output) = commit_phase1_for_test(rng)?;
+
+        let (_, piece_infos, staged_sector_file) = create_staged_sector(SECTOR_SIZE_ONE_KIB)?;
+        let staged_sector = std::fs::read(staged_sector_file.path())?;
+        let output = seal_in_memory(
+            sealed.config,
+            &staged_sector,
+            sealed.prover_id,
+            sealed.sector_id,
+            sealed.ticket,
+            sealed.seed,
+            &piece_infos,
+        )?;
+        assert_eq!(output.comm_r, sealed.comm_r);
+        assert_eq!(output.comm_d, sealed.comm_d);
+        assert_eq!(
+            output.commit_phase1_output.replica_id,
+            disk_output.replica_id
+        );
+
+        let commit_output = seal_commit_phase2(
+            sealed.config,
+            output.commit_phase1_output,
+            sealed.prover_id,
+            sealed.sector_id,
+        )?;
+        assert!(verify_seal(
+            sealed.config,
+            sealed.comm_r,
+            sealed.comm_d,
+            sealed.prover_id,
+            sealed.sector_id,
+            sealed.ticket,
+            sealed.seed,
+            &commit_output.proof,
+        )?);
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "measurements")]
+    fn test_export_measurements_csv() -> Result<()> {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+        seal_sector_for_test(rng)?;
+
+        let mut csv = Vec::new();
+        storage_proofs::measurements::export_measurements_csv(&mut csv)?;
+        let csv = String::from_utf8(csv)?;
+
+        let mut lines = csv.lines();
+        assert_eq!(
+            lines.next(),
+            Some("operation,layer,cpu_time_ms,wall_time_ms")
+        );
+        let rows: Vec<(&str, &str)> = lines
+            .map(|line| {
+                let mut fields = line.split(',');
+                let operation = fields.next().expect("empty csv row");
+                (operation, fields.next().expect("csv row without a layer"))
+            })
+            .collect();
+        assert!(rows.contains(&("CommD", "")));
+        assert!(rows.contains(&("PorepCommitTime", "")));
+        assert!(rows.contains(&("EncodeLayerTotal", "1")));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_seal_sector_range() -> Result<()> {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+        let config = one_kib_porep_config();
+        let cache_base = tempfile::tempdir()?;
+        let input_base = tempfile::tempdir()?;
+        let prover_id: ProverId = rng.gen();
+        let ticket: Ticket = rng.gen();
+        let seed: Ticket = rng.gen();
+
+        let sector_ids = 100..102;
+        let mut piece_infos_per_sector = Vec::new();
+        for id in sector_ids.clone() {
+            let (_, piece_infos, staged_sector_file) =
+                create_staged_sector(u64::from(config.sector_size))?;
+            let (_, in_path, _) = sector_range_paths(cache_base.path(), input_base.path(), id);
+            std::fs::copy(staged_sector_file.path(), in_path)?;
+            piece_infos_per_sector.push(piece_infos);
+        }
+
+        let outputs = seal_sector_range(
+            config,
+            cache_base.path(),
+            input_base.path(),
+            sector_ids.clone(),
+            prover_id,
+            ticket,
+            seed,
+            &piece_infos_per_sector,
+            2,
+        )?;
+        assert_eq!(outputs.len(), 2);
+
+        for ((id, piece_infos), output) in sector_ids
+            .zip(piece_infos_per_sector.iter())
+            .zip(outputs.iter())
+        {
+            let (cache_path, _, _) = sector_range_paths(cache_base.path(), input_base.path(), id);
+            let comm_r = comm_r_from_p_aux(&cache_path)?;
+            let comm_d = compute_comm_d(config.sector_size, piece_infos)?;
+
+            assert!(verify_seal(
+                config,
+                comm_r,
+                comm_d,
+                prover_id,
+                SectorId::from(id),
+                ticket,
+                seed,
+                &output.proof,
+            )?);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_seal_commit_phase1_rejects_cache_of_another_sector() -> Result<()> {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+
+        // Two sectors of the same data, prover and sector id, sealed with different tickets.
+        let sector = stage_sector_for_test(rng)?;
+        let mut other_sector = stage_sector_for_test(rng)?;
+        other_sector.prover_id = sector.prover_id;
+
+        let pre_commit_output = sector.pre_commit()?;
+        let other_pre_commit_output = other_sector.pre_commit()?;
+        let other_cache_dir = other_sector.files.cache_dir.path();
+        assert_eq!(
+            comm_r_from_p_aux(sector.files.cache_dir.path())?,
+            pre_commit_output.comm_r
+        );
+        assert_eq!(
+            comm_r_from_p_aux(other_cache_dir)?,
+            other_pre_commit_output.comm_r
+        );
+
+        let result = seal_commit_phase1(
+            sector.config,
+            other_cache_dir,
+            sector.prover_id,
+            sector.sector_id,
+            sector.ticket,
+            sector.seed,
+            pre_commit_output,
+            &sector.files.piece_infos,
+        );
+
+        let err = result.expect_err("mismatched cache_path should fail");
+        assert!(
+            format!("{}", err).contains("comm_r does not match the p_aux"),
+            "unexpected error: {}",
+            err
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_seal_commit_phase2_timed() -> Result<()> {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+        let (sealed, commit_phase1_output) = commit_phase1_for_test(rng)?;
+
+        let start = std::time::Instant::now();
+        let (output, timings) = seal_commit_phase2_timed(
+            sealed.config,
+            commit_phase1_output,
+            sealed.prover_id,
+            sealed.sector_id,
+        )?;
+        let elapsed = start.elapsed();
+
+        // The steps are timed within the total, which is timed within the call.
+        let steps = timings.setup + timings.params + timings.circuit_proofs + timings.post_verify;
+        assert!(steps <= timings.total);
+        assert!(timings.total <= elapsed);
+
+        assert!(verify_seal(
+            sealed.config,
+            sealed.comm_r,
+            sealed.comm_d,
+            sealed.prover_id,
+            sealed.sector_id,
+            sealed.ticket,
+            sealed.seed,
+            &output.proof,
+        )?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_seal_commit_output_json_round_trip() -> Result<()> {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+        let sealed = seal_sector_for_test(rng)?;
+        let output = SealCommitOutput {
+            proof: sealed.proof.clone(),
+        };
+
+        let json = output.to_json(sealed.config).to_string();
+        let parsed = SealCommitOutput::from_json(&serde_json::from_str(&json)?, sealed.config)?;
+        assert_eq!(parsed.proof, sealed.proof);
+
+        assert!(verify_seal(
+            sealed.config,
+            sealed.comm_r,
+            sealed.comm_d,
+            sealed.prover_id,
+            sealed.sector_id,
+            sealed.ticket,
+            sealed.seed,
+            &parsed.proof,
+        )?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_vanilla_proofs_catches_a_corrupted_partition() -> Result<()> {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+        let config = PoRepConfig {
+            partitions: PoRepProofPartitions(2),
+            ..one_kib_porep_config()
+        };
+
+        let (_, mut commit_phase1_output) = commit_phase1_for_test_with_config(rng, config)?;
+        assert_eq!(commit_phase1_output.vanilla_proofs.len(), 2);
+        assert!(verify_vanilla_proofs(config, &commit_phase1_output)?);
+
+        // Drop the labeling proofs of a single challenge of the second partition only.
+        commit_phase1_output.vanilla_proofs[1][0]
+            .labeling_proofs
+            .clear();
+        assert!(!verify_vanilla_proofs(config, &commit_phase1_output)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_seal_rejects_invalid_sector_size() {
+        let config = PoRepConfig {
+            sector_size: SectorSize(SECTOR_SIZE_ONE_KIB + 32),
+            ..one_kib_porep_config()
+        };
+
+        let err = verify_seal(
+            config,
+            [1; 32],
+            [2; 32],
+            [0; 32],
+            SectorId::from(12),
+            [0; 32],
+            [0; 32],
+            &[],
+        )
+        .expect_err("a non-power-of-two sector size must be rejected");
+        assert!(err.to_string().contains("invalid sector size"));
+    }
+
+    #[test]
+    fn test_verify_seal_with_metrics() -> Result<()> {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+        let sealed = seal_sector_for_test(rng)?;
+
+        let (valid, metrics) = verify_seal_with_metrics(
+            sealed.config,
+            sealed.comm_r,
+            sealed.comm_d,
+            sealed.prover_id,
+            sealed.sector_id,
+            sealed.ticket,
+            sealed.seed,
+            &sealed.proof,
+        )?;
+        assert!(valid);
+
+        let partitions = usize::from(PoRepProofPartitions::from(sealed.config));
+        assert_eq!(metrics.partitions, partitions);
+        assert!(metrics.public_inputs > 0);
+        assert_eq!(metrics.public_inputs % partitions, 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_seal_parsed() -> Result<()> {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+        let sealed = seal_sector_for_test(rng)?;
+
+        let verifying_key = crate::caches::get_stacked_verifying_key(sealed.config)?;
+        let proof = MultiProof::<Bls12>::new_from_reader(
+            Some(usize::from(sealed.config.partitions)),
+            &sealed.proof[..],
+            &verifying_key,
+        )?;
+
+        // Verify the once parsed proof under the right seed and a wrong one.
+        for &seed in &[sealed.seed, [0; 32]] {
+            let parsed = verify_seal_parsed(
+                sealed.config,
+                sealed.comm_r,
+                sealed.comm_d,
+                sealed.prover_id,
+                sealed.sector_id,
+                sealed.ticket,
+                seed,
+                &proof,
+            )?;
+            let from_bytes = verify_seal(
+                sealed.config,
+                sealed.comm_r,
+                sealed.comm_d,
+                sealed.prover_id,
+                sealed.sector_id,
+                sealed.ticket,
+                seed,
+                &sealed.proof,
+            )?;
+            assert_eq!(parsed, from_bytes);
+            assert_eq!(parsed, seed == sealed.seed);
+        }
+
+        // A proof parsed with any other verifying key is rejected.
+        let mut other_key = (*verifying_key).clone();
+        other_key.ic.push(other_key.ic[0]);
+        let other_proof = MultiProof::new(proof.circuit_proofs.clone(), &other_key);
+        assert!(verify_seal_parsed(
+            sealed.config,
+            sealed.comm_r,
+            sealed.comm_d,
+            sealed.prover_id,
+            sealed.sector_id,
+            sealed.ticket,
+            sealed.seed,
+            &other_proof,
+        )
+        .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_batch_seal_with_inputs() -> Result<()> {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+        let sealed = seal_sector_for_test(rng)?;
+
+        let comm_r = as_safe_commitment(&sealed.comm_r, "comm_r")?;
+        let comm_d = as_safe_commitment(&sealed.comm_d, "comm_d")?;
+        let replica_id = generate_replica_id::<DefaultTreeHasher, _>(
+            &sealed.prover_id,
+            sealed.sector_id.into(),
+            &sealed.ticket,
+            comm_d,
+        );
+        let verifying_key = crate::caches::get_stacked_verifying_key(sealed.config)?;
+        let proof = MultiProof::<Bls12>::new_from_reader(
+            Some(usize::from(sealed.config.partitions)),
+            &sealed.proof[..],
+            &verifying_key,
+        )?;
+
+        for &seed in &[sealed.seed, [0; 32]] {
+            let public_inputs = storage_proofs::stacked::PublicInputs {
+                replica_id,
+                tau: Some(storage_proofs::stacked::Tau { comm_r, comm_d }),
+                seed,
+                k: None,
+            };
+
+            let with_inputs = verify_batch_seal_with_inputs(
+                sealed.config,
+                &[public_inputs.clone(), public_inputs],
+                &[proof.clone(), proof.clone()],
+            )?;
+            let from_commitments = verify_batch_seal(
+                sealed.config,
+                &[sealed.comm_r; 2],
+                &[sealed.comm_d; 2],
+                &[sealed.prover_id; 2],
+                &[sealed.sector_id; 2],
+                &[sealed.ticket; 2],
+                &[seed; 2],
+                &[&sealed.proof[..]; 2],
+            )?;
+
+            assert_eq!(with_inputs, from_commitments);
+            assert_eq!(with_inputs, seed == sealed.seed);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_batch_seal_common() -> Result<()> {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+        let sealed = seal_sector_for_test(rng)?;
+
+        for &seed in &[sealed.seed, [0; 32]] {
+            let full = verify_batch_seal(
+                sealed.config,
+                &[sealed.comm_r; 3],
+                &[sealed.comm_d; 3],
+                &[sealed.prover_id; 3],
+                &[sealed.sector_id; 3],
+                &[sealed.ticket; 3],
+                &[seed; 3],
+                &[&sealed.proof[..]; 3],
+            )?;
+            let common = verify_batch_seal_common(
+                sealed.config,
+                &[sealed.comm_r; 3],
+                &[sealed.comm_d; 3],
+                &[sealed.sector_id; 3],
+                sealed.prover_id,
+                sealed.ticket,
+                seed,
+                &[&sealed.proof[..]; 3],
+            )?;
+
+            assert_eq!(full, common);
+            assert_eq!(common, seed == sealed.seed);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_seal_multi_vk() -> Result<()> {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+        let sealed = seal_sector_for_test(rng)?;
+
+        let verifying_key = crate::caches::get_stacked_verifying_key(sealed.config)?;
+        let correct = &*verifying_key;
+        let mut wrong = correct.clone();
+        wrong.ic.swap(0, 1);
+        // A key for a circuit with fewer public inputs.
+        let mut mismatched = correct.clone();
+        mismatched.ic.pop();
+
+        let verify = |vks: &[&groth16::VerifyingKey<Bls12>]| {
+            verify_seal_multi_vk(
+                sealed.config,
+                sealed.comm_r,
+                sealed.comm_d,
+                sealed.prover_id,
+                sealed.sector_id,
+                sealed.ticket,
+                sealed.seed,
+                &sealed.proof,
+                vks,
+            )
+        };
+
+        assert!(verify(&[&wrong, correct])?);
+        assert!(!verify(&[&wrong, &mismatched])?);
+        assert!(verify(&[]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_chain_message_proof_verifies() -> Result<()> {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+        let sealed = seal_sector_for_test(rng)?;
+
+        let output = SealCommitOutput {
+            proof: sealed.proof.clone(),
+        };
+        let message = output.to_chain_message(sealed.sector_id);
+        let parsed = ProveCommitSectorParams::parse(&message)?;
+        assert_eq!(parsed.sector_id, sealed.sector_id);
+        assert_eq!(parsed.proof, sealed.proof);
+
+        assert!(verify_seal(
+            sealed.config,
+            sealed.comm_r,
+            sealed.comm_d,
+            sealed.prover_id,
+            parsed.sector_id,
+            sealed.ticket,
+            sealed.seed,
+            &parsed.proof,
+        )?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_from_output() -> Result<()> {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+        let (sealed, commit_phase1_output) = commit_phase1_for_test(rng)?;
+
+        let output = seal_commit_phase2_with_inputs(
+            sealed.config,
+            commit_phase1_output,
+            sealed.prover_id,
+            sealed.sector_id,
+        )?;
+        assert_eq!(
+            output.public_inputs.len(),
+            usize::from(sealed.config.partitions)
+        );
+
+        let valid = verify_seal(
+            sealed.config,
+            sealed.comm_r,
+            sealed.comm_d,
+            sealed.prover_id,
+            sealed.sector_id,
+            sealed.ticket,
+            sealed.seed,
+            &output.proof,
+        )?;
+        assert!(valid);
+        assert_eq!(valid, verify_from_output(&output)?);
+
+        // The bundled inputs must match the bundled commitments.
+        let mut tampered = output.clone();
+        tampered.seed[0] ^= 1;
+        assert!(verify_from_output(&tampered).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_seal_commit_phase2_skip_post_verify() -> Result<()> {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+        let (sealed, commit_phase1_output) = commit_phase1_for_test(rng)?;
+
+        // A proof generated for the wrong comm_r is caught by the post-seal check.
+        let mut corrupted = commit_phase1_output.clone();
+        corrupted.comm_r[0] ^= 1;
+        let err = seal_commit_phase2_with_options(
+            sealed.config,
+            corrupted,
+            sealed.prover_id,
+            sealed.sector_id,
+            SealCommitPhase2Options::default(),
+        )
+        .expect_err("a proof from a corrupted phase1 output must not be returned");
+        assert!(err.to_string().contains("post-seal verification"));
+
+        // Skipping the check returns the proof as is, so it must be verified separately.
+        let output = seal_commit_phase2_with_options(
+            sealed.config,
+            commit_phase1_output,
+            sealed.prover_id,
+            sealed.sector_id,
+            SealCommitPhase2Options {
+                skip_post_verify: true,
+                ..Default::default()
+            },
+        )?;
+        assert!(verify_seal(
+            sealed.config,
+            sealed.comm_r,
+            sealed.comm_d,
+            sealed.prover_id,
+            sealed.sector_id,
+            sealed.ticket,
+            sealed.seed,
+            &output.proof,
+        )?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_seal_commit_phase2_proof_len() -> Result<()> {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+        let config = PoRepConfig {
+            partitions: PoRepProofPartitions(2),
+            ..one_kib_porep_config()
+        };
+        let (sealed, commit_phase1_output) = commit_phase1_for_test_with_config(rng, config)?;
+
+        let output = seal_commit_phase2(
+            sealed.config,
+            commit_phase1_output,
+            sealed.prover_id,
+            sealed.sector_id,
+        )?;
+        // The format version byte, then one groth proof per partition.
+        assert_eq!(output.proof.len(), 1 + 2 * SINGLE_PARTITION_PROOF_LEN);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_seal_commit_phase2_progress() -> Result<()> {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+        let config = PoRepConfig {
+            partitions: PoRepProofPartitions(2),
+            ..one_kib_porep_config()
+        };
+        let (sealed, commit_phase1_output) = commit_phase1_for_test_with_config(rng, config)?;
+
+        let mut reported = Vec::new();
+        let output = seal_commit_phase2_with_options(
+            sealed.config,
+            commit_phase1_output,
+            sealed.prover_id,
+            sealed.sector_id,
+            SealCommitPhase2Options {
+                progress: Some(&mut |done: usize, total: usize| reported.push((done, total))),
+                ..Default::default()
+            },
+        )?;
+        assert_eq!(reported, vec![(1, 2), (2, 2)]);
+
+        assert!(verify_seal(
+            sealed.config,
+            sealed.comm_r,
+            sealed.comm_d,
+            sealed.prover_id,
+            sealed.sector_id,
+            sealed.ticket,
+            sealed.seed,
+            &output.proof,
+        )?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_seal_commit_phase2_with_params_dir() -> Result<()> {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+        let (sealed, commit_phase1_output) = commit_phase1_for_test(rng)?;
+        let params_dir = tempfile::tempdir()?;
+
+        // The parameters are generated in the empty directory, then used for proving.
+        let output = seal_commit_phase2_with_options(
+            sealed.config,
+            commit_phase1_output,
+            sealed.prover_id,
+            sealed.sector_id,
+            SealCommitPhase2Options {
+                params_dir: Some(params_dir.path()),
+                ..Default::default()
+            },
+        )?;
+
+        let mut extensions: Vec<_> = std::fs::read_dir(params_dir.path())?
+            .map(|entry| Ok(entry?.path().extension().unwrap().to_owned()))
+            .collect::<Result<_>>()?;
+        extensions.sort();
+        assert_eq!(extensions, vec!["params", "vk"]);
+
+        assert!(verify_seal_with_params_dir(
+            sealed.config,
+            params_dir.path(),
+            sealed.comm_r,
+            sealed.comm_d,
+            sealed.prover_id,
+            sealed.sector_id,
+            sealed.ticket,
+            sealed.seed,
+            &output.proof,
+        )?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_seal_with_vk_bytes() -> Result<()> {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+        let sealed = seal_sector_for_test(rng)?;
+
+        let verify_with_cache = |seed: Ticket| {
+            verify_seal(
+                sealed.config,
+                sealed.comm_r,
+                sealed.comm_d,
+                sealed.prover_id,
+                sealed.sector_id,
+                sealed.ticket,
+                seed,
+                &sealed.proof,
+            )
+        };
+        // Verifying through the cache also makes sure the verifying key has been written to it.
+        let valid_with_cache = verify_with_cache(sealed.seed)?;
+
+        let vk_bytes = std::fs::read(sealed.config.get_cache_verifying_key_path()?)?;
+        let verify_with_bytes = |seed: Ticket| {
+            verify_seal_with_vk_bytes(
+                sealed.config,
+                &vk_bytes,
+                sealed.comm_r,
+                sealed.comm_d,
+                sealed.prover_id,
+                sealed.sector_id,
+                sealed.ticket,
+                seed,
+                &sealed.proof,
+            )
+        };
+
+        assert!(valid_with_cache);
+        assert_eq!(valid_with_cache, verify_with_bytes(sealed.seed)?);
+
+        let mut wrong_seed = sealed.seed;
+        wrong_seed[0] ^= 1;
+        assert_eq!(
+            verify_with_cache(wrong_seed)?,
+            verify_with_bytes(wrong_seed)?
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_seal_malformed_proofs() -> Result<()> {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+        let sealed = seal_sector_for_test(rng)?;
+
+        // Verification must reject malformed proofs cleanly, never panic nor accept them.
+        let assert_rejected = |proof: &[u8]| {
+            let result = verify_seal(
+                sealed.config,
+                sealed.comm_r,
+                sealed.comm_d,
+                sealed.prover_id,
+                sealed.sector_id,
+                sealed.ticket,
+                sealed.seed,
+                proof,
+            );
+            if let Ok(valid) = result {
+                assert!(!valid, "malformed proof verified: {:?}", proof);
+            }
+        };
+
+        let proof_len = sealed.proof.len();
+        assert_eq!(proof_len, 1 + SINGLE_PARTITION_PROOF_LEN);
+        assert_eq!(sealed.proof[0], PROOF_FORMAT_VERSION);
+
+        // Single bit flips at random positions.
+        for _ in 0..32 {
+            let mut proof = sealed.proof.clone();
+            let bit = rng.gen_range(0, proof_len * 8);
+            proof[bit / 8] ^= 1 << (bit % 8);
+            assert_rejected(&proof);
+        }
+
+        // The flag bits of each compressed point (a, b, c), after the version byte.
+        for &offset in &[1, 49, 145] {
+            for &flag in &[0x80u8, 0x40, 0x20] {
+                let mut proof = sealed.proof.clone();
+                proof[offset] ^= flag;
+                assert_rejected(&proof);
+            }
+        }
+
+        // Random and constant proofs of the expected length.
+        for _ in 0..8 {
+            let proof: Vec<u8> = (0..proof_len).map(|_| rng.gen()).collect();
+            assert_rejected(&proof);
+        }
+        assert_rejected(&vec![0u8; proof_len]);
+        assert_rejected(&vec![0xffu8; proof_len]);
+
+        // Truncated, empty and padded proofs.
+        assert_rejected(&sealed.proof[..proof_len - 1]);
+        assert_rejected(&[]);
+        let mut padded = sealed.proof.clone();
+        padded.extend_from_slice(&sealed.proof);
+        assert_rejected(&padded);
+        padded.truncate(proof_len + 1);
+        assert_rejected(&padded);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_seal_partition_count_mismatch() -> Result<()> {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+        let sealed = seal_sector_for_test(rng)?;
+
+        let verifying_key = crate::caches::get_stacked_verifying_key(sealed.config)?;
+        let proof = MultiProof::<Bls12>::new_from_reader(
+            Some(usize::from(sealed.config.partitions)),
+            &sealed.proof[..],
+            &verifying_key,
+        )?;
+        seal::ensure_partition_count(sealed.config, &proof)?;
+
+        let two_partitions = PoRepConfig {
+            sector_size: sealed.config.sector_size,
+            partitions: PoRepProofPartitions(2),
+            graph_seed: None,
+        };
+        let err = seal::ensure_partition_count(two_partitions, &proof)
+            .expect_err("a proof with the wrong partition count must be rejected");
+        assert!(
+            err.to_string().contains("partition count"),
+            "unclear error: {}",
+            err
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_seal_pre_commit_phase2_reuses_data_tree() -> Result<()> {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+
+        let config = one_kib_porep_config();
+        let (_, piece_infos, staged_sector_file) = create_staged_sector(SECTOR_SIZE_ONE_KIB)?;
+        let prover_id: ProverId = rng.gen();
+        let ticket: Ticket = rng.gen();
+        let sector_id = SectorId::from(12);
+
+        let mut comm_rs = Vec::new();
+        for &keep_data_tree in &[false, true] {
+            let cache_dir = tempfile::tempdir()?;
+            let sealed_sector_file = NamedTempFile::new()?;
+
+            let phase1_output = seal_pre_commit_phase1_with_data_tree(
+                config,
+                cache_dir.path(),
+                staged_sector_file.path(),
+                sealed_sector_file.path(),
+                prover_id,
+                sector_id,
+                ticket,
+                &piece_infos,
+                keep_data_tree,
+            )?;
+            assert_eq!(phase1_output.data_tree.is_some(), keep_data_tree);
+
+            let output = seal_pre_commit_phase2(
+                config,
+                phase1_output,
+                cache_dir.path(),
+                sealed_sector_file.path(),
+            )?;
+            comm_rs.push(output.comm_r);
+        }
+
+        assert_eq!(comm_rs[0], comm_rs[1], "comm_r differs when reusing tree-d");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_seal_pre_commit_phase2_after_rename() -> Result<()> {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+
+        let config = one_kib_porep_config();
+        let (_, piece_infos, staged_sector_file) = create_staged_sector(SECTOR_SIZE_ONE_KIB)?;
+        let prover_id: ProverId = rng.gen();
+        let ticket: Ticket = rng.gen();
+        let sector_id = SectorId::from(12);
+
+        let seal = |rename: bool| -> Result<SealPreCommitOutput> {
+            let cache_dir = tempfile::tempdir()?;
+            let out_dir = tempfile::tempdir()?;
+            let out_path = out_dir.path().join("sealed");
+            File::create(&out_path)?;
+
+            let phase1_output = seal_pre_commit_phase1(
+                config,
+                cache_dir.path(),
+                staged_sector_file.path(),
+                &out_path,
+                prover_id,
+                sector_id,
+                ticket,
+                &piece_infos,
+            )?;
+
+            // Phase1 must not hold `out_path` open, or this fails on Windows.
+            let out_path = if rename {
+                let renamed = out_dir.path().join("sealed-renamed");
+                std::fs::rename(&out_path, &renamed)?;
+                renamed
+            } else {
+                out_path
+            };
+
+            seal_pre_commit_phase2(config, phase1_output, cache_dir.path(), &out_path)
+        };
+
+        let unmoved = seal(false)?;
+        let renamed = seal(true)?;
+        assert_eq!(unmoved.comm_d, renamed.comm_d);
+        assert_eq!(unmoved.comm_r, renamed.comm_r);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_seal_pre_commit_in_place() -> Result<()> {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+
+        let config = one_kib_porep_config();
+        let (_, piece_infos, staged_sector_file) = create_staged_sector(SECTOR_SIZE_ONE_KIB)?;
+        let prover_id: ProverId = rng.gen();
+        let ticket: Ticket = rng.gen();
+        let sector_id = SectorId::from(12);
+
+        let seal = |in_path: &Path, out_path: &Path| -> Result<SealPreCommitOutput> {
+            let cache_dir = tempfile::tempdir()?;
+            let phase1_output = seal_pre_commit_phase1(
+                config,
+                cache_dir.path(),
+                in_path,
+                out_path,
+                prover_id,
+                sector_id,
+                ticket,
+                &piece_infos,
+            )?;
+            seal_pre_commit_phase2(config, phase1_output, cache_dir.path(), out_path)
+        };
+
+        let sealed_sector_file = NamedTempFile::new()?;
+        let copied = seal(staged_sector_file.path(), sealed_sector_file.path())?;
+
+        let in_place_file = NamedTempFile::new()?;
+        std::fs::copy(staged_sector_file.path(), in_place_file.path())?;
+        let in_place = seal(in_place_file.path(), in_place_file.path())?;
+
+        assert_eq!(copied.comm_d, in_place.comm_d);
+        assert_eq!(copied.comm_r, in_place.comm_r);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_seal_pre_commit_mmap_window() -> Result<()> {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+
+        let config = one_kib_porep_config();
+        let (_, piece_infos, staged_sector_file) = create_staged_sector(SECTOR_SIZE_ONE_KIB)?;
+        let prover_id: ProverId = rng.gen();
+        let ticket: Ticket = rng.gen();
+        let sector_id = SectorId::from(12);
+
+        let comm_d = |mmap_window_bytes: Option<usize>| -> Result<Commitment> {
+            let cache_dir = tempfile::tempdir()?;
+            let sealed_sector_file = NamedTempFile::new()?;
+            let phase1_output = seal_pre_commit_phase1_with_mmap_window(
+                config,
+                cache_dir.path(),
+                staged_sector_file.path(),
+                sealed_sector_file.path(),
+                prover_id,
+                sector_id,
+                ticket,
+                &piece_infos,
+                mmap_window_bytes,
+            )?;
+            Ok(phase1_output.comm_d)
+        };
+
+        let full = comm_d(None)?;
+        let windowed = comm_d(Some(storage_proofs::merkle::MMAP_WINDOW_ALIGNMENT))?;
+        assert_eq!(full, windowed);
+
+        assert!(comm_d(Some(100)).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_deserialize_aux_file_streaming() -> Result<()> {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+        let (staged, _) = pre_commit_for_test(rng)?;
+
+        let t_aux_path = staged
+            .files
+            .cache_dir
+            .path()
+            .join(CacheKey::TAux.to_string());
+        let buffered = bincode::deserialize(&std::fs::read(&t_aux_path)?)?;
+        let streamed = seal::deserialize_aux_file(&t_aux_path)?;
+        assert_t_aux_eq(&buffered, &streamed)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_seal_commit_phase1_metadata_mismatch() -> Result<()> {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+        let (staged, pre_commit_output) = pre_commit_for_test(rng)?;
+        let config = staged.config;
+        let cache_dir = staged.files.cache_dir.path();
+
+        assert_eq!(
+            read_sector_metadata(cache_dir)?,
+            SectorMetadata::new(config)
+        );
+
+        let mismatched_config = PoRepConfig {
+            sector_size: config.sector_size,
+            partitions: PoRepProofPartitions(2),
+            graph_seed: None,
+        };
+        let err = seal_commit_phase1(
+            mismatched_config,
+            cache_dir,
+            staged.prover_id,
+            staged.sector_id,
+            staged.ticket,
+            staged.seed,
+            pre_commit_output,
+            &staged.files.piece_infos,
+        )
+        .unwrap_err();
+        assert!(format!("{}", err).contains("sector metadata mismatch"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_detect_seal_phase() -> Result<()> {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+        let staged = stage_sector_for_test(rng)?;
+        let cache_dir = staged.files.cache_dir.path();
+
+        let detect = || detect_seal_phase(cache_dir, staged.files.sealed_sector_file.path());
+
+        assert_eq!(detect()?, SealPhase::Staged);
+
+        let phase1_output = staged.pre_commit_phase1()?;
+        assert_eq!(detect()?, SealPhase::PreCommit1Done);
+
+        let pre_commit_output = seal_pre_commit_phase2(
+            staged.config,
+            phase1_output,
+            cache_dir,
+            staged.files.sealed_sector_file.path(),
+        )?;
+        assert_eq!(detect()?, SealPhase::PreCommit2Done);
+
+        let commit_phase1_output = staged.commit_phase1(pre_commit_output)?;
+        assert_eq!(detect()?, SealPhase::Commit1Done);
+
+        let commit_output = seal_commit_phase2(
+            staged.config,
+            commit_phase1_output,
+            staged.prover_id,
+            staged.sector_id,
+        )?;
+        persist_seal_commit_output(cache_dir, &commit_output)?;
+        assert_eq!(detect()?, SealPhase::Commit2Done);
+
+        let missing_replica = cache_dir.join("missing-replica");
+        assert!(detect_seal_phase(cache_dir, &missing_replica).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_pad_pieces_to_sector_matches_partial_sector() -> Result<()> {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+
+        // A single piece filling only half of the sector, which phase1 zero-pads.
+        let config = one_kib_porep_config();
+        let piece_size = UnpaddedBytesAmount::from(PaddedBytesAmount(SECTOR_SIZE_ONE_KIB / 2));
+        let piece_bytes: Vec<u8> = (0..piece_size.0).map(|x| x as u8 % 200).collect();
+
+        let piece_info = generate_piece_commitment(&piece_bytes[..], piece_size)?;
+        let mut staged_sector_file = NamedTempFile::new()?;
+        add_piece(&piece_bytes[..], &mut staged_sector_file, piece_size, &[])?;
+
+        let piece_infos = pad_pieces_to_sector(config.sector_size, &[piece_info.clone()])?;
+        assert_eq!(piece_infos.len(), 2);
+        assert_eq!(piece_infos[0], piece_info);
+
+        let cache_dir = tempfile::tempdir()?;
+        let sealed_sector_file = NamedTempFile::new()?;
+        let phase1_output = seal_pre_commit_phase1(
+            config,
+            cache_dir.path(),
+            staged_sector_file.path(),
+            sealed_sector_file.path(),
+            rng.gen(),
+            SectorId::from(12),
+            rng.gen(),
+            &piece_infos,
+        )?;
+
+        assert_eq!(
+            compute_comm_d(config.sector_size, &piece_infos)?,
+            phase1_output.comm_d
+        );
+        assert_ne!(
+            compute_comm_d(config.sector_size, &[piece_info])?,
+            phase1_output.comm_d
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_replica_consistency() -> Result<()> {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+        let (staged, _) = pre_commit_for_test(rng)?;
+        let sealed_sector_path = staged.files.sealed_sector_file.path();
+
+        let check = || {
+            check_replica_consistency(
+                staged.config,
+                sealed_sector_path,
+                staged.files.cache_dir.path(),
+            )
+        };
+        assert!(check()?);
+
+        // Flip the least significant bit of the first node, which keeps it a valid field
+        // element.
+        let mut replica = std::fs::read(sealed_sector_path)?;
+        replica[0] ^= 1;
+        std::fs::write(sealed_sector_path, &replica)?;
+        assert!(!check()?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_concurrent_seal_on_same_cache_path_is_locked() -> Result<()> {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+        let staged = stage_sector_for_test(rng)?;
+        let other = stage_sector_for_test_with_sector_id(rng, staged.config, SectorId::from(13))?;
+        let cache_dir = staged.files.cache_dir.path();
+
+        // Seals the other sector into the cache_path of the first one.
+        let seal_other = || {
+            seal_pre_commit_phase1(
+                other.config,
+                cache_dir,
+                other.files.staged_sector_file.path(),
+                other.files.sealed_sector_file.path(),
+                other.prover_id,
+                other.sector_id,
+                other.ticket,
+                &other.files.piece_infos,
+            )
+        };
+
+        // The second seal is started while the first one is labeling, and so holds the lock.
+        let mut second = None;
+        seal_pre_commit_phase1_with_progress(
+            staged.config,
+            cache_dir,
+            staged.files.staged_sector_file.path(),
+            staged.files.sealed_sector_file.path(),
+            staged.prover_id,
+            staged.sector_id,
+            staged.ticket,
+            &staged.files.piece_infos,
+            &mut |_: usize, _: usize, _: usize| {
+                if second.is_none() {
+                    second = Some(seal_other());
+                }
+            },
+        )?;
+
+        let err = second
+            .expect("the first seal reported no progress")
+            .expect_err("the second seal should have failed");
+        match err.downcast_ref::<storage_proofs::error::Error>() {
+            Some(storage_proofs::error::Error::CacheLocked(path)) => assert_eq!(path, cache_dir),
+            _ => panic!("expected CacheLocked, got {:?}", err),
+        }
+
+        // The lock is released once the first seal is done.
+        assert!(seal_other().is_ok());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_seal_pre_commit_phase1_progress() -> Result<()> {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+        let staged = stage_sector_for_test(rng)?;
+        let config = staged.config;
+
+        let mut reports = Vec::new();
+        seal_pre_commit_phase1_with_progress(
+            config,
+            staged.files.cache_dir.path(),
+            staged.files.staged_sector_file.path(),
+            staged.files.sealed_sector_file.path(),
+            staged.prover_id,
+            staged.sector_id,
+            staged.ticket,
+            &staged.files.piece_infos,
+            &mut |layer: usize, node: usize, total: usize| reports.push((layer, node, total)),
+        )?;
+
+        // A 1KiB sector is labeled in a single chunk per layer, so there is one report for each
+        // layer.
+        let layers = porep_setup_params(config)?.layer_challenges.layers();
+        let nodes = SECTOR_SIZE_ONE_KIB as usize / 32;
+        let expected: Vec<_> = (1..=layers).map(|layer| (layer, nodes, nodes)).collect();
+        assert_eq!(reports, expected);
+
+        Ok(())
+    }
+
     #[test]
     fn test_seal_lifecycle_part() -> Result<()> {      
 
@@ -560,6 +2769,7 @@ mod tests {
             partitions: PoRepProofPartitions(
                 *POREP_PARTITIONS.read().unwrap().get(&sector_size).unwrap(),
             ),
+            graph_seed: None,
         };
 
         println!("PoRepConfig = {:?}",config); 
@@ -5,9 +5,13 @@ use std::path::{Path, PathBuf};
 
 use anyhow::{anyhow, ensure, Context, Result};
 use bincode::deserialize;
+use ff::PrimeField;
 use log::{info, trace};
 use merkletree::store::{LevelCacheStore, Store, StoreConfig};
 use paired::bls12_381::Bls12;
+use rand::rngs::OsRng;
+use rand::{RngCore, SeedableRng};
+use rand_chacha::ChaChaRng;
 use rayon::prelude::*;
 use storage_proofs::circuit::election_post::ElectionPoStCompound;
 use storage_proofs::circuit::multi_proof::MultiProof;
@@ -151,6 +155,10 @@ impl PublicReplicaInfo {
 
 /// Generates proof-of-spacetime candidates for ElectionPoSt.
 ///
+/// The candidates are sorted by `sector_id`, then by `partial_ticket`, and identical
+/// candidates are removed, so the same inputs always yield the same candidates in the same
+/// order.
+///
 /// # Arguments
 ///
 /// * `post_config` - post config that contains the sector size of each sector that we are
@@ -213,9 +221,10 @@ pub fn generate_candidates(
     unique_challenged_replicas.sort_unstable(); // dedup requires a sorted list
     unique_challenged_replicas.dedup();
 
-    let tree_size = get_tree_size::<<DefaultTreeHasher as Hasher>::Domain>(post_config.sector_size);
+    let tree_size =
+        get_tree_size::<<DefaultTreeHasher as Hasher>::Domain>(post_config.sector_size)?;
     let tree_leafs =
-        get_tree_leafs::<<DefaultTreeHasher as Hasher>::Domain>(post_config.sector_size);
+        get_tree_leafs::<<DefaultTreeHasher as Hasher>::Domain>(post_config.sector_size)?;
 
     let unique_trees_res: Vec<_> = unique_challenged_replicas
         .into_par_iter()
@@ -246,7 +255,7 @@ pub fn generate_candidates(
     let trees: BTreeMap<SectorId, LCTree> =
         unique_trees_res.into_iter().collect::<Result<_, _>>()?;
 
-    let candidates = election_post::generate_candidates::<DefaultTreeHasher>(
+    let mut candidates = election_post::generate_candidates::<DefaultTreeHasher>(
         &public_params.vanilla_params,
         &challenged_sectors,
         &trees,
@@ -254,6 +263,15 @@ pub fn generate_candidates(
         randomness,
     )?;
 
+    candidates.sort_by(|a, b| {
+        a.sector_id.cmp(&b.sector_id).then_with(|| {
+            a.partial_ticket
+                .into_repr()
+                .cmp(&b.partial_ticket.into_repr())
+        })
+    });
+    candidates.dedup();
+
     info!("generate_candidates:finish");
 
     Ok(candidates)
@@ -284,9 +302,53 @@ pub fn generate_post(
     replicas: &BTreeMap<SectorId, PrivateReplicaInfo>,
     winners: Vec<Candidate>,
     prover_id: ProverId,
+) -> Result<Vec<SnarkProof>> {
+    generate_post_with_rng(
+        post_config,
+        randomness,
+        replicas,
+        winners,
+        prover_id,
+        &mut OsRng,
+    )
+}
+
+/// Generates a proof-of-spacetime whose proof randomness is derived from `seed`, so that
+/// the same inputs and seed always produce byte-identical proofs. This is meant for test
+/// vectors only and MUST NOT be used in production: reusing the blinding randomness makes
+/// proofs linkable and can leak information about the private inputs.
+///
+/// Arguments are the same as for `generate_post`.
+pub fn generate_post_deterministic(
+    post_config: PoStConfig,
+    randomness: &ChallengeSeed,
+    replicas: &BTreeMap<SectorId, PrivateReplicaInfo>,
+    winners: Vec<Candidate>,
+    prover_id: ProverId,
+    seed: [u8; 32],
+) -> Result<Vec<SnarkProof>> {
+    generate_post_with_rng(
+        post_config,
+        randomness,
+        replicas,
+        winners,
+        prover_id,
+        &mut ChaChaRng::from_seed(seed),
+    )
+}
+
+fn generate_post_with_rng<R: RngCore>(
+    post_config: PoStConfig,
+    randomness: &ChallengeSeed,
+    replicas: &BTreeMap<SectorId, PrivateReplicaInfo>,
+    winners: Vec<Candidate>,
+    prover_id: ProverId,
+    rng: &mut R,
 ) -> Result<Vec<SnarkProof>> {
     info!("generate_post:start");
 
+    post_config.validate()?;
+
     let sector_count = replicas.len() as u64;
     ensure!(sector_count > 0, "Must supply at least one replica");
     ensure!(!winners.is_empty(), "Winners must not be empty");
@@ -302,9 +364,10 @@ pub fn generate_post(
         ElectionPoStCompound::setup(&setup_params)?;
     let groth_params = get_post_params(post_config)?;
 
-    let tree_size = get_tree_size::<<DefaultTreeHasher as Hasher>::Domain>(post_config.sector_size);
+    let tree_size =
+        get_tree_size::<<DefaultTreeHasher as Hasher>::Domain>(post_config.sector_size)?;
     let tree_leafs =
-        get_tree_leafs::<<DefaultTreeHasher as Hasher>::Domain>(post_config.sector_size);
+        get_tree_leafs::<<DefaultTreeHasher as Hasher>::Domain>(post_config.sector_size)?;
 
     let mut proofs = Vec::with_capacity(winners.len());
 
@@ -339,8 +402,13 @@ pub fn generate_post(
         .collect::<Result<_>>()?;
 
     for (pub_inputs, priv_inputs) in &inputs {
-        let proof =
-            ElectionPoStCompound::prove(&pub_params, &pub_inputs, &priv_inputs, &groth_params)?;
+        let proof = ElectionPoStCompound::prove_with_rng(
+            &pub_params,
+            &pub_inputs,
+            &priv_inputs,
+            &groth_params,
+            rng,
+        )?;
         proofs.push(proof.to_vec()?);
     }
 
@@ -372,6 +440,8 @@ pub fn verify_post(
 ) -> Result<bool> {
     info!("verify_post:start");
 
+    post_config.validate()?;
+
     let sector_count = replicas.len() as u64;
     ensure!(sector_count > 0, "Must supply at least one replica");
     ensure!(!winners.is_empty(), "Winners must not be empty");
@@ -1,42 +1,130 @@
+use std::collections::BTreeMap;
 use std::fs::{self, File, OpenOptions};
 use std::io::prelude::*;
+use std::io::{BufReader, BufWriter};
+use std::ops::Range;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Instant;
 
 use anyhow::{ensure, Context, Result};
-use bincode::{deserialize, serialize};
-use log::{info/*, trace*/};
+use bellperson::groth16;
+use bincode::{deserialize_from, serialize, serialize_into};
+use fs2::FileExt;
+use groupy::CurveAffine;
+use log::{info, trace};
 use memmap::MmapOptions;
 use merkletree::merkle::MerkleTree;
 use merkletree::store::{DiskStore, Store, StoreConfig};
-use paired::bls12_381::{Bls12, Fr};
-use storage_proofs::circuit::multi_proof::MultiProof;
+use paired::bls12_381::{Bls12, Fr, G1Affine, G2Affine};
+use rayon::prelude::*;
+use serde::de::DeserializeOwned;
+pub use storage_proofs::circuit::multi_proof::PROOF_FORMAT_VERSION;
+use storage_proofs::circuit::multi_proof::{is_versioned, MultiProof};
 use storage_proofs::circuit::stacked::StackedCompound;
 use storage_proofs::compound_proof::{self, CompoundProof};
 use storage_proofs::drgraph::Graph;
-use storage_proofs::hasher::{Domain, Hasher};
-use storage_proofs::measurements::{measure_op, Operation::CommD};
-use storage_proofs::merkle::create_merkle_tree;
+use storage_proofs::error::Error;
+use storage_proofs::fr32::bytes_into_fr;
+use storage_proofs::hasher::{Domain, HashFunction, Hasher};
+use storage_proofs::measurements::{
+    measure_op,
+    Operation::{CommD, PorepCommitTime},
+};
+use storage_proofs::merkle::{create_merkle_tree, create_merkle_tree_windowed};
 use storage_proofs::proof::ProofScheme;
 use storage_proofs::sector::SectorId;
 use storage_proofs::stacked::{
-    self, generate_replica_id, CacheKey, ChallengeRequirements, StackedDrg, Tau, TemporaryAux,
-    TemporaryAuxCache,
+    self, generate_replica_id, CacheKey, ChallengeRequirements, StackedBucketGraph, StackedDrg,
+    Tau, TemporaryAux, TemporaryAuxCache,
 };
+use storage_proofs::util::NODE_SIZE;
 
 use crate::api::util::{as_safe_commitment, commitment_from_fr, get_tree_leafs, get_tree_size};
-use crate::caches::{get_stacked_params, get_stacked_verifying_key};
+use crate::caches::{
+    get_stacked_params_from_dir, get_stacked_verifying_key, get_stacked_verifying_key_from_dir,
+    load_params_verified, load_verifying_key_verified, Bls12VerifyingKey,
+};
 use crate::constants::{
     DefaultPieceHasher, DefaultTreeHasher, POREP_MINIMUM_CHALLENGES, SINGLE_PARTITION_PROOF_LEN,
 };
-use crate::parameters::setup_params;
+use crate::parameters::{porep_public_params, porep_setup_params};
 pub use crate::pieces;
-pub use crate::pieces::verify_pieces;
+pub use crate::pieces::{
+    pad_pieces_to_sector, verify_pieces, verify_pieces_detailed, PieceVerifyOutcome,
+};
+use crate::sector_log::SectorSpan;
 use crate::types::{
-    Commitment, PaddedBytesAmount, PieceInfo, PoRepConfig, PoRepProofPartitions, ProverId,
-    SealCommitOutput, SealCommitPhase1Output, SealPreCommitOutput, SealPreCommitPhase1Output,
-    SectorSize, Ticket,
+    CommitTimings, Commitment, DataTree, PaddedBytesAmount, PersistentAux, PieceInfo, PoRepConfig,
+    PoRepProofPartitions, ProverId, SealCommitOutput, SealCommitOutputWithInputs,
+    SealCommitPhase1Output, SealPhase, SealPreCommitOutput, SealPreCommitPhase1Output,
+    SectorMetadata, SectorSize, Ticket, VerifyMetrics,
 };
 
+/// Builds the stacked DRG graph for `porep_config` once and persists its parents cache in
+/// the parameter cache directory. When `maximize_caching` is set, the seal phases load this
+/// cache instead of regenerating it; a cache built for another seed or cache version is
+/// ignored and regenerated in memory.
+pub fn precompute_graph(porep_config: PoRepConfig) -> Result<()> {
+    let vanilla_params = porep_setup_params(porep_config)?;
+    let graph = StackedBucketGraph::<DefaultTreeHasher>::new_stacked(
+        vanilla_params.nodes,
+        vanilla_params.degree,
+        vanilla_params.expansion_degree,
+        vanilla_params.seed,
+    )?;
+
+    let path = graph.persist_parent_cache()?;
+    info!(
+        "precomputed graph for {:?} in {:?}",
+        porep_config.sector_size, path
+    );
+
+    Ok(())
+}
+
+/// Returns the DRG (base) parents and the expander parents of `node` in the stacked DRG graph
+/// the seal of `porep_config` uses. This is meant for inspecting the graph while debugging a
+/// proof failure.
+///
+/// # Arguments
+///
+/// * `porep_config` - the porep config whose graph to inspect.
+/// * `node` - the index of the node in a layer, below the number of nodes in the sector.
+pub fn graph_parents(porep_config: PoRepConfig, node: usize) -> Result<(Vec<usize>, Vec<usize>)> {
+    let compound_setup_params = compound_proof::SetupParams {
+        vanilla_params: porep_setup_params(porep_config)?,
+        partitions: Some(usize::from(PoRepProofPartitions::from(porep_config))),
+        priority: false,
+    };
+    let compound_public_params =
+        <StackedCompound<DefaultTreeHasher, DefaultPieceHasher> as CompoundProof<
+            _,
+            StackedDrg<DefaultTreeHasher, DefaultPieceHasher>,
+            _,
+        >>::setup(&compound_setup_params)?;
+
+    let graph = &compound_public_params.vanilla_params.graph;
+    ensure!(
+        node < graph.size(),
+        "node {} is out of range for a graph of {} nodes",
+        node,
+        graph.size()
+    );
+
+    let mut base_parents = vec![0; graph.base_graph().degree()];
+    graph.base_parents(node, &mut base_parents)?;
+    let mut expanded_parents = vec![0; graph.expansion_degree()];
+    graph.expanded_parents(node, &mut expanded_parents);
+
+    Ok((
+        base_parents.into_iter().map(|p| p as usize).collect(),
+        expanded_parents.into_iter().map(|p| p as usize).collect(),
+    ))
+}
+
+/// Replicates the staged sector at `in_path` into `out_path`. If both paths refer to the
+/// same file, the staged sector is sealed in place and no copy is made.
 #[allow(clippy::too_many_arguments)]
 pub fn seal_pre_commit_phase1<R, S, T>(
     porep_config: PoRepConfig,
@@ -53,7 +141,222 @@ where
     S: AsRef<Path>,
     T: AsRef<Path>,
 {
-    info!("seal_pre_commit_phase1: start");
+    seal_pre_commit_phase1_with_data_tree(
+        porep_config,
+        cache_path,
+        in_path,
+        out_path,
+        prover_id,
+        sector_id,
+        ticket,
+        piece_infos,
+        false,
+    )
+}
+
+/// Like `seal_pre_commit_phase1`, but if `keep_data_tree` is set the data tree (tree-d)
+/// built during this phase is kept in the returned output, so that a subsequent
+/// `seal_pre_commit_phase2` in the same process can use it instead of reloading it from
+/// disk. This increases peak memory, as the tree is held until phase2 consumes it.
+#[allow(clippy::too_many_arguments)]
+pub fn seal_pre_commit_phase1_with_data_tree<R, S, T>(
+    porep_config: PoRepConfig,
+    cache_path: R,
+    in_path: S,
+    out_path: T,
+    prover_id: ProverId,
+    sector_id: SectorId,
+    ticket: Ticket,
+    piece_infos: &[PieceInfo],
+    keep_data_tree: bool,
+) -> Result<SealPreCommitPhase1Output>
+where
+    R: AsRef<Path>,
+    S: AsRef<Path>,
+    T: AsRef<Path>,
+{
+    seal_pre_commit_phase1_inner(
+        porep_config,
+        cache_path,
+        in_path,
+        out_path,
+        prover_id,
+        sector_id,
+        ticket,
+        piece_infos,
+        keep_data_tree,
+        None,
+        &mut |_, _, _| {},
+    )
+}
+
+/// Like `seal_pre_commit_phase1`, but if `mmap_window_bytes` is set the data tree (tree-d)
+/// is built by mapping the sealed file that many bytes at a time, rather than mapping the
+/// whole sector at once. This bounds the resident set on memory constrained hosts, at the
+/// cost of building the tree leaves sequentially. The window must be a multiple of
+/// `storage_proofs::merkle::MMAP_WINDOW_ALIGNMENT`. `None` maps the whole sector.
+#[allow(clippy::too_many_arguments)]
+pub fn seal_pre_commit_phase1_with_mmap_window<R, S, T>(
+    porep_config: PoRepConfig,
+    cache_path: R,
+    in_path: S,
+    out_path: T,
+    prover_id: ProverId,
+    sector_id: SectorId,
+    ticket: Ticket,
+    piece_infos: &[PieceInfo],
+    mmap_window_bytes: Option<usize>,
+) -> Result<SealPreCommitPhase1Output>
+where
+    R: AsRef<Path>,
+    S: AsRef<Path>,
+    T: AsRef<Path>,
+{
+    seal_pre_commit_phase1_inner(
+        porep_config,
+        cache_path,
+        in_path,
+        out_path,
+        prover_id,
+        sector_id,
+        ticket,
+        piece_infos,
+        false,
+        mmap_window_bytes,
+        &mut |_, _, _| {},
+    )
+}
+
+/// Like `seal_pre_commit_phase1`, but reports the progress of labeling the layers, the
+/// dominant cost of this phase, to `progress`. It is called with the layer being labeled,
+/// counted from 1, the number of its nodes labeled so far and the number of nodes in the
+/// sector; see `StackedDrg::replicate_phase1` for how often.
+#[allow(clippy::too_many_arguments)]
+pub fn seal_pre_commit_phase1_with_progress<R, S, T>(
+    porep_config: PoRepConfig,
+    cache_path: R,
+    in_path: S,
+    out_path: T,
+    prover_id: ProverId,
+    sector_id: SectorId,
+    ticket: Ticket,
+    piece_infos: &[PieceInfo],
+    progress: &mut dyn FnMut(usize, usize, usize),
+) -> Result<SealPreCommitPhase1Output>
+where
+    R: AsRef<Path>,
+    S: AsRef<Path>,
+    T: AsRef<Path>,
+{
+    seal_pre_commit_phase1_inner(
+        porep_config,
+        cache_path,
+        in_path,
+        out_path,
+        prover_id,
+        sector_id,
+        ticket,
+        piece_infos,
+        false,
+        None,
+        progress,
+    )
+}
+
+/// Estimates the number of bytes `seal_pre_commit_phase1` writes to its `cache_path`: the data
+/// tree (tree-d) and the labels of every layer.
+pub fn estimate_cache_size(porep_config: PoRepConfig) -> Result<u64> {
+    let layers = porep_setup_params(porep_config)?.layer_challenges.layers() as u64;
+    let tree_d_bytes =
+        get_tree_size::<<DefaultPieceHasher as Hasher>::Domain>(porep_config.sector_size)?
+            * NODE_SIZE;
+    let labels_bytes = layers * u64::from(PaddedBytesAmount::from(porep_config));
+
+    Ok(tree_d_bytes as u64 + labels_bytes)
+}
+
+/// Checks that `cache_path` is a writable directory with room for `estimate_cache_size` bytes,
+/// so that a full or read-only cache fails before any sealing work is done.
+fn ensure_cache_path_usable(porep_config: PoRepConfig, cache_path: &Path) -> Result<()> {
+    let metadata = fs::metadata(cache_path)
+        .with_context(|| format!("could not read cache_path={:?}", cache_path.display()))?;
+    ensure!(
+        metadata.is_dir(),
+        "cache_path={:?} is not a directory",
+        cache_path.display()
+    );
+    ensure!(
+        !metadata.permissions().readonly(),
+        "cache_path={:?} is read-only",
+        cache_path.display()
+    );
+    tempfile::tempfile_in(cache_path)
+        .with_context(|| format!("cache_path={:?} is not writable", cache_path.display()))?;
+
+    let required = estimate_cache_size(porep_config)?;
+    let available = fs2::available_space(cache_path).with_context(|| {
+        format!(
+            "could not read free space of cache_path={:?}",
+            cache_path.display()
+        )
+    })?;
+    ensure!(
+        available >= required,
+        "cache_path={:?} has {} bytes free, but sealing needs about {} bytes",
+        cache_path.display(),
+        available,
+        required
+    );
+
+    Ok(())
+}
+
+/// Name of the lock file each seal phase holds in the cache directory.
+const CACHE_LOCK: &str = "seal.lock";
+
+/// Takes an exclusive advisory lock on `cache_path` for the duration of a seal phase, which is
+/// released when the returned file is dropped. Fails with `Error::CacheLocked` if another seal
+/// operation, in this or another process, holds the lock, as two seals sharing a cache
+/// directory would corrupt each other.
+fn lock_cache_path(cache_path: &Path) -> Result<File> {
+    let lock_path = cache_path.join(CACHE_LOCK);
+    let lock = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .open(&lock_path)
+        .with_context(|| format!("could not open lock file {:?}", lock_path))?;
+
+    if let Err(err) = lock.try_lock_exclusive() {
+        if err.raw_os_error() == fs2::lock_contended_error().raw_os_error() {
+            return Err(Error::CacheLocked(cache_path.to_path_buf()).into());
+        }
+        return Err(err).with_context(|| format!("could not lock {:?}", lock_path));
+    }
+
+    Ok(lock)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn seal_pre_commit_phase1_inner<R, S, T>(
+    porep_config: PoRepConfig,
+    cache_path: R,
+    in_path: S,
+    out_path: T,
+    prover_id: ProverId,
+    sector_id: SectorId,
+    ticket: Ticket,
+    piece_infos: &[PieceInfo],
+    keep_data_tree: bool,
+    mmap_window_bytes: Option<usize>,
+    progress: &mut dyn FnMut(usize, usize, usize),
+) -> Result<SealPreCommitPhase1Output>
+where
+    R: AsRef<Path>,
+    S: AsRef<Path>,
+    T: AsRef<Path>,
+{
+    let _span = SectorSpan::enter(sector_id);
+    info!("seal_pre_commit_phase1:start");
     
     println!("seal_pre_commit_phase1: start");
 
@@ -67,17 +370,40 @@ where
     fs::metadata(&out_path)
         .with_context(|| format!("could not read out_path={:?}", out_path.as_ref().display()))?;
 
-    println!("Copy unsealed data to output location = {:?}  to  {:?}",in_path.as_ref().display(),out_path.as_ref().display());
-    // Copy unsealed data to output location, where it will be sealed in place.
-    let copy_len = fs::copy(&in_path, &out_path).with_context(|| {
-        format!(
-            "could not copy in_path={:?} to out_path={:?}",
+    ensure_cache_path_usable(porep_config, cache_path.as_ref())?;
+    let _cache_lock = lock_cache_path(cache_path.as_ref())?;
+
+    // If the staged sector already is the output file, it is sealed in place and no copy is
+    // made. Copying a file onto itself would truncate it, so this must be detected up front.
+    let in_place = fs::canonicalize(&in_path)? == fs::canonicalize(&out_path)?;
+
+    if in_place {
+        let staged_len = fs::metadata(&in_path)?.len();
+        // Zero-padding below must never truncate the caller's staged data.
+        ensure!(
+            staged_len <= sector_bytes as u64,
+            "staged sector in_path={:?} is larger than the sector ({} > {})",
             in_path.as_ref().display(),
+            staged_len,
+            sector_bytes
+        );
+        info!(
+            "sealing staged sector in place: {:?}",
             out_path.as_ref().display()
-        )
-    })?;
-
-    println!("total copyed bytes amout = {:?}",copy_len);
+        );
+    } else {
+        println!("Copy unsealed data to output location = {:?}  to  {:?}",in_path.as_ref().display(),out_path.as_ref().display());
+        // Copy unsealed data to output location, where it will be sealed in place.
+        let copy_len = fs::copy(&in_path, &out_path).with_context(|| {
+            format!(
+                "could not copy in_path={:?} to out_path={:?}",
+                in_path.as_ref().display(),
+                out_path.as_ref().display()
+            )
+        })?;
+
+        println!("total copyed bytes amout = {:?}",copy_len);
+    }
 
     println!("open out_path file for ...");
     let f_data = OpenOptions::new()
@@ -90,19 +416,9 @@ where
     f_data.set_len(sector_bytes as u64)?;
     println!("set out file len = {:?}",sector_bytes);
 
-    println!("mmap file to var data ...");
-    let data = unsafe {
-        MmapOptions::new()
-            .map_mut(&f_data)
-            .with_context(|| format!("could not mmap out_path={:?}", out_path.as_ref().display()))?
-    };
-
     println!("create setup & public params from porep_config ...");
     let compound_setup_params = compound_proof::SetupParams {
-        vanilla_params: setup_params(
-            PaddedBytesAmount::from(porep_config),
-            usize::from(PoRepProofPartitions::from(porep_config)),
-        )?,
+        vanilla_params: porep_setup_params(porep_config)?,
         partitions: Some(usize::from(PoRepProofPartitions::from(porep_config))),
         priority: false,
     };
@@ -116,9 +432,9 @@ where
     println!("compound_public_params = {:?}",compound_public_params);
     
     println!("building merkle tree for the original data");
-    let (config, comm_d) = measure_op(CommD, || -> Result<_> {
+    let (config, comm_d, data_tree) = measure_op(CommD, || -> Result<_> {
         let tree_leafs =
-            get_tree_leafs::<<DefaultPieceHasher as Hasher>::Domain>(porep_config.sector_size);
+            get_tree_leafs::<<DefaultPieceHasher as Hasher>::Domain>(porep_config.sector_size)?;
         ensure!(
             compound_public_params.vanilla_params.graph.size() == tree_leafs,
             "graph size and leaf size don't match"
@@ -127,7 +443,7 @@ where
         println!(
             "seal phase 1: sector_size {}, tree size {}, tree leafs {}, cached above base {}",
             u64::from(porep_config.sector_size),
-            get_tree_size::<<DefaultPieceHasher as Hasher>::Domain>(porep_config.sector_size),
+            get_tree_size::<<DefaultPieceHasher as Hasher>::Domain>(porep_config.sector_size)?,
             tree_leafs,
             StoreConfig::default_cached_above_base_layer(tree_leafs)
         );
@@ -142,9 +458,26 @@ where
 
         println!("StoreConfig = {:?}",config);
 
-        let data_tree =
-            create_merkle_tree::<DefaultPieceHasher>(Some(config.clone()), tree_leafs, &data)?;
-        drop(data);
+        let data_tree = match mmap_window_bytes {
+            Some(window_bytes) => {
+                info!("building tree_d in mmap windows of {} bytes", window_bytes);
+                create_merkle_tree_windowed::<DefaultPieceHasher>(
+                    Some(config.clone()),
+                    tree_leafs,
+                    &f_data,
+                    window_bytes,
+                )?
+            }
+            None => {
+                println!("mmap file to var data ...");
+                let data = unsafe {
+                    MmapOptions::new().map_mut(&f_data).with_context(|| {
+                        format!("could not mmap out_path={:?}", out_path.as_ref().display())
+                    })?
+                };
+                create_merkle_tree::<DefaultPieceHasher>(Some(config.clone()), tree_leafs, &data)?
+            }
+        };
 
         println!("data_tree = {:?}",data_tree);
 
@@ -152,11 +485,21 @@ where
         println!("comm_d_root = {:?}",comm_d_root);
         let comm_d = commitment_from_fr::<Bls12>(comm_d_root);
         println!("comm_d = {:?}",comm_d);
-        drop(data_tree);
 
-        Ok((config, comm_d))
+        let data_tree = if keep_data_tree {
+            Some(data_tree)
+        } else {
+            drop(data_tree);
+            None
+        };
+
+        Ok((config, comm_d, data_tree))
     })?;
 
+    // The data mmap is already unmapped; release the handle too, so that callers can move or
+    // replace `out_path` (which Windows refuses while it is open) as soon as this returns.
+    drop(f_data);
+
     info!("verifying pieces");
 
     ensure!(
@@ -173,14 +516,17 @@ where
         &compound_public_params.vanilla_params,
         &replica_id,
         config.clone(),
+        progress,
     )?;
     println!("labels = {:?}",labels);
    
+    info!("seal_pre_commit_phase1:finish");
 
     Ok(SealPreCommitPhase1Output {
         labels,
         config,
         comm_d,
+        data_tree,
     })
 }
 
@@ -195,7 +541,96 @@ where
     R: AsRef<Path>,
     S: AsRef<Path>,
 {
+    let cache_path = cache_path.as_ref();
+
+    seal_pre_commit_phase2_inner(
+        porep_config,
+        phase1_output,
+        cache_path,
+        out_path,
+        |p_aux, t_aux| {
+            // Persist p_aux and t_aux here 存储
+            let p_aux_path = cache_path.join(CacheKey::PAux.to_string());
+            let mut f_p_aux = File::create(&p_aux_path)
+                .with_context(|| format!("could not create file p_aux={:?}", p_aux_path))?;
+            let p_aux_bytes = serialize(p_aux)?;
+            f_p_aux
+                .write_all(&p_aux_bytes)
+                .with_context(|| format!("could not write to file p_aux={:?}", p_aux_path))?;
+
+            let t_aux_path = cache_path.join(CacheKey::TAux.to_string());
+            let mut f_t_aux = File::create(&t_aux_path)
+                .with_context(|| format!("could not create file t_aux={:?}", t_aux_path))?;
+            let t_aux_bytes = serialize(t_aux)?;
+            f_t_aux
+                .write_all(&t_aux_bytes)
+                .with_context(|| format!("could not write to file t_aux={:?}", t_aux_path))?;
+
+            Ok(())
+        },
+    )
+}
+
+/// Like `seal_pre_commit_phase2`, but serializes `p_aux` and `t_aux` to `p_aux_writer` and
+/// `t_aux_writer` instead of to files in `cache_path`, e.g. to upload them to object storage.
+///
+/// Only `p_aux` and `t_aux` are redirected: the trees built by this phase are still stored in
+/// `cache_path`, and `t_aux` refers to them by their path there, since `TemporaryAuxCache`
+/// reloads them from it. `seal_commit_phase1` reads `p_aux` and `t_aux` from `cache_path`, so
+/// they must be written back there, byte for byte, before it runs.
+///
+/// # Arguments
+///
+/// * `porep_config` - this sector's porep config that contains the number of bytes in the sector.
+/// * `phase1_output` - the output of `seal_pre_commit_phase1` for this sector.
+/// * `cache_path` - path to the directory in which the trees of the sector are stored.
+/// * `out_path` - path to the replica written by `seal_pre_commit_phase1`.
+/// * `p_aux_writer` - where the bincode serialized `p_aux` is written.
+/// * `t_aux_writer` - where the bincode serialized `t_aux` is written.
+pub fn seal_pre_commit_phase2_with_writers<R, S>(
+    porep_config: PoRepConfig,
+    phase1_output: SealPreCommitPhase1Output,
+    cache_path: S,
+    out_path: R,
+    p_aux_writer: &mut dyn Write,
+    t_aux_writer: &mut dyn Write,
+) -> Result<SealPreCommitOutput>
+where
+    R: AsRef<Path>,
+    S: AsRef<Path>,
+{
+    seal_pre_commit_phase2_inner(
+        porep_config,
+        phase1_output,
+        cache_path.as_ref(),
+        out_path,
+        |p_aux, t_aux| {
+            serialize_into(&mut *p_aux_writer, p_aux).context("could not write p_aux")?;
+            p_aux_writer.flush().context("could not flush p_aux")?;
+            serialize_into(&mut *t_aux_writer, t_aux).context("could not write t_aux")?;
+            t_aux_writer.flush().context("could not flush t_aux")?;
+
+            Ok(())
+        },
+    )
+}
+
+/// Runs phase2 on the sector in `cache_path` and `out_path`, then has `persist_aux` store its
+/// `p_aux` and `t_aux`.
+fn seal_pre_commit_phase2_inner<R, F>(
+    porep_config: PoRepConfig,
+    phase1_output: SealPreCommitPhase1Output,
+    cache_path: &Path,
+    out_path: R,
+    persist_aux: F,
+) -> Result<SealPreCommitOutput>
+where
+    R: AsRef<Path>,
+    F: FnOnce(&PersistentAux, &TemporaryAux<DefaultTreeHasher, DefaultPieceHasher>) -> Result<()>,
+{
+    info!("seal_pre_commit_phase2:start");
     println!("seal_pre_commit_phase2: start");
+    let _cache_lock = lock_cache_path(cache_path)?;
 
     //获取第一阶段输出参数赋值到变量中
     /*
@@ -223,12 +658,21 @@ where
         mut labels,
         config,
         comm_d,
-        ..
+        data_tree,
     } = phase1_output;
 
 
     //所有label的path设置为cache_path
-    labels.update_root(cache_path.as_ref());
+    labels.update_root(cache_path);
+
+    // A missing replica usually means phase1 never ran for this sector, or its output was
+    // removed since, so say so rather than surfacing a bare OS error.
+    ensure!(
+        out_path.as_ref().exists(),
+        "out_path={:?} does not exist: seal_pre_commit_phase1 must have written the replica \
+         there before phase2 runs, use detect_seal_phase to check the sector's sealing state",
+        out_path.as_ref().display()
+    );
 
     //将磁盘文件映射到内存中
     let f_data = OpenOptions::new()
@@ -236,45 +680,41 @@ where
         .write(true)
         .open(&out_path)
         .with_context(|| format!("could not open out_path={:?}", out_path.as_ref().display()))?;
+    let sector_bytes = u64::from(PaddedBytesAmount::from(porep_config));
+    let out_len = f_data
+        .metadata()
+        .with_context(|| format!("could not stat out_path={:?}", out_path.as_ref().display()))?
+        .len();
+    ensure!(
+        out_len == sector_bytes,
+        "out_path={:?} holds {} bytes, but a sealed sector has {}: it was not written by \
+         seal_pre_commit_phase1 for this porep_config",
+        out_path.as_ref().display(),
+        out_len,
+        sector_bytes
+    );
     let data = unsafe {
         MmapOptions::new()
             .map_mut(&f_data)
             .with_context(|| format!("could not mmap out_path={:?}", out_path.as_ref().display()))?
     };
+    // The mapping stays valid without the handle, so don't hold it for the whole phase.
+    drop(f_data);
     let data: storage_proofs::porep::Data<'_> = (data, PathBuf::from(out_path.as_ref())).into();
 
-    // Load data tree from disk
-    let data_tree = {
-        let tree_size =
-            get_tree_size::<<DefaultPieceHasher as Hasher>::Domain>(porep_config.sector_size);
-        let tree_leafs =
-            get_tree_leafs::<<DefaultPieceHasher as Hasher>::Domain>(porep_config.sector_size);
-
-        println!(
-            "seal phase 2: tree size {}, tree leafs {}, cached above base {}",
-            tree_size,
-            tree_leafs,
-            StoreConfig::default_cached_above_base_layer(tree_leafs)
-        );
-        let config = StoreConfig::new(
-            cache_path.as_ref(),
-            CacheKey::CommDTree.to_string(),
-            StoreConfig::default_cached_above_base_layer(tree_leafs),
-        );
-        println!("config used for tree_d = {:?}",config);
-        //使用DefaultPieceHasher生成treed
-        let store: DiskStore<<DefaultPieceHasher as Hasher>::Domain> =
-            DiskStore::new_from_disk(tree_size, &config)?;
-        MerkleTree::from_data_store(store, tree_leafs)
-    }?;
+    // Reuse the data tree kept by phase1 if there is one, otherwise load it from disk.
+    let data_tree = match data_tree {
+        Some(data_tree) => {
+            info!("seal phase 2: reusing in-memory data tree");
+            data_tree
+        }
+        None => load_data_tree(porep_config, cache_path)?,
+    };
 
     //treed is done
 
     let compound_setup_params = compound_proof::SetupParams {
-        vanilla_params: setup_params(
-            PaddedBytesAmount::from(porep_config),
-            usize::from(PoRepProofPartitions::from(porep_config)),
-        )?,
+        vanilla_params: porep_setup_params(porep_config)?,
         partitions: Some(usize::from(PoRepProofPartitions::from(porep_config))),
         priority: false,
     };
@@ -300,26 +740,48 @@ where
     //得到复制处理后的root
     let comm_r = commitment_from_fr::<Bls12>(tau.comm_r.into());
 
-    // Persist p_aux and t_aux here 存储
-    let p_aux_path = cache_path.as_ref().join(CacheKey::PAux.to_string());
-    let mut f_p_aux = File::create(&p_aux_path)
-        .with_context(|| format!("could not create file p_aux={:?}", p_aux_path))?;
-    let p_aux_bytes = serialize(&p_aux)?;
-    f_p_aux
-        .write_all(&p_aux_bytes)
-        .with_context(|| format!("could not write to file p_aux={:?}", p_aux_path))?;
-
-    let t_aux_path = cache_path.as_ref().join(CacheKey::TAux.to_string());
-    let mut f_t_aux = File::create(&t_aux_path)
-        .with_context(|| format!("could not create file t_aux={:?}", t_aux_path))?;
-    let t_aux_bytes = serialize(&t_aux)?;
-    f_t_aux
-        .write_all(&t_aux_bytes)
-        .with_context(|| format!("could not write to file t_aux={:?}", t_aux_path))?;
+    persist_aux(&p_aux, &t_aux)?;
+
+    write_sector_metadata(cache_path, &SectorMetadata::new(porep_config))?;
+
+    info!("seal_pre_commit_phase2:finish");
 
     Ok(SealPreCommitOutput { comm_r, comm_d })
 }
 
+fn load_data_tree(porep_config: PoRepConfig, cache_path: &Path) -> Result<DataTree> {
+    let tree_size =
+        get_tree_size::<<DefaultPieceHasher as Hasher>::Domain>(porep_config.sector_size)?;
+    let tree_leafs =
+        get_tree_leafs::<<DefaultPieceHasher as Hasher>::Domain>(porep_config.sector_size)?;
+
+    println!(
+        "seal phase 2: tree size {}, tree leafs {}, cached above base {}",
+        tree_size,
+        tree_leafs,
+        StoreConfig::default_cached_above_base_layer(tree_leafs)
+    );
+    let config = StoreConfig::new(
+        cache_path,
+        CacheKey::CommDTree.to_string(),
+        StoreConfig::default_cached_above_base_layer(tree_leafs),
+    );
+    println!("config used for tree_d = {:?}",config);
+    //使用DefaultPieceHasher生成treed
+    let store: DiskStore<<DefaultPieceHasher as Hasher>::Domain> =
+        DiskStore::new_from_disk(tree_size, &config)?;
+    MerkleTree::from_data_store(store, tree_leafs)
+}
+
+/// Deserializes a persisted aux file (p_aux or t_aux) directly from a buffered reader,
+/// rather than reading the whole file into memory first.
+pub(crate) fn deserialize_aux_file<T: DeserializeOwned>(path: &Path) -> Result<T> {
+    let file = File::open(path).with_context(|| format!("could not open file {:?}", path))?;
+    let aux = deserialize_from(BufReader::new(file))?;
+
+    Ok(aux)
+}
+
 #[allow(clippy::too_many_arguments)]
 pub fn seal_commit_phase1<T: AsRef<Path>>(
     porep_config: PoRepConfig,
@@ -331,7 +793,15 @@ pub fn seal_commit_phase1<T: AsRef<Path>>(
     pre_commit: SealPreCommitOutput,
     piece_infos: &[PieceInfo],
 ) -> Result<SealCommitPhase1Output> {
+    let _span = SectorSpan::enter(sector_id);
+    info!("seal_commit_phase1:start");
     println!("seal_commit_phase1:start");
+    let _cache_lock = lock_cache_path(cache_path.as_ref())?;
+
+    // Sectors sealed before the metadata sidecar existed have none, and are not checked.
+    if cache_path.as_ref().join(SECTOR_METADATA).exists() {
+        read_sector_metadata(&cache_path)?.validate(porep_config)?;
+    }
 
     let SealPreCommitOutput { comm_d, comm_r } = pre_commit;
 
@@ -343,23 +813,20 @@ pub fn seal_commit_phase1<T: AsRef<Path>>(
     );
 
     let p_aux = {
-        let mut p_aux_bytes = vec![];
         let p_aux_path = cache_path.as_ref().join(CacheKey::PAux.to_string());
-        let mut f_p_aux = File::open(&p_aux_path)
-            .with_context(|| format!("could not open file p_aux={:?}", p_aux_path))?;
-        f_p_aux.read_to_end(&mut p_aux_bytes)?;
-
-        deserialize(&p_aux_bytes)
+        deserialize_aux_file(&p_aux_path)
+            .with_context(|| format!("could not read file p_aux={:?}", p_aux_path))
     }?;
+    ensure!(
+        comm_r_of_p_aux(&p_aux) == comm_r,
+        "comm_r does not match the p_aux in cache_path={:?}, is it the cache of another sector?",
+        cache_path.as_ref()
+    );
 
     let t_aux = {
-        let mut t_aux_bytes = vec![];
         let t_aux_path = cache_path.as_ref().join(CacheKey::TAux.to_string());
-        let mut f_t_aux = File::open(&t_aux_path)
-            .with_context(|| format!("could not open file t_aux={:?}", t_aux_path))?;
-        f_t_aux.read_to_end(&mut t_aux_bytes)?;
-
-        let mut res: TemporaryAux<_, _> = deserialize(&t_aux_bytes)?;
+        let mut res: TemporaryAux<_, _> = deserialize_aux_file(&t_aux_path)
+            .with_context(|| format!("could not read file t_aux={:?}", t_aux_path))?;
 
         // Switch t_aux to the passed in cache_path
         res.set_cache_path(cache_path);
@@ -376,14 +843,14 @@ pub fn seal_commit_phase1<T: AsRef<Path>>(
     let comm_r_safe = as_safe_commitment(&comm_r, "comm_r")?;
     let comm_d_safe = <DefaultPieceHasher as Hasher>::Domain::try_from_bytes(&comm_d)?;
 
-    let sys_time = std::time::SystemTime::now();
+    let start_time = Instant::now();
     let replica_id = generate_replica_id::<DefaultTreeHasher, _>(
         &prover_id,
         sector_id.into(),
         &ticket,
         comm_d_safe,
     );
-    println!("generate_replica_id duration = {:?}", std::time::SystemTime::now().duration_since(sys_time));
+    println!("generate_replica_id duration = {:?}", start_time.elapsed());
 
     println!("replica_id = {:?}",replica_id);
 
@@ -403,10 +870,7 @@ pub fn seal_commit_phase1<T: AsRef<Path>>(
     };
 
     let compound_setup_params = compound_proof::SetupParams {
-        vanilla_params: setup_params(
-            PaddedBytesAmount::from(porep_config),
-            usize::from(PoRepProofPartitions::from(porep_config)),
-        )?,
+        vanilla_params: porep_setup_params(porep_config)?,
         partitions: Some(usize::from(PoRepProofPartitions::from(porep_config))),
         priority: false,
     };
@@ -442,6 +906,7 @@ pub fn seal_commit_phase1<T: AsRef<Path>>(
     //println!("compact(t_aux) = {:?}", &t_aux);
 
     println!("seal_commit_phase1:end");
+    info!("seal_commit_phase1:finish");
 
     Ok(SealCommitPhase1Output {
         vanilla_proofs,
@@ -453,37 +918,197 @@ pub fn seal_commit_phase1<T: AsRef<Path>>(
     })
 }
 
-#[allow(clippy::too_many_arguments)]
-pub fn seal_commit_phase2(
+/// Verifies the vanilla proofs of every partition of `phase1_output`, the sanity check
+/// `seal_commit_phase1` runs on the proofs it generates. The public inputs are rebuilt from
+/// the output's `comm_r`, `comm_d`, `replica_id` and `seed`.
+///
+/// The partitions, and the challenges within each partition, are verified concurrently on
+/// the rayon thread pool. Use it to check, or audit, a `SealCommitPhase1Output` which was
+/// persisted or sent elsewhere without spending the groth proving time of `seal_commit_phase2`
+/// on it.
+///
+/// # Arguments
+///
+/// * `porep_config` - this sector's porep config that contains the number of bytes in this sector.
+/// * `phase1_output` - the output of `seal_commit_phase1` for this sector.
+pub fn verify_vanilla_proofs(
     porep_config: PoRepConfig,
-    phase1_output: SealCommitPhase1Output,
-    prover_id: ProverId,
-    sector_id: SectorId,
-) -> Result<SealCommitOutput> {
-    info!("seal_commit_phase2:start");
-    println!("seal_commit_phase2:start");
-    let sys_time = std::time::SystemTime::now();
-
-
+    phase1_output: &SealCommitPhase1Output,
+) -> Result<bool> {
     let SealCommitPhase1Output {
         vanilla_proofs,
         comm_d,
         comm_r,
         replica_id,
         seed,
-        ticket,
+        ..
     } = phase1_output;
 
-    ensure!(comm_d != [0; 32], "Invalid all zero commitment (comm_d)");
-    ensure!(comm_r != [0; 32], "Invalid all zero commitment (comm_r)");
+    ensure!(*comm_d != [0; 32], "Invalid all zero commitment (comm_d)");
+    ensure!(*comm_r != [0; 32], "Invalid all zero commitment (comm_r)");
+
+    let partitions = usize::from(PoRepProofPartitions::from(porep_config));
+    ensure!(
+        vanilla_proofs.len() == partitions,
+        "expected vanilla proofs of {} partitions, got {}",
+        partitions,
+        vanilla_proofs.len()
+    );
 
-    println!("comm_r = {:?}",comm_r);
-    let comm_r_safe = as_safe_commitment(&comm_r, "comm_r")?;
-    println!("comm_r_safe = {:?}",comm_r_safe);
-    println!("comm_d = {:?}",comm_d);
-    let comm_d_safe = <DefaultPieceHasher as Hasher>::Domain::try_from_bytes(&comm_d)?;
-    println!("comm_d_safe = {:?}",comm_d_safe);
-    println!("PublicInputs:start");
+    let public_inputs = stacked::PublicInputs {
+        replica_id: *replica_id,
+        tau: Some(stacked::Tau {
+            comm_d: <DefaultPieceHasher as Hasher>::Domain::try_from_bytes(comm_d)?,
+            comm_r: as_safe_commitment(comm_r, "comm_r")?,
+        }),
+        k: None,
+        seed: *seed,
+    };
+
+    StackedDrg::<DefaultTreeHasher, DefaultPieceHasher>::verify_all_partitions(
+        &porep_public_params(porep_config)?,
+        &public_inputs,
+        vanilla_proofs,
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn seal_commit_phase2(
+    porep_config: PoRepConfig,
+    phase1_output: SealCommitPhase1Output,
+    prover_id: ProverId,
+    sector_id: SectorId,
+) -> Result<SealCommitOutput> {
+    seal_commit_phase2_with_options(
+        porep_config,
+        phase1_output,
+        prover_id,
+        sector_id,
+        SealCommitPhase2Options::default(),
+    )
+}
+
+/// Options of `seal_commit_phase2_with_options`. The defaults are what `seal_commit_phase2`
+/// does.
+pub struct SealCommitPhase2Options<'a> {
+    /// Whether to return the proof without the `verify_seal` sanity check, which otherwise costs
+    /// as much as verifying the proof a second time. Defaults to `false`.
+    ///
+    /// Skipping the check means an invalid proof, e.g. one generated from a corrupted
+    /// `phase1_output` or on faulty hardware, is returned as if it were valid and is only
+    /// discovered once it fails verification on chain. Only skip it in pipelines whose proofs
+    /// are verified elsewhere.
+    pub skip_post_verify: bool,
+    /// A directory to read the groth parameters and verifying key from, instead of the
+    /// parameter cache. Defaults to `None`.
+    pub params_dir: Option<&'a Path>,
+    /// The format version byte the proof is prefixed with, which `verify_seal` checks before
+    /// parsing the proof. Defaults to `PROOF_FORMAT_VERSION`.
+    pub proof_format_version: u8,
+    /// Called with the number of partitions proven so far and the total number of partitions
+    /// as each partition's groth proof is created. The partitions are then proven one after
+    /// another rather than in a single batch. Defaults to `None`.
+    pub progress: Option<&'a mut dyn FnMut(usize, usize)>,
+}
+
+impl Default for SealCommitPhase2Options<'_> {
+    fn default() -> Self {
+        SealCommitPhase2Options {
+            skip_post_verify: false,
+            params_dir: None,
+            proof_format_version: PROOF_FORMAT_VERSION,
+            progress: None,
+        }
+    }
+}
+
+/// Like `seal_commit_phase2`, with the behaviour adjusted by `options`.
+///
+/// # Arguments
+///
+/// * `porep_config` - this sector's porep config that contains the number of bytes in this sector.
+/// * `phase1_output` - the output of `seal_commit_phase1` for this sector.
+/// * `prover_id` - the prover-id that sealed this sector.
+/// * `sector_id` - this sector's sector-id.
+/// * `options` - how to deviate from `seal_commit_phase2`, see `SealCommitPhase2Options`.
+pub fn seal_commit_phase2_with_options(
+    porep_config: PoRepConfig,
+    phase1_output: SealCommitPhase1Output,
+    prover_id: ProverId,
+    sector_id: SectorId,
+    options: SealCommitPhase2Options,
+) -> Result<SealCommitOutput> {
+    let (output, _) =
+        seal_commit_phase2_timed_inner(porep_config, phase1_output, prover_id, sector_id, options)?;
+
+    Ok(output)
+}
+
+/// Like `seal_commit_phase2`, but also returns how long each step of proving took, e.g. to
+/// record per-sector proving time without the `measurements` feature.
+///
+/// # Arguments
+///
+/// * `porep_config` - this sector's porep config that contains the number of bytes in this sector.
+/// * `phase1_output` - the output of `seal_commit_phase1` for this sector.
+/// * `prover_id` - the prover-id that sealed this sector.
+/// * `sector_id` - this sector's sector-id.
+pub fn seal_commit_phase2_timed(
+    porep_config: PoRepConfig,
+    phase1_output: SealCommitPhase1Output,
+    prover_id: ProverId,
+    sector_id: SectorId,
+) -> Result<(SealCommitOutput, CommitTimings)> {
+    seal_commit_phase2_timed_inner(
+        porep_config,
+        phase1_output,
+        prover_id,
+        sector_id,
+        SealCommitPhase2Options::default(),
+    )
+}
+
+fn seal_commit_phase2_timed_inner(
+    porep_config: PoRepConfig,
+    phase1_output: SealCommitPhase1Output,
+    prover_id: ProverId,
+    sector_id: SectorId,
+    options: SealCommitPhase2Options,
+) -> Result<(SealCommitOutput, CommitTimings)> {
+    validate_proof_len_constant()?;
+
+    let SealCommitPhase2Options {
+        skip_post_verify,
+        params_dir,
+        proof_format_version,
+        progress,
+    } = options;
+
+    let _span = SectorSpan::enter(sector_id);
+    info!("seal_commit_phase2:start");
+    println!("seal_commit_phase2:start");
+    let start_time = Instant::now();
+    let mut timings = CommitTimings::default();
+
+    let SealCommitPhase1Output {
+        vanilla_proofs,
+        comm_d,
+        comm_r,
+        replica_id,
+        seed,
+        ticket,
+    } = phase1_output;
+
+    ensure!(comm_d != [0; 32], "Invalid all zero commitment (comm_d)");
+    ensure!(comm_r != [0; 32], "Invalid all zero commitment (comm_r)");
+
+    println!("comm_r = {:?}",comm_r);
+    let comm_r_safe = as_safe_commitment(&comm_r, "comm_r")?;
+    println!("comm_r_safe = {:?}",comm_r_safe);
+    println!("comm_d = {:?}",comm_d);
+    let comm_d_safe = <DefaultPieceHasher as Hasher>::Domain::try_from_bytes(&comm_d)?;
+    println!("comm_d_safe = {:?}",comm_d_safe);
+    println!("PublicInputs:start");
     let public_inputs = stacked::PublicInputs {
         replica_id,
         tau: Some(stacked::Tau {
@@ -493,19 +1118,24 @@ pub fn seal_commit_phase2(
         k: None,
         seed,
     };
+    timings.setup += start_time.elapsed();
+
     println!("get_stacked_params:start");
-    let groth_params = get_stacked_params(porep_config)?;
+    let params_start = Instant::now();
+    let groth_params = match params_dir {
+        Some(dir) => get_stacked_params_from_dir(porep_config, dir)?,
+        None => load_params_verified(porep_config, None)?,
+    };
+    timings.params = params_start.elapsed();
     //println!("groth_params = {:?}",groth_params);  很长
     println!(
         "got groth params ({}) while sealing",
         u64::from(PaddedBytesAmount::from(porep_config))
     );
     println!("SetupParams:start");
+    let setup_start = Instant::now();
     let compound_setup_params = compound_proof::SetupParams {
-        vanilla_params: setup_params(
-            PaddedBytesAmount::from(porep_config),
-            usize::from(PoRepProofPartitions::from(porep_config)),
-        )?,
+        vanilla_params: porep_setup_params(porep_config)?,
         partitions: Some(usize::from(PoRepProofPartitions::from(porep_config))),
         priority: false,
     };
@@ -519,59 +1149,785 @@ pub fn seal_commit_phase2(
         >>::setup(&compound_setup_params)?;
     println!("compound_public_params setup= {:?}",compound_public_params);
 
+    timings.setup += setup_start.elapsed();
+
     println!("StackedCompound::circuit_proofs  :start");
-    println!("Time Passed = {:?}", std::time::SystemTime::now().duration_since(sys_time));
-    let groth_proofs = StackedCompound::circuit_proofs(
-        &public_inputs,
-        vanilla_proofs,
-        &compound_public_params.vanilla_params,
-        &groth_params,
-        compound_public_params.priority,
-    )?;   
+    let circuit_proofs_start = Instant::now();
+    println!("Time Passed = {:?}", start_time.elapsed());
+    let groth_proofs = measure_op(PorepCommitTime, || match progress {
+        Some(progress) => StackedCompound::circuit_proofs_with_progress(
+            &public_inputs,
+            vanilla_proofs,
+            &compound_public_params.vanilla_params,
+            &groth_params,
+            compound_public_params.priority,
+            progress,
+        ),
+        None => StackedCompound::circuit_proofs(
+            &public_inputs,
+            vanilla_proofs,
+            &compound_public_params.vanilla_params,
+            &groth_params,
+            compound_public_params.priority,
+        ),
+    })?;
     println!("groth_proofs = {:?}",groth_proofs);
     println!("StackedCompound::circuit_proofs  :finish");
-    println!("Time Passed = {:?}", std::time::SystemTime::now().duration_since(sys_time));
+    println!("Time Passed = {:?}", start_time.elapsed());
 
     let proof = MultiProof::new(groth_proofs, &groth_params.vk);
     println!("MultiProof = {:?}",proof);
-    let mut buf = Vec::with_capacity(
-        SINGLE_PARTITION_PROOF_LEN * usize::from(PoRepProofPartitions::from(porep_config)),
-    );
-    println!("SINGLE_PARTITION_PROOF_LEN ={} Size = {:?}",SINGLE_PARTITION_PROOF_LEN,usize::from(PoRepProofPartitions::from(porep_config)));
+    let partitions = usize::from(PoRepProofPartitions::from(porep_config));
+    // The format version byte, then one groth proof per partition.
+    let expected_len = 1 + SINGLE_PARTITION_PROOF_LEN * partitions;
+    let mut buf = Vec::with_capacity(expected_len);
+    println!("SINGLE_PARTITION_PROOF_LEN ={} Size = {:?}",SINGLE_PARTITION_PROOF_LEN,partitions);
 
-    proof.write(&mut buf)?;
+    proof.write_versioned(&mut buf, proof_format_version)?;
+    ensure!(
+        buf.len() == expected_len,
+        "seal proof is {} bytes, but a proof of {} partitions must be {} bytes",
+        buf.len(),
+        partitions,
+        expected_len
+    );
+    timings.circuit_proofs = circuit_proofs_start.elapsed();
     println!("MultiProof buf = {:?}",buf);
-    println!("Time Passed = {:?}", std::time::SystemTime::now().duration_since(sys_time));
+    println!("Time Passed = {:?}", start_time.elapsed());
     // Verification is cheap when parameters are cached,
     // and it is never correct to return a proof which does not verify.
-    verify_seal(
+    if !skip_post_verify {
+        let post_verify_start = Instant::now();
+        let verified = verify_seal_inner(
+            porep_config,
+            comm_r,
+            comm_d,
+            prover_id,
+            sector_id,
+            ticket,
+            seed,
+            &buf,
+            || match params_dir {
+                Some(dir) => get_stacked_verifying_key_from_dir(porep_config, dir),
+                None => get_stacked_verifying_key(porep_config),
+            },
+        )
+        .context("post-seal verification sanity check failed")?;
+        ensure!(verified, "post-seal verification sanity check failed");
+        timings.post_verify = post_verify_start.elapsed();
+    }
+
+    println!("seal_commit_phase2:end");
+    info!("seal_commit_phase2:finish");
+    println!("Time Passed = {:?}", start_time.elapsed());
+    timings.total = start_time.elapsed();
+    Ok((SealCommitOutput { proof: buf }, timings))
+}
+
+/// Returns the paths `seal_sector_range` uses for the sector `sector_id`: its cache directory
+/// `<cache_base>/<sector_id>`, its staged sector `<input_base>/<sector_id>`, and its sealed
+/// replica `<cache_base>/<sector_id>.sealed`.
+pub fn sector_range_paths(
+    cache_base: &Path,
+    input_base: &Path,
+    sector_id: u64,
+) -> (PathBuf, PathBuf, PathBuf) {
+    (
+        cache_base.join(sector_id.to_string()),
+        input_base.join(sector_id.to_string()),
+        cache_base.join(format!("{}.sealed", sector_id)),
+    )
+}
+
+/// Seals a contiguous range of sectors with the same config, prover, ticket and seed, running
+/// all four seal phases for each sector. The per-sector paths are derived from the bases as
+/// described in `sector_range_paths`; the staged sectors must already exist. Up to
+/// `max_concurrent` sectors are sealed at a time. Returns the sectors' proofs in the order of
+/// `sector_ids`.
+///
+/// # Arguments
+///
+/// * `porep_config` - the porep config of all sectors in the range.
+/// * `cache_base` - directory in which each sector's cache directory and replica are created.
+/// * `input_base` - directory holding each sector's staged sector.
+/// * `sector_ids` - the range of sector-ids to seal.
+/// * `prover_id` - the prover-id that seals the sectors.
+/// * `ticket` - the ticket used to generate each sector's replica-id.
+/// * `seed` - the seed used to derive the porep challenges of each sector.
+/// * `piece_infos_per_sector` - the pieces of each sector, in the order of `sector_ids`.
+/// * `max_concurrent` - how many sectors may be sealed at the same time.
+#[allow(clippy::too_many_arguments)]
+pub fn seal_sector_range(
+    porep_config: PoRepConfig,
+    cache_base: &Path,
+    input_base: &Path,
+    sector_ids: Range<u64>,
+    prover_id: ProverId,
+    ticket: Ticket,
+    seed: Ticket,
+    piece_infos_per_sector: &[Vec<PieceInfo>],
+    max_concurrent: usize,
+) -> Result<Vec<SealCommitOutput>> {
+    let sector_ids: Vec<u64> = sector_ids.collect();
+    ensure!(
+        sector_ids.len() == piece_infos_per_sector.len(),
+        "{} sectors but {} lists of piece infos",
+        sector_ids.len(),
+        piece_infos_per_sector.len()
+    );
+    ensure!(max_concurrent > 0, "max_concurrent must be at least 1");
+
+    let seal_sector = |id: u64, piece_infos: &[PieceInfo]| -> Result<_> {
+        let (cache_path, in_path, out_path) = sector_range_paths(cache_base, input_base, id);
+        fs::create_dir_all(&cache_path)
+            .with_context(|| format!("could not create cache_path={:?}", cache_path))?;
+        let sector_id = SectorId::from(id);
+
+        let phase1_output = seal_pre_commit_phase1(
+            porep_config,
+            &cache_path,
+            &in_path,
+            &out_path,
+            prover_id,
+            sector_id,
+            ticket,
+            piece_infos,
+        )?;
+        let pre_commit =
+            seal_pre_commit_phase2(porep_config, phase1_output, &cache_path, &out_path)?;
+        let commit_phase1_output = seal_commit_phase1(
+            porep_config,
+            &cache_path,
+            prover_id,
+            sector_id,
+            ticket,
+            seed,
+            pre_commit,
+            piece_infos,
+        )?;
+
+        seal_commit_phase2(porep_config, commit_phase1_output, prover_id, sector_id)
+    };
+
+    // Sealing parallelizes internally on the global thread pool, so the concurrency is capped
+    // by sealing chunks of sectors rather than by restricting the pool.
+    let mut outputs = Vec::with_capacity(sector_ids.len());
+    for (ids, piece_infos) in sector_ids
+        .chunks(max_concurrent)
+        .zip(piece_infos_per_sector.chunks(max_concurrent))
+    {
+        let chunk = ids
+            .par_iter()
+            .zip(piece_infos.par_iter())
+            .map(|(&id, piece_infos)| {
+                seal_sector(id, piece_infos.as_slice())
+                    .with_context(|| format!("failed to seal sector {}", id))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        outputs.extend(chunk);
+    }
+
+    Ok(outputs)
+}
+
+/// Like `seal_commit_phase2`, but bundles the proof with the commitments, replica-id, seed
+/// and circuit public inputs it was generated for, so that it can later be audited with
+/// `verify_from_output` without reconstructing any of them.
+///
+/// # Arguments
+///
+/// * `porep_config` - this sector's porep config that contains the number of bytes in this sector.
+/// * `phase1_output` - the output of `seal_commit_phase1` for this sector.
+/// * `prover_id` - the prover-id that sealed this sector.
+/// * `sector_id` - this sector's sector-id.
+pub fn seal_commit_phase2_with_inputs(
+    porep_config: PoRepConfig,
+    phase1_output: SealCommitPhase1Output,
+    prover_id: ProverId,
+    sector_id: SectorId,
+) -> Result<SealCommitOutputWithInputs> {
+    let replica_id = phase1_output.replica_id;
+    let comm_r = phase1_output.comm_r;
+    let comm_d = phase1_output.comm_d;
+    let seed = phase1_output.seed;
+
+    let SealCommitOutput { proof } =
+        seal_commit_phase2(porep_config, phase1_output, prover_id, sector_id)?;
+
+    let public_inputs = seal_circuit_public_inputs(porep_config, replica_id, comm_r, comm_d, seed)?
+        .into_iter()
+        .map(|inputs| {
+            inputs
+                .into_iter()
+                .map(commitment_from_fr::<Bls12>)
+                .collect()
+        })
+        .collect();
+
+    Ok(SealCommitOutputWithInputs {
+        proof,
+        sector_size: u64::from(porep_config.sector_size),
+        partitions: porep_config.partitions.0,
+        replica_id,
+        comm_r,
+        comm_d,
+        seed,
+        public_inputs,
+        graph_seed: porep_config.graph_seed,
+    })
+}
+
+/// Verifies the output of `seal_commit_phase2_with_inputs` using only the data bundled in it.
+/// Errors if the bundled public inputs do not match the bundled commitments, replica-id and seed.
+///
+/// # Arguments
+///
+/// * `output` - the bundled seal proof and public inputs.
+pub fn verify_from_output(output: &SealCommitOutputWithInputs) -> Result<bool> {
+    ensure!(
+        output.comm_d != [0; 32],
+        "Invalid all zero commitment (comm_d)"
+    );
+    ensure!(
+        output.comm_r != [0; 32],
+        "Invalid all zero commitment (comm_r)"
+    );
+
+    let porep_config = PoRepConfig {
+        sector_size: SectorSize(output.sector_size),
+        partitions: PoRepProofPartitions(output.partitions),
+        graph_seed: output.graph_seed,
+    };
+
+    let public_inputs = output
+        .public_inputs
+        .iter()
+        .map(|inputs| {
+            inputs
+                .iter()
+                .map(|input| bytes_into_fr::<Bls12>(input))
+                .collect::<Result<Vec<_>>>()
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let expected_public_inputs = seal_circuit_public_inputs(
+        porep_config,
+        output.replica_id,
+        output.comm_r,
+        output.comm_d,
+        output.seed,
+    )?;
+    ensure!(
+        public_inputs == expected_public_inputs,
+        "bundled public inputs do not match the bundled commitments"
+    );
+
+    ensure_seal_proof_len(porep_config, &output.proof)?;
+    let verifying_key = get_stacked_verifying_key(porep_config)?;
+    let proof = MultiProof::new_from_reader(
+        Some(usize::from(porep_config.partitions)),
+        &output.proof[..],
+        &verifying_key,
+    )?;
+    ensure_partition_count(porep_config, &proof)?;
+
+    let pvk = groth16::prepare_batch_verifying_key(&proof.verifying_key);
+    let proofs: Vec<_> = proof.circuit_proofs.iter().collect();
+
+    groth16::verify_proofs_batch(&pvk, &mut rand::rngs::OsRng, &proofs, &public_inputs)
+        .map_err(Into::into)
+}
+
+/// Derives the circuit public inputs of each partition of a seal proof.
+fn seal_circuit_public_inputs(
+    porep_config: PoRepConfig,
+    replica_id: <DefaultTreeHasher as Hasher>::Domain,
+    comm_r: Commitment,
+    comm_d: Commitment,
+    seed: Ticket,
+) -> Result<Vec<Vec<Fr>>> {
+    let partitions = usize::from(PoRepProofPartitions::from(porep_config));
+    let compound_setup_params = compound_proof::SetupParams {
+        vanilla_params: porep_setup_params(porep_config)?,
+        partitions: Some(partitions),
+        priority: false,
+    };
+
+    let compound_public_params: compound_proof::PublicParams<
+        '_,
+        StackedDrg<'_, DefaultTreeHasher, DefaultPieceHasher>,
+    > = StackedCompound::setup(&compound_setup_params)?;
+
+    let public_inputs = stacked::PublicInputs::<
+        <DefaultTreeHasher as Hasher>::Domain,
+        <DefaultPieceHasher as Hasher>::Domain,
+    > {
+        replica_id,
+        tau: Some(Tau {
+            comm_r: as_safe_commitment(&comm_r, "comm_r")?,
+            comm_d: as_safe_commitment(&comm_d, "comm_d")?,
+        }),
+        seed,
+        k: None,
+    };
+
+    <StackedCompound<DefaultTreeHasher, DefaultPieceHasher> as CompoundProof<
+        _,
+        StackedDrg<DefaultTreeHasher, DefaultPieceHasher>,
+        _,
+    >>::partition_public_inputs(
+        &public_inputs,
+        &compound_public_params.vanilla_params,
+        partitions,
+    )
+}
+
+/// Name of the file in a sector's cache directory holding its `SectorMetadata`.
+const SECTOR_METADATA: &str = "sector.meta";
+
+fn write_sector_metadata<T: AsRef<Path>>(cache_path: T, metadata: &SectorMetadata) -> Result<()> {
+    let metadata_path = cache_path.as_ref().join(SECTOR_METADATA);
+    let f_metadata = File::create(&metadata_path)
+        .with_context(|| format!("could not create file metadata={:?}", metadata_path))?;
+    serde_json::to_writer(f_metadata, metadata)
+        .with_context(|| format!("could not write to file metadata={:?}", metadata_path))
+}
+
+/// Reads the `SectorMetadata` written by `seal_pre_commit_phase2` into the sector's cache
+/// directory.
+///
+/// # Arguments
+///
+/// * `cache_path` - path to the directory holding the sector's sealing artifacts.
+pub fn read_sector_metadata<T: AsRef<Path>>(cache_path: T) -> Result<SectorMetadata> {
+    let metadata_path = cache_path.as_ref().join(SECTOR_METADATA);
+    let f_metadata = File::open(&metadata_path)
+        .with_context(|| format!("could not open file metadata={:?}", metadata_path))?;
+    let metadata = serde_json::from_reader(BufReader::new(f_metadata))
+        .with_context(|| format!("could not parse file metadata={:?}", metadata_path))?;
+
+    Ok(metadata)
+}
+
+/// Name of the file in a sector's cache directory holding its persisted seal proof.
+const SEAL_COMMIT_PROOF: &str = "seal-commit-proof";
+
+/// Persists the output of `seal_commit_phase2` into the sector's cache directory, which
+/// marks the sector as fully sealed for `detect_seal_phase`.
+///
+/// # Arguments
+///
+/// * `cache_path` - path to the directory holding the sector's sealing artifacts.
+/// * `output` - the output of `seal_commit_phase2` for this sector.
+pub fn persist_seal_commit_output<T: AsRef<Path>>(
+    cache_path: T,
+    output: &SealCommitOutput,
+) -> Result<()> {
+    let proof_path = cache_path.as_ref().join(SEAL_COMMIT_PROOF);
+    fs::write(&proof_path, &output.proof)
+        .with_context(|| format!("could not write seal proof={:?}", proof_path))
+}
+
+/// Version of the format `persist_commit_phase1` writes.
+pub const COMMIT_PHASE1_VERSION: u32 = 1;
+
+/// Persists the output of `seal_commit_phase1` to `path`, so that a restarted process can
+/// `load_commit_phase1` it and go straight to `seal_commit_phase2` instead of proving the
+/// partitions again. The file is replaced atomically, a reader never sees a partial output.
+///
+/// # Arguments
+///
+/// * `output` - the output of `seal_commit_phase1`.
+/// * `path` - path to the file to write the output to.
+pub fn persist_commit_phase1<T: AsRef<Path>>(
+    output: &SealCommitPhase1Output,
+    path: T,
+) -> Result<()> {
+    let path = path.as_ref();
+    let tmp_path = path.with_extension("tmp");
+    {
+        let mut writer = BufWriter::new(
+            File::create(&tmp_path)
+                .with_context(|| format!("could not create file={:?}", tmp_path))?,
+        );
+        serialize_into(&mut writer, &COMMIT_PHASE1_VERSION)?;
+        serialize_into(&mut writer, output)?;
+        writer.flush()?;
+    }
+    fs::rename(&tmp_path, path)
+        .with_context(|| format!("could not move commit phase1 output into {:?}", path))
+}
+
+/// Loads a `seal_commit_phase1` output written by `persist_commit_phase1`. Fails if it was
+/// written in another format version or for another partition count than `porep_config`'s.
+///
+/// # Arguments
+///
+/// * `porep_config` - porep configuration the output will be passed to `seal_commit_phase2` with.
+/// * `path` - path to the file the output was persisted to.
+pub fn load_commit_phase1<T: AsRef<Path>>(
+    porep_config: PoRepConfig,
+    path: T,
+) -> Result<SealCommitPhase1Output> {
+    let path = path.as_ref();
+    let mut reader = BufReader::new(
+        File::open(path).with_context(|| format!("could not open file={:?}", path))?,
+    );
+
+    let version: u32 = deserialize_from(&mut reader)
+        .with_context(|| format!("could not read commit phase1 output={:?}", path))?;
+    ensure!(
+        version == COMMIT_PHASE1_VERSION,
+        "unsupported commit phase1 output version: {}",
+        version
+    );
+    let output: SealCommitPhase1Output = deserialize_from(&mut reader)
+        .with_context(|| format!("could not read commit phase1 output={:?}", path))?;
+
+    let partitions = usize::from(PoRepProofPartitions::from(porep_config));
+    ensure!(
+        output.vanilla_proofs.len() == partitions,
+        "commit phase1 output has {} partitions, but the config has {}",
+        output.vanilla_proofs.len(),
+        partitions
+    );
+
+    Ok(output)
+}
+
+/// Infers the furthest completed sealing phase of a sector from the artifacts present in
+/// its cache directory, so that an orchestrator can recover a sector's state after a
+/// restart.
+///
+/// # Arguments
+///
+/// * `cache_path` - path to the directory holding the sector's sealing artifacts.
+/// * `replica_path` - path to the sector's (partially) sealed replica.
+pub fn detect_seal_phase<R, S>(cache_path: R, replica_path: S) -> Result<SealPhase>
+where
+    R: AsRef<Path>,
+    S: AsRef<Path>,
+{
+    let cache_path = cache_path.as_ref().to_path_buf();
+    let file_exists = |name: String| cache_path.join(name).exists();
+    let store_exists = |id: String| StoreConfig::data_path(&cache_path, &id).exists();
+
+    let has_aux =
+        file_exists(CacheKey::PAux.to_string()) && file_exists(CacheKey::TAux.to_string());
+    let has_tree_d = store_exists(CacheKey::CommDTree.to_string());
+    let has_labels = store_exists(CacheKey::label_layer(1));
+
+    let phase = if has_aux {
+        if file_exists(SEAL_COMMIT_PROOF.to_string()) {
+            SealPhase::Commit2Done
+        } else if !has_tree_d {
+            // seal_commit_phase1 compacts the cached trees once the vanilla proofs exist.
+            SealPhase::Commit1Done
+        } else {
+            SealPhase::PreCommit2Done
+        }
+    } else if has_tree_d && has_labels {
+        SealPhase::PreCommit1Done
+    } else {
+        SealPhase::Staged
+    };
+
+    if phase > SealPhase::Staged {
+        ensure!(
+            replica_path.as_ref().exists(),
+            "found sealing artifacts in cache_path={:?} but replica_path={:?} is missing",
+            cache_path,
+            replica_path.as_ref()
+        );
+    }
+
+    Ok(phase)
+}
+
+/// Checks that the sealed replica still hashes to the `comm_r_last` persisted in the sector's
+/// `p_aux`, by rebuilding the root of tree-r-last from the replica bytes. This detects silent
+/// corruption of the replica without generating or verifying any SNARK, so it is much
+/// cheaper than `verify_seal` for periodic health checks.
+///
+/// # Arguments
+///
+/// * `porep_config` - this sector's porep config that contains the number of bytes in this sector.
+/// * `replica_path` - path to the sector's sealed replica.
+/// * `cache_path` - path to the directory holding the sector's `p_aux`.
+pub fn check_replica_consistency<R, S>(
+    porep_config: PoRepConfig,
+    replica_path: R,
+    cache_path: S,
+) -> Result<bool>
+where
+    R: AsRef<Path>,
+    S: AsRef<Path>,
+{
+    let p_aux: PersistentAux = {
+        let p_aux_path = cache_path.as_ref().join(CacheKey::PAux.to_string());
+        deserialize_aux_file(&p_aux_path)
+            .with_context(|| format!("could not read file p_aux={:?}", p_aux_path))
+    }?;
+
+    let f_replica = File::open(replica_path.as_ref())
+        .with_context(|| format!("could not open replica_path={:?}", replica_path.as_ref()))?;
+    let sector_bytes = u64::from(PaddedBytesAmount::from(porep_config));
+    let replica_bytes = f_replica.metadata()?.len();
+    ensure!(
+        replica_bytes == sector_bytes,
+        "replica_path={:?} holds {} bytes, expected {}",
+        replica_path.as_ref(),
+        replica_bytes,
+        sector_bytes
+    );
+
+    let replica = unsafe { MmapOptions::new().map(&f_replica) }
+        .with_context(|| format!("could not mmap replica_path={:?}", replica_path.as_ref()))?;
+    let tree_leafs =
+        get_tree_leafs::<<DefaultTreeHasher as Hasher>::Domain>(porep_config.sector_size)?;
+    let tree_r_last = create_merkle_tree::<DefaultTreeHasher>(None, tree_leafs, &replica)?;
+
+    Ok(tree_r_last.root() == p_aux.comm_r_last)
+}
+
+/// Recomputes a sector's `comm_r` from the `comm_c` and `comm_r_last` persisted in its `p_aux`.
+///
+/// # Arguments
+///
+/// * `cache_path` - path to the directory holding the sector's `p_aux`.
+pub fn comm_r_from_p_aux<T: AsRef<Path>>(cache_path: T) -> Result<Commitment> {
+    let p_aux_path = cache_path.as_ref().join(CacheKey::PAux.to_string());
+    let p_aux = deserialize_aux_file(&p_aux_path)
+        .with_context(|| format!("could not read file p_aux={:?}", p_aux_path))?;
+
+    Ok(comm_r_of_p_aux(&p_aux))
+}
+
+// comm_r = H(comm_c || comm_r_last)
+fn comm_r_of_p_aux(p_aux: &PersistentAux) -> Commitment {
+    let comm_r = <DefaultTreeHasher as Hasher>::Function::hash2(&p_aux.comm_c, &p_aux.comm_r_last);
+    commitment_from_fr::<Bls12>(comm_r.into())
+}
+
+/// Computes a sectors's `comm_d` given its pieces.
+///
+/// # Arguments
+///
+/// * `porep_config` - this sector's porep config that contains the number of bytes in the sector.
+/// * `piece_infos` - the piece info (commitment and byte length) for each piece in this sector.
+pub fn compute_comm_d(sector_size: SectorSize, piece_infos: &[PieceInfo]) -> Result<Commitment> {
+    pieces::compute_comm_d(sector_size, piece_infos)
+}
+
+/// Verifies the output of some previously-run seal operation.
+///
+/// # Arguments
+///
+/// * `porep_config` - this sector's porep config that contains the number of bytes in this sector.
+/// * `comm_r_in` - commitment to the sector's replica (`comm_r`).
+/// * `comm_d_in` - commitment to the sector's data (`comm_d`).
+/// * `prover_id` - the prover-id that sealed this sector.
+/// * `sector_id` - this sector's sector-id.
+/// * `ticket` - the ticket that was used to generate this sector's replica-id.
+/// * `seed` - the seed used to derive the porep challenges.
+/// * `proof_vec` - the porep circuit proof serialized into a vector of bytes.
+#[allow(clippy::too_many_arguments)]
+pub fn verify_seal(
+    porep_config: PoRepConfig,
+    comm_r_in: Commitment,
+    comm_d_in: Commitment,
+    prover_id: ProverId,
+    sector_id: SectorId,
+    ticket: Ticket,
+    seed: Ticket,
+    proof_vec: &[u8],
+) -> Result<bool> {
+    verify_seal_with_metrics(
+        porep_config,
+        comm_r_in,
+        comm_d_in,
+        prover_id,
+        sector_id,
+        ticket,
+        seed,
+        proof_vec,
+    )
+    .map(|(valid, _)| valid)
+}
+
+/// Like `verify_seal`, but verifies an already parsed proof, so that verifying the same proof
+/// repeatedly does not parse it each time. The proof must have been parsed with the verifying
+/// key of `porep_config`, as returned by `get_stacked_verifying_key`, and is rejected otherwise.
+///
+/// # Arguments
+///
+/// * `porep_config` - this sector's porep config that contains the number of bytes in this sector.
+/// * `comm_r_in` - commitment to the sector's replica (`comm_r`).
+/// * `comm_d_in` - commitment to the sector's data (`comm_d`).
+/// * `prover_id` - the prover-id that sealed this sector.
+/// * `sector_id` - this sector's sector-id.
+/// * `ticket` - the ticket that was used to generate this sector's replica-id.
+/// * `seed` - the seed used to derive the porep challenges.
+/// * `proof` - the porep circuit proof, parsed with the verifying key of `porep_config`.
+#[allow(clippy::too_many_arguments)]
+pub fn verify_seal_parsed(
+    porep_config: PoRepConfig,
+    comm_r_in: Commitment,
+    comm_d_in: Commitment,
+    prover_id: ProverId,
+    sector_id: SectorId,
+    ticket: Ticket,
+    seed: Ticket,
+    proof: &MultiProof<'_, Bls12>,
+) -> Result<bool> {
+    ensure_seal_commitments(porep_config, comm_r_in, comm_d_in)?;
+
+    // Any proof verifies under a key of the prover's choosing, so only the canonical one counts.
+    let verifying_key = get_stacked_verifying_key(porep_config)?;
+    ensure!(
+        *proof.verifying_key == *verifying_key,
+        "seal proof was not parsed with the verifying key of its porep config"
+    );
+
+    verify_parsed_seal_inner(
+        porep_config,
+        comm_r_in,
+        comm_d_in,
+        prover_id,
+        sector_id,
+        ticket,
+        seed,
+        proof,
+        Instant::now(),
+    )
+    .map(|(valid, _)| valid)
+}
+
+/// Like `verify_seal`, but also reports what the verification cost, so that callers can
+/// learn the verification load of each sector size and schedule accordingly.
+///
+/// # Arguments
+///
+/// * `porep_config` - this sector's porep config that contains the number of bytes in this sector.
+/// * `comm_r_in` - commitment to the sector's replica (`comm_r`).
+/// * `comm_d_in` - commitment to the sector's data (`comm_d`).
+/// * `prover_id` - the prover-id that sealed this sector.
+/// * `sector_id` - this sector's sector-id.
+/// * `ticket` - the ticket that was used to generate this sector's replica-id.
+/// * `seed` - the seed used to derive the porep challenges.
+/// * `proof_vec` - the porep circuit proof serialized into a vector of bytes.
+#[allow(clippy::too_many_arguments)]
+pub fn verify_seal_with_metrics(
+    porep_config: PoRepConfig,
+    comm_r_in: Commitment,
+    comm_d_in: Commitment,
+    prover_id: ProverId,
+    sector_id: SectorId,
+    ticket: Ticket,
+    seed: Ticket,
+    proof_vec: &[u8],
+) -> Result<(bool, VerifyMetrics)> {
+    verify_seal_inner(
+        porep_config,
+        comm_r_in,
+        comm_d_in,
+        prover_id,
+        sector_id,
+        ticket,
+        seed,
+        proof_vec,
+        || load_verifying_key_verified(porep_config, None),
+    )
+}
+
+/// Like `verify_seal`, but reads the verifying key from `params_dir` instead of the parameter
+/// cache.
+///
+/// # Arguments
+///
+/// * `porep_config` - this sector's porep config that contains the number of bytes in this sector.
+/// * `params_dir` - the directory holding the verifying key for `porep_config`.
+/// * `comm_r_in` - commitment to the sector's replica (`comm_r`).
+/// * `comm_d_in` - commitment to the sector's data (`comm_d`).
+/// * `prover_id` - the prover-id that sealed this sector.
+/// * `sector_id` - this sector's sector-id.
+/// * `ticket` - the ticket that was used to generate this sector's replica-id.
+/// * `seed` - the seed used to derive the porep challenges.
+/// * `proof_vec` - the porep circuit proof serialized into a vector of bytes.
+#[allow(clippy::too_many_arguments)]
+pub fn verify_seal_with_params_dir(
+    porep_config: PoRepConfig,
+    params_dir: &Path,
+    comm_r_in: Commitment,
+    comm_d_in: Commitment,
+    prover_id: ProverId,
+    sector_id: SectorId,
+    ticket: Ticket,
+    seed: Ticket,
+    proof_vec: &[u8],
+) -> Result<bool> {
+    verify_seal_inner(
+        porep_config,
+        comm_r_in,
+        comm_d_in,
+        prover_id,
+        sector_id,
+        ticket,
+        seed,
+        proof_vec,
+        || get_stacked_verifying_key_from_dir(porep_config, params_dir),
+    )
+    .map(|(valid, _)| valid)
+}
+
+/// Like `verify_seal`, but reads the verifying key from `vk_bytes` (as written by
+/// `groth16::VerifyingKey::write`) instead of the parameter cache, so that verification
+/// does not touch the filesystem.
+///
+/// # Arguments
+///
+/// * `porep_config` - this sector's porep config that contains the number of bytes in this sector.
+/// * `vk_bytes` - the serialized verifying key for `porep_config`.
+/// * `comm_r_in` - commitment to the sector's replica (`comm_r`).
+/// * `comm_d_in` - commitment to the sector's data (`comm_d`).
+/// * `prover_id` - the prover-id that sealed this sector.
+/// * `sector_id` - this sector's sector-id.
+/// * `ticket` - the ticket that was used to generate this sector's replica-id.
+/// * `seed` - the seed used to derive the porep challenges.
+/// * `proof_vec` - the porep circuit proof serialized into a vector of bytes.
+#[allow(clippy::too_many_arguments)]
+pub fn verify_seal_with_vk_bytes(
+    porep_config: PoRepConfig,
+    vk_bytes: &[u8],
+    comm_r_in: Commitment,
+    comm_d_in: Commitment,
+    prover_id: ProverId,
+    sector_id: SectorId,
+    ticket: Ticket,
+    seed: Ticket,
+    proof_vec: &[u8],
+) -> Result<bool> {
+    verify_seal_inner(
         porep_config,
-        comm_r,
-        comm_d,
+        comm_r_in,
+        comm_d_in,
         prover_id,
         sector_id,
         ticket,
         seed,
-        &buf,
+        proof_vec,
+        || {
+            let verifying_key =
+                Bls12VerifyingKey::read(vk_bytes).context("could not deserialize verifying key")?;
+            Ok(Arc::new(verifying_key))
+        },
     )
-    .context("post-seal verification sanity check failed")?;
-
-    println!("seal_commit_phase2:end");
-    println!("Time Passed = {:?}", std::time::SystemTime::now().duration_since(sys_time));
-    Ok(SealCommitOutput { proof: buf })
-}
-
-/// Computes a sectors's `comm_d` given its pieces.
-///
-/// # Arguments
-///
-/// * `porep_config` - this sector's porep config that contains the number of bytes in the sector.
-/// * `piece_infos` - the piece info (commitment and byte length) for each piece in this sector.
-pub fn compute_comm_d(sector_size: SectorSize, piece_infos: &[PieceInfo]) -> Result<Commitment> {
-    pieces::compute_comm_d(sector_size, piece_infos)
+    .map(|(valid, _)| valid)
 }
 
-/// Verifies the output of some previously-run seal operation.
+/// Like `verify_seal`, but accepts the proof if it verifies under any of `vks`, e.g. under
+/// either the old or the new groth parameters during a parameter upgrade. A key that fails to
+/// verify the proof, or that does not fit the circuit at all, is skipped.
 ///
 /// # Arguments
 ///
@@ -583,8 +1939,9 @@ pub fn compute_comm_d(sector_size: SectorSize, piece_infos: &[PieceInfo]) -> Res
 /// * `ticket` - the ticket that was used to generate this sector's replica-id.
 /// * `seed` - the seed used to derive the porep challenges.
 /// * `proof_vec` - the porep circuit proof serialized into a vector of bytes.
+/// * `[vks]` - the candidate verifying keys for `porep_config`, tried in order.
 #[allow(clippy::too_many_arguments)]
-pub fn verify_seal(
+pub fn verify_seal_multi_vk(
     porep_config: PoRepConfig,
     comm_r_in: Commitment,
     comm_d_in: Commitment,
@@ -593,14 +1950,110 @@ pub fn verify_seal(
     ticket: Ticket,
     seed: Ticket,
     proof_vec: &[u8],
+    vks: &[&groth16::VerifyingKey<Bls12>],
 ) -> Result<bool> {
-
-    println!("seal verify_seal start");
-
+    ensure!(!vks.is_empty(), "no verifying keys given");
+    // Reject malformed inputs up front, as they are invalid under every key.
     ensure!(comm_d_in != [0; 32], "Invalid all zero commitment (comm_d)");
     ensure!(comm_r_in != [0; 32], "Invalid all zero commitment (comm_r)");
+    ensure_seal_proof_len(porep_config, proof_vec)?;
+
+    for (i, vk) in vks.iter().enumerate() {
+        let verified = verify_seal_inner(
+            porep_config,
+            comm_r_in,
+            comm_d_in,
+            prover_id,
+            sector_id,
+            ticket,
+            seed,
+            proof_vec,
+            || Ok(Arc::new((*vk).clone())),
+        );
+        match verified {
+            Ok((true, _)) => return Ok(true),
+            Ok((false, _)) => info!("seal proof does not verify under verifying key {}", i),
+            Err(err) => info!(
+                "could not verify seal proof with verifying key {}: {}",
+                i, err
+            ),
+        }
+    }
+
+    Ok(false)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn verify_seal_inner<F>(
+    porep_config: PoRepConfig,
+    comm_r_in: Commitment,
+    comm_d_in: Commitment,
+    prover_id: ProverId,
+    sector_id: SectorId,
+    ticket: Ticket,
+    seed: Ticket,
+    proof_vec: &[u8],
+    load_verifying_key: F,
+) -> Result<(bool, VerifyMetrics)>
+where
+    F: FnOnce() -> Result<Arc<Bls12VerifyingKey>>,
+{
+    ensure_seal_commitments(porep_config, comm_r_in, comm_d_in)?;
+    ensure_seal_proof_len(porep_config, proof_vec)?;
+
+    let setup_start = Instant::now();
+
+    let verifying_key = load_verifying_key()?;
+
+    trace!(
+        "got verifying key ({}) while verifying seal",
+        u64::from(PaddedBytesAmount::from(porep_config))
+    );
+
+    let partitioncount = usize::from(PoRepProofPartitions::from(porep_config));
+    let proof = MultiProof::new_from_reader(
+        Some(partitioncount),
+        proof_vec,
+        &verifying_key,
+    )?;
+
+    verify_parsed_seal_inner(
+        porep_config,
+        comm_r_in,
+        comm_d_in,
+        prover_id,
+        sector_id,
+        ticket,
+        seed,
+        &proof,
+        setup_start,
+    )
+}
+
+/// Verifies an already parsed seal proof. `setup_start` is when the caller started preparing
+/// the verification, so that the reported setup time includes parsing the proof.
+#[allow(clippy::too_many_arguments)]
+fn verify_parsed_seal_inner(
+    porep_config: PoRepConfig,
+    comm_r_in: Commitment,
+    comm_d_in: Commitment,
+    prover_id: ProverId,
+    sector_id: SectorId,
+    ticket: Ticket,
+    seed: Ticket,
+    proof: &MultiProof<'_, Bls12>,
+    setup_start: Instant,
+) -> Result<(bool, VerifyMetrics)> {
+    ensure_partition_count(porep_config, proof)?;
+
+    // Every partition's circuit has one input per verifying key `ic` element but the first.
+    let inputs_per_partition = proof
+        .verifying_key
+        .ic
+        .len()
+        .checked_sub(1)
+        .context("invalid verifying key: no ic elements")?;
 
-    let sector_bytes = PaddedBytesAmount::from(porep_config);
     let comm_r = as_safe_commitment(&comm_r_in, "comm_r")?;
     let comm_d = as_safe_commitment(&comm_d_in, "comm_d")?;
 
@@ -608,10 +2061,7 @@ pub fn verify_seal(
         generate_replica_id::<DefaultTreeHasher, _>(&prover_id, sector_id.into(), &ticket, comm_d);
 
     let compound_setup_params = compound_proof::SetupParams {
-        vanilla_params: setup_params(
-            PaddedBytesAmount::from(porep_config),
-            usize::from(PoRepProofPartitions::from(porep_config)),
-        )?,
+        vanilla_params: porep_setup_params(porep_config)?,
         partitions: Some(usize::from(PoRepProofPartitions::from(porep_config))),
         priority: false,
     };
@@ -631,29 +2081,16 @@ pub fn verify_seal(
         k: None,
     };
 
-    let verifying_key = get_stacked_verifying_key(porep_config)?;
-    println!("verifying_key = {:?}",verifying_key);
-
-    println!(
-        "got verifying key ({}) while verifying seal",
-        u64::from(sector_bytes)
-    );
-
     let partitioncount = usize::from(PoRepProofPartitions::from(porep_config));
-    println!("partitioncount = {:?}",partitioncount);
-    let proof = MultiProof::new_from_reader(
-        Some(partitioncount),
-        proof_vec,
-        &verifying_key,
-    )?;
+    let setup_duration = setup_start.elapsed();
 
     println!("StackedCompound::verify");
 
-
-    StackedCompound::verify(
+    let verify_start = Instant::now();
+    let valid = StackedCompound::verify(
         &compound_public_params,
         &public_inputs,
-        &proof,
+        proof,
         &ChallengeRequirements {
             minimum_challenges: *POREP_MINIMUM_CHALLENGES
                 .read()
@@ -661,8 +2098,87 @@ pub fn verify_seal(
                 .get(&u64::from(SectorSize::from(porep_config)))
                 .expect("unknown sector size") as usize,
         },
-    )
-    .map_err(Into::into)
+    )?;
+    let verify_duration = verify_start.elapsed();
+
+    let metrics = VerifyMetrics {
+        public_inputs: inputs_per_partition * partitioncount,
+        partitions: partitioncount,
+        setup_duration,
+        verify_duration,
+    };
+
+    Ok((valid, metrics))
+}
+
+fn ensure_seal_commitments(
+    porep_config: PoRepConfig,
+    comm_r_in: Commitment,
+    comm_d_in: Commitment,
+) -> Result<()> {
+    ensure!(
+        porep_config.sector_size.is_valid(),
+        "invalid sector size: {} is not a registered power of two",
+        u64::from(porep_config.sector_size)
+    );
+    ensure!(comm_d_in != [0; 32], "Invalid all zero commitment (comm_d)");
+    ensure!(comm_r_in != [0; 32], "Invalid all zero commitment (comm_r)");
+
+    Ok(())
+}
+
+/// Seal proofs are untrusted input, so reject any that do not hold exactly one groth proof
+/// per partition, after the format version byte if any, before parsing them. Trailing bytes
+/// would otherwise be silently ignored.
+fn ensure_seal_proof_len(porep_config: PoRepConfig, proof_vec: &[u8]) -> Result<()> {
+    let version_len = if is_versioned(proof_vec) { 1 } else { 0 };
+    let expected = version_len
+        + SINGLE_PARTITION_PROOF_LEN * usize::from(PoRepProofPartitions::from(porep_config));
+    ensure!(
+        proof_vec.len() == expected,
+        "invalid seal proof length: expected {} bytes, got {}",
+        expected,
+        proof_vec.len()
+    );
+
+    Ok(())
+}
+
+/// Checks that `SINGLE_PARTITION_PROOF_LEN` is the serialized size of a groth proof, by
+/// serializing a dummy one. Proof buffers are sized and validated with this constant, so a
+/// change to the proof serialization must fail loudly instead of producing malformed proofs.
+pub fn validate_proof_len_constant() -> Result<()> {
+    let proof = groth16::Proof::<Bls12> {
+        a: G1Affine::one(),
+        b: G2Affine::one(),
+        c: G1Affine::one(),
+    };
+    let mut buf = Vec::with_capacity(SINGLE_PARTITION_PROOF_LEN);
+    proof.write(&mut buf)?;
+    ensure!(
+        buf.len() == SINGLE_PARTITION_PROOF_LEN,
+        "SINGLE_PARTITION_PROOF_LEN is {} bytes, but a groth proof serializes to {}",
+        SINGLE_PARTITION_PROOF_LEN,
+        buf.len()
+    );
+
+    Ok(())
+}
+
+/// A parsed seal proof must hold exactly one groth proof per partition of `porep_config`.
+pub(crate) fn ensure_partition_count(
+    porep_config: PoRepConfig,
+    proof: &MultiProof<Bls12>,
+) -> Result<()> {
+    let expected = usize::from(PoRepProofPartitions::from(porep_config));
+    ensure!(
+        proof.partition_count() == expected,
+        "invalid seal proof partition count: expected {}, got {}",
+        expected,
+        proof.partition_count()
+    );
+
+    Ok(())
 }
 
 /// Verifies a batch of outputs of some previously-run seal operations.
@@ -688,14 +2204,73 @@ pub fn verify_batch_seal(
     seeds: &[Ticket],
     proof_vecs: &[&[u8]],
 ) -> Result<bool> {
-    ensure!(!comm_r_ins.is_empty(), "Cannot prove empty batch");
     let l = comm_r_ins.len();
-    ensure!(l == comm_d_ins.len(), "Inconsistent inputs");
     ensure!(l == prover_ids.len(), "Inconsistent inputs");
-    ensure!(l == prover_ids.len(), "Inconsistent inputs");
-    ensure!(l == sector_ids.len(), "Inconsistent inputs");
     ensure!(l == tickets.len(), "Inconsistent inputs");
     ensure!(l == seeds.len(), "Inconsistent inputs");
+
+    verify_batch_seal_inner(
+        porep_config,
+        comm_r_ins,
+        comm_d_ins,
+        sector_ids,
+        proof_vecs,
+        |i| (prover_ids[i], tickets[i], seeds[i]),
+    )
+}
+
+/// Like `verify_batch_seal`, for a batch whose sectors were all sealed by the same prover
+/// with the same ticket and seed, e.g. the batch of a single miner. The shared values are
+/// used for every sector instead of being repeated in full-length lists.
+///
+/// # Arguments
+///
+/// * `porep_config` - this sector's porep config that contains the number of bytes in this sector.
+/// * `[comm_r_ins]` - list of commitments to the sector's replica (`comm_r`).
+/// * `[comm_d_ins]` - list of commitments to the sector's data (`comm_d`).
+/// * `[sector_ids]` - list of the sector's sector-id.
+/// * `prover_id` - the prover-id that sealed all sectors.
+/// * `ticket` - the ticket that was used to generate every sector's replica-id.
+/// * `seed` - the seed used to derive the porep challenges of every sector.
+/// * `[proof_vecs]` - list of porep circuit proofs serialized into a vector of bytes.
+#[allow(clippy::too_many_arguments)]
+pub fn verify_batch_seal_common(
+    porep_config: PoRepConfig,
+    comm_r_ins: &[Commitment],
+    comm_d_ins: &[Commitment],
+    sector_ids: &[SectorId],
+    prover_id: ProverId,
+    ticket: Ticket,
+    seed: Ticket,
+    proof_vecs: &[&[u8]],
+) -> Result<bool> {
+    verify_batch_seal_inner(
+        porep_config,
+        comm_r_ins,
+        comm_d_ins,
+        sector_ids,
+        proof_vecs,
+        |_| (prover_id, ticket, seed),
+    )
+}
+
+/// Verifies a batch of seals, taking the prover-id, ticket and seed of the `i`th sector from
+/// `sealed_with(i)`.
+fn verify_batch_seal_inner<F>(
+    porep_config: PoRepConfig,
+    comm_r_ins: &[Commitment],
+    comm_d_ins: &[Commitment],
+    sector_ids: &[SectorId],
+    proof_vecs: &[&[u8]],
+    sealed_with: F,
+) -> Result<bool>
+where
+    F: Fn(usize) -> (ProverId, Ticket, Ticket),
+{
+    ensure!(!comm_r_ins.is_empty(), "Cannot prove empty batch");
+    let l = comm_r_ins.len();
+    ensure!(l == comm_d_ins.len(), "Inconsistent inputs");
+    ensure!(l == sector_ids.len(), "Inconsistent inputs");
     ensure!(l == proof_vecs.len(), "Inconsistent inputs");
 
     for comm_d_in in comm_d_ins {
@@ -710,50 +2285,35 @@ pub fn verify_batch_seal(
             "Invalid all zero commitment (comm_r)"
         );
     }
-
-    let sector_bytes = PaddedBytesAmount::from(porep_config);
+    for proof_vec in proof_vecs {
+        ensure_seal_proof_len(porep_config, proof_vec)?;
+    }
 
     let verifying_key = get_stacked_verifying_key(porep_config)?;
     info!(
         "got verifying key ({}) while verifying seal",
-        u64::from(sector_bytes)
+        u64::from(PaddedBytesAmount::from(porep_config))
     );
 
-    let compound_setup_params = compound_proof::SetupParams {
-        vanilla_params: setup_params(
-            PaddedBytesAmount::from(porep_config),
-            usize::from(PoRepProofPartitions::from(porep_config)),
-        )?,
-        partitions: Some(usize::from(PoRepProofPartitions::from(porep_config))),
-        priority: false,
-    };
-
-    let compound_public_params: compound_proof::PublicParams<
-        '_,
-        StackedDrg<'_, DefaultTreeHasher, DefaultPieceHasher>,
-    > = StackedCompound::setup(&compound_setup_params)?;
-
     let mut public_inputs = Vec::with_capacity(l);
     let mut proofs = Vec::with_capacity(l);
 
     for i in 0..l {
         let comm_r = as_safe_commitment(&comm_r_ins[i], "comm_r")?;
         let comm_d = as_safe_commitment(&comm_d_ins[i], "comm_d")?;
+        let (prover_id, ticket, seed) = sealed_with(i);
 
         let replica_id = generate_replica_id::<DefaultTreeHasher, _>(
-            &prover_ids[i],
+            &prover_id,
             sector_ids[i].into(),
-            &tickets[i],
+            &ticket,
             comm_d,
         );
 
-        public_inputs.push(stacked::PublicInputs::<
-            <DefaultTreeHasher as Hasher>::Domain,
-            <DefaultPieceHasher as Hasher>::Domain,
-        > {
+        public_inputs.push(stacked::PublicInputs {
             replica_id,
             tau: Some(Tau { comm_r, comm_d }),
-            seed: seeds[i],
+            seed,
             k: None,
         });
         proofs.push(MultiProof::new_from_reader(
@@ -763,10 +2323,47 @@ pub fn verify_batch_seal(
         )?);
     }
 
+    verify_batch_seal_with_inputs(porep_config, &public_inputs, &proofs)
+}
+
+/// Like `verify_batch_seal`, but takes the already constructed public inputs and parsed
+/// proofs of the seals, e.g. kept from sealing, instead of re-deriving them from the
+/// commitments and identifiers.
+///
+/// # Arguments
+///
+/// * `porep_config` - this sector's porep config that contains the number of bytes in this sector.
+/// * `[public_inputs]` - list of the public inputs of each seal.
+/// * `[proofs]` - list of porep circuit proofs, in the same order as `public_inputs`.
+pub fn verify_batch_seal_with_inputs(
+    porep_config: PoRepConfig,
+    public_inputs: &[stacked::PublicInputs<
+        <DefaultTreeHasher as Hasher>::Domain,
+        <DefaultPieceHasher as Hasher>::Domain,
+    >],
+    proofs: &[MultiProof<Bls12>],
+) -> Result<bool> {
+    ensure!(!public_inputs.is_empty(), "Cannot prove empty batch");
+    ensure!(public_inputs.len() == proofs.len(), "Inconsistent inputs");
+    for proof in proofs {
+        ensure_partition_count(porep_config, proof)?;
+    }
+
+    let compound_setup_params = compound_proof::SetupParams {
+        vanilla_params: porep_setup_params(porep_config)?,
+        partitions: Some(usize::from(PoRepProofPartitions::from(porep_config))),
+        priority: false,
+    };
+
+    let compound_public_params: compound_proof::PublicParams<
+        '_,
+        StackedDrg<'_, DefaultTreeHasher, DefaultPieceHasher>,
+    > = StackedCompound::setup(&compound_setup_params)?;
+
     StackedCompound::batch_verify(
         &compound_public_params,
-        &public_inputs,
-        &proofs,
+        public_inputs,
+        proofs,
         &ChallengeRequirements {
             minimum_challenges: *POREP_MINIMUM_CHALLENGES
                 .read()
@@ -777,3 +2374,195 @@ pub fn verify_batch_seal(
     )
     .map_err(Into::into)
 }
+
+/// Verifies the outputs of previously-run seal operations which may use different
+/// `PoRepConfig`s, e.g. a mix of sector sizes. The items are grouped by config and each group
+/// is verified with `verify_batch_seal_find_invalid`, so each verifying key is loaded once.
+/// Returns whether each item's proof is valid, in the order of `items`.
+///
+/// # Arguments
+///
+/// * `items` - the `(porep_config, comm_r, comm_d, prover_id, sector_id, ticket, seed, proof)`
+/// of each seal operation, as passed to `verify_seal`.
+#[allow(clippy::type_complexity)]
+pub fn verify_seals_grouped(
+    items: &[(
+        PoRepConfig,
+        Commitment,
+        Commitment,
+        ProverId,
+        SectorId,
+        Ticket,
+        Ticket,
+        Vec<u8>,
+    )],
+) -> Result<Vec<bool>> {
+    // PoRepConfig is not comparable, so group by the fields that identify it.
+    let mut groups: Vec<((u64, u8, Option<[u8; 28]>), PoRepConfig, Vec<usize>)> = Vec::new();
+    for (i, item) in items.iter().enumerate() {
+        let config = item.0;
+        let key = (
+            u64::from(config.sector_size),
+            config.partitions.0,
+            config.graph_seed,
+        );
+        match groups.iter_mut().find(|group| group.0 == key) {
+            Some(group) => group.2.push(i),
+            None => groups.push((key, config, vec![i])),
+        }
+    }
+
+    let mut valid = vec![true; items.len()];
+    for (_, porep_config, indices) in groups {
+        let comm_r_ins: Vec<Commitment> = indices.iter().map(|&i| items[i].1).collect();
+        let comm_d_ins: Vec<Commitment> = indices.iter().map(|&i| items[i].2).collect();
+        let prover_ids: Vec<ProverId> = indices.iter().map(|&i| items[i].3).collect();
+        let sector_ids: Vec<SectorId> = indices.iter().map(|&i| items[i].4).collect();
+        let tickets: Vec<Ticket> = indices.iter().map(|&i| items[i].5).collect();
+        let seeds: Vec<Ticket> = indices.iter().map(|&i| items[i].6).collect();
+        let proof_vecs: Vec<&[u8]> = indices.iter().map(|&i| &items[i].7[..]).collect();
+
+        let invalid = verify_batch_seal_find_invalid(
+            porep_config,
+            &comm_r_ins,
+            &comm_d_ins,
+            &prover_ids,
+            &sector_ids,
+            &tickets,
+            &seeds,
+            &proof_vecs,
+        )?;
+        for k in invalid {
+            valid[indices[k]] = false;
+        }
+    }
+
+    Ok(valid)
+}
+
+/// Verifies each of a batch of outputs of some previously-run seal operations individually
+/// (in parallel) and returns whether each sector's proof is valid, keyed by `SectorId`. Use
+/// this over `verify_batch_seal` when a failure must be attributed to a sector.
+///
+/// Arguments are the same as for `verify_batch_seal`. The `sector_ids` must be unique.
+#[allow(clippy::too_many_arguments)]
+pub fn verify_batch_seal_map(
+    porep_config: PoRepConfig,
+    comm_r_ins: &[Commitment],
+    comm_d_ins: &[Commitment],
+    prover_ids: &[ProverId],
+    sector_ids: &[SectorId],
+    tickets: &[Ticket],
+    seeds: &[Ticket],
+    proof_vecs: &[&[u8]],
+) -> Result<BTreeMap<SectorId, bool>> {
+    let l = comm_r_ins.len();
+    ensure!(
+        [
+            comm_d_ins.len(),
+            prover_ids.len(),
+            sector_ids.len(),
+            tickets.len(),
+            seeds.len(),
+            proof_vecs.len(),
+        ]
+        .iter()
+        .all(|&len| len == l),
+        "Inconsistent inputs"
+    );
+
+    let results: BTreeMap<SectorId, bool> = (0..l)
+        .into_par_iter()
+        .map(|i| {
+            let valid = verify_seal(
+                porep_config,
+                comm_r_ins[i],
+                comm_d_ins[i],
+                prover_ids[i],
+                sector_ids[i],
+                tickets[i],
+                seeds[i],
+                proof_vecs[i],
+            );
+            // A proof which cannot even be checked is not a valid one.
+            (sector_ids[i], valid.unwrap_or(false))
+        })
+        .collect::<Vec<_>>()
+        .into_iter()
+        .collect();
+    ensure!(results.len() == l, "Duplicate sector ids in batch");
+
+    Ok(results)
+}
+
+/// Verifies a batch of outputs of some previously-run seal operations and returns the
+/// indices of the proofs which failed to verify. The batch is verified at once first; only
+/// if that fails is each proof verified individually (in parallel), since batch verification
+/// cannot attribute a failure to a single proof. This allows re-proving just the bad sectors.
+///
+/// Arguments are the same as for `verify_batch_seal`.
+#[allow(clippy::too_many_arguments)]
+pub fn verify_batch_seal_find_invalid(
+    porep_config: PoRepConfig,
+    comm_r_ins: &[Commitment],
+    comm_d_ins: &[Commitment],
+    prover_ids: &[ProverId],
+    sector_ids: &[SectorId],
+    tickets: &[Ticket],
+    seeds: &[Ticket],
+    proof_vecs: &[&[u8]],
+) -> Result<Vec<usize>> {
+    let l = comm_r_ins.len();
+    ensure!(
+        [
+            comm_d_ins.len(),
+            prover_ids.len(),
+            sector_ids.len(),
+            tickets.len(),
+            seeds.len(),
+            proof_vecs.len(),
+        ]
+        .iter()
+        .all(|&len| len == l),
+        "Inconsistent inputs"
+    );
+
+    let batch_valid = verify_batch_seal(
+        porep_config,
+        comm_r_ins,
+        comm_d_ins,
+        prover_ids,
+        sector_ids,
+        tickets,
+        seeds,
+        proof_vecs,
+    );
+
+    if let Ok(true) = batch_valid {
+        return Ok(Vec::new());
+    }
+
+    info!("batch seal verification failed, verifying proofs individually");
+
+    let invalid = (0..comm_r_ins.len())
+        .into_par_iter()
+        .filter(|&i| {
+            let valid = verify_seal(
+                porep_config,
+                comm_r_ins[i],
+                comm_d_ins[i],
+                prover_ids[i],
+                sector_ids[i],
+                tickets[i],
+                seeds[i],
+                proof_vecs[i],
+            );
+            match valid {
+                Ok(valid) => !valid,
+                Err(_) => true,
+            }
+        })
+        .collect();
+
+    Ok(invalid)
+}
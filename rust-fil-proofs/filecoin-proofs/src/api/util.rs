@@ -1,8 +1,12 @@
-use anyhow::{Context, Result};
+use std::env;
+use std::path::PathBuf;
+
+use anyhow::{ensure, Context, Result};
 use paired::bls12_381::Bls12;
 use paired::Engine;
 use storage_proofs::fr32::{bytes_into_fr, fr_into_bytes};
 use storage_proofs::hasher::Domain;
+use storage_proofs::settings;
 
 use crate::types::{Commitment, SectorSize};
 
@@ -23,15 +27,68 @@ pub(crate) fn commitment_from_fr<E: Engine>(fr: E::Fr) -> Commitment {
     commitment
 }
 
-pub(crate) fn get_tree_size<D: Domain>(sector_size: SectorSize) -> usize {
-    let sector_size = u64::from(sector_size);
-    let elems = sector_size as usize / D::byte_len();
+/// The number of nodes of a merkle tree of `D` elements over a sector of `sector_size`.
+/// Fails unless the sector holds a power of two number of elements; see `get_tree_leafs`.
+pub(crate) fn get_tree_size<D: Domain>(sector_size: SectorSize) -> Result<usize> {
+    let elems = get_tree_leafs::<D>(sector_size)?;
 
-    2 * elems - 1
+    Ok(2 * elems - 1)
 }
 
-pub(crate) fn get_tree_leafs<D: Domain>(sector_size: SectorSize) -> usize {
+/// The number of leaves of a merkle tree of `D` elements over a sector of `sector_size`.
+/// Fails unless the sector size is a multiple of the element size and holds a power of two
+/// number of elements, rather than rounding to a tree which doesn't cover the sector.
+pub(crate) fn get_tree_leafs<D: Domain>(sector_size: SectorSize) -> Result<usize> {
     let sector_size = u64::from(sector_size);
+    let elem_size = D::byte_len() as u64;
+    ensure!(
+        sector_size % elem_size == 0,
+        "sector size {} is not a multiple of the tree element size {}",
+        sector_size,
+        elem_size
+    );
 
-    sector_size as usize / D::byte_len()
+    let leafs = sector_size / elem_size;
+    ensure!(
+        leafs.is_power_of_two(),
+        "sector size {} holds {} tree elements, which is not a power of two",
+        sector_size,
+        leafs
+    );
+
+    Ok(leafs as usize)
+}
+
+/// Returns the directory the seal pipeline creates its intermediate files in: the configured
+/// `seal_temp_dir` (`FIL_PROOFS_SEAL_TEMP_DIR`) or, if that is unset, the system temp directory.
+pub(crate) fn seal_temp_dir() -> PathBuf {
+    let dir = settings::SETTINGS.lock().unwrap().seal_temp_dir.clone();
+    if dir.is_empty() {
+        env::temp_dir()
+    } else {
+        PathBuf::from(dir)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use storage_proofs::hasher::{Hasher, PedersenHasher};
+
+    type PedersenDomain = <PedersenHasher as Hasher>::Domain;
+
+    #[test]
+    fn test_get_tree_leafs_and_size() {
+        let sector_size = SectorSize(1024);
+        assert_eq!(get_tree_leafs::<PedersenDomain>(sector_size).unwrap(), 32);
+        assert_eq!(get_tree_size::<PedersenDomain>(sector_size).unwrap(), 63);
+
+        // Not a multiple of the 32 byte elements.
+        let err = get_tree_leafs::<PedersenDomain>(SectorSize(1000)).unwrap_err();
+        assert!(err.to_string().contains("not a multiple"));
+        // A multiple of the element size, but 48 leaves.
+        let err = get_tree_size::<PedersenDomain>(SectorSize(1536)).unwrap_err();
+        assert!(err.to_string().contains("not a power of two"));
+    }
 }
@@ -164,6 +164,7 @@ pub fn main() {
             challenge_count: POST_CHALLENGE_COUNT,
             challenged_nodes: POST_CHALLENGED_NODES,
             priority: true,
+            read_chunk_bytes: None,
         });
 
         if !only_election_post {
@@ -176,6 +177,7 @@ pub fn main() {
                         .get(&sector_size)
                         .expect("missing sector size"),
                 ),
+                graph_seed: None,
             });
         }
     }
@@ -1,9 +1,13 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsStr;
+use std::fs::File;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::sync::Mutex;
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use bellperson::groth16;
+use blake2b_simd::Params as Blake2b;
 use lazy_static::lazy_static;
 use log::info;
 use paired::bls12_381::Bls12;
@@ -12,9 +16,11 @@ use storage_proofs::circuit::election_post::ElectionPoStCompound;
 use storage_proofs::circuit::stacked::StackedCompound;
 use storage_proofs::compound_proof::CompoundProof;
 use storage_proofs::election_post::ElectionPoSt;
+use storage_proofs::error::Error;
 use storage_proofs::stacked::StackedDrg;
 
-use crate::constants::{DefaultPieceHasher, DefaultTreeHasher};
+use crate::constants::{DefaultPieceHasher, DefaultTreeHasher, PARAMETERS};
+use crate::param::{get_digest_for_file_within_cache, get_full_path_for_file_within_cache};
 use crate::parameters::{post_public_params, public_params};
 use crate::types::*;
 
@@ -28,6 +34,9 @@ type VerifyingKeyMemCache = Cache<Bls12VerifyingKey>;
 lazy_static! {
     static ref GROTH_PARAM_MEMORY_CACHE: Mutex<GrothMemCache> = Default::default();
     static ref VERIFYING_KEY_MEMORY_CACHE: Mutex<VerifyingKeyMemCache> = Default::default();
+    /// Parameter files `ensure_param_file_verified` already checked, with the hex digest they
+    /// were checked against.
+    static ref VERIFIED_PARAM_FILES: Mutex<HashSet<(PathBuf, String)>> = Default::default();
 }
 
 pub fn cache_lookup<F, G>(
@@ -84,31 +93,173 @@ where
 }
 
 pub fn get_stacked_params(porep_config: PoRepConfig) -> Result<Arc<Bls12GrothParams>> {
-    
+    stacked_params(porep_config, None)
+}
+
+/// Like `get_stacked_params`, but reads the parameters from `dir` instead of the parameter
+/// cache, generating them there if they are missing.
+pub fn get_stacked_params_from_dir(
+    porep_config: PoRepConfig,
+    dir: &Path,
+) -> Result<Arc<Bls12GrothParams>> {
+    stacked_params(porep_config, Some(dir))
+}
+
+fn stacked_params(porep_config: PoRepConfig, dir: Option<&Path>) -> Result<Arc<Bls12GrothParams>> {
     let public_params = public_params(
         PaddedBytesAmount::from(porep_config),
         usize::from(PoRepProofPartitions::from(porep_config)),
     )?;
-  
+
     let parameters_generator = || {
-        <StackedCompound<DefaultTreeHasher, DefaultPieceHasher> as CompoundProof<
-            _,
-            StackedDrg<DefaultTreeHasher, DefaultPieceHasher>,
-            _,
-        >>::groth_params(&public_params)
+        match dir {
+            Some(dir) => {
+                <StackedCompound<DefaultTreeHasher, DefaultPieceHasher> as CompoundProof<
+                    _,
+                    StackedDrg<DefaultTreeHasher, DefaultPieceHasher>,
+                    _,
+                >>::groth_params_from_dir(&public_params, dir)
+            }
+            None => <StackedCompound<DefaultTreeHasher, DefaultPieceHasher> as CompoundProof<
+                _,
+                StackedDrg<DefaultTreeHasher, DefaultPieceHasher>,
+                _,
+            >>::groth_params(&public_params),
+        }
         .map_err(Into::into)
     };
-    
+
     println!("lookup_groth_params:start");
     Ok(lookup_groth_params(
-        format!(
-            "STACKED[{}]",
-            usize::from(PaddedBytesAmount::from(porep_config))
-        ),
+        stacked_identifier(porep_config, dir),
         parameters_generator,
     )?)
 }
 
+/// Like `get_stacked_params`, but first checks the parameter file against a digest, so that a
+/// corrupted file is rejected with `ParamDigestMismatch` instead of producing invalid proofs.
+///
+/// The file is checked against `expected_digest`, the blake2b-256 digest of the whole file as
+/// computed by `param_file_digest`, if supplied, and else against the digest `parameters.json`
+/// ships for it. Files `parameters.json` does not list, like the parameters of the test sector
+/// sizes, are only checked against a supplied digest.
+///
+/// Each file is hashed once per digest it is checked against, which takes about as long as
+/// reading it, before its parameters are loaded.
+pub fn load_params_verified(
+    porep_config: PoRepConfig,
+    expected_digest: Option<[u8; 32]>,
+) -> Result<Arc<Bls12GrothParams>> {
+    ensure_param_file_verified(&porep_config.get_cache_params_path()?, expected_digest)?;
+
+    get_stacked_params(porep_config)
+}
+
+/// Like `load_params_verified`, but for the verifying key.
+pub fn load_verifying_key_verified(
+    porep_config: PoRepConfig,
+    expected_digest: Option<[u8; 32]>,
+) -> Result<Arc<Bls12VerifyingKey>> {
+    ensure_param_file_verified(
+        &porep_config.get_cache_verifying_key_path()?,
+        expected_digest,
+    )?;
+
+    get_stacked_verifying_key(porep_config)
+}
+
+/// Checks the file at `path` in the parameter cache as described in `load_params_verified`,
+/// unless it already passed the same check. A missing file is generated when loaded, so there
+/// is nothing to check yet.
+fn ensure_param_file_verified(path: &Path, expected_digest: Option<[u8; 32]>) -> Result<()> {
+    let filename = path
+        .file_name()
+        .and_then(OsStr::to_str)
+        .with_context(|| format!("invalid parameter file path={:?}", path))?;
+    let expected = match expected_digest {
+        Some(digest) => hex::encode(digest),
+        None => match PARAMETERS.get(filename) {
+            Some(data) => data.digest.clone(),
+            None => return Ok(()),
+        },
+    };
+    if !path.exists() {
+        return Ok(());
+    }
+
+    let mut verified = VERIFIED_PARAM_FILES.lock().unwrap();
+    let checked = (path.to_path_buf(), expected);
+    if verified.contains(&checked) {
+        return Ok(());
+    }
+    match expected_digest {
+        Some(digest) => ensure_param_file_digest(path, &digest)?,
+        None => check_param_file_digest(filename, &checked.1)?,
+    }
+    verified.insert(checked);
+
+    Ok(())
+}
+
+/// The blake2b-256 digest of the file at `path`.
+pub fn param_file_digest(path: &Path) -> Result<[u8; 32]> {
+    let file = File::open(path).with_context(|| format!("could not open path={:?}", path))?;
+    let data = unsafe {
+        memmap::MmapOptions::new()
+            .map(&file)
+            .with_context(|| format!("could not mmap path={:?}", path))?
+    };
+
+    let mut digest = [0u8; 32];
+    digest.copy_from_slice(Blake2b::new().hash_length(32).hash(&data[..]).as_bytes());
+
+    Ok(digest)
+}
+
+/// Checks that the `param_file_digest` of the file at `path` is `expected`.
+pub(crate) fn ensure_param_file_digest(path: &Path, expected: &[u8; 32]) -> Result<()> {
+    let actual = param_file_digest(path)?;
+    if &actual != expected {
+        return Err(Error::ParamDigestMismatch(
+            path.to_path_buf(),
+            hex::encode(expected),
+            hex::encode(actual),
+        )
+        .into());
+    }
+
+    Ok(())
+}
+
+/// Checks that the digest of the parameter cache file `filename`, as computed by `paramfetch`
+/// and listed in `parameters.json`, is `expected`.
+pub(crate) fn check_param_file_digest(filename: &str, expected: &str) -> Result<()> {
+    let actual = get_digest_for_file_within_cache(filename)?;
+    if actual != expected {
+        return Err(Error::ParamDigestMismatch(
+            get_full_path_for_file_within_cache(filename),
+            expected.to_string(),
+            actual,
+        )
+        .into());
+    }
+
+    Ok(())
+}
+
+/// Parameters read from another directory must not be confused with the cached ones in memory.
+fn stacked_identifier(porep_config: PoRepConfig, dir: Option<&Path>) -> String {
+    let identifier = format!(
+        "STACKED[{}]",
+        usize::from(PaddedBytesAmount::from(porep_config))
+    );
+
+    match dir {
+        Some(dir) => format!("{}@{}", identifier, dir.display()),
+        None => identifier,
+    }
+}
+
 pub fn get_post_params(post_config: PoStConfig) -> Result<Arc<Bls12GrothParams>> {
     let post_public_params = post_public_params(post_config)?;
 
@@ -131,25 +282,47 @@ pub fn get_post_params(post_config: PoStConfig) -> Result<Arc<Bls12GrothParams>>
 }
 
 pub fn get_stacked_verifying_key(porep_config: PoRepConfig) -> Result<Arc<Bls12VerifyingKey>> {
+    stacked_verifying_key(porep_config, None)
+}
+
+/// Like `get_stacked_verifying_key`, but reads the key from `dir` instead of the parameter
+/// cache, generating it there if it is missing.
+pub fn get_stacked_verifying_key_from_dir(
+    porep_config: PoRepConfig,
+    dir: &Path,
+) -> Result<Arc<Bls12VerifyingKey>> {
+    stacked_verifying_key(porep_config, Some(dir))
+}
+
+fn stacked_verifying_key(
+    porep_config: PoRepConfig,
+    dir: Option<&Path>,
+) -> Result<Arc<Bls12VerifyingKey>> {
     let public_params = public_params(
         PaddedBytesAmount::from(porep_config),
         usize::from(PoRepProofPartitions::from(porep_config)),
     )?;
 
     let vk_generator = || {
-        <StackedCompound<DefaultTreeHasher, DefaultPieceHasher> as CompoundProof<
-            Bls12,
-            StackedDrg<DefaultTreeHasher, DefaultPieceHasher>,
-            _,
-        >>::verifying_key(&public_params)
+        match dir {
+            Some(dir) => {
+                <StackedCompound<DefaultTreeHasher, DefaultPieceHasher> as CompoundProof<
+                    Bls12,
+                    StackedDrg<DefaultTreeHasher, DefaultPieceHasher>,
+                    _,
+                >>::verifying_key_from_dir(&public_params, dir)
+            }
+            None => <StackedCompound<DefaultTreeHasher, DefaultPieceHasher> as CompoundProof<
+                Bls12,
+                StackedDrg<DefaultTreeHasher, DefaultPieceHasher>,
+                _,
+            >>::verifying_key(&public_params),
+        }
         .map_err(Into::into)
     };
 
     Ok(lookup_verifying_key(
-        format!(
-            "STACKED[{}]",
-            usize::from(PaddedBytesAmount::from(porep_config))
-        ),
+        stacked_identifier(porep_config, dir),
         vk_generator,
     )?)
 }
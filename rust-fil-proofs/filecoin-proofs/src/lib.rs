@@ -8,11 +8,13 @@ pub mod fr32;
 pub mod param;
 pub mod parameters;
 pub mod pieces;
+pub mod sector_log;
 pub mod serde_big_array;
 pub mod singletons;
 pub mod types;
 
 pub use self::api::*;
+pub use self::caches::{load_params_verified, load_verifying_key_verified, param_file_digest};
 pub use self::constants::SINGLE_PARTITION_PROOF_LEN;
 pub use self::param::{ParameterData, ParameterMap};
 pub use self::types::*;
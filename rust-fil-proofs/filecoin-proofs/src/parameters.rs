@@ -8,7 +8,7 @@ use storage_proofs::stacked::{self, LayerChallenges, StackedDrg};
 use crate::constants::{
     DefaultPieceHasher, DefaultTreeHasher, DRG_DEGREE, EXP_DEGREE, LAYERS, POREP_MINIMUM_CHALLENGES,
 };
-use crate::types::{PaddedBytesAmount, PoStConfig};
+use crate::types::{PaddedBytesAmount, PoRepConfig, PoRepProofPartitions, PoStConfig};
 
 const DRG_SEED: [u8; 28] = [
     0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24, 25,
@@ -28,6 +28,12 @@ pub fn public_params(
     )?)
 }
 
+pub fn porep_public_params(
+    porep_config: PoRepConfig,
+) -> Result<stacked::PublicParams<DefaultTreeHasher>> {
+    StackedDrg::<DefaultTreeHasher, DefaultPieceHasher>::setup(&porep_setup_params(porep_config)?)
+}
+
 pub fn post_public_params(post_config: PoStConfig) -> Result<PostPublicParams> {
     ElectionPoSt::<DefaultTreeHasher>::setup(&post_setup_params(post_config))
 }
@@ -39,12 +45,31 @@ pub fn post_setup_params(post_config: PoStConfig) -> PostSetupParams {
         sector_size: size.into(),
         challenge_count: post_config.challenge_count,
         challenged_nodes: post_config.challenged_nodes,
+        read_chunk_bytes: post_config.read_chunk_bytes,
     }
 }
 
 pub fn setup_params(
     sector_bytes: PaddedBytesAmount,
     partitions: usize,
+) -> Result<stacked::SetupParams> {
+    setup_params_with_seed(sector_bytes, partitions, DRG_SEED)
+}
+
+/// Like `setup_params`, but takes the graph seed from `porep_config`, falling back to the
+/// default seed when none is set.
+pub fn porep_setup_params(porep_config: PoRepConfig) -> Result<stacked::SetupParams> {
+    setup_params_with_seed(
+        PaddedBytesAmount::from(porep_config),
+        usize::from(PoRepProofPartitions::from(porep_config)),
+        porep_config.graph_seed.unwrap_or(DRG_SEED),
+    )
+}
+
+fn setup_params_with_seed(
+    sector_bytes: PaddedBytesAmount,
+    partitions: usize,
+    seed: [u8; 28],
 ) -> Result<stacked::SetupParams> {
     let layer_challenges = select_challenges(
         partitions,
@@ -72,7 +97,7 @@ pub fn setup_params(
         nodes,
         degree: DRG_DEGREE.load(Ordering::Relaxed) as usize,
         expansion_degree: EXP_DEGREE.load(Ordering::Relaxed) as usize,
-        seed: DRG_SEED,
+        seed,
         layer_challenges,
     })
 }
@@ -1,3 +1,4 @@
+use std::cmp::min;
 use std::io::Cursor;
 use std::io::Read;
 use std::iter::Iterator;
@@ -5,6 +6,7 @@ use std::iter::Iterator;
 use anyhow::{ensure, Context, Result};
 use log::info;
 use storage_proofs::hasher::{HashFunction, Hasher};
+use storage_proofs::pieces::generate_piece_commitment_bytes_from_source;
 use storage_proofs::util::NODE_SIZE;
 
 use crate::constants::{
@@ -26,6 +28,109 @@ pub fn verify_pieces(
     Ok(&comm_d_calculated == comm_d)
 }
 
+/// The result of checking a set of `piece_infos` against a sector's `comm_d`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PieceVerifyOutcome {
+    /// The pieces reduce to the expected `comm_d`.
+    Valid,
+    /// There are no pieces, or more than fit into the sector.
+    InvalidPieceCount { count: usize, max: u64 },
+    /// The sum of the padded piece sizes exceeds the sector size.
+    InvalidTotalSize {
+        total: PaddedBytesAmount,
+        sector: PaddedBytesAmount,
+    },
+    /// The piece at `index` has a padded size which is not a power of two.
+    InvalidPieceSize {
+        index: usize,
+        size: PaddedBytesAmount,
+    },
+    /// The commitment of the piece at `index` does not match the subtree of
+    /// the sector data it spans, whose root is `computed`.
+    PieceCommitmentMismatch { index: usize, computed: Commitment },
+    /// Every piece matches the sector data, but the pieces reduce to a
+    /// different commitment than `comm_d`.
+    CommitmentMismatch { computed: Commitment },
+}
+
+impl PieceVerifyOutcome {
+    pub fn is_valid(&self) -> bool {
+        *self == PieceVerifyOutcome::Valid
+    }
+}
+
+/// Verify that the provided `piece_infos` and `comm_d` match, reporting why
+/// they do not if verification fails.
+///
+/// `sector_data` is the staged sector, i.e. the padded piece bytes as written
+/// by `add_piece`; bytes past its end are zeros, as when sealing. Each piece's
+/// commitment is checked against the subtree of `sector_data` it spans, so a
+/// mismatch is attributed to the first piece that does not match.
+pub fn verify_pieces_detailed(
+    comm_d: &Commitment,
+    piece_infos: &[PieceInfo],
+    sector_size: SectorSize,
+    sector_data: &[u8],
+) -> Result<PieceVerifyOutcome> {
+    let unpadded_sector: UnpaddedBytesAmount = sector_size.into();
+    let max_pieces = u64::from(unpadded_sector) / MINIMUM_PIECE_SIZE;
+
+    if piece_infos.is_empty() || piece_infos.len() as u64 > max_pieces {
+        return Ok(PieceVerifyOutcome::InvalidPieceCount {
+            count: piece_infos.len(),
+            max: max_pieces,
+        });
+    }
+
+    let total: u64 = piece_infos
+        .iter()
+        .map(|info| u64::from(PaddedBytesAmount::from(info.size)))
+        .sum();
+
+    if total > u64::from(sector_size) {
+        return Ok(PieceVerifyOutcome::InvalidTotalSize {
+            total: PaddedBytesAmount(total),
+            sector: PaddedBytesAmount::from(sector_size),
+        });
+    }
+
+    for (index, piece_info) in piece_infos.iter().enumerate() {
+        let size = PaddedBytesAmount::from(piece_info.size);
+        if !u64::from(size).is_power_of_two() {
+            return Ok(PieceVerifyOutcome::InvalidPieceSize { index, size });
+        }
+    }
+
+    // Pieces are aligned to their own size, as in `compute_comm_d`.
+    let mut offset = 0;
+    for (index, piece_info) in piece_infos.iter().enumerate() {
+        let size = u64::from(PaddedBytesAmount::from(piece_info.size));
+        offset = (offset + size - 1) / size * size;
+
+        let start = min(offset as usize, sector_data.len());
+        let end = min((offset + size) as usize, sector_data.len());
+        let mut source = (&sector_data[start..end]).chain(std::io::repeat(0));
+        let computed = generate_piece_commitment_bytes_from_source::<DefaultPieceHasher>(
+            &mut source,
+            size as usize,
+        )
+        .with_context(|| format!("could not commit to the data of piece {}", index))?;
+
+        if computed != piece_info.commitment {
+            return Ok(PieceVerifyOutcome::PieceCommitmentMismatch { index, computed });
+        }
+        offset += size;
+    }
+
+    let computed = compute_comm_d(sector_size, piece_infos)?;
+
+    if &computed == comm_d {
+        Ok(PieceVerifyOutcome::Valid)
+    } else {
+        Ok(PieceVerifyOutcome::CommitmentMismatch { computed })
+    }
+}
+
 pub fn compute_comm_d(sector_size: SectorSize, piece_infos: &[PieceInfo]) -> Result<Commitment> {
     info!("verifying {} pieces", piece_infos.len());
     ensure!(!piece_infos.is_empty(), "Missing piece infos");
@@ -83,6 +188,56 @@ pub fn compute_comm_d(sector_size: SectorSize, piece_infos: &[PieceInfo]) -> Res
     Ok(comm_d_calculated)
 }
 
+/// Appends zero-padding pieces to `piece_infos`, so that together with the alignment padding
+/// `compute_comm_d` inserts between them they fill the whole sector.
+///
+/// A staged sector which is only partially filled with pieces is zero-padded to the sector size
+/// by `seal_pre_commit_phase1`, so its `comm_d` commits to those zeros as well. The returned
+/// pieces account for them, and can be passed to `verify_pieces` and `compute_comm_d`.
+pub fn pad_pieces_to_sector(
+    sector_size: SectorSize,
+    piece_infos: &[PieceInfo],
+) -> Result<Vec<PieceInfo>> {
+    let sector_bytes = u64::from(sector_size);
+    ensure!(
+        sector_bytes.is_power_of_two(),
+        "Sector size ({}) must be a power of 2.",
+        sector_bytes
+    );
+
+    // The padded bytes the pieces span, each aligned to its own size.
+    let mut offset = 0;
+    for piece_info in piece_infos {
+        let size = u64::from(PaddedBytesAmount::from(piece_info.size));
+        ensure!(
+            size.is_power_of_two(),
+            "Piece size ({:?}) must be a power of 2.",
+            PaddedBytesAmount::from(piece_info.size)
+        );
+        offset = (offset + size - 1) / size * size + size;
+    }
+    ensure!(
+        offset <= sector_bytes,
+        "Pieces ({} bytes) are larger than the sector ({} bytes).",
+        offset,
+        sector_bytes
+    );
+
+    let mut padded = piece_infos.to_vec();
+    while offset < sector_bytes {
+        // The largest padding aligned at `offset`, so no further alignment is inserted.
+        let size = if offset == 0 {
+            sector_bytes
+        } else {
+            1 << offset.trailing_zeros()
+        };
+        padded.push(zero_padding(PaddedBytesAmount(size).into())?);
+        offset += size;
+    }
+
+    Ok(padded)
+}
+
 /// Stack used for piece reduction.
 struct Stack(Vec<PieceInfo>);
 
@@ -308,6 +463,13 @@ mod tests {
 
     use std::io::{Seek, SeekFrom};
 
+    /// A random piece commitment, trimmed to a valid field element.
+    fn random_commitment<R: Rng>(rng: &mut R) -> Commitment {
+        let mut comm: Commitment = rng.gen();
+        comm[31] &= 0b0011_1111;
+        comm
+    }
+
     #[test]
     fn test_get_piece_alignment() {
         let table = vec![
@@ -373,7 +535,12 @@ mod tests {
         // / \  / \
         // a  b c  d
 
-        let (a, b, c, d): ([u8; 32], [u8; 32], [u8; 32], [u8; 32]) = rng.gen();
+        let (a, b, c, d) = (
+            random_commitment(rng),
+            random_commitment(rng),
+            random_commitment(rng),
+            random_commitment(rng),
+        );
 
         let mut e = [0u8; 32];
         let h = piece_hash(&a, &b);
@@ -436,6 +603,34 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_pad_pieces_to_sector() {
+        let sector_size = SectorSize(32 * 128);
+        let pieces = vec![
+            PieceInfo::new([1u8; 32], UnpaddedBytesAmount(127)).unwrap(),
+            PieceInfo::new([2u8; 32], UnpaddedBytesAmount(4 * 127)).unwrap(),
+        ];
+
+        // In units of 128 bytes: A(1), aligned B(4) ending at 8, then P(8) and P(16) fill the
+        // sector of 32.
+        let padded = pad_pieces_to_sector(sector_size, &pieces).unwrap();
+        let sizes: Vec<u64> = padded
+            .iter()
+            .map(|piece| u64::from(PaddedBytesAmount::from(piece.size)) / 128)
+            .collect();
+        assert_eq!(sizes, vec![1, 4, 8, 16]);
+        assert_eq!(padded[..2], pieces[..]);
+
+        // The padded pieces fill the sector, so padding them again adds nothing.
+        assert_eq!(pad_pieces_to_sector(sector_size, &padded).unwrap(), padded);
+
+        let empty = pad_pieces_to_sector(sector_size, &[]).unwrap();
+        assert_eq!(empty, vec![zero_padding(sector_size.into()).unwrap()]);
+
+        let too_large = vec![PieceInfo::new([1u8; 32], UnpaddedBytesAmount(256 * 127)).unwrap()];
+        assert!(pad_pieces_to_sector(sector_size, &too_large).is_err());
+    }
+
     #[test]
     fn test_verify_padded_pieces() {
         // [
@@ -451,14 +646,14 @@ mod tests {
         let pad = zero_padding(UnpaddedBytesAmount(127)).unwrap();
 
         let pieces = vec![
-            PieceInfo::new([1u8; 32], UnpaddedBytesAmount(1 * 127)).unwrap(),
+            PieceInfo::new([1u8; 32], UnpaddedBytesAmount(127)).unwrap(),
             PieceInfo::new([2u8; 32], UnpaddedBytesAmount(4 * 127)).unwrap(),
             PieceInfo::new([3u8; 32], UnpaddedBytesAmount(2 * 127)).unwrap(),
             PieceInfo::new([4u8; 32], UnpaddedBytesAmount(8 * 127)).unwrap(),
         ];
 
         let padded_pieces = vec![
-            PieceInfo::new([1u8; 32], UnpaddedBytesAmount(1 * 127)).unwrap(),
+            PieceInfo::new([1u8; 32], UnpaddedBytesAmount(127)).unwrap(),
             pad.clone(),
             pad.clone(),
             pad.clone(),
@@ -530,6 +725,72 @@ mod tests {
         assert!(verify_pieces(&comm_d, &pieces, sector_size).unwrap());
     }
 
+    #[test]
+    fn test_verify_pieces_detailed() {
+        let sector_size = SectorSize(4 * 128);
+        let piece_sizes = [UnpaddedBytesAmount(127); 4];
+        let (comm_d, pieces, staged_sector) = build_sector(&piece_sizes, sector_size).unwrap();
+
+        assert_eq!(
+            verify_pieces_detailed(&comm_d, &pieces, sector_size, &staged_sector).unwrap(),
+            PieceVerifyOutcome::Valid
+        );
+
+        let mut corrupted = pieces.clone();
+        corrupted[2].commitment[0] ^= 1;
+        assert_eq!(
+            verify_pieces_detailed(&comm_d, &corrupted, sector_size, &staged_sector).unwrap(),
+            PieceVerifyOutcome::PieceCommitmentMismatch {
+                index: 2,
+                computed: pieces[2].commitment,
+            }
+        );
+
+        let mut corrupted_data = staged_sector.clone();
+        corrupted_data[128] ^= 1;
+        match verify_pieces_detailed(&comm_d, &pieces, sector_size, &corrupted_data).unwrap() {
+            PieceVerifyOutcome::PieceCommitmentMismatch { index, computed } => {
+                assert_eq!(index, 1);
+                assert_ne!(computed, pieces[1].commitment);
+            }
+            other => panic!("unexpected outcome: {:?}", other),
+        }
+
+        let mut wrong_comm_d = comm_d;
+        wrong_comm_d[0] ^= 1;
+        assert_eq!(
+            verify_pieces_detailed(&wrong_comm_d, &pieces, sector_size, &staged_sector).unwrap(),
+            PieceVerifyOutcome::CommitmentMismatch { computed: comm_d }
+        );
+
+        let (a, b) = (pieces[0].commitment, pieces[1].commitment);
+        assert_eq!(
+            verify_pieces_detailed(&comm_d, &[], sector_size, &[]).unwrap(),
+            PieceVerifyOutcome::InvalidPieceCount { count: 0, max: 4 }
+        );
+
+        let oversized = vec![
+            PieceInfo::new(a, UnpaddedBytesAmount(254)).unwrap(),
+            PieceInfo::new(b, UnpaddedBytesAmount(254)).unwrap(),
+        ];
+        assert_eq!(
+            verify_pieces_detailed(&comm_d, &oversized, SectorSize(2 * 128), &[]).unwrap(),
+            PieceVerifyOutcome::InvalidTotalSize {
+                total: PaddedBytesAmount(4 * 128),
+                sector: PaddedBytesAmount(2 * 128),
+            }
+        );
+
+        let misaligned = vec![PieceInfo::new(a, UnpaddedBytesAmount(3 * 127)).unwrap()];
+        assert_eq!(
+            verify_pieces_detailed(&comm_d, &misaligned, sector_size, &[]).unwrap(),
+            PieceVerifyOutcome::InvalidPieceSize {
+                index: 0,
+                size: PaddedBytesAmount(3 * 128),
+            }
+        );
+    }
+
     #[ignore] // slow test
     #[test]
     fn test_verify_random_pieces() -> Result<()> {
@@ -603,12 +864,17 @@ mod tests {
                 assert!(sum_piece_bytes_with_alignment(&piece_sizes) <= unpadded_sector_size);
                 assert!(!piece_sizes.is_empty());
 
-                let (comm_d, piece_infos) = build_sector(&piece_sizes, sector_size)?;
+                let (comm_d, piece_infos, staged_sector) = build_sector(&piece_sizes, sector_size)?;
 
                 assert!(
                     verify_pieces(&comm_d, &piece_infos, sector_size)?,
                     "invalid pieces"
                 );
+                assert!(
+                    verify_pieces_detailed(&comm_d, &piece_infos, sector_size, &staged_sector)?
+                        .is_valid(),
+                    "invalid pieces"
+                );
             }
         }
 
@@ -618,7 +884,7 @@ mod tests {
     fn build_sector(
         piece_sizes: &[UnpaddedBytesAmount],
         sector_size: SectorSize,
-    ) -> Result<([u8; 32], Vec<PieceInfo>)> {
+    ) -> Result<([u8; 32], Vec<PieceInfo>, Vec<u8>)> {
         let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
         let graph = StackedBucketGraph::<DefaultPieceHasher>::new_stacked(
             u64::from(sector_size) as usize / NODE_SIZE,
@@ -656,7 +922,7 @@ mod tests {
         let comm_d_root: Fr = data_tree.root().into();
         let comm_d = commitment_from_fr::<Bls12>(comm_d_root);
 
-        Ok((comm_d, piece_infos))
+        Ok((comm_d, piece_infos, staged_sector))
     }
 
     fn prev_power_of_two(mut x: u32) -> u32 {
@@ -0,0 +1,94 @@
+//! Tags log output with the sector it concerns, so that log lines from concurrently sealed
+//! sectors can be told apart.
+//!
+//! The seal functions taking a `SectorId` enter a `SectorSpan` for the duration of the phase,
+//! which storage-proofs carries over to the rayon tasks it hands the phase's work to.
+//! Applications install a `SectorLogger` around their own logger, which then prefixes every
+//! record logged in a span with `sector_id=<id>: `, whichever crate logs it. Records logged
+//! outside of any span are passed on untagged.
+
+use std::fmt;
+
+use log::{Log, Metadata, Record};
+
+pub use storage_proofs::sector_log::{current_sector, SectorSpan};
+
+/// Formats as the `sector_id=<id>: ` prefix of the `current_sector()`, or as nothing outside of
+/// any span.
+pub struct SectorTag;
+
+impl fmt::Display for SectorTag {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match current_sector() {
+            Some(sector_id) => write!(f, "sector_id={}: ", u64::from(sector_id)),
+            None => Ok(()),
+        }
+    }
+}
+
+/// A logger prefixing the records it passes on to `inner` with their `SectorTag`.
+///
+/// Install it in place of the application's logger, e.g. with `log::set_boxed_logger`.
+pub struct SectorLogger<L> {
+    inner: L,
+}
+
+impl<L> SectorLogger<L> {
+    pub const fn new(inner: L) -> Self {
+        SectorLogger { inner }
+    }
+}
+
+impl<L: Log> Log for SectorLogger<L> {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        self.inner.log(
+            &Record::builder()
+                .args(format_args!("{}{}", SectorTag, record.args()))
+                .metadata(record.metadata().clone())
+                .module_path(record.module_path())
+                .file(record.file())
+                .line(record.line())
+                .build(),
+        );
+    }
+
+    fn flush(&self) {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use storage_proofs::sector::SectorId;
+
+    #[test]
+    fn test_sector_span_nesting() {
+        assert_eq!(current_sector(), None);
+        {
+            let _outer = SectorSpan::enter(SectorId::from(1));
+            {
+                let _inner = SectorSpan::enter(SectorId::from(2));
+                assert_eq!(current_sector(), Some(SectorId::from(2)));
+                assert_eq!(format!("{}", SectorTag), "sector_id=2: ");
+
+                // Other threads are in no span, unless they resume this one.
+                let sector_id = current_sector();
+                let untagged = std::thread::spawn(|| format!("{}", SectorTag));
+                let resumed = std::thread::spawn(move || {
+                    let _span = SectorSpan::resume(sector_id);
+                    format!("{}", SectorTag)
+                });
+                assert_eq!(untagged.join().unwrap(), "");
+                assert_eq!(resumed.join().unwrap(), "sector_id=2: ");
+            }
+            assert_eq!(current_sector(), Some(SectorId::from(1)));
+        }
+        assert_eq!(current_sector(), None);
+    }
+}
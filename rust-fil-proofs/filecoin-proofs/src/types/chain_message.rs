@@ -0,0 +1,207 @@
+use anyhow::{anyhow, ensure, Context, Result};
+use serde_cbor::Value;
+use serde_json::json;
+use storage_proofs::circuit::multi_proof::is_versioned;
+use storage_proofs::sector::SectorId;
+
+use crate::constants::SINGLE_PARTITION_PROOF_LEN;
+use crate::types::*;
+
+/// The parameters of the chain's `ProveCommitSector` message, as written by
+/// `SealCommitOutput::to_chain_message`.
+///
+/// They are CBOR encoded as a tuple, i.e. a two element array of:
+///
+/// * the sector number, as an unsigned integer.
+/// * the proof, as returned by `seal_commit_phase2`, as a byte string.
+///
+/// The commitments are not part of the message: the chain already has them from the sector's
+/// `PreCommitSector` message.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProveCommitSectorParams {
+    pub sector_id: SectorId,
+    pub proof: Vec<u8>,
+}
+
+impl ProveCommitSectorParams {
+    /// Parses the CBOR encoded parameters of a `ProveCommitSector` message, rejecting any
+    /// other encoding or trailing bytes.
+    pub fn parse(bytes: &[u8]) -> Result<Self> {
+        let value: Value =
+            serde_cbor::from_slice(bytes).context("invalid ProveCommitSector parameters")?;
+
+        match value {
+            Value::Array(fields) => match fields.as_slice() {
+                [Value::Integer(sector_id), Value::Bytes(proof)] => {
+                    ensure!(
+                        *sector_id >= 0 && *sector_id <= i128::from(u64::max_value()),
+                        "invalid sector number: {}",
+                        sector_id
+                    );
+
+                    Ok(ProveCommitSectorParams {
+                        sector_id: SectorId::from(*sector_id as u64),
+                        proof: proof.clone(),
+                    })
+                }
+                _ => Err(anyhow!(
+                    "ProveCommitSector parameters must be a sector number and a proof"
+                )),
+            },
+            _ => Err(anyhow!("ProveCommitSector parameters must be a CBOR array")),
+        }
+    }
+}
+
+impl SealCommitOutput {
+    /// Serializes the proof of the sector `sector_id` into the parameters of the chain's
+    /// `ProveCommitSector` message. See `ProveCommitSectorParams` for the encoding, and
+    /// `ProveCommitSectorParams::parse` to read it back.
+    pub fn to_chain_message(&self, sector_id: SectorId) -> Vec<u8> {
+        let params = Value::Array(vec![
+            Value::Integer(i128::from(u64::from(sector_id))),
+            Value::Bytes(self.proof.clone()),
+        ]);
+
+        serde_cbor::to_vec(&params).expect("CBOR values always serialize")
+    }
+
+    /// Dumps the proof as JSON for inspection and RPC payloads:
+    ///
+    /// ```json
+    /// { "proof": "<hex>", "proof_len": <bytes>, "partitions": <count>, "sector_size": <bytes> }
+    /// ```
+    ///
+    /// `from_json` reads it back.
+    pub fn to_json(&self, porep_config: PoRepConfig) -> serde_json::Value {
+        json!({
+            "proof": hex::encode(&self.proof),
+            "proof_len": self.proof.len(),
+            "partitions": usize::from(PoRepProofPartitions::from(porep_config)),
+            "sector_size": u64::from(porep_config.sector_size),
+        })
+    }
+
+    /// Parses a dump written by `to_json` for a sector of `porep_config`, rejecting it unless
+    /// the proof holds exactly one groth proof per partition, after the format version byte
+    /// if any.
+    pub fn from_json(value: &serde_json::Value, porep_config: PoRepConfig) -> Result<Self> {
+        let field = |name: &str| {
+            value
+                .get(name)
+                .ok_or_else(|| anyhow!("seal commit output JSON is missing {:?}", name))
+        };
+        let number = |name: &str| {
+            field(name)?
+                .as_u64()
+                .ok_or_else(|| anyhow!("{:?} is not an unsigned integer", name))
+        };
+
+        let partitions = usize::from(PoRepProofPartitions::from(porep_config));
+        ensure!(
+            number("partitions")? == partitions as u64,
+            "proof has {} partitions, but the porep config has {}",
+            number("partitions")?,
+            partitions
+        );
+        ensure!(
+            number("sector_size")? == u64::from(porep_config.sector_size),
+            "proof is for sector size {}, but the porep config is for {}",
+            number("sector_size")?,
+            u64::from(porep_config.sector_size)
+        );
+
+        let proof_hex = field("proof")?
+            .as_str()
+            .ok_or_else(|| anyhow!("\"proof\" is not a string"))?;
+        let proof = hex::decode(proof_hex).context("\"proof\" is not valid hex")?;
+
+        let version_len = if is_versioned(&proof) { 1 } else { 0 };
+        let expected = version_len + SINGLE_PARTITION_PROOF_LEN * partitions;
+        ensure!(
+            proof.len() == expected,
+            "invalid seal proof length: expected {} bytes, got {}",
+            expected,
+            proof.len()
+        );
+        ensure!(
+            number("proof_len")? == proof.len() as u64,
+            "\"proof_len\" is {}, but the proof holds {} bytes",
+            number("proof_len")?,
+            proof.len()
+        );
+
+        Ok(SealCommitOutput { proof })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chain_message_encoding() {
+        let output = SealCommitOutput {
+            proof: vec![1, 2, 3],
+        };
+
+        // A two element array of the unsigned integer 1000 and a three byte string.
+        let message = output.to_chain_message(SectorId::from(1000));
+        assert_eq!(hex::encode(&message), "821903e843010203");
+
+        // Proofs are long enough for a two byte length.
+        let output = SealCommitOutput {
+            proof: (0..=255).collect(),
+        };
+        let message = output.to_chain_message(SectorId::from(7));
+        assert_eq!(hex::encode(&message[..5]), "8207590100");
+        assert_eq!(message.len(), 5 + 256);
+
+        let parsed = ProveCommitSectorParams::parse(&message).unwrap();
+        assert_eq!(
+            parsed,
+            ProveCommitSectorParams {
+                sector_id: SectorId::from(7),
+                proof: output.proof,
+            }
+        );
+
+        assert!(ProveCommitSectorParams::parse(&message[..message.len() - 1]).is_err());
+        let mut trailing = message.clone();
+        trailing.push(0);
+        assert!(ProveCommitSectorParams::parse(&trailing).is_err());
+        // The proof as an array of integers rather than a byte string.
+        let integers = hex::decode("820783010203").unwrap();
+        assert!(ProveCommitSectorParams::parse(&integers).is_err());
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let porep_config = PoRepConfig {
+            sector_size: SectorSize(2048),
+            partitions: PoRepProofPartitions(2),
+            graph_seed: None,
+        };
+        let mut proof = vec![1];
+        proof.extend((0..2 * SINGLE_PARTITION_PROOF_LEN).map(|i| (i as u8) | 0x80));
+        let output = SealCommitOutput { proof };
+
+        let value = output.to_json(porep_config);
+        assert_eq!(value["proof_len"], 1 + 2 * SINGLE_PARTITION_PROOF_LEN);
+        assert_eq!(value["partitions"], 2);
+        let parsed = SealCommitOutput::from_json(&value, porep_config).unwrap();
+        assert_eq!(parsed.proof, output.proof);
+
+        let one_partition = PoRepConfig {
+            partitions: PoRepProofPartitions(1),
+            ..porep_config
+        };
+        assert!(SealCommitOutput::from_json(&value, one_partition).is_err());
+
+        let truncated = SealCommitOutput {
+            proof: output.proof[..output.proof.len() - 1].to_vec(),
+        };
+        let truncated_json = truncated.to_json(porep_config);
+        assert!(SealCommitOutput::from_json(&truncated_json, porep_config).is_err());
+    }
+}
@@ -1,3 +1,5 @@
+use std::time::Duration;
+
 use serde::{Deserialize, Serialize};
 use storage_proofs::hasher::Hasher;
 use storage_proofs::merkle::{LCMerkleTree, MerkleTree};
@@ -6,21 +8,27 @@ use storage_proofs::stacked;
 use crate::constants::{DefaultPieceHasher, DefaultTreeDomain, DefaultTreeHasher};
 
 mod bytes_amount;
+mod chain_message;
 mod piece_info;
 mod porep_config;
 mod porep_proof_partitions;
 mod post_config;
 mod post_proof_partitions;
+mod seal_phase;
 mod sector_class;
+mod sector_metadata;
 mod sector_size;
 
 pub use self::bytes_amount::*;
+pub use self::chain_message::*;
 pub use self::piece_info::*;
 pub use self::porep_config::*;
 pub use self::porep_proof_partitions::*;
 pub use self::post_config::*;
 pub use self::post_proof_partitions::*;
+pub use self::seal_phase::*;
 pub use self::sector_class::*;
+pub use self::sector_metadata::*;
 pub use self::sector_size::*;
 
 pub type Commitment = [u8; 32];
@@ -32,12 +40,49 @@ pub type Ticket = [u8; 32];
 pub type Tree = MerkleTree<DefaultTreeDomain, <DefaultTreeHasher as Hasher>::Function>;
 pub type LCTree = LCMerkleTree<DefaultTreeDomain, <DefaultTreeHasher as Hasher>::Function>;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct SealPreCommitOutput {
     pub comm_r: Commitment,
     pub comm_d: Commitment,
 }
 
+impl SealPreCommitOutput {
+    /// Checks that this output matches an expected `comm_r` and `comm_d`, e.g. the ones
+    /// committed on chain, before proceeding to commit. The comparison takes the same time
+    /// no matter where the commitments differ.
+    pub fn matches(&self, expected_comm_r: &Commitment, expected_comm_d: &Commitment) -> bool {
+        // Use a non-short-circuiting `&` so both commitments are always compared.
+        constant_time_eq(&self.comm_r, expected_comm_r)
+            & constant_time_eq(&self.comm_d, expected_comm_d)
+    }
+}
+
+fn constant_time_eq(a: &Commitment, b: &Commitment) -> bool {
+    a.iter()
+        .zip(b.iter())
+        .fold(0u8, |acc, (x, y)| acc | (x ^ y))
+        == 0
+}
+
+/// Derives the prover-id of the actor identified by `actor`, the payload of its on-chain
+/// address. For an ID address this is the actor id as an unsigned LEB128 varint, e.g.
+/// `[0xe8, 0x07]` for actor 1000.
+///
+/// An `actor` of at most 32 bytes is copied to the start of the prover-id, with the remaining
+/// bytes zeroed, as the chain does. A longer `actor` can't be copied, so its prover-id is its
+/// 32-byte Blake2b hash instead.
+pub fn prover_id_from_actor_bytes(actor: &[u8]) -> ProverId {
+    let mut prover_id = [0; 32];
+    if actor.len() <= prover_id.len() {
+        prover_id[..actor.len()].copy_from_slice(actor);
+    } else {
+        let hash = blake2b_simd::Params::new().hash_length(32).hash(actor);
+        prover_id.copy_from_slice(hash.as_bytes());
+    }
+
+    prover_id
+}
+
 pub type VanillaSealProof = storage_proofs::stacked::Proof<DefaultTreeHasher, DefaultPieceHasher>;
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -55,6 +100,68 @@ pub struct SealCommitOutput {
     pub proof: Vec<u8>,
 }
 
+/// A seal proof bundled with the public inputs it was generated for, so that it can be
+/// re-verified from this data alone with `verify_from_output`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SealCommitOutputWithInputs {
+    pub proof: Vec<u8>,
+    pub sector_size: u64,
+    pub partitions: u8,
+    pub replica_id: <DefaultTreeHasher as Hasher>::Domain,
+    pub comm_r: Commitment,
+    pub comm_d: Commitment,
+    pub seed: Ticket,
+    /// The circuit public inputs of each partition, as serialized field elements.
+    pub public_inputs: Vec<Vec<[u8; 32]>>,
+    /// The graph seed the sector was sealed with, `None` for the default seed.
+    #[serde(default)]
+    pub graph_seed: Option<[u8; 28]>,
+}
+
+/// How long each step of `seal_commit_phase2_timed` took.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CommitTimings {
+    /// Time spent building the public inputs and setting up the public parameters.
+    pub setup: Duration,
+    /// Time spent loading the groth parameters.
+    pub params: Duration,
+    /// Time spent creating and serializing the groth proofs of all partitions.
+    pub circuit_proofs: Duration,
+    /// Time spent on the post-seal verification sanity check, zero if it was skipped.
+    pub post_verify: Duration,
+    /// The wall-clock time of the whole of `seal_commit_phase2_timed`.
+    pub total: Duration,
+}
+
+/// The cost of verifying a single seal proof, as reported by `verify_seal_with_metrics`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VerifyMetrics {
+    /// The number of circuit public inputs, summed over all partitions.
+    pub public_inputs: usize,
+    pub partitions: usize,
+    /// Time spent setting up the public parameters, loading the verifying key and parsing
+    /// the proof.
+    pub setup_duration: Duration,
+    /// Time spent verifying the groth proofs.
+    pub verify_duration: Duration,
+}
+
+/// The estimated duration of each sealing phase, as returned by `estimate_seal_time`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SealTimeEstimate {
+    pub pre_commit_phase1: Duration,
+    pub pre_commit_phase2: Duration,
+    pub commit_phase1: Duration,
+    pub commit_phase2: Duration,
+}
+
+impl SealTimeEstimate {
+    /// The estimated duration of the whole seal.
+    pub fn total(&self) -> Duration {
+        self.pre_commit_phase1 + self.pre_commit_phase2 + self.commit_phase1 + self.commit_phase2
+    }
+}
+
 pub type Labels = storage_proofs::stacked::Labels<DefaultTreeHasher>;
 pub type DataTree = storage_proofs::stacked::Tree<DefaultPieceHasher>;
 pub use merkletree::store::StoreConfig;
@@ -64,4 +171,67 @@ pub struct SealPreCommitPhase1Output {
     pub labels: Labels,
     pub config: StoreConfig,
     pub comm_d: Commitment,
+    /// The in-memory data tree, only kept if requested in phase1. It is never serialized.
+    #[serde(skip)]
+    pub data_tree: Option<DataTree>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seal_pre_commit_output_matches() {
+        let output = SealPreCommitOutput {
+            comm_r: [1; 32],
+            comm_d: [2; 32],
+        };
+
+        assert!(output.matches(&[1; 32], &[2; 32]));
+        assert_eq!(
+            output,
+            SealPreCommitOutput {
+                comm_r: [1; 32],
+                comm_d: [2; 32],
+            }
+        );
+
+        let mut other_comm_r = [1; 32];
+        other_comm_r[31] = 0;
+        let mut other_comm_d = [2; 32];
+        other_comm_d[0] = 0;
+
+        assert!(!output.matches(&other_comm_r, &[2; 32]));
+        assert!(!output.matches(&[1; 32], &other_comm_d));
+        assert!(!output.matches(&other_comm_r, &other_comm_d));
+        // The commitments are not interchangeable.
+        assert!(!output.matches(&[2; 32], &[1; 32]));
+        assert_ne!(
+            output,
+            SealPreCommitOutput {
+                comm_r: other_comm_r,
+                comm_d: [2; 32],
+            }
+        );
+    }
+
+    #[test]
+    fn prover_id_from_actor() {
+        let mut expected = [0; 32];
+        expected[..2].copy_from_slice(&[0xe8, 0x07]);
+        assert_eq!(prover_id_from_actor_bytes(&[0xe8, 0x07]), expected);
+
+        let full: Vec<u8> = (0..32).collect();
+        assert_eq!(prover_id_from_actor_bytes(&full)[..], full[..]);
+
+        let long: Vec<u8> = (0..33).collect();
+        assert_eq!(
+            prover_id_from_actor_bytes(&long),
+            [
+                0xb7, 0x63, 0x4f, 0xe1, 0x3c, 0x7a, 0xca, 0x39, 0x14, 0xee, 0x89, 0x6e, 0x22, 0xcf,
+                0xab, 0xc9, 0xda, 0x5b, 0x4f, 0x13, 0xe7, 0x2a, 0x2c, 0xcb, 0xec, 0xb6, 0xd4, 0x4b,
+                0xbd, 0xa9, 0x5b, 0xcc,
+            ]
+        );
+    }
 }
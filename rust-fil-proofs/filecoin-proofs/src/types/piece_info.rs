@@ -2,6 +2,8 @@ use std::fmt;
 
 use anyhow::{ensure, Result};
 
+use crate::api::util::as_safe_commitment;
+use crate::constants::DefaultPieceDomain;
 use crate::types::{Commitment, UnpaddedBytesAmount};
 
 #[derive(Clone, Default, PartialEq, Eq)]
@@ -20,8 +22,40 @@ impl fmt::Debug for PieceInfo {
 }
 
 impl PieceInfo {
+    /// Creates a `PieceInfo`, e.g. from stored piece metadata. The commitment must be a
+    /// canonical, non-zero field element and the piece must not be empty.
     pub fn new(commitment: Commitment, size: UnpaddedBytesAmount) -> Result<Self> {
         ensure!(commitment != [0; 32], "Invalid all zero commitment");
+        as_safe_commitment::<DefaultPieceDomain, _>(&commitment, "piece")?;
+        ensure!(u64::from(size) > 0, "Invalid zero size piece");
+
         Ok(PieceInfo { commitment, size })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_new_valid_piece() {
+        let piece = PieceInfo::new([1; 32], UnpaddedBytesAmount(127)).unwrap();
+        assert_eq!(piece.commitment, [1; 32]);
+        assert_eq!(piece.size, UnpaddedBytesAmount(127));
+    }
+
+    #[test]
+    fn test_new_rejects_invalid_commitment() {
+        let err = PieceInfo::new([0; 32], UnpaddedBytesAmount(127)).unwrap_err();
+        assert!(format!("{}", err).contains("all zero commitment"));
+
+        // Larger than the field modulus.
+        assert!(PieceInfo::new([0xff; 32], UnpaddedBytesAmount(127)).is_err());
+    }
+
+    #[test]
+    fn test_new_rejects_zero_size() {
+        let err = PieceInfo::new([1; 32], UnpaddedBytesAmount(0)).unwrap_err();
+        assert!(format!("{}", err).contains("zero size"));
+    }
+}
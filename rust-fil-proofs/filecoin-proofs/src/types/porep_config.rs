@@ -1,18 +1,24 @@
 use std::path::PathBuf;
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 
 use paired::bls12_381::Bls12;
 use storage_proofs::circuit::stacked::{StackedCircuit, StackedCompound};
 use storage_proofs::parameter_cache::{self, CacheableParameters};
 
-use crate::constants::{DefaultPieceHasher, DefaultTreeHasher};
+use crate::constants::{DefaultPieceHasher, DefaultTreeHasher, POREP_PARTITIONS};
 use crate::types::*;
 
 #[derive(Clone, Copy, Debug)]
 pub struct PoRepConfig {
     pub sector_size: SectorSize,
     pub partitions: PoRepProofPartitions,
+    /// Seed for the DRG graph. `None` uses the fixed seed every sector has been sealed with so
+    /// far; a sector must be verified with the same seed it was sealed with.
+    ///
+    /// With `maximize_caching` enabled, parent caches are keyed by sector size only, so sectors
+    /// of the same size must not be sealed with different seeds in one process.
+    pub graph_seed: Option<[u8; 28]>,
 }
 
 impl From<PoRepConfig> for PaddedBytesAmount {
@@ -47,6 +53,22 @@ impl From<PoRepConfig> for SectorSize {
 }
 
 impl PoRepConfig {
+    /// Creates a config for `sector_size`, using the partition count registered in
+    /// `POREP_PARTITIONS`. Fails if no partition count is registered for the size.
+    pub fn from_sector_size(sector_size: SectorSize) -> Result<Self> {
+        let partitions = *POREP_PARTITIONS
+            .read()
+            .expect("POREP_PARTITIONS poisoned")
+            .get(&u64::from(sector_size))
+            .with_context(|| format!("unregistered sector size: {}", u64::from(sector_size)))?;
+
+        Ok(PoRepConfig {
+            sector_size,
+            partitions: PoRepProofPartitions(partitions),
+            graph_seed: None,
+        })
+    }
+
     /// Returns the cache identifier as used by `storage-proofs::paramater_cache`.
     pub fn get_cache_identifier(&self) -> Result<String> {
         let params =
@@ -76,3 +98,20 @@ impl PoRepConfig {
         Ok(parameter_cache::parameter_cache_params_path(&id))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::constants::SECTOR_SIZE_ONE_KIB;
+
+    #[test]
+    fn test_from_sector_size() {
+        let config = PoRepConfig::from_sector_size(SectorSize(SECTOR_SIZE_ONE_KIB)).unwrap();
+        assert_eq!(u64::from(config.sector_size), SECTOR_SIZE_ONE_KIB);
+        assert_eq!(usize::from(config.partitions), 1);
+
+        let err = PoRepConfig::from_sector_size(SectorSize(4096)).unwrap_err();
+        assert!(format!("{}", err).contains("unregistered sector size: 4096"));
+    }
+}
@@ -1,10 +1,11 @@
 use std::path::PathBuf;
 
-use anyhow::Result;
+use anyhow::{ensure, Result};
 
 use paired::bls12_381::Bls12;
 use storage_proofs::circuit::election_post::{ElectionPoStCircuit, ElectionPoStCompound};
 use storage_proofs::parameter_cache::{self, CacheableParameters};
+use storage_proofs::util::NODE_SIZE;
 
 use crate::constants::DefaultTreeHasher;
 use crate::types::*;
@@ -16,6 +17,9 @@ pub struct PoStConfig {
     pub challenged_nodes: usize,
     /// High priority (always runs on GPU) == true
     pub priority: bool,
+    /// The number of bytes of each challenged range read from the replica at once, to tune
+    /// PoSt I/O to the storage medium. `None` reads each range at once.
+    pub read_chunk_bytes: Option<usize>,
 }
 
 impl From<PoStConfig> for PaddedBytesAmount {
@@ -35,6 +39,50 @@ impl From<PoStConfig> for UnpaddedBytesAmount {
 }
 
 impl PoStConfig {
+    /// Creates a validated, normal priority `PoStConfig`.
+    pub fn new(
+        sector_size: SectorSize,
+        challenge_count: usize,
+        challenged_nodes: usize,
+    ) -> Result<Self> {
+        let config = PoStConfig {
+            sector_size,
+            challenge_count,
+            challenged_nodes,
+            priority: false,
+            read_chunk_bytes: None,
+        };
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    /// Checks that the challenge parameters are usable for the configured sector size.
+    pub fn validate(&self) -> Result<()> {
+        ensure!(self.challenge_count > 0, "challenge_count must be > 0");
+        ensure!(
+            self.challenged_nodes.is_power_of_two(),
+            "challenged_nodes must be a power of two, got {}",
+            self.challenged_nodes
+        );
+        ensure!(
+            (self.challenged_nodes * NODE_SIZE) as u64 <= u64::from(self.sector_size),
+            "challenged_nodes ({}) exceed the sector size ({})",
+            self.challenged_nodes,
+            u64::from(self.sector_size)
+        );
+        if let Some(read_chunk_bytes) = self.read_chunk_bytes {
+            ensure!(
+                read_chunk_bytes > 0 && read_chunk_bytes % NODE_SIZE == 0,
+                "read_chunk_bytes must be a positive multiple of {}, got {}",
+                NODE_SIZE,
+                read_chunk_bytes
+            );
+        }
+
+        Ok(())
+    }
+
     /// Returns the cache identifier as used by `storage-proofs::paramater_cache`.
     pub fn get_cache_identifier(self) -> Result<String> {
         let params = crate::parameters::post_public_params(self)?;
@@ -63,3 +111,48 @@ impl PoStConfig {
         Ok(parameter_cache::parameter_cache_params_path(&id))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::constants::{POST_CHALLENGED_NODES, POST_CHALLENGE_COUNT, SECTOR_SIZE_ONE_KIB};
+
+    #[test]
+    fn test_new_valid() {
+        let config = PoStConfig::new(
+            SectorSize(SECTOR_SIZE_ONE_KIB),
+            POST_CHALLENGE_COUNT,
+            POST_CHALLENGED_NODES,
+        )
+        .unwrap();
+        assert_eq!(config.challenge_count, POST_CHALLENGE_COUNT);
+        assert_eq!(config.challenged_nodes, POST_CHALLENGED_NODES);
+        assert!(!config.priority);
+    }
+
+    #[test]
+    fn test_new_challenged_nodes_not_power_of_two() {
+        let err =
+            PoStConfig::new(SectorSize(SECTOR_SIZE_ONE_KIB), POST_CHALLENGE_COUNT, 3).unwrap_err();
+        assert!(format!("{}", err).contains("challenged_nodes must be a power of two"));
+    }
+
+    #[test]
+    fn test_validate_read_chunk_bytes() {
+        let mut config = PoStConfig::new(
+            SectorSize(SECTOR_SIZE_ONE_KIB),
+            POST_CHALLENGE_COUNT,
+            POST_CHALLENGED_NODES,
+        )
+        .unwrap();
+        assert!(config.read_chunk_bytes.is_none());
+
+        config.read_chunk_bytes = Some(2 * NODE_SIZE);
+        assert!(config.validate().is_ok());
+
+        config.read_chunk_bytes = Some(NODE_SIZE + 1);
+        let err = config.validate().unwrap_err();
+        assert!(format!("{}", err).contains("read_chunk_bytes"));
+    }
+}
@@ -0,0 +1,15 @@
+/// The furthest completed sealing phase of a sector, as inferred from the artifacts on disk.
+/// Variants are ordered by progress.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SealPhase {
+    /// No sealing artifacts exist yet; the sector has only been staged.
+    Staged,
+    /// The data tree and the layer labels have been written.
+    PreCommit1Done,
+    /// `p_aux` and `t_aux` have been persisted.
+    PreCommit2Done,
+    /// The cached trees and labels have been compacted after generating vanilla proofs.
+    Commit1Done,
+    /// The seal proof has been persisted via `persist_seal_commit_output`.
+    Commit2Done,
+}
@@ -15,6 +15,7 @@ impl From<SectorClass> for PoRepConfig {
             } => PoRepConfig {
                 sector_size,
                 partitions,
+                graph_seed: None,
             },
         }
     }
@@ -0,0 +1,53 @@
+use std::sync::atomic::Ordering;
+
+use anyhow::{ensure, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::constants::{DRG_DEGREE, EXP_DEGREE};
+use crate::types::*;
+
+/// Version of the `SectorMetadata` format written by this crate.
+pub const SECTOR_METADATA_VERSION: u32 = 1;
+
+/// Describes the parameters a sealed sector was produced with. It is persisted next to the
+/// sector's other sealing artifacts so that tooling can identify the replica format.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SectorMetadata {
+    pub version: u32,
+    pub sector_size: u64,
+    pub partitions: usize,
+    pub base_degree: usize,
+    pub expansion_degree: usize,
+}
+
+impl SectorMetadata {
+    /// Builds the metadata for a sector sealed with `porep_config` and the current graph degrees.
+    pub fn new(porep_config: PoRepConfig) -> Self {
+        SectorMetadata {
+            version: SECTOR_METADATA_VERSION,
+            sector_size: u64::from(porep_config.sector_size),
+            partitions: usize::from(porep_config.partitions),
+            base_degree: DRG_DEGREE.load(Ordering::Relaxed) as usize,
+            expansion_degree: EXP_DEGREE.load(Ordering::Relaxed) as usize,
+        }
+    }
+
+    /// Checks that a sector with this metadata can be processed with `porep_config`.
+    pub fn validate(&self, porep_config: PoRepConfig) -> Result<()> {
+        ensure!(
+            self.version == SECTOR_METADATA_VERSION,
+            "unsupported sector metadata version: {}",
+            self.version
+        );
+
+        let expected = SectorMetadata::new(porep_config);
+        ensure!(
+            *self == expected,
+            "sector metadata mismatch: sealed with {:?}, but got {:?}",
+            self,
+            expected
+        );
+
+        Ok(())
+    }
+}
@@ -1,9 +1,30 @@
+use storage_proofs::util::NODE_SIZE;
+
+use crate::constants::{LAYERS, POREP_MINIMUM_CHALLENGES};
 use crate::fr32::unpadded_bytes;
 use crate::types::*;
 
 #[derive(Clone, Copy, Debug)]
 pub struct SectorSize(pub u64);
 
+impl SectorSize {
+    /// Whether this is a size sectors can be sealed and verified at: a power of two of at
+    /// least one node, with challenges and layers registered in `POREP_MINIMUM_CHALLENGES`
+    /// and `LAYERS`.
+    pub fn is_valid(&self) -> bool {
+        self.0.is_power_of_two()
+            && self.0 >= NODE_SIZE as u64
+            && POREP_MINIMUM_CHALLENGES
+                .read()
+                .expect("POREP_MINIMUM_CHALLENGES poisoned")
+                .contains_key(&self.0)
+            && LAYERS
+                .read()
+                .expect("LAYERS poisoned")
+                .contains_key(&self.0)
+    }
+}
+
 impl From<SectorSize> for UnpaddedBytesAmount {
     fn from(x: SectorSize) -> Self {
         UnpaddedBytesAmount(unpadded_bytes(x.0))
@@ -21,3 +42,24 @@ impl From<SectorSize> for u64 {
         x.0
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::constants::{SECTOR_SIZE_32_GIB, SECTOR_SIZE_ONE_KIB};
+
+    #[test]
+    fn sector_size_is_valid() {
+        assert!(SectorSize(SECTOR_SIZE_ONE_KIB).is_valid());
+        assert!(SectorSize(SECTOR_SIZE_32_GIB).is_valid());
+
+        // Not a power of two.
+        assert!(!SectorSize(SECTOR_SIZE_ONE_KIB + 32).is_valid());
+        assert!(!SectorSize(0).is_valid());
+        // A power of two, but smaller than a node.
+        assert!(!SectorSize(16).is_valid());
+        // A power of two, but not registered.
+        assert!(!SectorSize(2048).is_valid());
+    }
+}
@@ -0,0 +1,101 @@
+use std::fs::{self, OpenOptions};
+use std::io::{Seek, SeekFrom, Write};
+use std::path::PathBuf;
+
+use anyhow::Result;
+use filecoin_proofs::constants::SECTOR_SIZE_ONE_KIB;
+use filecoin_proofs::{
+    add_piece, generate_piece_commitment, precompute_graph, seal_pre_commit_phase1,
+    seal_pre_commit_phase2, Commitment, PaddedBytesAmount, PieceInfo, PoRepConfig, SectorSize,
+    UnpaddedBytesAmount,
+};
+use storage_proofs::parameter_cache::{parameter_cache_dir, PARAMETER_CACHE_ENV_VAR};
+use storage_proofs::sector::SectorId;
+use storage_proofs::settings::SETTINGS;
+use storage_proofs::stacked::drop_parent_cache;
+use tempfile::NamedTempFile;
+
+fn seal_comm_r(
+    config: PoRepConfig,
+    staged_sector_file: &NamedTempFile,
+    piece_infos: &[PieceInfo],
+) -> Result<Commitment> {
+    let cache_dir = tempfile::tempdir()?;
+    let sealed_sector_file = NamedTempFile::new()?;
+
+    let phase1_output = seal_pre_commit_phase1(
+        config,
+        cache_dir.path(),
+        staged_sector_file.path(),
+        sealed_sector_file.path(),
+        [1; 32],
+        SectorId::from(7),
+        [2; 32],
+        piece_infos,
+    )?;
+    let output = seal_pre_commit_phase2(
+        config,
+        phase1_output,
+        cache_dir.path(),
+        sealed_sector_file.path(),
+    )?;
+
+    Ok(output.comm_r)
+}
+
+fn persisted_parent_caches() -> Result<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+    for entry in fs::read_dir(parameter_cache_dir())? {
+        let path = entry?.path();
+        if path.extension().map_or(false, |ext| ext == "parents") {
+            paths.push(path);
+        }
+    }
+
+    Ok(paths)
+}
+
+#[test]
+fn sealing_with_a_precomputed_graph_matches_regenerating_it() -> Result<()> {
+    // Persisted caches live in the parameter cache, keep this test's caches to itself.
+    let parameter_dir = tempfile::tempdir()?;
+    std::env::set_var(PARAMETER_CACHE_ENV_VAR, parameter_dir.path());
+    SETTINGS.lock().unwrap().maximize_caching = true;
+
+    let config = PoRepConfig::from_sector_size(SectorSize(SECTOR_SIZE_ONE_KIB))?;
+    let piece_size = UnpaddedBytesAmount::from(PaddedBytesAmount(SECTOR_SIZE_ONE_KIB));
+    let piece_bytes: Vec<u8> = (0..piece_size.0).map(|x| x as u8 % 200).collect();
+
+    let mut piece_file = NamedTempFile::new()?;
+    piece_file.write_all(&piece_bytes)?;
+    piece_file.as_file_mut().seek(SeekFrom::Start(0))?;
+    let piece_info = generate_piece_commitment(piece_file.as_file_mut(), piece_size)?;
+    piece_file.as_file_mut().seek(SeekFrom::Start(0))?;
+
+    let mut staged_sector_file = NamedTempFile::new()?;
+    add_piece(&mut piece_file, &mut staged_sector_file, piece_size, &[])?;
+    let piece_infos = vec![piece_info];
+
+    // No persisted cache yet, so the graph is regenerated.
+    let regenerated = seal_comm_r(config, &staged_sector_file, &piece_infos)?;
+    assert!(persisted_parent_caches()?.is_empty());
+
+    precompute_graph(config)?;
+    let caches = persisted_parent_caches()?;
+    assert_eq!(caches.len(), 1);
+
+    drop_parent_cache(SECTOR_SIZE_ONE_KIB);
+    let cached = seal_comm_r(config, &staged_sector_file, &piece_infos)?;
+    assert_eq!(regenerated, cached);
+
+    // A cache from another version must be ignored rather than used.
+    let mut stale = OpenOptions::new().write(true).open(&caches[0])?;
+    stale.write_all(&u32::max_value().to_le_bytes())?;
+    drop(stale);
+
+    drop_parent_cache(SECTOR_SIZE_ONE_KIB);
+    let ignored = seal_comm_r(config, &staged_sector_file, &piece_infos)?;
+    assert_eq!(regenerated, ignored);
+
+    Ok(())
+}
@@ -0,0 +1,127 @@
+use std::io::{Seek, SeekFrom, Write};
+use std::sync::Mutex;
+
+use anyhow::Result;
+use filecoin_proofs::constants::SECTOR_SIZE_ONE_KIB;
+use filecoin_proofs::sector_log::SectorLogger;
+use filecoin_proofs::{
+    add_piece, generate_piece_commitment, seal_commit_phase1, seal_pre_commit_phase1,
+    seal_pre_commit_phase2, PaddedBytesAmount, PoRepConfig, SectorSize, UnpaddedBytesAmount,
+};
+use lazy_static::lazy_static;
+use log::{Log, Metadata, Record};
+use storage_proofs::sector::SectorId;
+use tempfile::NamedTempFile;
+
+lazy_static! {
+    static ref RECORDS: Mutex<Vec<(String, String)>> = Mutex::new(Vec::new());
+}
+
+struct CaptureLogger;
+
+impl Log for CaptureLogger {
+    fn enabled(&self, _metadata: &Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record) {
+        RECORDS
+            .lock()
+            .unwrap()
+            .push((record.target().to_string(), format!("{}", record.args())));
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: SectorLogger<CaptureLogger> = SectorLogger::new(CaptureLogger);
+
+// The logger is process-global, so this test runs without the test harness (see Cargo.toml): no
+// other test shares the process and logs into, or reads, the records.
+fn main() -> Result<()> {
+    log::set_logger(&LOGGER).expect("logger already set");
+    // Trace level, to also capture the records storage-proofs logs from rayon threads.
+    log::set_max_level(log::LevelFilter::Trace);
+
+    let config = PoRepConfig::from_sector_size(SectorSize(SECTOR_SIZE_ONE_KIB))?;
+    let piece_size = UnpaddedBytesAmount::from(PaddedBytesAmount(SECTOR_SIZE_ONE_KIB));
+    let piece_bytes: Vec<u8> = (0..piece_size.0).map(|x| x as u8 % 200).collect();
+
+    let mut piece_file = NamedTempFile::new()?;
+    piece_file.write_all(&piece_bytes)?;
+    piece_file.as_file_mut().seek(SeekFrom::Start(0))?;
+    let piece_info = generate_piece_commitment(piece_file.as_file_mut(), piece_size)?;
+    piece_file.as_file_mut().seek(SeekFrom::Start(0))?;
+
+    let mut staged_sector_file = NamedTempFile::new()?;
+    add_piece(&mut piece_file, &mut staged_sector_file, piece_size, &[])?;
+
+    let cache_dir = tempfile::tempdir()?;
+    let sealed_sector_file = NamedTempFile::new()?;
+    let sector_id = SectorId::from(4242);
+    let piece_infos = vec![piece_info];
+
+    let phase1_output = seal_pre_commit_phase1(
+        config,
+        cache_dir.path(),
+        staged_sector_file.path(),
+        sealed_sector_file.path(),
+        [1; 32],
+        sector_id,
+        [2; 32],
+        &piece_infos,
+    )?;
+    let pre_commit_output = seal_pre_commit_phase2(
+        config,
+        phase1_output,
+        cache_dir.path(),
+        sealed_sector_file.path(),
+    )?;
+
+    RECORDS.lock().unwrap().clear();
+    seal_commit_phase1(
+        config,
+        cache_dir.path(),
+        [1; 32],
+        sector_id,
+        [2; 32],
+        [3; 32],
+        pre_commit_output,
+        &piece_infos,
+    )?;
+
+    let records = RECORDS.lock().unwrap();
+    assert!(
+        records
+            .iter()
+            .any(|(_, message)| message.ends_with("seal_commit_phase1:finish")),
+        "expected the phase records"
+    );
+    // Logged by the rayon tasks proving, then verifying, each challenge.
+    assert!(
+        records
+            .iter()
+            .any(|(target, message)| target.starts_with("storage_proofs")
+                && message.ends_with("  c_x")),
+        "expected the challenge proving records"
+    );
+    assert!(
+        records
+            .iter()
+            .any(|(_, message)| message.contains("verify challenge")),
+        "expected the challenge verification records"
+    );
+    // Dependencies log from rayon tasks of their own, which run outside of any span.
+    for (target, message) in records.iter().filter(|(target, _)| {
+        target.starts_with("filecoin_proofs") || target.starts_with("storage_proofs")
+    }) {
+        assert!(
+            message.starts_with("sector_id=4242: "),
+            "untagged record from {}: {}",
+            target,
+            message
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,64 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use rand::{thread_rng, Rng};
+use rayon::prelude::*;
+use storage_proofs::circuit::por::PoRCompound;
+use storage_proofs::compound_proof::CompoundProof;
+use storage_proofs::drgraph::{new_seed, BucketGraph, Graph, BASE_DEGREE};
+use storage_proofs::hasher::pedersen::PedersenHasher;
+use storage_proofs::merklepor;
+use storage_proofs::proof::ProofScheme;
+
+fn partition_public_inputs_benchmark(c: &mut Criterion) {
+    let mut rng = thread_rng();
+    let leaves = 64;
+    let data: Vec<u8> = (0..32 * leaves).map(|_| rng.gen()).collect();
+    let graph = BucketGraph::<PedersenHasher>::new(leaves, BASE_DEGREE, 0, new_seed()).unwrap();
+    let tree = graph.merkle_tree(None, &data).unwrap();
+
+    let public_inputs = merklepor::PublicInputs {
+        challenge: 3,
+        commitment: Some(tree.root()),
+    };
+    let setup_params = merklepor::SetupParams {
+        leaves,
+        private: false,
+    };
+    let public_params = merklepor::MerklePoR::<PedersenHasher>::setup(&setup_params).unwrap();
+
+    let mut group = c.benchmark_group("partition-public-inputs");
+
+    group.bench_function("single-partition", |b| {
+        b.iter(|| {
+            black_box(
+                PoRCompound::<PedersenHasher>::partition_public_inputs(
+                    &public_inputs,
+                    &public_params,
+                    1,
+                )
+                .unwrap(),
+            )
+        })
+    });
+
+    group.bench_function("single-partition-par-iter", |b| {
+        b.iter(|| {
+            let inputs: Vec<_> = (0..1)
+                .into_par_iter()
+                .map(|k| {
+                    PoRCompound::<PedersenHasher>::generate_public_inputs(
+                        &public_inputs,
+                        &public_params,
+                        Some(k),
+                    )
+                })
+                .collect::<Result<_, _>>()
+                .unwrap();
+            black_box(inputs)
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, partition_public_inputs_benchmark);
+criterion_main!(benches);
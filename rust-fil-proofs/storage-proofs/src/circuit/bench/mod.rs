@@ -1,8 +1,15 @@
+use std::collections::HashMap;
 use std::marker::PhantomData;
+use std::sync::Mutex;
 
 use bellperson::{ConstraintSystem, Index, LinearCombination, SynthesisError, Variable};
+use lazy_static::lazy_static;
 use paired::Engine;
 
+lazy_static! {
+    static ref CONSTRAINT_COUNTS: Mutex<HashMap<String, usize>> = Default::default();
+}
+
 #[derive(Debug)]
 pub struct BenchCS<E: Engine> {
     inputs: usize,
@@ -93,3 +100,78 @@ impl<E: Engine> ConstraintSystem<E> for BenchCS<E> {
         self
     }
 }
+
+/// Counts the constraints `synthesize` allocates into a fresh `BenchCS`, memoized by
+/// `params_fingerprint`, so the same circuit is only synthesized once per process.
+///
+/// The fingerprint must identify both the circuit and every parameter its size depends on,
+/// e.g. `ParameterSetMetadata::identifier` of its public params: a changed configuration
+/// then yields a new fingerprint instead of a stale count.
+pub fn constraint_count<E, F>(
+    params_fingerprint: &str,
+    synthesize: F,
+) -> Result<usize, SynthesisError>
+where
+    E: Engine,
+    F: FnOnce(&mut BenchCS<E>) -> Result<(), SynthesisError>,
+{
+    if let Some(count) = CONSTRAINT_COUNTS.lock().unwrap().get(params_fingerprint) {
+        return Ok(*count);
+    }
+
+    // Synthesizing can be slow, so do not hold the lock meanwhile.
+    let mut cs = BenchCS::<E>::new();
+    synthesize(&mut cs)?;
+    let count = cs.num_constraints();
+
+    CONSTRAINT_COUNTS
+        .lock()
+        .unwrap()
+        .insert(params_fingerprint.to_string(), count);
+
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use paired::bls12_381::Bls12;
+
+    fn synthesize_constraints(
+        cs: &mut BenchCS<Bls12>,
+        constraints: usize,
+    ) -> Result<(), SynthesisError> {
+        for i in 0..constraints {
+            cs.enforce(|| format!("constraint {}", i), |lc| lc, |lc| lc, |lc| lc);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_constraint_count_is_memoized() {
+        let mut synthesized = 0;
+
+        let first = constraint_count::<Bls12, _>("test_constraint_count_is_memoized", |cs| {
+            synthesized += 1;
+            synthesize_constraints(cs, 3)
+        })
+        .unwrap();
+        let second = constraint_count::<Bls12, _>("test_constraint_count_is_memoized", |cs| {
+            synthesized += 1;
+            synthesize_constraints(cs, 3)
+        })
+        .unwrap();
+
+        assert_eq!(first, 3);
+        assert_eq!(first, second);
+        assert_eq!(synthesized, 1, "the second call must be a cache hit");
+
+        // Other parameters have their own count.
+        let other = constraint_count::<Bls12, _>("test_constraint_count_is_memoized 2", |cs| {
+            synthesize_constraints(cs, 5)
+        })
+        .unwrap();
+        assert_eq!(other, 5);
+    }
+}
@@ -366,6 +366,7 @@ mod tests {
             sector_size,
             challenge_count: 40,
             challenged_nodes: 1,
+            read_chunk_bytes: None,
         };
 
         let mut sectors: Vec<SectorId> = Vec::new();
@@ -527,6 +528,7 @@ mod tests {
                 sector_size,
                 challenge_count: 40,
                 challenged_nodes: 1,
+                read_chunk_bytes: None,
             },
             partitions: None,
             priority: true,
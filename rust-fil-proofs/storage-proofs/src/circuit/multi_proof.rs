@@ -1,10 +1,26 @@
 use bellperson::groth16;
 
 use crate::error::Result;
-use anyhow::Context;
+use anyhow::{ensure, Context};
 use paired::Engine;
+use std::hash::{Hash, Hasher};
 use std::io::{self, Read, Write};
 
+/// The format version `write_versioned` prepends to a serialized proof.
+pub const PROOF_FORMAT_VERSION: u8 = 1;
+
+/// The first byte of a compressed point always has this flag set, which tells a proof
+/// written without a version byte apart from a versioned one. Versions stay below it.
+const COMPRESSED_POINT_FLAG: u8 = 0x80;
+
+/// Returns whether `bytes` start with a format version byte, rather than with the first
+/// partition proof of a proof written before versioning.
+pub fn is_versioned(bytes: &[u8]) -> bool {
+    bytes
+        .first()
+        .map_or(false, |byte| byte & COMPRESSED_POINT_FLAG == 0)
+}
+
 #[derive(Clone,Debug)]
 pub struct MultiProof<'a, E: Engine> {
     pub circuit_proofs: Vec<groth16::Proof<E>>,
@@ -22,6 +38,9 @@ impl<'a, E: Engine> MultiProof<'a, E> {
         }
     }
 
+    /// Reads a proof of `partitions` partition proofs, written either by `write_versioned`
+    /// or, without a version byte, by `write`. A proof of an unsupported format version is
+    /// rejected instead of misparsed.
     pub fn new_from_reader<R: Read>(
         partitions: Option<usize>,
         mut reader: R,
@@ -31,6 +50,22 @@ impl<'a, E: Engine> MultiProof<'a, E> {
             Some(n) => n,
             None => 1,
         };
+        ensure!(
+            num_proofs > 0,
+            "a multi proof must contain at least one proof"
+        );
+
+        let mut first = [0u8; 1];
+        reader.read_exact(&mut first)?;
+        // Without a version byte, the byte read belongs to the first partition proof.
+        let prefix: &[u8] = if is_versioned(&first) {
+            ensure_supported_version(first[0])?;
+            &[]
+        } else {
+            &first
+        };
+        let mut reader = prefix.chain(reader);
+
         let proofs = (0..num_proofs)
             .map(|_| groth16::Proof::read(&mut reader))
             .collect::<io::Result<Vec<_>>>()?;
@@ -38,6 +73,11 @@ impl<'a, E: Engine> MultiProof<'a, E> {
         Ok(Self::new(proofs, verifying_key))
     }
 
+    /// The number of partition proofs this proof holds.
+    pub fn partition_count(&self) -> usize {
+        self.circuit_proofs.len()
+    }
+
     pub fn write<W: Write>(&self, mut writer: W) -> Result<()> {
         for proof in &self.circuit_proofs {
             proof.write(&mut writer)?
@@ -45,9 +85,251 @@ impl<'a, E: Engine> MultiProof<'a, E> {
         Ok(())
     }
 
+    /// Writes the proof prefixed with the format `version`, which must be supported by
+    /// `new_from_reader`.
+    pub fn write_versioned<W: Write>(&self, mut writer: W, version: u8) -> Result<()> {
+        ensure_supported_version(version)?;
+        writer.write_all(&[version])?;
+        self.write(writer)
+    }
+
+    /// Splits the serialized proof into `n` shards of roughly equal size, which
+    /// `merge_from` reassembles. When there are at least `n` partitions, each shard holds
+    /// whole partition proofs; otherwise the bytes are split evenly.
+    pub fn split_into(&self, n: usize) -> Result<Vec<Vec<u8>>> {
+        ensure!(n > 0, "cannot split a proof into zero shards");
+
+        let bytes = self.to_vec()?;
+        let partitions = self.partition_count();
+        let boundary = |i: usize| {
+            if n <= partitions {
+                (i * partitions / n) * (bytes.len() / partitions)
+            } else {
+                i * bytes.len() / n
+            }
+        };
+
+        Ok((0..n)
+            .map(|i| bytes[boundary(i)..boundary(i + 1)].to_vec())
+            .collect())
+    }
+
+    /// Reassembles a proof of `partitions` partition proofs from the `shards` returned by
+    /// `split_into`, in order. Fails unless the shards hold exactly that many valid proofs.
+    pub fn merge_from<S: AsRef<[u8]>>(
+        shards: &[S],
+        partitions: usize,
+        verifying_key: &'a groth16::VerifyingKey<E>,
+    ) -> Result<Self> {
+        let bytes: Vec<u8> = shards
+            .iter()
+            .flat_map(|shard| shard.as_ref().iter().copied())
+            .collect();
+
+        let mut reader = &bytes[..];
+        let proof = Self::new_from_reader(Some(partitions), &mut reader, verifying_key)?;
+        ensure!(
+            reader.is_empty(),
+            "shards hold {} bytes beyond {} partition proofs",
+            reader.len(),
+            partitions
+        );
+
+        Ok(proof)
+    }
+
     pub fn to_vec(&self) -> Result<Vec<u8>> {
         let mut out = Vec::new();
         self.write(&mut out).context("known allocation target")?;
         Ok(out)
     }
+
+    /// Returns a blake2s digest over the serialized `circuit_proofs`, suitable for
+    /// deduplicating proofs by content.
+    pub fn content_hash(&self) -> [u8; 32] {
+        let mut hash = [0u8; 32];
+        hash.copy_from_slice(blake2s_simd::blake2s(&self.proof_bytes()).as_bytes());
+        hash
+    }
+
+    fn proof_bytes(&self) -> Vec<u8> {
+        self.to_vec().expect("writing to a vec cannot fail")
+    }
+}
+
+fn ensure_supported_version(version: u8) -> Result<()> {
+    ensure!(
+        version > 0 && version <= PROOF_FORMAT_VERSION,
+        "unsupported proof format version {}, this build supports versions 1 to {}",
+        version,
+        PROOF_FORMAT_VERSION
+    );
+
+    Ok(())
+}
+
+/// Two `MultiProof`s are equal if their serialized `circuit_proofs` are identical.
+/// The `verifying_key` is not compared.
+impl<'a, E: Engine> PartialEq for MultiProof<'a, E> {
+    fn eq(&self, other: &Self) -> bool {
+        self.proof_bytes() == other.proof_bytes()
+    }
+}
+
+impl<'a, E: Engine> Eq for MultiProof<'a, E> {}
+
+impl<'a, E: Engine> Hash for MultiProof<'a, E> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.proof_bytes().hash(state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use bellperson::gadgets::num::AllocatedNum;
+    use bellperson::groth16::{create_random_proof, generate_random_parameters};
+    use bellperson::{Circuit, ConstraintSystem, SynthesisError};
+    use ff::Field;
+    use paired::bls12_381::{Bls12, Fr};
+    use rand::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+
+    struct SquareCircuit {
+        x: Option<Fr>,
+    }
+
+    impl Circuit<Bls12> for SquareCircuit {
+        fn synthesize<CS: ConstraintSystem<Bls12>>(
+            self,
+            cs: &mut CS,
+        ) -> std::result::Result<(), SynthesisError> {
+            let x = AllocatedNum::alloc(cs.namespace(|| "x"), || {
+                self.x.ok_or(SynthesisError::AssignmentMissing)
+            })?;
+            let x2 = x.square(cs.namespace(|| "x^2"))?;
+            x2.inputize(cs.namespace(|| "x^2 input"))?;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_multi_proof_eq_and_content_hash() {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+
+        let params =
+            generate_random_parameters::<Bls12, _, _>(SquareCircuit { x: None }, rng).unwrap();
+        let prove = |rng: &mut XorShiftRng| {
+            create_random_proof(SquareCircuit { x: Some(Fr::one()) }, &params, rng).unwrap()
+        };
+
+        let proof = MultiProof::new(vec![prove(rng)], &params.vk);
+        let bytes = proof.to_vec().unwrap();
+
+        let a = MultiProof::<Bls12>::new_from_reader(Some(1), &bytes[..], &params.vk).unwrap();
+        let b = MultiProof::<Bls12>::new_from_reader(Some(1), &bytes[..], &params.vk).unwrap();
+        assert_eq!(a, b);
+        assert_eq!(a.content_hash(), b.content_hash());
+        assert_eq!(a.partition_count(), 1);
+
+        let other = MultiProof::new(vec![prove(rng)], &params.vk);
+        assert_ne!(a, other);
+        assert_ne!(a.content_hash(), other.content_hash());
+    }
+
+    #[test]
+    fn test_multi_proof_split_and_merge() {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+
+        let params =
+            generate_random_parameters::<Bls12, _, _>(SquareCircuit { x: None }, rng).unwrap();
+        let proofs = (0..4)
+            .map(|_| {
+                create_random_proof(SquareCircuit { x: Some(Fr::one()) }, &params, rng).unwrap()
+            })
+            .collect();
+        let proof = MultiProof::new(proofs, &params.vk);
+        let proof_len = proof.to_vec().unwrap().len() / 4;
+
+        let shards = proof.split_into(3).unwrap();
+        let lens: Vec<usize> = shards.iter().map(Vec::len).collect();
+        assert_eq!(lens, vec![proof_len, proof_len, 2 * proof_len]);
+        assert_eq!(
+            MultiProof::merge_from(&shards, 4, &params.vk).unwrap(),
+            proof
+        );
+
+        // More shards than partitions split the bytes instead.
+        let shards = proof.split_into(8).unwrap();
+        assert!(shards.iter().all(|shard| shard.len() == proof_len / 2));
+        assert_eq!(
+            MultiProof::merge_from(&shards, 4, &params.vk).unwrap(),
+            proof
+        );
+
+        assert!(proof.split_into(0).is_err());
+        assert!(MultiProof::merge_from(&shards, 3, &params.vk).is_err());
+        assert!(MultiProof::merge_from(&shards[1..], 4, &params.vk).is_err());
+    }
+
+    #[test]
+    fn test_multi_proof_format_version() {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+
+        let params =
+            generate_random_parameters::<Bls12, _, _>(SquareCircuit { x: None }, rng).unwrap();
+        let proofs = (0..2)
+            .map(|_| {
+                create_random_proof(SquareCircuit { x: Some(Fr::one()) }, &params, rng).unwrap()
+            })
+            .collect();
+        let proof = MultiProof::new(proofs, &params.vk);
+        let read = |bytes: &[u8]| MultiProof::<Bls12>::new_from_reader(Some(2), bytes, &params.vk);
+
+        let mut versioned = Vec::new();
+        proof.write_versioned(&mut versioned, 1).unwrap();
+        assert_eq!(versioned[0], PROOF_FORMAT_VERSION);
+        assert_eq!(&versioned[1..], &proof.to_vec().unwrap()[..]);
+        assert!(is_versioned(&versioned));
+        assert_eq!(read(&versioned).unwrap(), proof);
+
+        // Proofs written before versioning are still read.
+        let unversioned = proof.to_vec().unwrap();
+        assert!(!is_versioned(&unversioned));
+        assert_eq!(read(&unversioned).unwrap(), proof);
+
+        let mut future = versioned.clone();
+        future[0] = PROOF_FORMAT_VERSION + 1;
+        let err = read(&future).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("unsupported proof format version 2"));
+
+        assert!(proof.write_versioned(Vec::new(), 0).is_err());
+        assert!(proof
+            .write_versioned(Vec::new(), PROOF_FORMAT_VERSION + 1)
+            .is_err());
+    }
+
+    #[test]
+    fn test_multi_proof_from_malformed_bytes() {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+
+        let params =
+            generate_random_parameters::<Bls12, _, _>(SquareCircuit { x: None }, rng).unwrap();
+        let proof =
+            create_random_proof(SquareCircuit { x: Some(Fr::one()) }, &params, rng).unwrap();
+        let bytes = MultiProof::new(vec![proof], &params.vk).to_vec().unwrap();
+
+        let read = |partitions, bytes: &[u8]| {
+            MultiProof::<Bls12>::new_from_reader(partitions, bytes, &params.vk)
+        };
+
+        assert!(read(Some(0), &bytes).is_err());
+        assert!(read(Some(2), &bytes).is_err());
+        assert!(read(Some(1), &bytes[..bytes.len() - 1]).is_err());
+        assert!(read(Some(1), &[0u8; 192][..]).is_err());
+        assert!(read(Some(1), &[0xffu8; 192][..]).is_err());
+    }
 }
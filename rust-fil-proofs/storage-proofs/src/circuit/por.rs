@@ -249,6 +249,7 @@ mod tests {
     use rand::SeedableRng;
     use rand_xorshift::XorShiftRng;
 
+    use crate::circuit::multi_proof::MultiProof;
     use crate::circuit::test::*;
     use crate::compound_proof;
     use crate::crypto::pedersen::JJ_PARAMS;
@@ -259,6 +260,48 @@ mod tests {
     use crate::proof::ProofScheme;
     use crate::util::data_at_node;
 
+    #[test]
+    fn por_partition_public_inputs() {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+        let leaves = 8;
+        let data: Vec<u8> = (0..leaves)
+            .flat_map(|_| fr_into_bytes::<Bls12>(&Fr::random(rng)))
+            .collect();
+        let graph = BucketGraph::<PedersenHasher>::new(leaves, BASE_DEGREE, 0, new_seed()).unwrap();
+        let tree = graph.merkle_tree(None, data.as_slice()).unwrap();
+
+        let public_inputs = merklepor::PublicInputs {
+            challenge: 3,
+            commitment: Some(tree.root()),
+        };
+        let setup_params = merklepor::SetupParams {
+            leaves,
+            private: false,
+        };
+        let public_params = merklepor::MerklePoR::<PedersenHasher>::setup(&setup_params).unwrap();
+
+        for partition_count in 1..=2 {
+            let expected: Vec<_> = (0..partition_count)
+                .map(|k| {
+                    PoRCompound::<PedersenHasher>::generate_public_inputs(
+                        &public_inputs,
+                        &public_params,
+                        Some(k),
+                    )
+                    .unwrap()
+                })
+                .collect();
+            let inputs = PoRCompound::<PedersenHasher>::partition_public_inputs(
+                &public_inputs,
+                &public_params,
+                partition_count,
+            )
+            .unwrap();
+
+            assert_eq!(expected, inputs);
+        }
+    }
+
     #[test]
     #[ignore] // Slow test – run only when compiled for release.
     fn por_test_compound() {
@@ -332,6 +375,130 @@ mod tests {
         }
     }
 
+    #[test]
+    #[ignore] // Slow test – run only when compiled for release.
+    fn por_test_circuit_proofs_streaming() {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+        let leaves = 8;
+        let data: Vec<u8> = (0..leaves)
+            .flat_map(|_| fr_into_bytes::<Bls12>(&Fr::random(rng)))
+            .collect();
+        let graph = BucketGraph::<PedersenHasher>::new(leaves, BASE_DEGREE, 0, new_seed()).unwrap();
+        let tree = graph.merkle_tree(None, data.as_slice()).unwrap();
+
+        let public_inputs = merklepor::PublicInputs {
+            challenge: 3,
+            commitment: Some(tree.root()),
+        };
+        let public_params = merklepor::PublicParams {
+            leaves,
+            private: false,
+        };
+        let private_inputs = merklepor::PrivateInputs::<PedersenHasher>::new(
+            bytes_into_fr::<Bls12>(data_at_node(data.as_slice(), public_inputs.challenge).unwrap())
+                .expect("failed to create Fr from node data")
+                .into(),
+            &tree,
+        );
+
+        let vanilla_proofs = merklepor::MerklePoR::<PedersenHasher>::prove_all_partitions(
+            &public_params,
+            &public_inputs,
+            &private_inputs,
+            2,
+        )
+        .expect("failed to generate partition proofs");
+        let gparams = PoRCompound::<PedersenHasher>::groth_params(&public_params)
+            .expect("failed to generate groth params");
+
+        let batch = PoRCompound::<PedersenHasher>::circuit_proofs_with_rng(
+            &public_inputs,
+            vanilla_proofs.clone(),
+            &public_params,
+            &gparams,
+            false,
+            &mut XorShiftRng::from_seed(crate::TEST_SEED),
+        )
+        .expect("failed to create batch proofs");
+
+        let mut streamed = Vec::new();
+        PoRCompound::<PedersenHasher>::circuit_proofs_streaming_with_rng(
+            &public_inputs,
+            vanilla_proofs,
+            &public_params,
+            &gparams,
+            false,
+            &mut XorShiftRng::from_seed(crate::TEST_SEED),
+            &mut |k, proof| {
+                assert_eq!(k, streamed.len(), "partitions must be streamed in order");
+                streamed.push(proof.clone());
+                Ok(())
+            },
+        )
+        .expect("failed to stream proofs");
+
+        assert_eq!(batch.len(), 2);
+        assert_eq!(batch, streamed);
+    }
+
+    #[test]
+    #[ignore] // Slow test – run only when compiled for release.
+    fn por_test_compound_split_and_merge() {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+        let leaves = 8;
+        let data: Vec<u8> = (0..leaves)
+            .flat_map(|_| fr_into_bytes::<Bls12>(&Fr::random(rng)))
+            .collect();
+        let graph = BucketGraph::<PedersenHasher>::new(leaves, BASE_DEGREE, 0, new_seed()).unwrap();
+        let tree = graph.merkle_tree(None, data.as_slice()).unwrap();
+
+        let public_inputs = merklepor::PublicInputs {
+            challenge: 3,
+            commitment: Some(tree.root()),
+        };
+        let setup_params = compound_proof::SetupParams {
+            vanilla_params: merklepor::SetupParams {
+                leaves,
+                private: false,
+            },
+            partitions: Some(4),
+            priority: false,
+        };
+        let public_params =
+            PoRCompound::<PedersenHasher>::setup(&setup_params).expect("setup failed");
+        let private_inputs = merklepor::PrivateInputs::<PedersenHasher>::new(
+            bytes_into_fr::<Bls12>(data_at_node(data.as_slice(), public_inputs.challenge).unwrap())
+                .expect("failed to create Fr from node data")
+                .into(),
+            &tree,
+        );
+        let gparams = PoRCompound::<PedersenHasher>::groth_params(&public_params.vanilla_params)
+            .expect("failed to generate groth params");
+
+        let proof = PoRCompound::<PedersenHasher>::prove(
+            &public_params,
+            &public_inputs,
+            &private_inputs,
+            &gparams,
+        )
+        .expect("failed while proving");
+
+        let shards = proof.split_into(3).expect("failed to split proof");
+        assert_eq!(shards.len(), 3);
+        let merged = MultiProof::merge_from(&shards, 4, proof.verifying_key)
+            .expect("failed to merge shards");
+        assert_eq!(merged, proof);
+
+        let verified = PoRCompound::<PedersenHasher>::verify(
+            &public_params,
+            &public_inputs,
+            &merged,
+            &NoRequirements,
+        )
+        .expect("failed while verifying");
+        assert!(verified);
+    }
+
     #[test]
     fn test_por_input_circuit_with_bls12_381_pedersen() {
         test_por_input_circuit_with_bls12_381::<PedersenHasher>(4125);
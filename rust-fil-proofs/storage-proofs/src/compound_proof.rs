@@ -1,13 +1,17 @@
+use std::path::Path;
+
 use rayon::prelude::*;
 
 use anyhow::{ensure, Context};
 use bellperson::{groth16, Circuit};
+use ff::Field;
 use fil_sapling_crypto::jubjub::JubjubEngine;
 use log::info;
 use rand::rngs::OsRng;
+use rand::RngCore;
 
 use crate::circuit::multi_proof::MultiProof;
-use crate::error::Result;
+use crate::error::{Error, Result};
 use crate::parameter_cache::{CacheableParameters, ParameterSetMetadata};
 use crate::partitions;
 use crate::proof::ProofScheme;
@@ -78,6 +82,21 @@ pub trait CompoundProof<
         priv_in: &S::PrivateInputs,
         groth_params: &'b groth16::MappedParameters<E>,
     ) -> Result<MultiProof<'b, E>>
+    where
+        E::Params: Sync,
+    {
+        Self::prove_with_rng(pub_params, pub_in, priv_in, groth_params, &mut OsRng)
+    }
+
+    /// Like `prove`, but draws the groth proof randomness from `rng`. A seeded `rng` makes
+    /// the proofs reproducible, which also makes them linkable, so only use one for tests.
+    fn prove_with_rng<'b, R: RngCore>(
+        pub_params: &PublicParams<'a, S>,
+        pub_in: &S::PublicInputs,
+        priv_in: &S::PrivateInputs,
+        groth_params: &'b groth16::MappedParameters<E>,
+        rng: &mut R,
+    ) -> Result<MultiProof<'b, E>>
     where
         E::Params: Sync,
     {
@@ -98,12 +117,13 @@ pub trait CompoundProof<
         ensure!(sanity_check, "sanity check failed");
 
         info!("snark_proof:start");
-        let groth_proofs = Self::circuit_proofs(
+        let groth_proofs = Self::circuit_proofs_with_rng(
             pub_in,
             vanilla_proofs,
             &pub_params.vanilla_params,
             groth_params,
             pub_params.priority,
+            rng,
         )?;
         info!("snark_proof:finish");
 
@@ -137,10 +157,11 @@ pub trait CompoundProof<
             return Ok(false);
         }
 
-        let inputs: Vec<_> = (0..multi_proof.circuit_proofs.len())
-            .into_par_iter()
-            .map(|k| Self::generate_public_inputs(public_inputs, vanilla_public_params, Some(k)))
-            .collect::<Result<_>>()?;
+        let inputs = Self::partition_public_inputs(
+            public_inputs,
+            vanilla_public_params,
+            multi_proof.circuit_proofs.len(),
+        )?;
         let proofs: Vec<_> = multi_proof.circuit_proofs.iter().collect();
         //println!("multi_proof.circuit_proofs = {:?}",proofs);
         let res = groth16::verify_proofs_batch(&pvk, &mut rand::rngs::OsRng, &proofs, &inputs)?;
@@ -151,6 +172,27 @@ pub trait CompoundProof<
         Ok(res)
     }
 
+    /// Generates the public inputs of each of `partition_count` partitions.
+    /// A single partition, the common case, is computed directly rather than through rayon.
+    fn partition_public_inputs(
+        public_inputs: &S::PublicInputs,
+        vanilla_public_params: &S::PublicParams,
+        partition_count: usize,
+    ) -> Result<Vec<Vec<E::Fr>>> {
+        if partition_count == 1 {
+            return Ok(vec![Self::generate_public_inputs(
+                public_inputs,
+                vanilla_public_params,
+                Some(0),
+            )?]);
+        }
+
+        (0..partition_count)
+            .into_par_iter()
+            .map(|k| Self::generate_public_inputs(public_inputs, vanilla_public_params, Some(k)))
+            .collect()
+    }
+
     /// Efficiently verify multiple proofs.
     fn batch_verify<'b>(
         public_params: &PublicParams<'a, S>,
@@ -184,23 +226,27 @@ pub trait CompoundProof<
             }
         }
 
-        let inputs: Vec<_> = multi_proofs
-            .par_iter()
-            .zip(public_inputs.par_iter())
-            .flat_map(|(multi_proof, pub_inputs)| {
-                (0..multi_proof.circuit_proofs.len())
-                    .into_par_iter()
-                    .map(|k| {
-                        Self::generate_public_inputs(pub_inputs, vanilla_public_params, Some(k))
-                    })
-                    .collect::<Result<Vec<_>>>()
-                    .expect("Invalid public inputs") // TODO: improve error handling
-            })
-            .collect::<Vec<_>>();
-        let circuit_proofs: Vec<_> = multi_proofs
-            .iter()
-            .flat_map(|m| m.circuit_proofs.iter())
-            .collect();
+        // Every proof has been checked to hold `partition_count` circuit proofs.
+        let circuit_proof_count =
+            batch_circuit_proof_count(multi_proofs.len(), Self::partition_count(public_params))?;
+
+        let mut inputs = Vec::with_capacity(circuit_proof_count);
+        inputs.par_extend(
+            multi_proofs
+                .par_iter()
+                .zip(public_inputs.par_iter())
+                .flat_map(|(multi_proof, pub_inputs)| {
+                    (0..multi_proof.circuit_proofs.len())
+                        .into_par_iter()
+                        .map(|k| {
+                            Self::generate_public_inputs(pub_inputs, vanilla_public_params, Some(k))
+                        })
+                        .collect::<Result<Vec<_>>>()
+                        .expect("Invalid public inputs") // TODO: improve error handling
+                }),
+        );
+        let mut circuit_proofs = Vec::with_capacity(circuit_proof_count);
+        circuit_proofs.extend(multi_proofs.iter().flat_map(|m| m.circuit_proofs.iter()));
 
         let res = groth16::verify_proofs_batch(
             &pvk,
@@ -223,7 +269,25 @@ pub trait CompoundProof<
         groth_params: &groth16::MappedParameters<E>,
         priority: bool,
     ) -> Result<Vec<groth16::Proof<E>>> {
-        let mut rng = OsRng;
+        Self::circuit_proofs_with_rng(
+            pub_in,
+            vanilla_proof,
+            pub_params,
+            groth_params,
+            priority,
+            &mut OsRng,
+        )
+    }
+
+    /// Like `circuit_proofs`, but draws the groth proof randomness from `rng`.
+    fn circuit_proofs_with_rng<R: RngCore>(
+        pub_in: &S::PublicInputs,
+        vanilla_proof: Vec<S::Proof>,
+        pub_params: &S::PublicParams,
+        groth_params: &groth16::MappedParameters<E>,
+        priority: bool,
+        rng: &mut R,
+    ) -> Result<Vec<groth16::Proof<E>>> {
         println!("circuit_proofs start");
 
         let circuits = vanilla_proof
@@ -241,9 +305,9 @@ pub trait CompoundProof<
             .collect::<Result<Vec<_>>>()?;
 
         let groth_proofs = if priority {
-            groth16::create_random_proof_batch_in_priority(circuits, groth_params, &mut rng)?
+            groth16::create_random_proof_batch_in_priority(circuits, groth_params, rng)?
         } else {
-            groth16::create_random_proof_batch(circuits, groth_params, &mut rng)?
+            groth16::create_random_proof_batch(circuits, groth_params, rng)?
         };
        // println!("groth_proofs={:?}",groth_proofs);
 
@@ -260,6 +324,100 @@ pub trait CompoundProof<
             .collect()
     }
 
+    /// Like `circuit_proofs`, but hands each partition's proof to `sink` as soon as it is
+    /// created, so callers can persist or send it while the remaining partitions are proven.
+    fn circuit_proofs_streaming(
+        pub_in: &S::PublicInputs,
+        vanilla_proof: Vec<S::Proof>,
+        pub_params: &S::PublicParams,
+        groth_params: &groth16::MappedParameters<E>,
+        priority: bool,
+        sink: &mut dyn FnMut(usize, &groth16::Proof<E>) -> Result<()>,
+    ) -> Result<()> {
+        Self::circuit_proofs_streaming_with_rng(
+            pub_in,
+            vanilla_proof,
+            pub_params,
+            groth_params,
+            priority,
+            &mut OsRng,
+            sink,
+        )
+    }
+
+    /// Like `circuit_proofs_streaming`, but draws the groth proof randomness from `rng`, in the
+    /// same order as `circuit_proofs_with_rng` does.
+    fn circuit_proofs_streaming_with_rng<R: RngCore>(
+        pub_in: &S::PublicInputs,
+        vanilla_proof: Vec<S::Proof>,
+        pub_params: &S::PublicParams,
+        groth_params: &groth16::MappedParameters<E>,
+        priority: bool,
+        rng: &mut R,
+        sink: &mut dyn FnMut(usize, &groth16::Proof<E>) -> Result<()>,
+    ) -> Result<()> {
+        let partitions = vanilla_proof.len();
+        let r_s: Vec<E::Fr> = (0..partitions).map(|_| E::Fr::random(rng)).collect();
+        let s_s: Vec<E::Fr> = (0..partitions).map(|_| E::Fr::random(rng)).collect();
+
+        for (k, ((vanilla_proof, r), s)) in vanilla_proof
+            .into_iter()
+            .zip(r_s.into_iter())
+            .zip(s_s.into_iter())
+            .enumerate()
+        {
+            let circuit = Self::circuit(
+                &pub_in,
+                C::ComponentPrivateInputs::default(),
+                &vanilla_proof,
+                &pub_params,
+            )?;
+
+            let groth_proof = if priority {
+                groth16::create_proof_in_priority(circuit, groth_params, r, s)?
+            } else {
+                groth16::create_proof(circuit, groth_params, r, s)?
+            };
+
+            sink(k, &groth_proof)?;
+        }
+
+        Ok(())
+    }
+
+    /// Like `circuit_proofs`, but calls `progress` with the number of partitions proven so far
+    /// and the total number of partitions as soon as each partition's proof is created, so
+    /// callers can report the progress of long proofs.
+    ///
+    /// The partitions are proven one after another, as by `circuit_proofs_streaming`, rather
+    /// than in a single batch.
+    fn circuit_proofs_with_progress(
+        pub_in: &S::PublicInputs,
+        vanilla_proof: Vec<S::Proof>,
+        pub_params: &S::PublicParams,
+        groth_params: &groth16::MappedParameters<E>,
+        priority: bool,
+        progress: &mut dyn FnMut(usize, usize),
+    ) -> Result<Vec<groth16::Proof<E>>> {
+        let partitions = vanilla_proof.len();
+        let mut groth_proofs = Vec::with_capacity(partitions);
+
+        Self::circuit_proofs_streaming(
+            pub_in,
+            vanilla_proof,
+            pub_params,
+            groth_params,
+            priority,
+            &mut |k, groth_proof| {
+                groth_proofs.push(groth_proof.clone());
+                progress(k + 1, partitions);
+                Ok(())
+            },
+        )?;
+
+        Ok(groth_proofs)
+    }
+
     /// generate_public_inputs generates public inputs suitable for use as input during verification
     /// of a proof generated from this CompoundProof's bellperson::Circuit (C). These inputs correspond
     /// to those allocated when C is synthesized.
@@ -290,6 +448,22 @@ pub trait CompoundProof<
         Self::get_verifying_key(Self::blank_circuit(public_params), public_params)
     }
 
+    /// Like `groth_params`, but the parameters live in `dir` instead of the parameter cache.
+    fn groth_params_from_dir(
+        public_params: &S::PublicParams,
+        dir: &Path,
+    ) -> Result<groth16::MappedParameters<E>> {
+        Self::get_groth_params_from_dir(Self::blank_circuit(public_params), public_params, dir)
+    }
+
+    /// Like `verifying_key`, but the key lives in `dir` instead of the parameter cache.
+    fn verifying_key_from_dir(
+        public_params: &S::PublicParams,
+        dir: &Path,
+    ) -> Result<groth16::VerifyingKey<E>> {
+        Self::get_verifying_key_from_dir(Self::blank_circuit(public_params), public_params, dir)
+    }
+
     fn circuit_for_test(
         public_parameters: &PublicParams<'a, S>,
         public_inputs: &S::PublicInputs,
@@ -332,3 +506,32 @@ pub trait CompoundProof<
         Ok((circuit, inputs))
     }
 }
+
+/// The number of circuit proofs in a batch of `batch_len` proofs of `partitions` partitions
+/// each. Batches are untrusted input, so a batch too large to count is an error rather than an
+/// overflow.
+fn batch_circuit_proof_count(batch_len: usize, partitions: usize) -> Result<usize> {
+    batch_len
+        .checked_mul(partitions)
+        .ok_or_else(|| Error::BatchTooLarge(batch_len, partitions).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn batch_circuit_proof_count_overflow() {
+        assert_eq!(batch_circuit_proof_count(3, 2).unwrap(), 6);
+        assert_eq!(batch_circuit_proof_count(0, usize::MAX).unwrap(), 0);
+
+        let err = batch_circuit_proof_count(usize::MAX / 2 + 1, 2).unwrap_err();
+        match err.downcast_ref::<Error>() {
+            Some(Error::BatchTooLarge(batch_len, partitions)) => {
+                assert_eq!(*batch_len, usize::MAX / 2 + 1);
+                assert_eq!(*partitions, 2);
+            }
+            other => panic!("expected BatchTooLarge, got {:?}", other),
+        }
+    }
+}
@@ -29,6 +29,9 @@ pub struct SetupParams {
     pub sector_size: u64,
     pub challenge_count: usize,
     pub challenged_nodes: usize,
+    /// The number of bytes of a challenged range read from disk at once, or the whole range
+    /// if `None`.
+    pub read_chunk_bytes: Option<usize>,
 }
 
 #[derive(Debug, Clone)]
@@ -37,6 +40,9 @@ pub struct PublicParams {
     pub sector_size: u64,
     pub challenge_count: usize,
     pub challenged_nodes: usize,
+    /// The number of bytes of a challenged range read from disk at once, or the whole range
+    /// if `None`. Only affects I/O, so it is not part of the parameter identifier.
+    pub read_chunk_bytes: Option<usize>,
 }
 
 impl ParameterSetMetadata for PublicParams {
@@ -72,7 +78,7 @@ pub struct PrivateInputs<H: Hasher> {
 }
 
 /// The candidate data, that is needed for ticket generation.
-#[derive(Clone, Serialize, Deserialize)]
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Candidate {
     pub sector_id: SectorId,
     pub partial_ticket: Fr,
@@ -173,23 +179,26 @@ fn generate_candidate<H: Hasher>(
     randomness: &[u8; 32],
     sector_challenge_index: u64,
 ) -> Result<Candidate> {
-    // 1. read the data for each challenge
-    let mut data = vec![0u8; pub_params.challenge_count * pub_params.challenged_nodes * NODE_SIZE];
-    for n in 0..pub_params.challenge_count {
+    // 1. read the data for each challenge, `chunk_nodes` nodes at a time
+    let range_bytes = pub_params.challenged_nodes * NODE_SIZE;
+    let chunk_nodes = pub_params
+        .read_chunk_bytes
+        .map_or(pub_params.challenged_nodes, |bytes| {
+            (bytes / NODE_SIZE).max(1)
+        });
+    let mut data = vec![0u8; pub_params.challenge_count * range_bytes];
+    for (n, range) in data.chunks_mut(range_bytes).enumerate() {
         let challenge_start =
             generate_leaf_challenge(pub_params, randomness, sector_challenge_index, n as u64)?;
 
-        let start = challenge_start as usize;
-        let end = start + pub_params.challenged_nodes;
+        for (i, chunk) in range.chunks_mut(chunk_nodes * NODE_SIZE).enumerate() {
+            let start = challenge_start as usize + i * chunk_nodes;
+            let end = start + chunk.len() / NODE_SIZE;
 
-        measure_op(Operation::PostReadChallengedRange, || {
-            tree.read_range_into(
-                start,
-                end,
-                &mut data[n * pub_params.challenged_nodes * NODE_SIZE
-                    ..(n + 1) * pub_params.challenged_nodes * NODE_SIZE],
-            )
-        })?;
+            measure_op(Operation::PostReadChallengedRange, || {
+                tree.read_range_into(start, end, chunk)
+            })?;
+        }
     }
 
     // 2. Ticket generation
@@ -320,6 +329,7 @@ impl<'a, H: 'a + Hasher> ProofScheme<'a> for ElectionPoSt<'a, H> {
             sector_size: sp.sector_size,
             challenge_count: sp.challenge_count,
             challenged_nodes: sp.challenged_nodes,
+            read_chunk_bytes: sp.read_chunk_bytes,
         })
     }
 
@@ -448,6 +458,7 @@ mod tests {
             sector_size,
             challenge_count: 40,
             challenged_nodes: 1,
+            read_chunk_bytes: None,
         };
 
         let randomness: [u8; 32] = rng.gen();
@@ -532,4 +543,61 @@ mod tests {
     fn election_post_poseidon() {
         test_election_post::<PoseidonHasher>();
     }
+
+    #[test]
+    fn generate_candidates_read_chunk_bytes() {
+        use merkletree::store::{StoreConfig, StoreConfigDataVersion};
+
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+
+        let leaves = 32;
+        let data: Vec<u8> = (0..leaves)
+            .flat_map(|_| fr_into_bytes::<Bls12>(&Fr::random(rng)))
+            .collect();
+
+        let temp_dir = tempdir::TempDir::new("read_chunk_bytes").unwrap();
+        let config = StoreConfig::new(
+            temp_dir.path(),
+            String::from("test-lc-tree"),
+            StoreConfig::default_cached_above_base_layer(leaves),
+        );
+        let graph = BucketGraph::<PedersenHasher>::new(leaves, BASE_DEGREE, 0, new_seed()).unwrap();
+        let mut tree = graph
+            .merkle_tree(Some(config.clone()), data.as_slice())
+            .unwrap();
+        assert!(tree
+            .compact(config.clone(), StoreConfigDataVersion::One as u32)
+            .unwrap());
+        let mut trees = BTreeMap::new();
+        trees.insert(
+            SectorId::from(0),
+            graph.lcmerkle_tree(Some(config), data.as_slice()).unwrap(),
+        );
+
+        let sectors = vec![SectorId::from(0); 3];
+        let prover_id: [u8; 32] = rng.gen();
+        let randomness: [u8; 32] = rng.gen();
+        let candidates = |read_chunk_bytes| {
+            let pub_params = PublicParams {
+                sector_size: (leaves * NODE_SIZE) as u64,
+                challenge_count: 5,
+                challenged_nodes: 4,
+                read_chunk_bytes,
+            };
+            generate_candidates::<PedersenHasher>(
+                &pub_params,
+                &sectors,
+                &trees,
+                &prover_id,
+                &randomness,
+            )
+            .unwrap()
+        };
+
+        let expected = candidates(None);
+        // Chunks of one node, of a size not dividing the range, and larger than the range.
+        for &chunk_nodes in &[1, 3, 64] {
+            assert_eq!(candidates(Some(chunk_nodes * NODE_SIZE)), expected);
+        }
+    }
 }
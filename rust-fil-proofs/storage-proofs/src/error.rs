@@ -1,4 +1,5 @@
 use std::any::Any;
+use std::path::PathBuf;
 
 use bellperson::SynthesisError;
 
@@ -37,6 +38,12 @@ pub enum Error {
     Unclassified(String),
     #[error("Missing Private Input {0} for sector {1}")]
     MissingPrivateInput(&'static str, u64),
+    #[error("cache_path {0:?} is locked by another seal operation")]
+    CacheLocked(PathBuf),
+    #[error("batch of {0} proofs of {1} partitions each is too large to verify")]
+    BatchTooLarge(usize, usize),
+    #[error("parameter file {0:?} has digest {2}, but {1} was expected")]
+    ParamDigestMismatch(PathBuf, String, String),
 }
 
 impl From<Box<dyn Any + Send>> for Error {
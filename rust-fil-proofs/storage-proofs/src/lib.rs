@@ -27,6 +27,7 @@ pub mod porep;
 pub mod proof;
 pub mod rational_post;
 pub mod sector;
+pub mod sector_log;
 pub mod settings;
 pub mod stacked;
 pub mod util;
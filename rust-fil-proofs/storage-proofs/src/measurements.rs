@@ -1,4 +1,6 @@
 #[cfg(feature = "measurements")]
+use std::io::Write;
+#[cfg(feature = "measurements")]
 use std::sync::mpsc::{channel, Receiver, Sender};
 #[cfg(feature = "measurements")]
 use std::sync::Mutex;
@@ -7,6 +9,8 @@ use std::time::Duration;
 #[cfg(feature = "measurements")]
 use std::time::{Duration, Instant};
 
+#[cfg(feature = "measurements")]
+use anyhow::Result;
 #[cfg(feature = "measurements")]
 use cpu_time::ProcessTime;
 
@@ -31,6 +35,9 @@ lazy_static! {
 #[serde(rename_all = "kebab-case")]
 pub struct OpMeasurement {
     pub op: Operation,
+    /// The layer an `EncodeLayerTotal` measurement belongs to, counted from 1.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub layer: Option<usize>,
     pub cpu_time: Duration,
     pub wall_time: Duration,
 }
@@ -42,6 +49,8 @@ pub enum Operation {
     GenerateTreeRLast,
     CommD,
     EncodeWindowTimeAll,
+    /// The encoding of a single layer, sent once per layer with `OpMeasurement::layer` set.
+    EncodeLayerTotal,
     WindowCommLeavesTime,
     PorepCommitTime,
     PostInclusionProofs,
@@ -52,6 +61,23 @@ pub enum Operation {
 
 #[cfg(feature = "measurements")]
 pub fn measure_op<T, F>(op: Operation, f: F) -> T
+where
+    F: FnOnce() -> T,
+{
+    measure_op_inner(op, None, f)
+}
+
+/// Measures the encoding of `layer` as an `EncodeLayerTotal` operation.
+#[cfg(feature = "measurements")]
+pub fn measure_layer_op<T, F>(layer: usize, f: F) -> T
+where
+    F: FnOnce() -> T,
+{
+    measure_op_inner(Operation::EncodeLayerTotal, Some(layer), f)
+}
+
+#[cfg(feature = "measurements")]
+fn measure_op_inner<T, F>(op: Operation, layer: Option<usize>, f: F) -> T
 where
     F: FnOnce() -> T,
 {
@@ -81,6 +107,7 @@ where
         tx.clone()
             .send(OpMeasurement {
                 op,
+                layer,
                 cpu_time: cpu_time_start.elapsed(),
                 wall_time: wall_start_time.elapsed(),
             })
@@ -90,6 +117,37 @@ where
     x
 }
 
+/// Drains the measurements sent to `OP_MEASUREMENTS` so far and writes them to `w` as CSV,
+/// one `operation,layer,cpu_time_ms,wall_time_ms` row per measurement after a header row. The
+/// `layer` is empty for the measurements of operations not done per layer.
+///
+/// Unlike consumers which drop the sending side to collect everything, this leaves the
+/// channel open, so it may be called again after later operations. The measurements it
+/// exports are consumed though: a later consumer of the channel, like `benchy flarp`, no longer
+/// receives them, so only one of them should be used in a process.
+#[cfg(feature = "measurements")]
+pub fn export_measurements_csv(w: &mut impl Write) -> Result<()> {
+    let measurements = OP_MEASUREMENTS
+        .1
+        .lock()
+        .expect("failed to acquire lock on rx side of perf channel");
+
+    writeln!(w, "operation,layer,cpu_time_ms,wall_time_ms")?;
+    for m in measurements.try_iter() {
+        let layer = m.layer.map(|layer| layer.to_string()).unwrap_or_default();
+        writeln!(
+            w,
+            "{:?},{},{},{}",
+            m.op,
+            layer,
+            m.cpu_time.as_millis(),
+            m.wall_time.as_millis()
+        )?;
+    }
+
+    Ok(())
+}
+
 #[cfg(not(feature = "measurements"))]
 pub fn measure_op<T, F>(_: Operation, f: F) -> T
 where
@@ -97,3 +155,11 @@ where
 {
     f()
 }
+
+#[cfg(not(feature = "measurements"))]
+pub fn measure_layer_op<T, F>(_: usize, f: F) -> T
+where
+    F: FnOnce() -> T,
+{
+    f()
+}
@@ -1,9 +1,11 @@
 #![allow(clippy::len_without_is_empty)]
 
+use std::fs::File;
 use std::marker::PhantomData;
 
 use anyhow::ensure;
 use log::trace;
+use memmap::{Mmap, MmapOptions};
 use merkletree::hash::Algorithm;
 use merkletree::merkle;
 use merkletree::proof;
@@ -250,6 +252,112 @@ pub fn create_merkle_tree<H: Hasher>(
     }
 }
 
+/// Page granularity required of `create_merkle_tree_windowed` windows, so that every window
+/// offset is a valid mmap offset.
+pub const MMAP_WINDOW_ALIGNMENT: usize = 4096;
+
+/// Construct a new merkle tree over the first `size` nodes of `file`, mapping at most
+/// `window_bytes` of it at a time instead of the whole file.
+///
+/// Leaves are produced sequentially, so this trades the parallel leaf conversion of
+/// `create_merkle_tree` for a bounded resident set. The resulting tree is identical.
+pub fn create_merkle_tree_windowed<H: Hasher>(
+    config: Option<StoreConfig>,
+    size: usize,
+    file: &File,
+    window_bytes: usize,
+) -> Result<MerkleTree<H::Domain, H::Function>> {
+    ensure!(
+        window_bytes > 0 && window_bytes % MMAP_WINDOW_ALIGNMENT == 0,
+        "mmap window must be a non-zero multiple of {} bytes, got {}",
+        MMAP_WINDOW_ALIGNMENT,
+        window_bytes
+    );
+    let file_len = file.metadata()?.len() as usize;
+    ensure!(
+        file_len >= NODE_SIZE * size,
+        Error::InvalidMerkleTreeArgs(file_len, NODE_SIZE, size)
+    );
+    trace!(
+        "create_merkle_tree_windowed called with size {} and window {}",
+        size,
+        window_bytes
+    );
+
+    let leaves = WindowedLeaves::<H> {
+        file,
+        window_bytes,
+        window: None,
+        window_start: 0,
+        node: 0,
+        size,
+        _h: PhantomData,
+    };
+
+    match config {
+        Some(x) => MerkleTree::try_from_iter_with_config(leaves, x),
+        None => MerkleTree::try_from_iter(leaves),
+    }
+}
+
+/// Yields the leaves of a file-backed tree, remapping a fixed-size window as it advances.
+struct WindowedLeaves<'a, H: Hasher> {
+    file: &'a File,
+    window_bytes: usize,
+    window: Option<Mmap>,
+    window_start: usize,
+    node: usize,
+    size: usize,
+    _h: PhantomData<H>,
+}
+
+impl<'a, H: Hasher> WindowedLeaves<'a, H> {
+    fn next_leaf(&mut self) -> Result<H::Domain> {
+        let start = self.node * NODE_SIZE;
+        let end = start + NODE_SIZE;
+        let mapped = match self.window {
+            Some(ref w) => start >= self.window_start && end <= self.window_start + w.len(),
+            None => false,
+        };
+        if !mapped {
+            // Drop the previous window before mapping the next one.
+            self.window = None;
+            self.window_start = start - start % self.window_bytes;
+            let len = std::cmp::min(self.window_bytes, self.size * NODE_SIZE - self.window_start);
+            let window = unsafe {
+                MmapOptions::new()
+                    .offset(self.window_start as u64)
+                    .len(len)
+                    .map(self.file)
+            }?;
+            self.window = Some(window);
+        }
+        let window = self.window.as_ref().expect("window mapped above");
+        let offset = start - self.window_start;
+
+        H::Domain::try_from_bytes(&window[offset..offset + NODE_SIZE])
+    }
+}
+
+impl<'a, H: Hasher> Iterator for WindowedLeaves<'a, H> {
+    type Item = Result<H::Domain>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.node >= self.size {
+            return None;
+        }
+        let leaf = self.next_leaf();
+        self.node += 1;
+
+        Some(leaf)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.size - self.node;
+        (remaining, Some(remaining))
+    }
+}
+
 /// Construct a new level cache merkle tree.
 pub fn create_lcmerkle_tree<H: Hasher>(
     config: Option<StoreConfig>,
@@ -345,4 +453,28 @@ mod tests {
     fn merklepath_blake2s() {
         merklepath::<Blake2sHasher>();
     }
+
+    #[test]
+    fn windowed_tree_matches_full_tree() {
+        // 512 nodes span four 4KiB windows.
+        let leafs = 512;
+        let mut rng = rand::thread_rng();
+        let mut data = Vec::new();
+        for _ in 0..leafs {
+            let elt = <PedersenHasher as Hasher>::Domain::random(&mut rng);
+            data.write(&elt.into_bytes()).unwrap();
+        }
+
+        let mut file = tempfile::tempfile().unwrap();
+        file.write_all(&data).unwrap();
+
+        let full = create_merkle_tree::<PedersenHasher>(None, leafs, &data).unwrap();
+        for window in &[MMAP_WINDOW_ALIGNMENT, 3 * MMAP_WINDOW_ALIGNMENT, data.len()] {
+            let windowed =
+                create_merkle_tree_windowed::<PedersenHasher>(None, leafs, &file, *window).unwrap();
+            assert_eq!(full.root(), windowed.root());
+        }
+
+        assert!(create_merkle_tree_windowed::<PedersenHasher>(None, leafs, &file, 100).is_err());
+    }
 }
@@ -90,7 +90,11 @@ pub fn parameter_cache_dir() -> PathBuf {
 }
 
 pub fn parameter_cache_params_path(parameter_set_identifier: &str) -> PathBuf {
-    let dir = Path::new(&parameter_cache_dir_name()).to_path_buf();
+    parameter_cache_params_path_in(&parameter_cache_dir(), parameter_set_identifier)
+}
+
+/// Like `parameter_cache_params_path`, but in `dir` instead of the parameter cache.
+pub fn parameter_cache_params_path_in(dir: &Path, parameter_set_identifier: &str) -> PathBuf {
     dir.join(format!(
         "v{}-{}.{}",
         VERSION, parameter_set_identifier, GROTH_PARAMETER_EXT
@@ -106,7 +110,14 @@ pub fn parameter_cache_metadata_path(parameter_set_identifier: &str) -> PathBuf
 }
 
 pub fn parameter_cache_verifying_key_path(parameter_set_identifier: &str) -> PathBuf {
-    let dir = Path::new(&parameter_cache_dir_name()).to_path_buf();
+    parameter_cache_verifying_key_path_in(&parameter_cache_dir(), parameter_set_identifier)
+}
+
+/// Like `parameter_cache_verifying_key_path`, but in `dir` instead of the parameter cache.
+pub fn parameter_cache_verifying_key_path_in(
+    dir: &Path,
+    parameter_set_identifier: &str,
+) -> PathBuf {
     dir.join(format!(
         "v{}-{}.{}",
         VERSION, parameter_set_identifier, VERIFYING_KEY_EXT
@@ -180,6 +191,16 @@ where
     }
 
     fn get_groth_params(circuit: C, pub_params: &P) -> Result<groth16::MappedParameters<E>> {
+        Self::get_groth_params_from_dir(circuit, pub_params, &parameter_cache_dir())
+    }
+
+    /// Like `get_groth_params`, but reads (or generates and writes) the parameters in `dir`
+    /// instead of the parameter cache.
+    fn get_groth_params_from_dir(
+        circuit: C,
+        pub_params: &P,
+        dir: &Path,
+    ) -> Result<groth16::MappedParameters<E>> {
         println!("get_groth_params pre start");
         let id = Self::cache_identifier(pub_params);
         println!("get_groth_params start");
@@ -207,7 +228,7 @@ where
         };
         
         // load or generate Groth parameter mappings
-        let cache_path = ensure_ancestor_dirs_exist(parameter_cache_params_path(&id))?;
+        let cache_path = ensure_ancestor_dirs_exist(parameter_cache_params_path_in(dir, &id))?;
         println!("cache_path={:?}",cache_path);
         let cache_result = read_cached_params(&cache_path);
         println!("cache_result finish");
@@ -230,16 +251,27 @@ where
     }
 
     fn get_verifying_key(circuit: C, pub_params: &P) -> Result<groth16::VerifyingKey<E>> {
+        Self::get_verifying_key_from_dir(circuit, pub_params, &parameter_cache_dir())
+    }
+
+    /// Like `get_verifying_key`, but reads (or generates and writes) the key in `dir` instead
+    /// of the parameter cache.
+    fn get_verifying_key_from_dir(
+        circuit: C,
+        pub_params: &P,
+        dir: &Path,
+    ) -> Result<groth16::VerifyingKey<E>> {
         let id = Self::cache_identifier(pub_params);
 
         let generate = || -> Result<groth16::VerifyingKey<E>> {
-            let groth_params = Self::get_groth_params(circuit, pub_params)?;
+            let groth_params = Self::get_groth_params_from_dir(circuit, pub_params, dir)?;
             info!("Getting verifying key. (id: {})", &id);
             Ok(groth_params.vk)
         };
 
         // generate (or load) verifying key
-        let cache_path = ensure_ancestor_dirs_exist(parameter_cache_verifying_key_path(&id))?;
+        let cache_path =
+            ensure_ancestor_dirs_exist(parameter_cache_verifying_key_path_in(dir, &id))?;
         read_cached_verifying_key(&cache_path)
             .or_else(|_| write_cached_verifying_key(&cache_path, generate()?))
     }
@@ -0,0 +1,46 @@
+//! The sector each thread is working on, which filecoin-proofs tags log records with.
+//!
+//! A `SectorSpan` only marks the thread it is entered on. Work a span hands to rayon runs on
+//! other threads, so the task re-enters it there with `SectorSpan::resume`, passing the
+//! `current_sector()` captured before handing the work over. Records logged on threads in no
+//! span concern no sector in particular.
+
+use std::cell::Cell;
+
+use crate::sector::SectorId;
+
+thread_local! {
+    static CURRENT_SECTOR: Cell<Option<SectorId>> = Cell::new(None);
+}
+
+/// Marks the current thread as working on a sector until dropped.
+#[must_use]
+pub struct SectorSpan {
+    previous: Option<SectorId>,
+}
+
+impl SectorSpan {
+    pub fn enter(sector_id: SectorId) -> Self {
+        Self::resume(Some(sector_id))
+    }
+
+    /// Re-enters, on a thread doing part of another thread's work, the span that thread was in
+    /// when it handed the work over, as returned by its `current_sector()`. `None` leaves the
+    /// current thread in no span until dropped.
+    pub fn resume(sector_id: Option<SectorId>) -> Self {
+        let previous = CURRENT_SECTOR.with(|current| current.replace(sector_id));
+
+        SectorSpan { previous }
+    }
+}
+
+impl Drop for SectorSpan {
+    fn drop(&mut self) {
+        CURRENT_SECTOR.with(|current| current.set(self.previous));
+    }
+}
+
+/// Returns the sector of the innermost `SectorSpan` entered on this thread, if any.
+pub fn current_sector() -> Option<SectorId> {
+    CURRENT_SECTOR.with(Cell::get)
+}
@@ -19,6 +19,9 @@ pub struct Settings {
     pub num_proving_threads: usize,
     pub replicated_trees_dir: String,
     pub pedersen_hash_exp_window_size: u32,
+    // Directory for the intermediate files created while sealing; an empty
+    // string means the system temp directory.
+    pub seal_temp_dir: String,
     // Generating MTs in parallel optimizes for speed while generating them
     // in sequence (`false`) optimizes for memory.
 }
@@ -31,6 +34,7 @@ impl Default for Settings {
             num_proving_threads: 1,
             replicated_trees_dir: "".into(),
             pedersen_hash_exp_window_size: 16,
+            seal_temp_dir: "".into(),
         }
     }
 }
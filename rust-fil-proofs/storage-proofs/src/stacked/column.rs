@@ -1,3 +1,5 @@
+use merkletree::merkle;
+use merkletree::store::Store;
 use paired::bls12_381::Fr;
 use serde::{Deserialize, Serialize};
 use std::marker::PhantomData;
@@ -5,7 +7,7 @@ use std::marker::PhantomData;
 use crate::error::Result;
 use crate::hasher::Hasher;
 use crate::merkle::MerkleProof;
-use crate::stacked::{column_proof::ColumnProof, hash::hash_single_column, params::Tree};
+use crate::stacked::{column_proof::ColumnProof, hash::hash_single_column};
 
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
 pub struct Column<H: Hasher> {
@@ -54,8 +56,11 @@ impl<H: Hasher> Column<H> {
         Ok(&self.rows[row_index])
     }
 
-    /// Create a column proof for this column.
-    pub fn into_proof(self, tree_c: &Tree<H>) -> Result<ColumnProof<H>> {
+    /// Create a column proof for this column, from a tree-c in any store.
+    pub fn into_proof<S: Store<H::Domain>>(
+        self,
+        tree_c: &merkle::MerkleTree<H::Domain, H::Function, S>,
+    ) -> Result<ColumnProof<H>> {
         let inclusion_proof =
             MerkleProof::new_from_proof(&tree_c.gen_proof(self.index() as usize)?);
         ColumnProof::<H>::from_column(self, inclusion_proof)
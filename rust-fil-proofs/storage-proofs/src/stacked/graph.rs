@@ -1,22 +1,31 @@
 use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Read, Write};
 use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, RwLock};
 
-use anyhow::ensure;
+use anyhow::{ensure, Context};
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 use lazy_static::lazy_static;
-use log::info;
+use log::{info, warn};
 use rayon::prelude::*;
 
 use crate::crypto::feistel::{self, FeistelPrecomputed};
 use crate::drgraph::{BucketGraph, Graph};
 use crate::error::Result;
 use crate::hasher::Hasher;
-use crate::parameter_cache::ParameterSetMetadata;
+use crate::parameter_cache::{parameter_cache_dir, ParameterSetMetadata};
 use crate::settings;
 use crate::util::{data_at_node_offset, NODE_SIZE};
 
 /// The expansion degree used for Stacked Graphs.
 pub const EXP_DEGREE: usize = 8;
+/// Bump this when the layout of persisted parents caches changes to invalidate them.
+pub const PARENT_CACHE_VERSION: u32 = 1;
+const PARENT_CACHE_EXT: &str = "parents";
+/// Number of cache entries converted per read when loading a persisted parents cache.
+const PARENT_CACHE_READ_CHUNK: usize = 1 << 16;
 const FEISTEL_KEYS: [feistel::Index; 4] = [1, 2, 3, 4];
 
 lazy_static! {
@@ -76,6 +85,77 @@ impl ParentCache {
         let end = start + self.degree;
         &self.cache[start..end]
     }
+
+    /// Write the cache, prefixed by the header of the graph it was built for.
+    fn write<W: Write>(&self, header: &ParentCacheHeader, writer: &mut W) -> Result<()> {
+        header.write(writer)?;
+        for parent in self.cache.iter() {
+            writer.write_u32::<LittleEndian>(*parent)?;
+        }
+
+        Ok(())
+    }
+
+    /// Read a cache written by `write`. Returns `None` if it was built for a different graph
+    /// or cache version, in which case it has to be regenerated.
+    fn read_from<R: Read>(header: &ParentCacheHeader, reader: &mut R) -> Result<Option<Self>> {
+        if ParentCacheHeader::read(reader)? != *header {
+            return Ok(None);
+        }
+
+        let degree = header.degree as usize;
+        let mut cache = vec![0u32; degree * header.nodes as usize];
+        for chunk in cache.chunks_mut(PARENT_CACHE_READ_CHUNK) {
+            reader.read_u32_into::<LittleEndian>(chunk)?;
+        }
+
+        Ok(Some(ParentCache {
+            cache: cache.into_boxed_slice(),
+            degree,
+        }))
+    }
+}
+
+/// Identifies the graph a persisted parents cache was generated from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ParentCacheHeader {
+    version: u32,
+    seed: [u8; 28],
+    nodes: u64,
+    base_degree: u32,
+    degree: u32,
+}
+
+impl ParentCacheHeader {
+    fn write<W: Write>(&self, writer: &mut W) -> Result<()> {
+        writer.write_u32::<LittleEndian>(self.version)?;
+        writer.write_all(&self.seed)?;
+        writer.write_u64::<LittleEndian>(self.nodes)?;
+        writer.write_u32::<LittleEndian>(self.base_degree)?;
+        writer.write_u32::<LittleEndian>(self.degree)?;
+
+        Ok(())
+    }
+
+    fn read<R: Read>(reader: &mut R) -> Result<Self> {
+        let version = reader.read_u32::<LittleEndian>()?;
+        let mut seed = [0u8; 28];
+        reader.read_exact(&mut seed)?;
+
+        Ok(ParentCacheHeader {
+            version,
+            seed,
+            nodes: reader.read_u64::<LittleEndian>()?,
+            base_degree: reader.read_u32::<LittleEndian>()?,
+            degree: reader.read_u32::<LittleEndian>()?,
+        })
+    }
+}
+
+/// Releases the in-memory parents cache for graphs of `sector_size`, so that the next graph
+/// of that size loads or regenerates it. Returns whether a cache was held.
+pub fn drop_parent_cache(sector_size: u64) -> bool {
+    PARENT_CACHE.write().unwrap().remove(&sector_size).is_some()
 }
 
 #[derive(Debug, Clone)]
@@ -135,16 +215,110 @@ where
                 .unwrap()
                 .contains_key(&res.sector_size())
             {
+                let cache = res.load_or_generate_parent_cache(&res.parent_cache_path())?;
                 PARENT_CACHE
                     .write()
                     .unwrap()
-                    .insert(res.sector_size(), ParentCache::new(nodes as u32, &res)?);
+                    .insert(res.sector_size(), cache);
             }
         }
 
         Ok(res)
     }
 
+    /// Location of the persisted parents cache for this graph, inside the parameter cache.
+    pub fn parent_cache_path(&self) -> PathBuf {
+        parameter_cache_dir().join(format!(
+            "v{}-stacked-parents-{}.{}",
+            PARENT_CACHE_VERSION,
+            self.sector_size(),
+            PARENT_CACHE_EXT
+        ))
+    }
+
+    /// Generate the full parents cache of this graph and write it to `parent_cache_path`,
+    /// where graphs created with `maximize_caching` set load it instead of regenerating it.
+    /// An existing file, e.g. one generated from a different seed, is replaced.
+    pub fn persist_parent_cache(&self) -> Result<PathBuf> {
+        let path = self.parent_cache_path();
+        let header = self.parent_cache_header();
+        ensure!(header.nodes <= std::u32::MAX as u64, "too many nodes");
+
+        self.write_parent_cache(&ParentCache::new(header.nodes as u32, self)?, &path)?;
+
+        Ok(path)
+    }
+
+    /// Write `cache`, the parents cache of this graph, to `path`.
+    fn write_parent_cache(&self, cache: &ParentCache, path: &Path) -> Result<()> {
+        info!("persisting parents cache to {:?}", path);
+
+        let dir = path.parent().expect("parent cache path has a parent");
+        fs::create_dir_all(dir)
+            .with_context(|| format!("could not create parameter cache dir {:?}", dir))?;
+
+        // Write to a temporary file first, so that a concurrent loader never sees a partial cache.
+        let tmp_path = path.with_extension(format!("{}.tmp", PARENT_CACHE_EXT));
+        {
+            let mut writer = BufWriter::new(
+                File::create(&tmp_path)
+                    .with_context(|| format!("could not create {:?}", tmp_path))?,
+            );
+            cache.write(&self.parent_cache_header(), &mut writer)?;
+            writer.flush()?;
+        }
+        fs::rename(&tmp_path, path)
+            .with_context(|| format!("could not move parents cache into {:?}", path))?;
+
+        Ok(())
+    }
+
+    /// Load the parents cache persisted at `path`, or generate it if there is none or it is
+    /// stale. A cache that cannot be read, e.g. a truncated file, is regenerated and rewritten.
+    fn load_or_generate_parent_cache(&self, path: &Path) -> Result<ParentCache> {
+        match self.load_parent_cache(path) {
+            Ok(Some(cache)) => Ok(cache),
+            Ok(None) => ParentCache::new(self.size() as u32, self),
+            Err(err) => {
+                warn!("regenerating corrupt parents cache {:?}: {:#}", path, err);
+                let cache = ParentCache::new(self.size() as u32, self)?;
+                if let Err(err) = self.write_parent_cache(&cache, path) {
+                    warn!("could not rewrite parents cache {:?}: {:#}", path, err);
+                }
+
+                Ok(cache)
+            }
+        }
+    }
+
+    /// Load the parents cache persisted at `path`, if there is one and it was generated for
+    /// this graph's seed and shape.
+    fn load_parent_cache(&self, path: &Path) -> Result<Option<ParentCache>> {
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(_) => return Ok(None),
+        };
+
+        let cache = ParentCache::read_from(&self.parent_cache_header(), &mut BufReader::new(file))
+            .with_context(|| format!("could not read parents cache {:?}", path))?;
+        match cache {
+            Some(_) => info!("loaded parents cache from {:?}", path),
+            None => info!("ignoring stale parents cache {:?}", path),
+        }
+
+        Ok(cache)
+    }
+
+    fn parent_cache_header(&self) -> ParentCacheHeader {
+        ParentCacheHeader {
+            version: PARENT_CACHE_VERSION,
+            seed: self.base_graph.seed(),
+            nodes: self.size() as u64,
+            base_degree: self.base_graph.degree() as u32,
+            degree: self.degree() as u32,
+        }
+    }
+
     pub fn copy_parents_data(
         &self,
         node: u32,
@@ -406,6 +580,9 @@ mod tests {
 
     use std::collections::HashSet;
 
+    use crate::drgraph::{new_seed, BASE_DEGREE};
+    use crate::hasher::PedersenHasher;
+
     // Test that 3 (or more) rounds of the Feistel cipher can be used
     // as a pseudorandom permutation, that is, each input will be mapped
     // to a unique output (and though not test here, since the cipher
@@ -444,4 +621,82 @@ mod tests {
         // have skipped as duplicates).
         assert_eq!(shuffled.len(), (n * d) as usize);
     }
+
+    #[test]
+    fn parent_cache_roundtrip() {
+        let nodes = 64;
+        let graph = StackedBucketGraph::<PedersenHasher>::new_stacked(
+            nodes,
+            BASE_DEGREE,
+            EXP_DEGREE,
+            new_seed(),
+        )
+        .unwrap();
+        let header = graph.parent_cache_header();
+        let cache = ParentCache::new(nodes as u32, &graph).unwrap();
+
+        let mut bytes = Vec::new();
+        cache.write(&header, &mut bytes).unwrap();
+
+        let loaded = ParentCache::read_from(&header, &mut bytes.as_slice())
+            .unwrap()
+            .expect("header matches");
+        assert_eq!(cache.cache, loaded.cache);
+        assert_eq!(cache.degree, loaded.degree);
+
+        let mut other_seed = header.clone();
+        other_seed.seed[0] ^= 1;
+        assert!(ParentCache::read_from(&other_seed, &mut bytes.as_slice())
+            .unwrap()
+            .is_none());
+
+        let mut other_version = header.clone();
+        other_version.version += 1;
+        assert!(
+            ParentCache::read_from(&other_version, &mut bytes.as_slice())
+                .unwrap()
+                .is_none()
+        );
+
+        // A truncated cache is an error, not a silently short graph.
+        let truncated = &bytes[..bytes.len() - 4];
+        assert!(ParentCache::read_from(&header, &mut &truncated[..]).is_err());
+    }
+
+    #[test]
+    fn corrupted_parent_cache_is_regenerated() {
+        let nodes = 64;
+        let graph = StackedBucketGraph::<PedersenHasher>::new_stacked(
+            nodes,
+            BASE_DEGREE,
+            EXP_DEGREE,
+            new_seed(),
+        )
+        .unwrap();
+        let expected = ParentCache::new(nodes as u32, &graph).unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("parents");
+        graph.write_parent_cache(&expected, &path).unwrap();
+
+        // Truncate the persisted cache, so that it can no longer be read.
+        let len = fs::metadata(&path).unwrap().len();
+        fs::OpenOptions::new()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_len(len - 4)
+            .unwrap();
+        assert!(graph.load_parent_cache(&path).is_err());
+
+        let regenerated = graph.load_or_generate_parent_cache(&path).unwrap();
+        assert_eq!(expected.cache, regenerated.cache);
+
+        // The cache was rewritten.
+        let reloaded = graph
+            .load_parent_cache(&path)
+            .unwrap()
+            .expect("rewritten cache matches the graph");
+        assert_eq!(expected.cache, reloaded.cache);
+    }
 }
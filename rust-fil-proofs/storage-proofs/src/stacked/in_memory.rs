@@ -0,0 +1,318 @@
+use anyhow::{ensure, Context};
+use merkletree::merkle::{self, FromIndexedParallelIterator};
+use merkletree::store::VecStore;
+use rayon::prelude::*;
+use sha2::{Digest, Sha256};
+
+use crate::drgraph::Graph;
+use crate::encode::encode;
+use crate::error::Result;
+use crate::hasher::{Domain, HashFunction, Hasher};
+use crate::merkle::MerkleProof;
+use crate::stacked::{
+    column::Column,
+    column_proof::ColumnProof,
+    graph::StackedBucketGraph,
+    params::{get_node, PersistentAux, Proof, PublicInputs, PublicParams, ReplicaAux, Tau},
+    proof::{create_key, StackedDrg},
+};
+use crate::util::NODE_SIZE;
+
+/// A merkle tree held in a `VecStore`.
+pub type VecTree<H> = merkle::MerkleTree<
+    <H as Hasher>::Domain,
+    <H as Hasher>::Function,
+    VecStore<<H as Hasher>::Domain>,
+>;
+
+/// The labels and trees of a replica sealed by `StackedDrg::replicate_in_memory`. Nothing is
+/// written to disk, so this is only meant for the tiny sectors of tests.
+#[derive(Debug)]
+pub struct InMemoryAux<H: Hasher, G: Hasher> {
+    /// The labels of the layers 1..layers.
+    labels: Vec<Vec<H::Domain>>,
+    tree_d: VecTree<G>,
+    tree_c: VecTree<H>,
+    tree_r_last: VecTree<H>,
+}
+
+impl<H: Hasher, G: Hasher> ReplicaAux<H, G> for InMemoryAux<H, G> {
+    fn layers(&self) -> usize {
+        self.labels.len()
+    }
+
+    fn tree_d_root(&self) -> G::Domain {
+        self.tree_d.root()
+    }
+
+    fn tree_c_root(&self) -> H::Domain {
+        self.tree_c.root()
+    }
+
+    fn tree_r_last_root(&self) -> H::Domain {
+        self.tree_r_last.root()
+    }
+
+    fn tree_d_proof(&self, node: usize) -> Result<MerkleProof<G>> {
+        Ok(MerkleProof::new_from_proof(&self.tree_d.gen_proof(node)?))
+    }
+
+    fn tree_r_last_proof(&self, node: usize) -> Result<MerkleProof<H>> {
+        Ok(MerkleProof::new_from_proof(
+            &self.tree_r_last.gen_proof(node)?,
+        ))
+    }
+
+    fn domain_node_at_layer(&self, layer: usize, node_index: u32) -> Result<H::Domain> {
+        self.labels
+            .get(layer - 1)
+            .and_then(|labels| labels.get(node_index as usize))
+            .copied()
+            .with_context(|| format!("no label for node {} at layer {}", node_index, layer))
+    }
+
+    fn column(&self, column_index: u32) -> Result<Column<H>> {
+        let rows = (1..=self.layers())
+            .map(|layer| self.domain_node_at_layer(layer, column_index))
+            .collect::<Result<_>>()?;
+
+        Column::new(column_index, rows)
+    }
+
+    fn column_proof(&self, column: Column<H>) -> Result<ColumnProof<H>> {
+        column.into_proof(&self.tree_c)
+    }
+}
+
+impl<'a, H: 'static + Hasher, G: 'static + Hasher> StackedDrg<'a, H, G> {
+    /// Replicates `data` in place, as `replicate_phase1` and `replicate_phase2` do, but keeps
+    /// the labels and trees in memory instead of in a cache directory. `data_tree` is the
+    /// tree over `data` built by `build_vec_tree`.
+    #[allow(clippy::type_complexity)]
+    pub fn replicate_in_memory(
+        pp: &PublicParams<H>,
+        replica_id: &H::Domain,
+        data: &mut [u8],
+        data_tree: VecTree<G>,
+    ) -> Result<(
+        Tau<H::Domain, G::Domain>,
+        PersistentAux<H::Domain>,
+        InMemoryAux<H, G>,
+    )> {
+        let graph = &pp.graph;
+        let nodes = graph.size();
+        ensure!(
+            data.len() == nodes * NODE_SIZE,
+            "expected {} bytes of data, got {}",
+            nodes * NODE_SIZE,
+            data.len()
+        );
+        ensure!(
+            data_tree.leafs() == nodes,
+            "data tree has {} leafs, expected {}",
+            data_tree.leafs(),
+            nodes
+        );
+
+        let labels = generate_labels_in_memory(graph, pp.layer_challenges.layers(), replica_id)?;
+
+        let tree_c = VecTree::<H>::from_par_iter((0..nodes).into_par_iter().map(|i| {
+            let rows = labels.iter().map(|layer| layer[i]).collect();
+            Column::<H>::new(i as u32, rows)
+                .expect("column of labels")
+                .hash()
+                .into()
+        }))?;
+
+        // Encode the data with the labels of the last layer.
+        let last_layer = labels.last().context("no layers labeled")?;
+        for (key, node_bytes) in last_layer.iter().zip(data.chunks_mut(NODE_SIZE)) {
+            let data_node = H::Domain::try_from_bytes(node_bytes)?;
+            let encoded_node = encode::<H::Domain>(*key, data_node);
+            node_bytes.copy_from_slice(AsRef::<[u8]>::as_ref(&encoded_node));
+        }
+        let tree_r_last = build_vec_tree::<H>(data)?;
+
+        let comm_r = H::Function::hash2(&tree_c.root(), &tree_r_last.root());
+
+        Ok((
+            Tau {
+                comm_d: data_tree.root(),
+                comm_r,
+            },
+            PersistentAux {
+                comm_c: tree_c.root(),
+                comm_r_last: tree_r_last.root(),
+            },
+            InMemoryAux {
+                labels,
+                tree_d: data_tree,
+                tree_c,
+                tree_r_last,
+            },
+        ))
+    }
+
+    /// Proves all partitions of a replica sealed by `replicate_in_memory`, as
+    /// `prove_all_partitions` does for a replica with its trees on disk.
+    pub fn prove_all_partitions_in_memory(
+        pp: &PublicParams<H>,
+        pub_inputs: &PublicInputs<H::Domain, G::Domain>,
+        p_aux: &PersistentAux<H::Domain>,
+        aux: &InMemoryAux<H, G>,
+        partition_count: usize,
+    ) -> Result<Vec<Vec<Proof<H, G>>>> {
+        ensure!(partition_count > 0, "partitions must not be 0");
+
+        Self::prove_layers(
+            &pp.graph,
+            pub_inputs,
+            p_aux,
+            aux,
+            &pp.layer_challenges,
+            pp.layer_challenges.layers(),
+            pp.layer_challenges.layers(),
+            partition_count,
+        )
+    }
+}
+
+/// Builds a merkle tree over the nodes of `data` in memory.
+pub fn build_vec_tree<K: Hasher>(data: &[u8]) -> Result<VecTree<K>> {
+    ensure!(
+        data.len() % NODE_SIZE == 0,
+        "data of {} bytes is not a whole number of nodes",
+        data.len()
+    );
+
+    VecTree::<K>::from_par_iter(
+        (0..data.len() / NODE_SIZE)
+            .into_par_iter()
+            .map(|i| get_node::<K>(data, i).expect("node within data")),
+    )
+}
+
+/// Labels all layers as `generate_labels` does, without writing them to disk.
+fn generate_labels_in_memory<H: Hasher>(
+    graph: &StackedBucketGraph<H>,
+    layers: usize,
+    replica_id: &H::Domain,
+) -> Result<Vec<Vec<H::Domain>>> {
+    let mut base_hasher = Sha256::new();
+    base_hasher.input(AsRef::<[u8]>::as_ref(replica_id));
+
+    let mut layer_bytes: Vec<Vec<u8>> = Vec::with_capacity(layers);
+    for _ in 1..=layers {
+        let mut layer_labels = vec![0u8; graph.size() * NODE_SIZE];
+        for node in 0..graph.size() {
+            create_key(
+                graph,
+                base_hasher.clone(),
+                layer_bytes.last(),
+                &mut layer_labels,
+                node,
+            )?;
+        }
+        layer_bytes.push(layer_labels);
+    }
+
+    layer_bytes
+        .iter()
+        .map(|layer| {
+            layer
+                .chunks(NODE_SIZE)
+                .map(H::Domain::try_from_bytes)
+                .collect()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use ff::Field;
+    use merkletree::store::StoreConfig;
+    use paired::bls12_381::{Bls12, Fr};
+    use rand::{Rng, SeedableRng};
+    use rand_xorshift::XorShiftRng;
+
+    use crate::drgraph::{new_seed, BASE_DEGREE};
+    use crate::fr32::fr_into_bytes;
+    use crate::hasher::{Blake2sHasher, PedersenHasher};
+    use crate::porep::PoRep;
+    use crate::proof::ProofScheme;
+    use crate::stacked::{CacheKey, LayerChallenges, SetupParams, EXP_DEGREE};
+
+    #[test]
+    fn replicate_in_memory_matches_disk() {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+
+        let nodes = 32;
+        let replica_id = <PedersenHasher as Hasher>::Domain::random(rng);
+        let data: Vec<u8> = (0..nodes)
+            .flat_map(|_| fr_into_bytes::<Bls12>(&Fr::random(rng)))
+            .collect();
+
+        let sp = SetupParams {
+            nodes,
+            degree: BASE_DEGREE,
+            expansion_degree: EXP_DEGREE,
+            seed: new_seed(),
+            layer_challenges: LayerChallenges::new(2, 4),
+        };
+        let pp = StackedDrg::<PedersenHasher, Blake2sHasher>::setup(&sp).unwrap();
+
+        let cache_dir = tempfile::tempdir().unwrap();
+        let config = StoreConfig::new(
+            cache_dir.path(),
+            CacheKey::CommDTree.to_string(),
+            StoreConfig::default_cached_above_base_layer(nodes),
+        );
+        let mut on_disk = data.clone();
+        let (disk_tau, (disk_p_aux, _)) = StackedDrg::<PedersenHasher, Blake2sHasher>::replicate(
+            &pp,
+            &replica_id,
+            (&mut on_disk[..]).into(),
+            None,
+            Some(config),
+        )
+        .unwrap();
+
+        let mut in_memory = data;
+        let data_tree = build_vec_tree::<Blake2sHasher>(&in_memory).unwrap();
+        let (tau, p_aux, aux) = StackedDrg::<PedersenHasher, Blake2sHasher>::replicate_in_memory(
+            &pp,
+            &replica_id,
+            &mut in_memory,
+            data_tree,
+        )
+        .unwrap();
+        assert_eq!(tau, disk_tau);
+        assert_eq!(p_aux.comm_c, disk_p_aux.comm_c);
+        assert_eq!(p_aux.comm_r_last, disk_p_aux.comm_r_last);
+        assert_eq!(in_memory, on_disk);
+
+        let pub_inputs = PublicInputs {
+            replica_id,
+            seed: rng.gen(),
+            tau: Some(tau),
+            k: None,
+        };
+        let proofs = StackedDrg::<PedersenHasher, Blake2sHasher>::prove_all_partitions_in_memory(
+            &pp,
+            &pub_inputs,
+            &p_aux,
+            &aux,
+            2,
+        )
+        .unwrap();
+        let valid = StackedDrg::<PedersenHasher, Blake2sHasher>::verify_all_partitions(
+            &pp,
+            &pub_inputs,
+            &proofs,
+        )
+        .unwrap();
+        assert!(valid);
+    }
+}
@@ -7,6 +7,8 @@ mod column_proof;
 mod encoding_proof;
 mod graph;
 pub(crate) mod hash;
+#[cfg(feature = "test-utils")]
+mod in_memory;
 mod labeling_proof;
 mod params;
 mod porep;
@@ -17,10 +19,15 @@ pub use self::challenges::{ChallengeRequirements, LayerChallenges};
 pub use self::column::Column;
 pub use self::column_proof::ColumnProof;
 pub use self::encoding_proof::EncodingProof;
-pub use self::graph::{StackedBucketGraph, StackedGraph, EXP_DEGREE};
+pub use self::graph::{
+    drop_parent_cache, StackedBucketGraph, StackedGraph, EXP_DEGREE, PARENT_CACHE_VERSION,
+};
+#[cfg(feature = "test-utils")]
+pub use self::in_memory::{build_vec_tree, InMemoryAux, VecTree};
 pub use self::params::{
     generate_replica_id, CacheKey, Labels, PersistentAux, PrivateInputs, Proof, PublicInputs,
-    PublicParams, ReplicaColumnProof, SetupParams, Tau, TemporaryAux, TemporaryAuxCache, Tree,
+    PublicParams, ReplicaAux, ReplicaColumnProof, SetupParams, Tau, TemporaryAux,
+    TemporaryAuxCache, Tree,
 };
 pub use self::proof::{create_key, StackedDrg, TOTAL_PARENTS};
 pub use labeling_proof::LabelingProof;
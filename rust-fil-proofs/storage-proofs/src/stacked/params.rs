@@ -486,6 +486,71 @@ impl<H: Hasher, G: Hasher> TemporaryAuxCache<H, G> {
     }
 }
 
+/// The labels and trees of a replica, which `StackedDrg` opens to prove it.
+pub trait ReplicaAux<H: Hasher, G: Hasher>: fmt::Debug + Sync {
+    /// The number of labeled layers.
+    fn layers(&self) -> usize;
+
+    fn tree_d_root(&self) -> G::Domain;
+
+    fn tree_c_root(&self) -> H::Domain;
+
+    fn tree_r_last_root(&self) -> H::Domain;
+
+    /// Opens `node` in the tree over the original data.
+    fn tree_d_proof(&self, node: usize) -> Result<MerkleProof<G>>;
+
+    /// Opens `node` in the tree over the replica.
+    fn tree_r_last_proof(&self, node: usize) -> Result<MerkleProof<H>>;
+
+    fn domain_node_at_layer(&self, layer: usize, node_index: u32) -> Result<H::Domain>;
+
+    fn column(&self, column_index: u32) -> Result<Column<H>>;
+
+    /// Opens `column` in tree-c.
+    fn column_proof(&self, column: Column<H>) -> Result<ColumnProof<H>>;
+}
+
+impl<H: Hasher, G: Hasher> ReplicaAux<H, G> for TemporaryAuxCache<H, G> {
+    fn layers(&self) -> usize {
+        self.labels.len()
+    }
+
+    fn tree_d_root(&self) -> G::Domain {
+        self.tree_d.root()
+    }
+
+    fn tree_c_root(&self) -> H::Domain {
+        self.tree_c.root()
+    }
+
+    fn tree_r_last_root(&self) -> H::Domain {
+        self.tree_r_last.root()
+    }
+
+    fn tree_d_proof(&self, node: usize) -> Result<MerkleProof<G>> {
+        Ok(MerkleProof::new_from_proof(&self.tree_d.gen_proof(node)?))
+    }
+
+    fn tree_r_last_proof(&self, node: usize) -> Result<MerkleProof<H>> {
+        Ok(MerkleProof::new_from_proof(
+            &self.tree_r_last.gen_proof(node)?,
+        ))
+    }
+
+    fn domain_node_at_layer(&self, layer: usize, node_index: u32) -> Result<H::Domain> {
+        Ok(self.labels_for_layer(layer).read_at(node_index as usize)?)
+    }
+
+    fn column(&self, column_index: u32) -> Result<Column<H>> {
+        self.labels.column(column_index)
+    }
+
+    fn column_proof(&self, column: Column<H>) -> Result<ColumnProof<H>> {
+        column.into_proof(&self.tree_c)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Labels<H: Hasher> {
     pub labels: Vec<StoreConfig>,
@@ -13,19 +13,20 @@ use crate::encode::{decode, encode};
 use crate::error::Result;
 use crate::hasher::{Domain, HashFunction, Hasher};
 use crate::measurements::{
-    measure_op,
+    measure_layer_op, measure_op,
     Operation::{CommD, EncodeWindowTimeAll, GenerateTreeC, GenerateTreeRLast},
 };
-use crate::merkle::{MerkleProof, MerkleTree, Store};
+use crate::merkle::{MerkleTree, Store};
 use crate::porep::Data;
 use crate::porep::PoRep;
+use crate::sector_log::{current_sector, SectorSpan};
 use crate::stacked::{
     challenges::LayerChallenges,
     column::Column,
     graph::StackedBucketGraph,
     params::{
         get_node, CacheKey, Labels, LabelsCache, PersistentAux, Proof, PublicInputs, PublicParams,
-        ReplicaColumnProof, Tau, TemporaryAux, TemporaryAuxCache, TransformedLayers, Tree,
+        ReplicaAux, ReplicaColumnProof, Tau, TemporaryAux, TransformedLayers, Tree,
     },
     EncodingProof, LabelingProof,
 };
@@ -34,6 +35,9 @@ use crate::util::{data_at_node_offset, NODE_SIZE};
 pub const TOTAL_PARENTS: usize = 37;
 pub const N: usize = 10;
 
+/// The number of nodes labeled between two calls of the `replicate_phase1` progress callback.
+pub const LABEL_PROGRESS_NODES: usize = 1 << 16;
+
 #[derive(Debug)]
 pub struct StackedDrg<'a, H: 'a + Hasher, G: 'a + Hasher> {
     _a: PhantomData<&'a H>,
@@ -42,31 +46,31 @@ pub struct StackedDrg<'a, H: 'a + Hasher, G: 'a + Hasher> {
 
 impl<'a, H: 'static + Hasher, G: 'static + Hasher> StackedDrg<'a, H, G> {
     #[allow(clippy::too_many_arguments)]
-    pub(crate) fn prove_layers(
+    pub(crate) fn prove_layers<A: ReplicaAux<H, G>>(
         graph: &StackedBucketGraph<H>,
         pub_inputs: &PublicInputs<<H as Hasher>::Domain, <G as Hasher>::Domain>,
         p_aux: &PersistentAux<H::Domain>,
-        t_aux: &TemporaryAuxCache<H, G>,
+        t_aux: &A,
         layer_challenges: &LayerChallenges,
         layers: usize,
         _total_layers: usize,
         partition_count: usize,
     ) -> Result<Vec<Vec<Proof<H, G>>>> {
         assert!(layers > 0);
-        assert_eq!(t_aux.labels.len(), layers);
+        assert_eq!(t_aux.layers(), layers);
 
         let graph_size = graph.size();
         println!("prove_layers begin:  graph ={:?}",graph);
         // Sanity checks on restored trees.
         assert!(pub_inputs.tau.is_some());
-        assert_eq!(pub_inputs.tau.as_ref().unwrap().comm_d, t_aux.tree_d.root());
-        assert_eq!(p_aux.comm_c, t_aux.tree_c.root());
-        assert_eq!(p_aux.comm_r_last, t_aux.tree_r_last.root());
+        assert_eq!(pub_inputs.tau.as_ref().unwrap().comm_d, t_aux.tree_d_root());
+        assert_eq!(p_aux.comm_c, t_aux.tree_c_root());
+        assert_eq!(p_aux.comm_r_last, t_aux.tree_r_last_root());
         println!("t_aux = {:?}",t_aux);
         println!("_total_layers = {:?}   layerIndex = {}",_total_layers,layers);
         //for i in 1..layers+1 {
            // let lables0 = t_aux.labels_for_layer(i);
-        println!("t_aux.labels.len() = {:?}",t_aux.labels.len());           
+        println!("t_aux.layers() = {:?}",t_aux.layers());           
        // println!("t_aux columns = {:?}",t_aux.labels.columns());
                          
         //}
@@ -106,22 +110,21 @@ impl<'a, H: 'static + Hasher, G: 'static + Hasher> StackedDrg<'a, H, G> {
                 // Derive the set of challenges we are proving over.
                 let challenges =  pub_inputs.challenges(layer_challenges, graph_size, Some(k));
                 println!("Derived challenges  = {:?}", challenges);
+                let sector_id = current_sector();
                 // Stacked commitment specifics
                 challenges
                     .into_par_iter()
                     .enumerate()
                     .map(|(challenge_index, challenge)| {
+                        let _span = SectorSpan::resume(sector_id);
                         println!(" challenge {} (index={})", challenge, challenge_index);
                         assert!(challenge < graph.size(), "Invalid challenge");
                         assert!(challenge > 0, "Invalid challenge");
 
                         // Initial data layer openings (c_X in Comm_D)
 
-                        let aproof = t_aux.tree_d.gen_proof(challenge)?;
-                        //println!("aproof  = {:?}", aproof);
                         //proof 到 merkleproof是从叶子到root的顺序调转,proof:叶子到根  merkleproof是root到leaf
-                        let comm_d_proof =
-                            MerkleProof::new_from_proof(&aproof);
+                        let comm_d_proof = t_aux.tree_d_proof(challenge)?;
                         //println!("comm_d_proof from aproof  = {:?}", comm_d_proof);
                         assert!(comm_d_proof.validate(challenge));
                         
@@ -130,20 +133,20 @@ impl<'a, H: 'static + Hasher, G: 'static + Hasher> StackedDrg<'a, H, G> {
                         let rpc = {
                             // All labels in C_X   列
                             trace!("  c_x");
-                            let c_x = t_aux.column(challenge as u32)?.into_proof(&t_aux.tree_c)?;
+                            let c_x = t_aux.column_proof(t_aux.column(challenge as u32)?)?;
 
                             // All labels in the DRG parents.
                             trace!("  drg_parents");
                             let drg_parents = get_drg_parents_columns(challenge)?
                                 .into_iter()
-                                .map(|column| column.into_proof(&t_aux.tree_c))
+                                .map(|column| t_aux.column_proof(column))
                                 .collect::<Result<_>>()?;
 
                             // Labels for the expander parents
                             trace!("  exp_parents");
                             let exp_parents = get_exp_parents_columns(challenge)?
                                 .into_iter()
-                                .map(|column| column.into_proof(&t_aux.tree_c))
+                                .map(|column| t_aux.column_proof(column))
                                 .collect::<Result<_>>()?;
 
                             ReplicaColumnProof {
@@ -156,8 +159,7 @@ impl<'a, H: 'static + Hasher, G: 'static + Hasher> StackedDrg<'a, H, G> {
 
                         // Final replica layer openings
                         println!("final replica layer openings");
-                        let comm_r_last_proof =
-                            MerkleProof::new_from_proof(&t_aux.tree_r_last.gen_proof(challenge)?);
+                        let comm_r_last_proof = t_aux.tree_r_last_proof(challenge)?;
                         assert!(comm_r_last_proof.validate(challenge));
                         //println!("comm_r_last_proof  = {:?}", comm_r_last_proof);
 
@@ -267,7 +269,13 @@ impl<'a, H: 'static + Hasher, G: 'static + Hasher> StackedDrg<'a, H, G> {
         assert!(layers > 0);
 
         // generate labels
-        let (labels, _) = Self::generate_labels(graph, layer_challenges, replica_id, config)?;
+        let (labels, _) = Self::generate_labels(
+            graph,
+            layer_challenges,
+            replica_id,
+            config,
+            &mut |_, _, _| {},
+        )?;
 
         let last_layer_labels = labels.labels_for_last_layer()?;
         let size = merkletree::store::Store::len(last_layer_labels);
@@ -293,6 +301,7 @@ impl<'a, H: 'static + Hasher, G: 'static + Hasher> StackedDrg<'a, H, G> {
         layer_challenges: &LayerChallenges,
         replica_id: &<H as Hasher>::Domain,
         config: StoreConfig,
+        progress: &mut dyn FnMut(usize, usize, usize),
     ) -> Result<(LabelsCache<H>, Labels<H>)> {
         println!("generate labels");
         let layers = layer_challenges.layers();
@@ -315,16 +324,25 @@ impl<'a, H: 'static + Hasher, G: 'static + Hasher> StackedDrg<'a, H, G> {
         for layer in 1..=layers {
             println!("generating labels(key) for layer: {:?}", layer);
 
-            for node in 0..graph.size() {
-                create_key(
-                    graph,
-                    base_hasher.clone(),
-                    exp_parents_data.as_ref(),
-                    &mut layer_labels,
-                    node,
-                )?;
-               //println!("layer_labels = {:?}", layer_labels);
-            }
+            measure_layer_op(layer, || -> Result<()> {
+                for node in 0..graph.size() {
+                    create_key(
+                        graph,
+                        base_hasher.clone(),
+                        exp_parents_data.as_ref(),
+                        &mut layer_labels,
+                        node,
+                    )?;
+                   //println!("layer_labels = {:?}", layer_labels);
+
+                    let labeled = node + 1;
+                    if labeled % LABEL_PROGRESS_NODES == 0 || labeled == graph.size() {
+                        progress(layer, labeled, graph.size());
+                    }
+                }
+
+                Ok(())
+            })?;
             //println!("layer_labels = {:?}", layer_labels);
             println!("setting exp parents");
 
@@ -520,7 +538,13 @@ impl<'a, H: 'static + Hasher, G: 'static + Hasher> StackedDrg<'a, H, G> {
     ) -> Result<TransformedLayers<H, G>> {
         // Generate key layers.
         let (_, labels) = measure_op(EncodeWindowTimeAll, || {
-            Self::generate_labels(graph, layer_challenges, replica_id, config.clone())
+            Self::generate_labels(
+                graph,
+                layer_challenges,
+                replica_id,
+                config.clone(),
+                &mut |_, _, _| {},
+            )
         })?;
 
         Self::transform_and_replicate_layers_inner(
@@ -702,15 +726,27 @@ impl<'a, H: 'static + Hasher, G: 'static + Hasher> StackedDrg<'a, H, G> {
     }
 
     /// Phase1 of replication.
+    ///
+    /// `progress` is called with the layer being labeled, counted from 1, the number of its
+    /// nodes labeled so far and its total number of nodes. It is called every
+    /// `LABEL_PROGRESS_NODES` nodes and once each layer is complete, so the last call reports
+    /// the last layer with all of its nodes labeled.
     pub fn replicate_phase1(
         pp: &'a PublicParams<H>,
         replica_id: &H::Domain,
         config: StoreConfig,
+        progress: &mut dyn FnMut(usize, usize, usize),
     ) -> Result<Labels<H>> {
         info!("replicate_phase1");
 
         let (_, labels) = measure_op(EncodeWindowTimeAll, || {
-            Self::generate_labels(&pp.graph, &pp.layer_challenges, replica_id, config)
+            Self::generate_labels(
+                &pp.graph,
+                &pp.layer_challenges,
+                replica_id,
+                config,
+                progress,
+            )
         })?;
       
 
@@ -881,7 +917,7 @@ mod tests {
     use crate::hasher::{Blake2sHasher, PedersenHasher, Sha256Hasher};
     use crate::porep::PoRep;
     use crate::proof::ProofScheme;
-    use crate::stacked::{PrivateInputs, SetupParams, EXP_DEGREE};
+    use crate::stacked::{PrivateInputs, SetupParams, TemporaryAuxCache, EXP_DEGREE};
 
     const DEFAULT_STACKED_LAYERS: usize = 11;
 
@@ -6,6 +6,7 @@ use crate::drgraph::Graph;
 use crate::error::Result;
 use crate::hasher::{HashFunction, Hasher};
 use crate::proof::ProofScheme;
+use crate::sector_log::{current_sector, SectorSpan};
 use crate::stacked::{
     challenges::ChallengeRequirements,
     graph::StackedBucketGraph,
@@ -90,8 +91,10 @@ impl<'a, 'c, H: 'static + Hasher, G: 'static + Hasher> ProofScheme<'a> for Stack
             return Ok(false);
         };
 
+        let sector_id = current_sector();
         //proofs 是 proof的数组，此文件重新定义了proof
         let res = partition_proofs.par_iter().enumerate().all(|(k, proofs)| {
+            let _span = SectorSpan::resume(sector_id);
             println!(
                 "verifying partition proof {}/{}",
                 k + 1,
@@ -113,6 +116,7 @@ impl<'a, 'c, H: 'static + Hasher, G: 'static + Hasher> ProofScheme<'a> for Stack
                 pub_inputs.challenges(&pub_params.layer_challenges, graph.size(), Some(k));
 
             proofs.par_iter().enumerate().all(|(i, proof)| {
+                let _span = SectorSpan::resume(sector_id);
                 trace!("verify challenge {}/{}", i + 1, challenges.len());
 
                 // Validate for this challenge
@@ -1,9 +1,12 @@
 use anyhow::ensure;
 use bellperson::gadgets::boolean::{self, AllocatedBit, Boolean};
 use bellperson::{ConstraintSystem, SynthesisError};
+use byteorder::{ByteOrder, LittleEndian};
+use paired::bls12_381::{Bls12, Fr};
 use paired::Engine;
 
 use crate::error;
+use crate::fr32::fr_into_bytes;
 
 pub const NODE_SIZE: usize = 32;
 
@@ -24,6 +27,21 @@ pub fn data_at_node(data: &[u8], v: usize) -> error::Result<&[u8]> {
     Ok(&data[offset..offset + NODE_SIZE])
 }
 
+/// Reduces a field element to a node index in a graph of `num_nodes` nodes.
+///
+/// The element is serialized into its canonical 32 byte little endian form, the first 8 bytes
+/// are read as a little endian `u64` and the result is taken modulo `num_nodes`. This is the
+/// same reduction the PoSt leaf challenges apply to their hash output, so it can be used to
+/// recompute which nodes a given seed challenges.
+pub fn challenge_index_from_fr(fr: Fr, num_nodes: usize) -> usize {
+    assert!(num_nodes > 0, "num_nodes must be non-zero");
+
+    let bytes = fr_into_bytes::<Bls12>(&fr);
+    let challenge = LittleEndian::read_u64(&bytes[..8]);
+
+    (challenge % num_nodes as u64) as usize
+}
+
 /// Converts bytes into their bit representation, in little endian format.
 pub fn bytes_into_bits(bytes: &[u8]) -> Vec<bool> {
     bytes
@@ -157,6 +175,57 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_challenge_index_from_fr() {
+        use ff::{Field, PrimeField};
+        use paired::bls12_381::FrRepr;
+
+        assert_eq!(challenge_index_from_fr(Fr::zero(), 7), 0);
+        assert_eq!(challenge_index_from_fr(Fr::one(), 7), 1);
+        assert_eq!(
+            challenge_index_from_fr(Fr::from_repr(FrRepr::from(12_345)).unwrap(), 100),
+            45
+        );
+
+        // Only the low 8 bytes take part in the reduction.
+        let high = Fr::from_repr(FrRepr([3, 1, 0, 0])).unwrap();
+        assert_eq!(challenge_index_from_fr(high, 1 << 20), 3);
+    }
+
+    #[test]
+    fn test_challenge_index_from_fr_matches_leaf_challenges() {
+        use crate::election_post::{generate_leaf_challenge, PublicParams};
+        use crate::fr32::bytes_into_fr;
+        use sha2::{Digest, Sha256};
+
+        let pub_params = PublicParams {
+            sector_size: 1024 * 32,
+            challenge_count: 40,
+            challenged_nodes: 2,
+            read_chunk_bytes: None,
+        };
+        let num_ranges =
+            pub_params.sector_size as usize / (pub_params.challenged_nodes * NODE_SIZE);
+        let randomness = [7u8; 32];
+
+        for leaf_challenge_index in 0..20u64 {
+            let mut hasher = Sha256::new();
+            hasher.input(&randomness[..]);
+            hasher.input(&3u64.to_le_bytes()[..]);
+            hasher.input(&leaf_challenge_index.to_le_bytes()[..]);
+            let mut hash = hasher.result();
+            // Make the hash a valid field element, leaving the low bytes untouched.
+            hash[31] &= 0b0011_1111;
+            let fr = bytes_into_fr::<Bls12>(hash.as_ref()).unwrap();
+
+            let expected =
+                generate_leaf_challenge(&pub_params, &randomness, 3, leaf_challenge_index).unwrap();
+            let index = challenge_index_from_fr(fr, num_ranges) * pub_params.challenged_nodes;
+
+            assert_eq!(index as u64, expected);
+        }
+    }
+
     #[test]
     fn test_bool_to_u8() {
         assert_eq!(bool_to_u8(false, 2), 0b0000_0000);